@@ -1,5 +1,5 @@
 /// Byte offset span in source code.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Span {
     pub start: u32,
     pub end: u32,
@@ -13,6 +13,11 @@ impl Span {
     pub fn dummy() -> Self {
         Self { start: 0, end: 0 }
     }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
 }
 
 // ── Top-level ──────────────────────────────────────────────
@@ -20,6 +25,52 @@ impl Span {
 #[derive(Debug, Clone)]
 pub struct Module {
     pub items: Vec<Item>,
+    /// Interpreter line (`#!/usr/bin/env node`) without the leading `#!`.
+    pub shebang: Option<String>,
+    /// Source comments, in order; only kept when the parser is asked to
+    /// preserve them.
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Comment {
+    pub kind: CommentKind,
+    /// The comment as written, including its `//`, `///` or `/* */` markers.
+    pub text: String,
+    pub span: Span,
+    /// Index into `Module::items` of the top-level item this comment
+    /// directly precedes, if any.
+    pub item: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentKind {
+    Line,
+    Block,
+    Doc,
+}
+
+impl Module {
+    /// The `///` comments directly preceding `items[index]`, in order.
+    pub fn doc_comments(&self, index: usize) -> impl Iterator<Item = &Comment> {
+        self.comments
+            .iter()
+            .filter(move |c| c.kind == CommentKind::Doc && c.item == Some(index))
+    }
+}
+
+/// What `await` outside a function means, for the checker and codegen
+/// alike.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TopLevelAwait {
+    /// Allowed, and emitted as-is for ES modules to await.
+    Allow,
+    /// Allowed, and the module code from the first statement that awaits
+    /// onward runs in an `async function main()` called at the end.
+    WrapInMain,
+    /// Rejected: `await` can only be used inside async functions.
+    #[default]
+    Error,
 }
 
 #[derive(Debug, Clone)]
@@ -27,8 +78,11 @@ pub enum Item {
     FnDecl(FnDecl),
     StructDecl(StructDecl),
     EnumDecl(EnumDecl),
+    InterfaceDecl(InterfaceDecl),
+    ImplBlock(ImplBlock),
     TypeAlias(TypeAlias),
     Import(Import),
+    ExportAlias(ExportAlias),
     VarDecl(VarDecl),
     ExprStmt(ExprStmt),
     DslBlock(DslBlock),
@@ -37,6 +91,43 @@ pub enum Item {
     ExternTypeDecl(ExternTypeDecl),
 }
 
+/// Generates one `Module` method per `Item` variant, iterating over the
+/// items of that kind in source order.
+macro_rules! item_iterators {
+    ($($(#[$doc:meta])* $method:ident => $variant:ident($ty:ty);)*) => {
+        impl Module {
+            $(
+                $(#[$doc])*
+                pub fn $method(&self) -> impl Iterator<Item = &$ty> {
+                    self.items.iter().filter_map(|item| match item {
+                        Item::$variant(x) => Some(x),
+                        _ => None,
+                    })
+                }
+            )*
+        }
+    };
+}
+
+item_iterators! {
+    /// Function declarations, including `@tool` functions.
+    fn_decls => FnDecl(FnDecl);
+    struct_decls => StructDecl(StructDecl);
+    enum_decls => EnumDecl(EnumDecl);
+    interface_decls => InterfaceDecl(InterfaceDecl);
+    impl_blocks => ImplBlock(ImplBlock);
+    type_aliases => TypeAlias(TypeAlias);
+    imports => Import(Import);
+    export_aliases => ExportAlias(ExportAlias);
+    /// Top-level `let`/`mut`/`const` declarations.
+    var_decls => VarDecl(VarDecl);
+    expr_stmts => ExprStmt(ExprStmt);
+    dsl_blocks => DslBlock(DslBlock);
+    extern_fn_decls => ExternFnDecl(ExternFnDecl);
+    extern_struct_decls => ExternStructDecl(ExternStructDecl);
+    extern_type_decls => ExternTypeDecl(ExternTypeDecl);
+}
+
 // ── DSL Block ─────────────────────────────────────────────
 
 #[derive(Debug, Clone)]
@@ -70,12 +161,15 @@ pub struct ExprStmt {
 #[derive(Debug, Clone)]
 pub enum Expr {
     Binary(BinaryExpr),
+    /// `a < b <= c`: two or more chained comparisons.
+    Comparison(Box<ComparisonChain>),
     Unary(UnaryExpr),
     Call(CallExpr),
     Member(MemberExpr),
     Index(IndexExpr),
     If(Box<IfExpr>),
     Match(Box<MatchExpr>),
+    Loop(Box<LoopExpr>),
     Block(Box<Block>),
     Ident(Ident),
     Literal(Literal),
@@ -89,6 +183,7 @@ pub enum Expr {
     ErrorPropagate(Box<ErrorPropagateExpr>),
     Assign(Box<AssignExpr>),
     TemplateString(TemplateStringExpr),
+    TaggedTemplate(TaggedTemplateExpr),
     Placeholder(Span),
 }
 
@@ -100,6 +195,16 @@ pub struct BinaryExpr {
     pub span: Span,
 }
 
+/// `a < b <= c`, which means `a < b && b <= c` with each operand evaluated
+/// once, left to right, and only as long as the comparisons hold.
+#[derive(Debug, Clone)]
+pub struct ComparisonChain {
+    pub operands: Vec<Expr>,
+    /// The operator between each pair of operands, with its span.
+    pub ops: Vec<(BinaryOp, Span)>,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
 pub struct UnaryExpr {
     pub op: UnaryOp,
@@ -142,6 +247,14 @@ pub enum ElseBranch {
     If(Box<IfExpr>),
 }
 
+/// `loop { ... }`: runs until a `break`. Also the desugaring target of
+/// `while let`.
+#[derive(Debug, Clone)]
+pub struct LoopExpr {
+    pub body: Block,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
 pub struct MatchExpr {
     pub subject: Expr,
@@ -229,6 +342,14 @@ pub struct TemplateStringExpr {
     pub span: Span,
 }
 
+/// `tag`text ${expr}`` — the tag is called with the template's strings and values.
+#[derive(Debug, Clone)]
+pub struct TaggedTemplateExpr {
+    pub tag: Box<Expr>,
+    pub template: TemplateStringExpr,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
 pub enum TemplatePart {
     String(String),
@@ -241,11 +362,18 @@ pub struct Ident {
     pub span: Span,
 }
 
+/// The largest integer a JS number represents exactly (`Number.MAX_SAFE_INTEGER`).
+pub const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
 #[derive(Debug, Clone)]
 pub enum Literal {
-    Int(i64, Span),
+    /// Value and source text. The text is kept for literals that a JS
+    /// number cannot hold exactly (beyond 2^53).
+    Int(i64, String, Span),
     Float(f64, Span),
     String(String, Span),
+    /// `'a'`, which evaluates to its code point.
+    Char(char, Span),
     Bool(bool, Span),
     Nil(Span),
 }
@@ -253,17 +381,105 @@ pub enum Literal {
 impl Literal {
     pub fn span(&self) -> Span {
         match self {
-            Literal::Int(_, s)
+            Literal::Int(_, _, s)
             | Literal::Float(_, s)
             | Literal::String(_, s)
+            | Literal::Char(_, s)
             | Literal::Bool(_, s)
             | Literal::Nil(s) => *s,
         }
     }
+
+    /// The number literal preceded by a `-` at `minus`, or the literal
+    /// unchanged if it is not a number.
+    pub fn negate(self, minus: Span) -> Result<Literal, Literal> {
+        match self {
+            Literal::Int(n, text, s) => {
+                let text = match text.strip_prefix('-') {
+                    Some(positive) => positive.to_string(),
+                    None => format!("-{text}"),
+                };
+                Ok(Literal::Int(-n, text, Span::new(minus.start, s.end)))
+            }
+            Literal::Float(n, s) => Ok(Literal::Float(-n, Span::new(minus.start, s.end))),
+            other => Err(other),
+        }
+    }
+}
+
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Binary(e) => e.span,
+            Expr::Comparison(e) => e.span,
+            Expr::Unary(e) => e.span,
+            Expr::Call(e) => e.span,
+            Expr::Member(e) => e.span,
+            Expr::Index(e) => e.span,
+            Expr::If(e) => e.span,
+            Expr::Match(e) => e.span,
+            Expr::Loop(e) => e.span,
+            Expr::Block(e) => e.span,
+            Expr::Ident(e) => e.span,
+            Expr::Literal(e) => e.span(),
+            Expr::Array(e) => e.span,
+            Expr::Object(e) => e.span,
+            Expr::Arrow(e) => e.span,
+            Expr::Pipe(e) => e.span,
+            Expr::OptionalChain(e) => e.span,
+            Expr::NullishCoalesce(e) => e.span,
+            Expr::Await(e) => e.span,
+            Expr::ErrorPropagate(e) => e.span,
+            Expr::Assign(e) => e.span,
+            Expr::TemplateString(e) => e.span,
+            Expr::TaggedTemplate(e) => e.span,
+            Expr::Placeholder(s) => *s,
+        }
+    }
+
+    /// The source extent of the whole expression. [`Expr::span`] is where
+    /// diagnostics point, which for operators and suffixes is only the
+    /// operator or suffix: `a + b` spans the `+`, `f(x)` the `(x)`.
+    pub fn full_span(&self) -> Span {
+        match self {
+            Expr::Binary(e) => e.span.to(e.left.full_span()).to(e.right.full_span()),
+            Expr::Comparison(e) => e.operands.iter().fold(e.span, |span, operand| span.to(operand.full_span())),
+            Expr::Unary(e) => e.span.to(e.operand.full_span()),
+            Expr::Call(e) => e.span.to(e.callee.full_span()),
+            Expr::Member(e) => e.span.to(e.object.full_span()),
+            Expr::Index(e) => e.span.to(e.object.full_span()),
+            Expr::Pipe(e) => e.span.to(e.left.full_span()).to(e.right.full_span()),
+            Expr::OptionalChain(e) => e.span.to(e.object.full_span()),
+            Expr::NullishCoalesce(e) => e.span.to(e.left.full_span()).to(e.right.full_span()),
+            Expr::Await(e) => e.span.to(e.expr.full_span()),
+            Expr::ErrorPropagate(e) => e.span.to(e.expr.full_span()),
+            Expr::Assign(e) => e.span.to(e.target.full_span()).to(e.value.full_span()),
+            Expr::TaggedTemplate(e) => e.span.to(e.tag.full_span()),
+            other => other.span(),
+        }
+    }
 }
 
 // ── Statements ─────────────────────────────────────────────
 
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::VarDecl(s) => s.span,
+            Stmt::ExprStmt(s) => s.span,
+            Stmt::Return(s) => s.span,
+            Stmt::If(s) => s.span,
+            Stmt::For(s) => s.span,
+            Stmt::ForRange(s) => s.span,
+            Stmt::While(s) => s.span,
+            Stmt::Loop(s) => s.span,
+            Stmt::Break(s) => s.span,
+            Stmt::Match(s) => s.span,
+            Stmt::TryCatch(s) => s.span,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Stmt {
     VarDecl(VarDecl),
@@ -271,7 +487,10 @@ pub enum Stmt {
     Return(ReturnStmt),
     If(IfExpr),
     For(ForStmt),
+    ForRange(ForRangeStmt),
     While(WhileStmt),
+    Loop(LoopExpr),
+    Break(BreakStmt),
     Match(MatchExpr),
     TryCatch(TryCatchStmt),
 }
@@ -290,6 +509,16 @@ pub struct ForStmt {
     pub span: Span,
 }
 
+/// C-style `for let i = 0; i < n; i += 1 { ... }`.
+#[derive(Debug, Clone)]
+pub struct ForRangeStmt {
+    pub init: VarDecl,
+    pub condition: Expr,
+    pub update: Expr,
+    pub body: Block,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
 pub struct WhileStmt {
     pub condition: Expr,
@@ -297,6 +526,13 @@ pub struct WhileStmt {
     pub span: Span,
 }
 
+#[derive(Debug, Clone)]
+pub struct BreakStmt {
+    /// Value the enclosing `loop` evaluates to (`break <value>`).
+    pub value: Option<Expr>,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
 pub struct TryCatchStmt {
     pub try_block: Block,
@@ -317,6 +553,8 @@ pub enum TypeExpr {
     Function(FunctionType),
     Object(ObjectType),
     Promise(Box<TypeExpr>, Span),
+    /// A named type applied to type arguments: `Pair<int>`.
+    Generic(String, Vec<TypeExpr>, Span),
 }
 
 #[derive(Debug, Clone)]
@@ -348,7 +586,8 @@ pub enum Pattern {
     Struct(StructPattern),
     Enum(EnumPattern),
     Wildcard(Span),
-    Range(Box<Expr>, Box<Expr>, Span),
+    /// `lo..hi`, excluding `hi`, or `lo..=hi` when the flag is set.
+    Range(Box<Expr>, Box<Expr>, bool, Span),
 }
 
 #[derive(Debug, Clone)]
@@ -435,21 +674,38 @@ pub enum VarKind {
 #[derive(Debug, Clone)]
 pub struct FnDecl {
     pub name: String,
+    /// `fn process<T>(...)`
+    pub type_params: Vec<TypeParam>,
     pub params: Vec<Param>,
     pub return_type: Option<TypeExpr>,
+    /// `where T: Serializable`, after the return type.
+    pub where_clause: Vec<TypeConstraint>,
     pub body: Block,
     pub is_pub: bool,
     pub is_async: bool,
+    /// `const fn`: the body may only use constant operations.
+    pub is_const: bool,
     pub tool_annotation: Option<ToolAnnotation>,
     pub span: Span,
 }
 
+/// A bound in a `where` clause: `T: Serializable`. `T: A + B` is one
+/// constraint per bound.
+#[derive(Debug, Clone)]
+pub struct TypeConstraint {
+    pub type_param: String,
+    pub bound: TypeExpr,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
 pub struct Param {
     pub name: String,
     pub ty: Option<TypeExpr>,
     pub default: Option<Expr>,
     pub is_variadic: bool,
+    /// `mut self`: the method may assign through `self`.
+    pub is_mut: bool,
     pub span: Span,
 }
 
@@ -457,6 +713,9 @@ pub struct Param {
 pub struct StructDecl {
     pub name: String,
     pub fields: Vec<Field>,
+    /// Names listed in a `@derive(...)` annotation, e.g. `Eq`.
+    pub derives: Vec<Ident>,
+    pub is_pub: bool,
     pub span: Span,
 }
 
@@ -472,6 +731,9 @@ pub struct Field {
 pub struct EnumDecl {
     pub name: String,
     pub variants: Vec<Variant>,
+    /// Names listed in a `@derive(...)` annotation, e.g. `Eq`.
+    pub derives: Vec<Ident>,
+    pub is_pub: bool,
     pub span: Span,
 }
 
@@ -482,21 +744,78 @@ pub struct Variant {
     pub span: Span,
 }
 
+/// `interface Shape extends Named { fn area(self) -> num }`
+#[derive(Debug, Clone)]
+pub struct InterfaceDecl {
+    pub name: String,
+    pub type_params: Vec<TypeParam>,
+    pub methods: Vec<MethodSignature>,
+    pub extends: Vec<String>,
+    pub is_pub: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeParam {
+    pub name: String,
+    pub span: Span,
+}
+
+/// `impl Shape for Circle { ... }`, or `impl Circle { ... }` without an
+/// interface.
+#[derive(Debug, Clone)]
+pub struct ImplBlock {
+    pub interface: Option<String>,
+    pub target: String,
+    pub methods: Vec<FnDecl>,
+    pub span: Span,
+}
+
+impl ImplBlock {
+    /// Name of the top-level JS function a method compiles to, since
+    /// struct values are plain objects: `Circle_area`.
+    pub fn method_fn_name(target: &str, method: &str) -> String {
+        format!("{target}_{method}")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TypeAlias {
     pub name: String,
+    pub type_params: Vec<TypeParam>,
     pub ty: TypeExpr,
+    pub is_pub: bool,
     pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct Import {
     pub names: Vec<ImportName>,
-    pub path: String,
+    pub path: ImportPath,
     pub namespace: Option<String>,
     pub span: Span,
 }
 
+/// The module specifier of an `import`.
+#[derive(Debug, Clone)]
+pub enum ImportPath {
+    /// `"./fs"`, or a template without interpolation such as `` `./fs` ``.
+    Static(String),
+    /// A template with interpolation; reported by the parser, since static
+    /// imports are resolved before the program runs.
+    Dynamic(TemplateStringExpr),
+}
+
+impl ImportPath {
+    /// The specifier, unless it is computed.
+    pub fn as_static(&self) -> Option<&str> {
+        match self {
+            ImportPath::Static(path) => Some(path),
+            ImportPath::Dynamic(_) => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ImportName {
     pub name: String,
@@ -504,6 +823,20 @@ pub struct ImportName {
     pub span: Span,
 }
 
+/// `pub use { local as exported }` — re-exports names declared in this module.
+#[derive(Debug, Clone)]
+pub struct ExportAlias {
+    pub names: Vec<ExportName>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportName {
+    pub local: String,
+    pub exported: String,
+    pub span: Span,
+}
+
 // ── Block ──────────────────────────────────────────────────
 
 #[derive(Debug, Clone)]
@@ -519,10 +852,17 @@ pub struct Block {
 pub struct MatchArm {
     pub pattern: Pattern,
     pub guard: Option<Expr>,
-    pub body: Expr,
+    pub body: MatchArmBody,
     pub span: Span,
 }
 
+/// `0 => x * 2`, or `0 => { let y = compute()\n y * 2 }`.
+#[derive(Debug, Clone)]
+pub enum MatchArmBody {
+    Expr(Expr),
+    Block(Block),
+}
+
 // ── Operators ──────────────────────────────────────────────
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -541,6 +881,8 @@ pub enum BinaryOp {
     Ge,
     And,
     Or,
+    /// `x in xs`: membership in an array, string or map.
+    In,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -556,6 +898,7 @@ pub enum AssignOp {
     SubAssign,
     MulAssign,
     DivAssign,
+    ModAssign,
 }
 
 // ── Tool Schema IR ─────────────────────────────────────────
@@ -587,10 +930,119 @@ pub struct ToolSchemaInfo {
     pub params: Vec<(std::string::String, JsonSchema)>,
 }
 
+// ── Builtin Member IR ──────────────────────────────────────
+
+/// How codegen emits a builtin member access resolved by the checker.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemberRewrite {
+    /// Emit the member under its JS name: `s.len` → `s.length`.
+    Rename(std::string::String),
+    /// Emit a call with the receiver as first argument:
+    /// `m.keys()` → `Object.keys(m)`.
+    ReceiverCall(std::string::String),
+}
+
+/// How codegen emits an `in` expression on a known collection type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Membership {
+    /// `x in xs` on an array or string → `xs.includes(x)`.
+    Includes,
+    /// `k in m` on a map → `Object.hasOwn(m, k)`.
+    HasOwn,
+}
+
+/// Constructs the checker resolved that codegen emits differently from the
+/// source, one table per kind of node, each keyed by that node's span.
+#[derive(Debug, Clone, Default)]
+pub struct Rewrites {
+    /// Builtin and method member accesses, keyed by the member expression.
+    pub members: std::collections::HashMap<Span, MemberRewrite>,
+    /// Associated functions, emitted as the top-level function they compile
+    /// to (`Circle::unit` → `Circle_unit`), keyed by the member expression.
+    pub associated_fns: std::collections::HashMap<Span, std::string::String>,
+    /// `in` expressions on arrays, strings and maps.
+    pub membership: std::collections::HashMap<Span, Membership>,
+    /// `for` statements over a map, which iterate its keys with JS
+    /// `for...in`: `for k in m` → `for (const k in m)`.
+    pub for_in: std::collections::HashSet<Span>,
+    /// Object literals built with the factory that fills in their struct's
+    /// field defaults, with the struct's name:
+    /// `let c: Config = { verbose: true }` → `Config({ verbose: true })`.
+    pub struct_literals: std::collections::HashMap<Span, std::string::String>,
+    /// Import specifiers naming a type with no runtime value (a type alias,
+    /// an interface or a struct without a factory), which codegen drops,
+    /// keyed by the specifier.
+    pub type_imports: std::collections::HashSet<Span>,
+}
+
 // ── Diagnostic ─────────────────────────────────────────────
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Information that needs no action.
+    Note,
+}
+
 #[derive(Debug, Clone)]
 pub struct Diagnostic {
     pub message: String,
     pub span: Span,
+    pub severity: Severity,
+    /// Secondary locations that explain the diagnostic, e.g. the
+    /// declaration a use site conflicts with.
+    pub labels: Vec<Label>,
+    /// Name of the lint that produced the diagnostic, e.g. `unreachable-code`;
+    /// lints can be silenced or promoted to errors.
+    pub code: Option<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            message: message.into(),
+            span,
+            severity: Severity::Error,
+            labels: Vec::new(),
+            code: None,
+        }
+    }
+
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(message, span)
+        }
+    }
+
+    pub fn note(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Note,
+            ..Diagnostic::error(message, span)
+        }
+    }
+
+    pub fn with_label(mut self, message: impl Into<String>, span: Span) -> Self {
+        self.labels.push(Label {
+            message: message.into(),
+            span,
+        });
+        self
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
 }
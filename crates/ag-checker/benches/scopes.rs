@@ -0,0 +1,60 @@
+//! Checks programs with deeply nested scopes, where symbol lookups and
+//! scope entry dominate. Run with `cargo bench -p ag-checker`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// `depth` nested blocks, each declaring a variable and reading the
+/// outermost ones.
+fn nested_blocks(depth: usize) -> String {
+    let mut src = String::from("let base: int = 0\nfn f() -> int {\n");
+    for i in 0..depth {
+        src.push_str(&format!("let v{i}: int = base + {i}\nif v{i} > 0 {{\n"));
+    }
+    src.push_str("base\n");
+    for _ in 0..depth {
+        src.push_str("}\n");
+    }
+    src.push_str("base\n}\n");
+    src
+}
+
+/// `count` closures nested inside one another, each capturing a parameter
+/// of the enclosing one.
+fn nested_arrows(count: usize) -> String {
+    let mut src = String::from("let f = ");
+    for i in 0..count {
+        src.push_str(&format!("(a{i}: int) => "));
+    }
+    let sum: Vec<String> = (0..count).map(|i| format!("a{i}")).collect();
+    src.push_str(&sum.join(" + "));
+    src.push('\n');
+    src
+}
+
+fn bench(name: &str, src: &str) {
+    let parsed = ag_parser::parse(src);
+    assert!(parsed.diagnostics.is_empty(), "{name}: {:?}", parsed.diagnostics);
+    let module = parsed.module;
+
+    // Warm up, then time enough iterations to fill about a second.
+    let start = Instant::now();
+    let mut iterations = 0u32;
+    while start.elapsed() < Duration::from_millis(200) {
+        black_box(ag_checker::check(black_box(&module)));
+        iterations += 1;
+    }
+    let iterations = (iterations * 5).max(1);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        black_box(ag_checker::check(black_box(&module)));
+    }
+    let per_iter = start.elapsed() / iterations;
+    println!("{name:<24} {per_iter:>12.2?}/check ({iterations} iterations)");
+}
+
+fn main() {
+    bench("nested_blocks/100", &nested_blocks(100));
+    bench("nested_blocks/400", &nested_blocks(400));
+    bench("nested_arrows/100", &nested_arrows(100));
+}
@@ -0,0 +1,109 @@
+//! Built-in member signatures for primitive and collection types.
+//!
+//! These back member access on values that have no declaration of their own
+//! (arrays, strings, ...). Each member maps onto the JS runtime API; members
+//! spelled differently in ag-lang (snake_case, `len`) carry the JS name that
+//! codegen must emit instead.
+
+use crate::{FnParam, Type};
+
+/// A builtin member: its JS name and its type.
+pub(crate) type BuiltinMember = (&'static str, Type);
+
+fn func(params: Vec<Type>, ret: Type) -> Type {
+    func_opt(params, vec![], ret)
+}
+
+/// A function whose trailing `optional` params may be omitted, like
+/// `separator` in `join`.
+fn func_opt(required: Vec<Type>, optional: Vec<Type>, ret: Type) -> Type {
+    let optional = optional.into_iter().map(|ty| FnParam {
+        optional: true,
+        ..FnParam::required(ty)
+    });
+    let params = required.into_iter().map(FnParam::required).chain(optional).collect();
+    Type::Function(params, Box::new(ret))
+}
+
+fn array_of(elem: Type) -> Type {
+    Type::Array(Box::new(elem))
+}
+
+/// Member `name` on an array with element type `elem`, or `None` if arrays
+/// have no such member.
+///
+/// Methods whose result depends on a callback (`map`) are typed with an
+/// `any` placeholder here; see [`refine_array_method_return`].
+pub(crate) fn array_member(elem: &Type, name: &str) -> Option<BuiltinMember> {
+    let t = || elem.clone();
+    let predicate = || func(vec![t()], Type::Bool);
+    let member = match name {
+        "len" => ("length", Type::Int),
+        "push" => ("push", Type::VariadicFunction(vec![t()], Box::new(Type::Int))),
+        "pop" => ("pop", func(vec![], Type::Nullable(Box::new(t())))),
+        "map" => ("map", func(vec![func(vec![t()], Type::Any)], array_of(Type::Any))),
+        "filter" => ("filter", func(vec![predicate()], array_of(t()))),
+        "find" => ("find", func(vec![predicate()], Type::Nullable(Box::new(t())))),
+        "includes" => ("includes", func(vec![t()], Type::Bool)),
+        "join" => ("join", func_opt(vec![], vec![Type::Str], Type::Str)),
+        "slice" => ("slice", func_opt(vec![], vec![Type::Int, Type::Int], array_of(t()))),
+        "concat" => ("concat", func(vec![array_of(t())], array_of(t()))),
+        _ => return None,
+    };
+    Some(member)
+}
+
+/// Refine the return type of an array method call using the argument types
+/// seen at the call site, e.g. `map: ((T) -> U) -> [U]`.
+pub(crate) fn refine_array_method_return(name: &str, arg_types: &[Type]) -> Option<Type> {
+    match (name, arg_types.first()) {
+        ("map", Some(Type::Function(_, ret))) => Some(array_of((**ret).clone())),
+        _ => None,
+    }
+}
+
+/// Member `name` on a map with key type `key` and value type `value`, or
+/// `None` if maps have no such member.
+///
+/// Maps are plain JS objects at runtime, so every member is emitted as a
+/// call of the returned function with the map as first argument
+/// (`m.keys()` → `Object.keys(m)`).
+pub(crate) fn map_member(key: &Type, value: &Type, name: &str) -> Option<(&'static str, Type)> {
+    let member = match name {
+        "keys" => ("Object.keys", func(vec![], array_of(key.clone()))),
+        "values" => ("Object.values", func(vec![], array_of(value.clone()))),
+        "has" => ("Object.hasOwn", func(vec![key.clone()], Type::Bool)),
+        _ => return None,
+    };
+    Some(member)
+}
+
+/// Member `name` on a string, or `None` if strings have no such member.
+///
+/// Methods use the snake_case form of the JS name (`starts_with` for
+/// `startsWith`).
+pub(crate) fn str_member(name: &str) -> Option<BuiltinMember> {
+    let s = || Type::Str;
+    let member = match name {
+        "len" => ("length", Type::Int),
+        "split" => ("split", func_opt(vec![], vec![s()], array_of(s()))),
+        "trim" => ("trim", func(vec![], s())),
+        "trim_start" => ("trimStart", func(vec![], s())),
+        "trim_end" => ("trimEnd", func(vec![], s())),
+        "includes" => ("includes", func(vec![s()], Type::Bool)),
+        "starts_with" => ("startsWith", func(vec![s()], Type::Bool)),
+        "ends_with" => ("endsWith", func(vec![s()], Type::Bool)),
+        "index_of" => ("indexOf", func(vec![s()], Type::Int)),
+        "to_upper_case" => ("toUpperCase", func(vec![], s())),
+        "to_lower_case" => ("toLowerCase", func(vec![], s())),
+        "slice" => ("slice", func_opt(vec![Type::Int], vec![Type::Int], s())),
+        "replace" => ("replace", func(vec![s(), s()], s())),
+        "replace_all" => ("replaceAll", func(vec![s(), s()], s())),
+        "repeat" => ("repeat", func(vec![Type::Int], s())),
+        "pad_start" => ("padStart", func_opt(vec![Type::Int], vec![s()], s())),
+        "pad_end" => ("padEnd", func_opt(vec![Type::Int], vec![s()], s())),
+        "char_at" => ("charAt", func(vec![Type::Int], s())),
+        _ => return None,
+    };
+    Some(member)
+}
@@ -0,0 +1,290 @@
+//! Validation of `const fn` bodies: they may only compute values from their
+//! parameters, `const` bindings and other `const fn`s, so that calls can be
+//! evaluated at compile time.
+
+use std::collections::HashSet;
+
+use ag_ast::*;
+
+use crate::{assign_root, Checker};
+
+/// Walks a `const fn` body, collecting the non-constant operations in it.
+pub(crate) struct ConstFnValidator<'a> {
+    checker: &'a Checker,
+    /// Name of the function, for messages.
+    name: &'a str,
+    /// Bindings local to the body, innermost scope last. Parameters are
+    /// tracked separately in `params`, as they may not be called.
+    locals: Vec<HashSet<String>>,
+    params: Vec<HashSet<String>>,
+    pub(crate) errors: Vec<(String, Span)>,
+}
+
+impl<'a> ConstFnValidator<'a> {
+    pub(crate) fn new(checker: &'a Checker, f: &'a FnDecl) -> Self {
+        Self {
+            checker,
+            name: &f.name,
+            locals: vec![HashSet::new()],
+            params: vec![f.params.iter().map(|p| p.name.clone()).collect()],
+            errors: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, msg: String, span: Span) {
+        self.errors.push((format!("const fn `{}`: {msg}", self.name), span));
+    }
+
+    fn bind(&mut self, name: &str) {
+        self.locals.last_mut().expect("body scope").insert(name.to_string());
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.locals.iter().chain(&self.params).any(|names| names.contains(name))
+    }
+
+    fn is_param(&self, name: &str) -> bool {
+        // The innermost binding wins: a `let` may shadow a parameter.
+        for (locals, params) in self.locals.iter().zip(&self.params).rev() {
+            if locals.contains(name) {
+                return false;
+            }
+            if params.contains(name) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn enter(&mut self, params: HashSet<String>) {
+        self.locals.push(HashSet::new());
+        self.params.push(params);
+    }
+
+    fn leave(&mut self) {
+        self.locals.pop();
+        self.params.pop();
+    }
+
+    pub(crate) fn block(&mut self, block: &Block) {
+        self.enter(HashSet::new());
+        for stmt in &block.stmts {
+            self.stmt(stmt);
+        }
+        if let Some(tail) = &block.tail_expr {
+            self.expr(tail);
+        }
+        self.leave();
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::VarDecl(v) => {
+                self.expr(&v.init);
+                self.bind(&v.name);
+            }
+            Stmt::ExprStmt(e) => self.expr(&e.expr),
+            Stmt::Return(r) => {
+                if let Some(value) = &r.value {
+                    self.expr(value);
+                }
+            }
+            Stmt::If(i) => self.if_expr(i),
+            Stmt::For(f) => {
+                // Iterating a finite collection always terminates
+                self.expr(&f.iter);
+                self.enter(HashSet::new());
+                self.bind(&f.binding);
+                self.block(&f.body);
+                self.leave();
+            }
+            Stmt::ForRange(fr) => {
+                self.error("C-style `for` loops are not allowed, as they may not terminate".to_string(), fr.span);
+            }
+            Stmt::While(w) => {
+                self.error("`while` loops are not allowed, as they may not terminate".to_string(), w.span);
+            }
+            Stmt::Loop(l) => {
+                self.error("`loop` is not allowed, as it may not terminate".to_string(), l.span);
+            }
+            Stmt::Break(b) => {
+                if let Some(value) = &b.value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Match(m) => self.match_expr(m),
+            Stmt::TryCatch(tc) => {
+                self.block(&tc.try_block);
+                self.enter(HashSet::new());
+                self.bind(&tc.catch_binding);
+                self.block(&tc.catch_block);
+                self.leave();
+            }
+        }
+    }
+
+    fn if_expr(&mut self, i: &IfExpr) {
+        self.expr(&i.condition);
+        self.block(&i.then_block);
+        match &i.else_branch {
+            Some(ElseBranch::Block(b)) => self.block(b),
+            Some(ElseBranch::If(i)) => self.if_expr(i),
+            None => {}
+        }
+    }
+
+    fn match_expr(&mut self, m: &MatchExpr) {
+        self.expr(&m.subject);
+        for arm in &m.arms {
+            self.enter(HashSet::new());
+            match &arm.pattern {
+                Pattern::Ident(name, _) => self.bind(name),
+                Pattern::Struct(sp) => sp.fields.iter().for_each(|f| self.bind(f)),
+                Pattern::Enum(ep) => ep.bindings.iter().for_each(|b| self.bind(b)),
+                Pattern::Range(lo, hi, _, _) => {
+                    self.expr(lo);
+                    self.expr(hi);
+                }
+                Pattern::Literal(_) | Pattern::Wildcard(_) => {}
+            }
+            if let Some(guard) = &arm.guard {
+                self.expr(guard);
+            }
+            match &arm.body {
+                MatchArmBody::Expr(e) => self.expr(e),
+                MatchArmBody::Block(b) => self.block(b),
+            }
+            self.leave();
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(_) | Expr::Placeholder(_) => {}
+            Expr::Ident(ident) => self.read(&ident.name, ident.span),
+            Expr::Binary(b) => {
+                self.expr(&b.left);
+                self.expr(&b.right);
+            }
+            Expr::Comparison(c) => c.operands.iter().for_each(|operand| self.expr(operand)),
+            Expr::Unary(u) => self.expr(&u.operand),
+            Expr::Call(call) => {
+                self.callee(&call.callee, call.span);
+                call.args.iter().for_each(|arg| self.expr(arg));
+            }
+            Expr::Member(m) => {
+                // `Enum::Variant` names a value; other members read a field
+                if !self.is_enum(&m.object) {
+                    self.expr(&m.object);
+                }
+            }
+            Expr::Index(i) => {
+                self.expr(&i.object);
+                self.expr(&i.index);
+            }
+            Expr::If(i) => self.if_expr(i),
+            Expr::Match(m) => self.match_expr(m),
+            Expr::Loop(l) => {
+                self.error("`loop` is not allowed, as it may not terminate".to_string(), l.span);
+            }
+            Expr::Block(b) => self.block(b),
+            Expr::Array(a) => a.elements.iter().for_each(|e| self.expr(e)),
+            Expr::Object(o) => o.fields.iter().for_each(|f| self.expr(&f.value)),
+            Expr::Arrow(arrow) => {
+                self.enter(arrow.params.iter().map(|p| p.name.clone()).collect());
+                match &arrow.body {
+                    ArrowBody::Expr(e) => self.expr(e),
+                    ArrowBody::Block(b) => self.block(b),
+                }
+                self.leave();
+            }
+            Expr::Pipe(p) => {
+                self.expr(&p.left);
+                match &p.right {
+                    Expr::Call(call) => {
+                        self.callee(&call.callee, call.span);
+                        call.args.iter().for_each(|arg| self.expr(arg));
+                    }
+                    other => self.callee(other, p.span),
+                }
+            }
+            Expr::OptionalChain(oc) => self.expr(&oc.object),
+            Expr::NullishCoalesce(nc) => {
+                self.expr(&nc.left);
+                self.expr(&nc.right);
+            }
+            Expr::Await(a) => self.error("`await` is not allowed".to_string(), a.span),
+            Expr::ErrorPropagate(ep) => self.expr(&ep.expr),
+            Expr::Assign(a) => {
+                match assign_root(&a.target) {
+                    Some(root) if self.is_local(&root.name) => {}
+                    Some(root) => self.error(
+                        format!("cannot assign to `{}`, which is not a local binding", root.name),
+                        a.span,
+                    ),
+                    None => {}
+                }
+                self.expr(&a.target);
+                self.expr(&a.value);
+            }
+            Expr::TemplateString(t) => self.template(t),
+            Expr::TaggedTemplate(t) => {
+                self.callee(&t.tag, t.span);
+                self.template(&t.template);
+            }
+        }
+    }
+
+    fn template(&mut self, t: &TemplateStringExpr) {
+        for part in &t.parts {
+            if let TemplatePart::Expr(e) = part {
+                self.expr(e);
+            }
+        }
+    }
+
+    /// Reading `name` as a value.
+    fn read(&mut self, name: &str, span: Span) {
+        if self.is_local(name) {
+            return;
+        }
+        let checker = self.checker;
+        let constant = checker.const_fns.contains_key(name)
+            || checker.const_globals.contains(name)
+            || checker.module_symbol(name).is_some_and(|sym| sym.is_enum_named(name));
+        if !constant {
+            self.error(format!("cannot use `{name}`, which is not constant"), span);
+        }
+    }
+
+    /// Calling `callee` (at `span`).
+    fn callee(&mut self, callee: &Expr, span: Span) {
+        match callee {
+            Expr::Ident(ident) if self.is_param(&ident.name) => {
+                self.error(format!("cannot call parameter `{}`", ident.name), span);
+            }
+            Expr::Ident(ident) if self.is_local(&ident.name) => {}
+            Expr::Ident(ident) => {
+                if !self.checker.const_fns.contains_key(&ident.name) {
+                    self.error(format!("cannot call `{}`, which is not a `const fn`", ident.name), span);
+                }
+            }
+            // Enum variant constructor
+            Expr::Member(m) if self.is_enum(&m.object) => {}
+            Expr::Member(m) => {
+                self.expr(&m.object);
+                self.error(format!("cannot call method `{}`", m.field), span);
+            }
+            other => {
+                self.expr(other);
+                self.error("cannot call a computed function".to_string(), span);
+            }
+        }
+    }
+
+    fn is_enum(&self, expr: &Expr) -> bool {
+        matches!(expr, Expr::Ident(ident)
+            if !self.is_local(&ident.name)
+                && self.checker.module_symbol(&ident.name).is_some_and(|sym| sym.is_enum_named(&ident.name)))
+    }
+}
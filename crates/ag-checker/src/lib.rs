@@ -1,6 +1,13 @@
 use ag_ast::*;
-use ag_dsl_core::DslPart as CoreDslPart;
-use std::collections::HashMap;
+use ag_dsl_core::{remove_directive, DslDiagnostic, DslHandler, DslPart as CoreDslPart, DslSeverity};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+
+mod builtins;
+mod const_fn;
+mod project;
+
+pub use project::{check_project, FileLoader, ModuleGraph, ProjectModule};
 
 // ── Type representation ────────────────────────────────────
 
@@ -16,11 +23,19 @@ pub enum Type {
     Map(Box<Type>, Box<Type>),
     Nullable(Box<Type>),
     Union(Box<Type>, Box<Type>),
-    Function(Vec<Type>, Box<Type>),
+    Function(Vec<FnParam>, Box<Type>),
     Struct(String, Vec<(String, Type)>),
+    /// Anonymous object type, e.g. of an object literal.
+    Object(Vec<(String, Type)>),
     Enum(String, Vec<(String, Vec<(String, Type)>)>),
     Promise(Box<Type>),
     VariadicFunction(Vec<Type>, Box<Type>), // fixed params + variadic element type as last
+    /// Type of expressions that never produce a value, e.g. `loop {}`
+    /// without a `break`.
+    Never,
+    /// A type parameter bounded by interfaces in a `where` clause, e.g. the
+    /// `T` of `where T: Named`.
+    Param(String, Vec<String>),
     Unknown,
 }
 
@@ -38,61 +53,153 @@ impl std::fmt::Display for Type {
             Type::Nullable(t) => write!(f, "{t}?"),
             Type::Union(a, b) => write!(f, "{a} | {b}"),
             Type::Function(params, ret) => {
-                let ps: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+                let ps: Vec<String> = params.iter().map(|p| p.ty.to_string()).collect();
                 write!(f, "({}) -> {ret}", ps.join(", "))
             }
             Type::Struct(name, _) => write!(f, "{name}"),
+            Type::Object(fields) if fields.is_empty() => write!(f, "{{}}"),
+            Type::Object(fields) => {
+                let fs: Vec<String> = fields.iter().map(|(n, t)| format!("{n}: {t}")).collect();
+                write!(f, "{{ {} }}", fs.join(", "))
+            }
             Type::Enum(name, _) => write!(f, "{name}"),
             Type::Promise(inner) => write!(f, "Promise<{inner}>"),
             Type::VariadicFunction(params, ret) => {
                 let ps: Vec<String> = params.iter().map(|p| p.to_string()).collect();
                 write!(f, "({}, ...) -> {ret}", ps.join(", "))
             }
+            Type::Never => write!(f, "never"),
+            Type::Param(name, _) => write!(f, "{name}"),
             Type::Unknown => write!(f, "unknown"),
         }
     }
 }
 
+/// A parameter of a function type.
+///
+/// `name` is known for declared functions and arrows and is used in arity
+/// diagnostics; `optional` parameters have a default and may be omitted.
+/// Names are not part of the type: parameters are equal when their types
+/// and optionality are.
+#[derive(Debug, Clone)]
+pub struct FnParam {
+    pub name: Option<String>,
+    pub ty: Type,
+    pub optional: bool,
+}
+
+impl PartialEq for FnParam {
+    fn eq(&self, other: &Self) -> bool {
+        self.ty == other.ty && self.optional == other.optional
+    }
+}
+
+impl FnParam {
+    /// An unnamed parameter that must be supplied.
+    pub fn required(ty: Type) -> Self {
+        FnParam {
+            name: None,
+            ty,
+            optional: false,
+        }
+    }
+}
+
 // ── Symbol table ───────────────────────────────────────────
 
 #[derive(Debug, Clone)]
 struct Symbol {
     ty: Type,
     mutable: bool,
+    /// Declared with `let`/`const`: nothing reachable through the binding
+    /// may be assigned either. Parameters are immutable but not frozen.
+    frozen: bool,
+    /// Index into `Checker::mut_decls` for `mut` bindings.
+    mut_decl: Option<usize>,
+    /// Index into `Checker::local_decls` for local variables.
+    local_decl: Option<usize>,
+    /// Value of a `const` whose initializer folds to a constant.
+    constant: Option<Const>,
+    /// A `const` binding or `const fn`, usable in `const` initializers.
+    is_const: bool,
+    /// Span of the declaration, for go-to-definition.
+    def: Span,
 }
 
-struct Scope {
-    symbols: HashMap<String, Symbol>,
-    parent: Option<Box<Scope>>,
+/// A compile-time value, for folding conditions.
+#[derive(Debug, Clone, PartialEq)]
+enum Const {
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Nil,
 }
 
-impl Scope {
-    fn new() -> Self {
-        Self {
-            symbols: HashMap::new(),
-            parent: None,
+impl Symbol {
+    /// An immutable, non-constant binding of type `ty` declared at `def`.
+    fn new(ty: Type, def: Span) -> Self {
+        Symbol {
+            ty,
+            mutable: false,
+            frozen: false,
+            mut_decl: None,
+            local_decl: None,
+            constant: None,
+            is_const: false,
+            def,
         }
     }
 
-    fn child(parent: Scope) -> Self {
+    /// Whether this is the symbol of the enum `name` itself, rather than
+    /// a value of an enum type.
+    fn is_enum_named(&self, name: &str) -> bool {
+        matches!(&self.ty, Type::Enum(enum_name, _) if enum_name == name)
+    }
+}
+
+/// Lexical scopes, innermost last; the first holds module-level symbols.
+struct Scope {
+    frames: Vec<HashMap<String, Symbol>>,
+}
+
+/// The scope depth to return to, from [`Checker::push_scope`].
+#[must_use]
+struct ScopeMark(usize);
+
+impl Scope {
+    fn new() -> Self {
         Self {
-            symbols: HashMap::new(),
-            parent: Some(Box::new(parent)),
+            frames: vec![HashMap::new()],
         }
     }
 
     fn define(&mut self, name: &str, sym: Symbol) -> bool {
-        if self.symbols.contains_key(name) {
+        let symbols = self.frames.last_mut().expect("module scope is never popped");
+        if symbols.contains_key(name) {
             return false; // duplicate
         }
-        self.symbols.insert(name.to_string(), sym);
+        symbols.insert(name.to_string(), sym);
         true
     }
 
     fn lookup(&self, name: &str) -> Option<&Symbol> {
-        self.symbols
-            .get(name)
-            .or_else(|| self.parent.as_ref().and_then(|p| p.lookup(name)))
+        self.frames.iter().rev().find_map(|symbols| symbols.get(name))
+    }
+
+    /// The binding `name` would shadow if defined in the innermost scope:
+    /// one from an enclosing scope, but not the innermost scope itself.
+    fn shadowed(&self, name: &str) -> Option<&Symbol> {
+        let (innermost, outer) = self.frames.split_last().expect("module scope is never popped");
+        if innermost.contains_key(name) {
+            return None;
+        }
+        outer.iter().rev().find_map(|symbols| symbols.get(name))
+    }
+
+    /// Whether `name` resolves to a module-level binding rather than a
+    /// local shadowing it.
+    fn is_module_level(&self, name: &str) -> bool {
+        self.frames.iter().rposition(|symbols| symbols.contains_key(name)) == Some(0)
     }
 }
 
@@ -105,7 +212,7 @@ pub fn type_to_json_schema(ty: &Type) -> JsonSchema {
         Type::Int => JsonSchema::Integer,
         Type::Bool => JsonSchema::Boolean,
         Type::Nil => JsonSchema::Null,
-        Type::Any | Type::Unknown => JsonSchema::Any,
+        Type::Any | Type::Never | Type::Param(..) | Type::Unknown => JsonSchema::Any,
         Type::Array(inner) => JsonSchema::Array(Box::new(type_to_json_schema(inner))),
         Type::Map(_key, value) => JsonSchema::Object {
             properties: vec![],
@@ -119,7 +226,7 @@ pub fn type_to_json_schema(ty: &Type) -> JsonSchema {
             collect_union_json_schemas(b, &mut schemas);
             JsonSchema::AnyOf(schemas)
         }
-        Type::Struct(_, fields) => {
+        Type::Struct(_, fields) | Type::Object(fields) => {
             let properties: Vec<(std::string::String, JsonSchema)> = fields
                 .iter()
                 .map(|(name, ty)| (name.clone(), type_to_json_schema(ty)))
@@ -152,26 +259,411 @@ fn collect_union_json_schemas(ty: &Type, out: &mut Vec<JsonSchema>) {
 
 // ── Checker ────────────────────────────────────────────────
 
+impl ag_dsl_core::ValidateContext for Checker {
+    fn is_type(&self, name: &str) -> bool {
+        self.is_valid_type_name(name)
+    }
+}
+
 pub struct Checker {
     scope: Scope,
     pub diagnostics: Vec<Diagnostic>,
     type_aliases: HashMap<String, Type>,
+    /// Type aliases with type parameters (`type Pair<T> = ...`), resolved
+    /// anew for each instantiation.
+    generic_aliases: HashMap<String, TypeAlias>,
+    /// Type arguments of the generic alias being instantiated, by parameter
+    /// name.
+    alias_args: HashMap<String, Type>,
     pub tool_registry: HashMap<String, ToolSchemaInfo>,
+    pub rewrites: Rewrites,
+    /// See [`CheckResult::variant_fields`].
+    variant_fields: HashMap<(String, String), Vec<String>>,
     in_async: bool,
+    /// Type of the value piped into the call being checked, which its `_`
+    /// placeholders stand for (`a |> f(_, x)`).
+    pipe_input: Option<Type>,
+    /// One entry per enclosing loop (innermost last) holding its `break`s:
+    /// the type of the break value, if any, and the break's span. Empty
+    /// outside loops.
+    loop_breaks: Vec<Vec<(Option<Type>, Span)>>,
+    /// Declared return type of the enclosing `fn`, which `ret` values must
+    /// match; for an `async fn`, the awaited type. `None` in arrows and
+    /// functions without an annotation.
+    return_type: Option<Type>,
+    /// Declarations of user structs, for field defaults and diagnostics.
+    struct_decls: HashMap<String, StructDecl>,
+    /// Declarations of user enums, for diagnostics.
+    enum_decls: HashMap<String, EnumDecl>,
+    /// Interface declarations, for checking `impl` blocks against.
+    interfaces: HashMap<String, InterfaceDecl>,
+    /// Methods and associated functions from `impl` blocks, keyed by the
+    /// struct they implement.
+    impl_methods: HashMap<String, Vec<ImplMethod>>,
+    /// Every type name declared in the module, collected up front so that
+    /// forward references are not reported as unknown types.
+    type_names: HashSet<String>,
+    /// Type parameters in scope (e.g. the `T` of `interface Box<T>`).
+    type_params: Vec<String>,
+    /// The interfaces a `where` clause bounds each type parameter by.
+    type_bounds: HashMap<String, Vec<String>>,
+    /// Spans of declaration errors already reported (unknown type names,
+    /// duplicate names); a declaration may be resolved more than once.
+    reported_spans: HashSet<Span>,
+    /// The first `@js` extern fn importing each JS name, keyed by module and
+    /// JS name, with its type: others importing it again should agree.
+    js_bindings: HashMap<(String, String), (String, Type, Span)>,
+    /// `mut` bindings declared so far, for the "never mutated" warning.
+    mut_decls: Vec<MutDecl>,
+    /// Local variables declared so far, for the "never used" warning.
+    local_decls: Vec<LocalDecl>,
+    /// Top-level bindings whose declaration has not been checked yet, with
+    /// their spans: module code referencing one runs before it is defined.
+    pending_globals: HashMap<String, Span>,
+    /// Number of arrow bodies around the current expression. They run when
+    /// called, so they may reference bindings declared further down.
+    arrow_depth: usize,
+    /// Filled in when `options.record_types` is set.
+    type_map: Option<TypeMap>,
+    /// Public symbols of the modules this one imports, keyed by import path
+    /// as written. Imports of modules not listed introduce `any` bindings.
+    imported: HashMap<String, ModuleExports>,
+    /// Top-level `const fn` declarations, for validating and folding calls.
+    const_fns: HashMap<String, FnDecl>,
+    /// `const fn` calls made folding the current expression, against
+    /// [`MAX_CONST_CALLS`].
+    const_calls: Cell<usize>,
+    /// Names of top-level `const` bindings.
+    const_globals: HashSet<String>,
+    /// Handlers validating DSL blocks, keyed by kind.
+    dsl_handlers: DslHandlers,
+    options: CheckerOptions,
+}
+
+/// Whether `expr` may initialize a `const`: it is built from literals,
+/// `const` bindings, enum variants and calls to `const fn`s, combined with
+/// operators, collection literals, templates and member access.
+fn is_const_expr(expr: &Expr, scope: &Scope) -> bool {
+    // The enum itself, not a value of its type
+    let is_enum = |expr: &Expr| {
+        matches!(expr, Expr::Ident(id)
+            if scope.is_module_level(&id.name)
+                && scope.lookup(&id.name).is_some_and(|sym| sym.is_enum_named(&id.name)))
+    };
+    match expr {
+        Expr::Literal(_) => true,
+        Expr::Ident(id) => scope.lookup(&id.name).is_some_and(|sym| sym.is_const),
+        Expr::Unary(u) => is_const_expr(&u.operand, scope),
+        Expr::Binary(b) => is_const_expr(&b.left, scope) && is_const_expr(&b.right, scope),
+        Expr::Comparison(c) => c.operands.iter().all(|operand| is_const_expr(operand, scope)),
+        Expr::NullishCoalesce(nc) => is_const_expr(&nc.left, scope) && is_const_expr(&nc.right, scope),
+        Expr::Array(a) => a.elements.iter().all(|e| is_const_expr(e, scope)),
+        Expr::Object(o) => o.fields.iter().all(|f| is_const_expr(&f.value, scope)),
+        Expr::TemplateString(t) => {
+            t.parts.iter().all(|part| !matches!(part, TemplatePart::Expr(e) if !is_const_expr(e, scope)))
+        }
+        // `Enum::Variant`, or a field of a constant
+        Expr::Member(m) => is_enum(&m.object) || is_const_expr(&m.object, scope),
+        Expr::Index(i) => is_const_expr(&i.object, scope) && is_const_expr(&i.index, scope),
+        // A `const fn` call or enum variant constructor
+        Expr::Call(call) => {
+            let callee = match call.callee.as_ref() {
+                Expr::Ident(id) => scope.lookup(&id.name).is_some_and(|sym| sym.is_const),
+                Expr::Member(m) => is_enum(&m.object),
+                _ => false,
+            };
+            callee && call.args.iter().all(|arg| is_const_expr(arg, scope))
+        }
+        _ => false,
+    }
+}
+
+/// A function declared in an `impl` block.
+struct ImplMethod {
+    name: String,
+    /// The type callers see, without `self`.
+    ty: Type,
+    /// Whether it is a method, called on a value, rather than an
+    /// associated function, called through the struct.
+    takes_self: bool,
+    span: Span,
+}
+
+/// A `mut` binding and whether anything assigns to (or through) it.
+struct MutDecl {
+    name: String,
+    span: Span,
+    mutated: bool,
+}
+
+/// A local variable and whether anything reads it.
+struct LocalDecl {
+    name: String,
+    span: Span,
+    used: bool,
 }
 
 pub struct CheckResult {
+    /// Errors and warnings, in source order.
     pub diagnostics: Vec<Diagnostic>,
     pub tool_registry: HashMap<String, ToolSchemaInfo>,
+    /// Constructs that codegen must emit differently from the source, keyed
+    /// by span (e.g. `s.len` → `s.length`, `m.keys()` → `Object.keys(m)`).
+    pub rewrites: Rewrites,
+    /// Field names of the enum variants that patterns match, in declaration
+    /// order, keyed by enum and variant name; codegen binds pattern
+    /// positions to them, also for enums imported from other modules.
+    pub variant_fields: HashMap<(String, String), Vec<String>>,
+    /// Present when [`CheckerOptions::record_types`] is set.
+    pub type_map: Option<TypeMap>,
+}
+
+/// Switches for optional, stricter checks; the defaults are lenient.
+#[derive(Debug, Clone, Default)]
+pub struct CheckerOptions {
+    /// Warn when a parameter or binding ends up `any` without being
+    /// annotated as such (an untyped parameter, `let xs = []`, ...).
+    pub strict_implicit_any: bool,
+    /// Level of each lint, keyed by name (see [`LINTS`]); lints not listed
+    /// use their [`default_lint_level`].
+    pub lint_levels: HashMap<String, LintLevel>,
+    /// Record the inferred types and resolved definitions into
+    /// [`CheckResult::type_map`], for editor tooling.
+    pub record_types: bool,
+    /// Whether module code may `await`.
+    pub top_level_await: TopLevelAwait,
+}
+
+/// Types and definitions computed while checking, keyed by source span.
+#[derive(Debug, Clone, Default)]
+pub struct TypeMap {
+    /// Type of every checked expression.
+    pub types: HashMap<ExprKey, Type>,
+    /// Type of each variable and parameter, keyed by its declaration's span.
+    pub decl_types: HashMap<Span, Type>,
+    /// Span of the declaration each identifier resolves to, keyed by the
+    /// identifier's span.
+    pub definitions: HashMap<Span, Span>,
+}
+
+/// An expression's entry in a [`TypeMap`]: its full extent (see
+/// [`Expr::full_span`]) and its kind, since `x not in y` is a `!` and an
+/// `in` over the same source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprKey {
+    pub span: Span,
+    pub kind: std::mem::Discriminant<Expr>,
+}
+
+impl ExprKey {
+    pub fn of(expr: &Expr) -> Self {
+        ExprKey { span: expr.full_span(), kind: std::mem::discriminant(expr) }
+    }
+}
+
+impl TypeMap {
+    /// The type of `expr`.
+    pub fn type_of(&self, expr: &Expr) -> Option<&Type> {
+        self.types.get(&ExprKey::of(expr))
+    }
+
+    /// The type of the variable or parameter declared at exactly `span`.
+    pub fn decl_type(&self, span: Span) -> Option<&Type> {
+        self.decl_types.get(&span)
+    }
+
+    /// The declaration the identifier at `span` refers to.
+    pub fn definition_of(&self, span: Span) -> Option<Span> {
+        self.definitions.get(&span).copied()
+    }
+}
+
+/// How a lint is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    /// Not reported.
+    Allow,
+    /// Reported as a warning.
+    Warn,
+    /// Reported as an error.
+    Deny,
+}
+
+/// How many nested `const fn` calls are folded before giving up.
+const MAX_CONST_CALL_DEPTH: usize = 64;
+/// How many `const fn` calls folding one expression may make in all;
+/// `fib(32)` is well within the depth limit but takes millions.
+const MAX_CONST_CALLS: usize = 10_000;
+
+const UNREACHABLE_CODE: &str = "unreachable-code";
+const DUPLICATE_KEY: &str = "duplicate-key";
+const UNKNOWN_FIELD: &str = "unknown-field";
+const UNUSED_MUT: &str = "unused-mut";
+const UNUSED_VARIABLE: &str = "unused-variable";
+const IMPLICIT_ANY: &str = "implicit-any";
+const CONSTANT_CONDITION: &str = "constant-condition";
+const WHILE_TRUE: &str = "while-true";
+const DISJOINT_COMPARISON: &str = "disjoint-comparison";
+const FOR_IN_ARRAY: &str = "for-in-array";
+const OVERFLOWING_LITERALS: &str = "overflowing-literals";
+const SHADOWING: &str = "shadowing";
+const FLOATING_PROMISE: &str = "floating-promise";
+const DSL_VALIDATION: &str = "dsl-validation";
+const INTERPOLATION: &str = "interpolation";
+const JS_BINDING: &str = "js-binding";
+const CONST_EVAL_LIMIT: &str = "const-eval-limit";
+
+/// Traits a `@derive(...)` annotation can implement.
+const DERIVES: &[&str] = &["Eq", "Debug"];
+
+/// Names of the checker's lints, whose level can be set in [`CheckerOptions`].
+pub const LINTS: &[&str] = &[
+    UNREACHABLE_CODE,
+    DUPLICATE_KEY,
+    UNKNOWN_FIELD,
+    UNUSED_MUT,
+    UNUSED_VARIABLE,
+    IMPLICIT_ANY,
+    CONSTANT_CONDITION,
+    WHILE_TRUE,
+    DISJOINT_COMPARISON,
+    FOR_IN_ARRAY,
+    OVERFLOWING_LITERALS,
+    SHADOWING,
+    FLOATING_PROMISE,
+    DSL_VALIDATION,
+    INTERPOLATION,
+    JS_BINDING,
+    CONST_EVAL_LIMIT,
+];
+
+/// The level of the lint `name` unless set in [`CheckerOptions`]: lints are
+/// warnings, except matters of team style, which are opt-in.
+pub fn default_lint_level(name: &str) -> LintLevel {
+    match name {
+        SHADOWING => LintLevel::Allow,
+        _ => LintLevel::Warn,
+    }
+}
+
+impl CheckResult {
+    /// Whether any diagnostic is an error; warnings alone do not fail a build.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.is_error())
+    }
 }
 
 pub fn check(module: &Module) -> CheckResult {
+    check_with_options(module, CheckerOptions::default())
+}
+
+pub fn check_with_options(module: &Module, options: CheckerOptions) -> CheckResult {
+    check_with_imports(module, options, HashMap::new()).0
+}
+
+/// DSL handlers keyed by block kind, as registered with the translator.
+pub type DslHandlers = HashMap<String, Box<dyn DslHandler>>;
+
+/// The handlers [`check`] validates DSL blocks with.
+pub fn builtin_dsl_handlers() -> DslHandlers {
+    let mut handlers: DslHandlers = HashMap::new();
+    handlers.insert("prompt".to_string(), Box::new(ag_dsl_prompt::handler::PromptDslHandler));
+    handlers.insert("component".to_string(), Box::new(ag_dsl_component::handler::ComponentDslHandler));
+    handlers
+}
+
+/// Check `module`, validating each DSL block with the handler registered
+/// for its kind (see [`DslHandler::validate`]) instead of the built-in ones.
+pub fn check_with_handlers(module: &Module, options: CheckerOptions, handlers: DslHandlers) -> CheckResult {
+    run_checker(module, options, HashMap::new(), handlers).0
+}
+
+/// Public symbols of a module (`pub fn`s, `pub use` aliases and `pub`
+/// types).
+pub(crate) type ModuleExports = HashMap<String, Export>;
+
+/// A public symbol of a module, as its importers see it.
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub ty: Type,
+    /// Value of an exported `const` whose initializer folds to a constant.
+    constant: Option<Const>,
+    /// An exported `const` binding or `const fn`, usable in the importer's
+    /// `const` initializers.
+    pub is_const: bool,
+    kind: ExportKind,
+}
+
+/// How an importer binds an export.
+#[derive(Debug, Clone)]
+enum ExportKind {
+    /// A function, binding or enum, in the importer's scope.
+    Value,
+    /// A struct, in the importer's scope like in its own module's. It only
+    /// exists at runtime as the factory filling in its field defaults.
+    Struct { has_factory: bool },
+    /// A type alias for `ty`.
+    TypeAlias,
+    GenericAlias(Box<TypeAlias>),
+    Interface(Box<InterfaceDecl>),
+}
+
+impl Export {
+    fn of(sym: &Symbol) -> Self {
+        Export { ty: sym.ty.clone(), constant: sym.constant.clone(), is_const: sym.is_const, kind: ExportKind::Value }
+    }
+
+    /// A symbol known only by its type, such as one of an unchecked module.
+    fn any(ty: Type) -> Self {
+        Export { ty, constant: None, is_const: false, kind: ExportKind::Value }
+    }
+
+    /// Whether the export has no runtime value, so importers may only name
+    /// it in types and codegen drops it from their imports.
+    pub fn is_type_only(&self) -> bool {
+        match self.kind {
+            ExportKind::Value => false,
+            ExportKind::Struct { has_factory } => !has_factory,
+            ExportKind::TypeAlias | ExportKind::GenericAlias(_) | ExportKind::Interface(_) => true,
+        }
+    }
+}
+
+/// Check `module` given the exports of the modules it imports, keyed by
+/// import path as written; also returns the module's own exports.
+pub(crate) fn check_with_imports(
+    module: &Module,
+    options: CheckerOptions,
+    imported: HashMap<String, ModuleExports>,
+) -> (CheckResult, ModuleExports) {
+    run_checker(module, options, imported, builtin_dsl_handlers())
+}
+
+fn run_checker(
+    module: &Module,
+    options: CheckerOptions,
+    imported: HashMap<String, ModuleExports>,
+    dsl_handlers: DslHandlers,
+) -> (CheckResult, ModuleExports) {
     let mut checker = Checker::new();
+    checker.dsl_handlers = dsl_handlers;
+    if options.record_types {
+        checker.type_map = Some(TypeMap::default());
+    }
+    checker.options = options;
+    checker.imported = imported;
     checker.check_module(module);
-    CheckResult {
+    let exports = checker.exports(module);
+    // Function bodies are checked after module code; report in source order.
+    checker.diagnostics.sort_by_key(|d| d.span.start);
+    let result = CheckResult {
         diagnostics: checker.diagnostics,
         tool_registry: checker.tool_registry,
-    }
+        rewrites: checker.rewrites,
+        variant_fields: checker.variant_fields,
+        type_map: checker.type_map,
+    };
+    (result, exports)
 }
 
 impl Checker {
@@ -180,26 +672,98 @@ impl Checker {
             scope: Scope::new(),
             diagnostics: Vec::new(),
             type_aliases: HashMap::new(),
+            generic_aliases: HashMap::new(),
+            alias_args: HashMap::new(),
             tool_registry: HashMap::new(),
+            rewrites: Rewrites::default(),
+            variant_fields: HashMap::new(),
             in_async: false,
+            pipe_input: None,
+            loop_breaks: Vec::new(),
+            return_type: None,
+            struct_decls: HashMap::new(),
+            enum_decls: HashMap::new(),
+            impl_methods: HashMap::new(),
+            interfaces: HashMap::new(),
+            js_bindings: HashMap::new(),
+            type_names: HashSet::new(),
+            type_params: Vec::new(),
+            type_bounds: HashMap::new(),
+            reported_spans: HashSet::new(),
+            mut_decls: Vec::new(),
+            local_decls: Vec::new(),
+            pending_globals: HashMap::new(),
+            arrow_depth: 0,
+            type_map: None,
+            imported: HashMap::new(),
+            const_fns: HashMap::new(),
+            const_calls: Cell::new(0),
+            const_globals: HashSet::new(),
+            dsl_handlers: HashMap::new(),
+            options: CheckerOptions::default(),
         }
     }
 
     fn error(&mut self, msg: impl Into<String>, span: Span) {
-        self.diagnostics.push(Diagnostic {
-            message: msg.into(),
-            span,
-        });
+        self.diagnostics.push(Diagnostic::error(msg, span));
+    }
+
+    /// Report an error with an optional secondary label (message, span).
+    fn error_with_label(&mut self, msg: impl Into<String>, span: Span, label: Option<(String, Span)>) {
+        let mut diag = Diagnostic::error(msg, span);
+        if let Some((message, label_span)) = label {
+            diag = diag.with_label(message, label_span);
+        }
+        self.diagnostics.push(diag);
+    }
+
+    /// Report `diag` from the lint `name` at the level configured for it.
+    fn lint(&mut self, name: &'static str, diag: Diagnostic) {
+        let level = self.options.lint_levels.get(name).copied().unwrap_or_else(|| default_lint_level(name));
+        let severity = match level {
+            LintLevel::Allow => return,
+            LintLevel::Warn => Severity::Warning,
+            LintLevel::Deny => Severity::Error,
+        };
+        self.diagnostics.push(Diagnostic { severity, ..diag.with_code(name) });
+    }
+
+    /// Lint `name`, about to be bound at `span`, if it shadows a binding
+    /// of an enclosing scope. `_`-prefixed names shadow on purpose.
+    fn lint_shadowing(&mut self, name: &str, span: Span) {
+        if name.starts_with('_') {
+            return;
+        }
+        let Some(outer) = self.scope.shadowed(name) else { return };
+        let def = outer.def;
+        self.lint(
+            SHADOWING,
+            Diagnostic::warning(format!("`{name}` shadows a binding from an outer scope"), span)
+                .with_label(format!("outer `{name}` is bound here"), def),
+        );
+    }
+
+    /// Lint a promise that is dropped rather than awaited, returned or
+    /// bound: nothing waits for it, and its errors go unhandled.
+    fn warn_floating_promise(&mut self, ty: &Type, span: Span) {
+        if matches!(ty, Type::Promise(_)) {
+            self.lint(
+                FLOATING_PROMISE,
+                Diagnostic::warning(format!("this `{ty}` is never awaited"), span),
+            );
+        }
     }
 
     fn is_serializable_type(&self, ty: &Type) -> bool {
         match ty {
-            Type::Str | Type::Num | Type::Int | Type::Bool | Type::Nil | Type::Any | Type::Unknown => true,
+            Type::Str | Type::Num | Type::Int | Type::Bool | Type::Nil | Type::Any | Type::Never | Type::Param(..) | Type::Unknown => true,
             Type::Array(inner) => self.is_serializable_type(inner),
             Type::Map(k, v) => matches!(**k, Type::Str) && self.is_serializable_type(v),
             Type::Nullable(inner) => self.is_serializable_type(inner),
             Type::Union(a, b) => self.is_serializable_type(a) && self.is_serializable_type(b),
-            Type::Struct(_, fields) => fields.iter().all(|(_, t)| self.is_serializable_type(t)),
+            Type::Struct(_, fields) | Type::Object(fields) => {
+                fields.iter().all(|(_, t)| self.is_serializable_type(t))
+            }
             Type::Enum(_, variants) => variants.iter().all(|(_, fields)| {
                 fields.iter().all(|(_, t)| self.is_serializable_type(t))
             }),
@@ -216,6 +780,9 @@ impl Checker {
         match (expected, actual) {
             (Type::Any, _) | (_, Type::Any) => true,
             (Type::Unknown, _) | (_, Type::Unknown) => true,
+            // Interfaces are not types yet, so neither is what a bound allows
+            (Type::Param(..), _) | (_, Type::Param(..)) => true,
+            (_, Type::Never) => true, // diverging code fits any type
             (Type::Num, Type::Int) => true, // int widens to num
             (Type::Nullable(inner), _) => {
                 self.type_compatible(inner, actual) || matches!(actual, Type::Nil)
@@ -231,29 +798,47 @@ impl Checker {
             (Type::Map(ek, ev), Type::Map(ak, av)) => {
                 self.type_compatible(ek, ak) && self.type_compatible(ev, av)
             }
+            // Maps are plain JS objects at runtime, so object literals fit
+            // string-keyed maps whose value type covers every field.
+            (Type::Map(ek, ev), Type::Object(fields)) => {
+                self.type_compatible(ek, &Type::Str)
+                    && fields.iter().all(|(_, t)| self.type_compatible(ev, t))
+            }
             (Type::Function(ep, er), Type::Function(ap, ar)) => {
                 ep.len() == ap.len()
-                    && ep.iter().zip(ap).all(|(e, a)| self.type_compatible(e, a))
+                    && ep.iter().zip(ap).all(|(e, a)| self.type_compatible(&e.ty, &a.ty))
                     && self.type_compatible(er, ar)
             }
             (Type::Promise(e), Type::Promise(a)) => self.type_compatible(e, a),
             // Structural subtyping for structs
-            (Type::Struct(_, expected_fields), Type::Struct(_, actual_fields)) => {
+            (Type::Struct(_, expected_fields), Type::Struct(_, actual_fields))
+            | (Type::Object(expected_fields), Type::Struct(_, actual_fields) | Type::Object(actual_fields)) => {
                 expected_fields.iter().all(|(name, ty)| {
                     actual_fields
                         .iter()
                         .any(|(n, t)| n == name && self.type_compatible(ty, t))
                 })
             }
+            // An object fits a struct when it has every field the struct
+            // requires; fields of a `T?` type may be left out. Defaults are
+            // only filled in for a literal checked against the struct.
+            (Type::Struct(_, expected_fields), Type::Object(actual_fields)) => {
+                expected_fields.iter().all(|(name, ty)| {
+                    match actual_fields.iter().find(|(n, _)| n == name) {
+                        Some((_, t)) => self.type_compatible(ty, t),
+                        None => matches!(ty, Type::Nullable(_)),
+                    }
+                })
+            }
             _ => false,
         }
     }
 
     // ── Resolve TypeExpr to Type ───────────────────────────
 
-    fn resolve_type(&self, ty: &TypeExpr) -> Type {
+    fn resolve_type(&mut self, ty: &TypeExpr) -> Type {
         match ty {
-            TypeExpr::Named(name, _) => match name.as_str() {
+            TypeExpr::Named(name, span) => match name.as_str() {
                 "str" => Type::Str,
                 "num" => Type::Num,
                 "int" => Type::Int,
@@ -261,11 +846,28 @@ impl Checker {
                 "nil" => Type::Nil,
                 "any" => Type::Any,
                 _ => {
-                    if let Some(alias) = self.type_aliases.get(name) {
+                    if let Some(arg) = self.alias_args.get(name) {
+                        arg.clone()
+                    } else if let Some(alias) = self.type_aliases.get(name) {
                         alias.clone()
+                    } else if let Some(alias) = self.generic_aliases.get(name) {
+                        let msg = format!(
+                            "type alias `{name}` expects {} type argument{}, found 0",
+                            alias.type_params.len(),
+                            if alias.type_params.len() == 1 { "" } else { "s" },
+                        );
+                        self.error(msg, *span);
+                        Type::Unknown
+                    } else if let Some(bounds) =
+                        self.type_bounds.get(name).filter(|_| self.type_params.contains(name))
+                    {
+                        Type::Param(name.clone(), bounds.clone())
                     } else if let Some(sym) = self.scope.lookup(name) {
                         sym.ty.clone()
                     } else {
+                        if !self.type_names.contains(name) && !self.type_params.contains(name) {
+                            self.report_unknown_type(name, *span);
+                        }
                         Type::Unknown
                     }
                 }
@@ -280,114 +882,338 @@ impl Checker {
                 Box::new(self.resolve_type(b)),
             ),
             TypeExpr::Function(ft) => {
-                let params: Vec<Type> = ft.params.iter().map(|p| self.resolve_type(p)).collect();
+                let params: Vec<FnParam> = ft
+                    .params
+                    .iter()
+                    .map(|p| FnParam::required(self.resolve_type(p)))
+                    .collect();
                 let ret = self.resolve_type(&ft.ret);
                 Type::Function(params, Box::new(ret))
             }
             TypeExpr::Object(ot) => {
+                self.report_duplicate_names("field", ot.fields.iter().map(|f| (f.name.as_str(), f.span)));
                 let fields: Vec<(String, Type)> = ot
                     .fields
                     .iter()
                     .map(|f| (f.name.clone(), self.resolve_type(&f.ty)))
                     .collect();
-                Type::Struct("anonymous".to_string(), fields)
+                Type::Object(fields)
             }
             TypeExpr::Promise(inner, _) => {
                 Type::Promise(Box::new(self.resolve_type(inner)))
             }
+            TypeExpr::Generic(name, args, span) => {
+                let args: Vec<Type> = args.iter().map(|a| self.resolve_type(a)).collect();
+                match self.generic_aliases.remove(name) {
+                    Some(alias) => {
+                        let ty = self.instantiate_alias(&alias, args, *span);
+                        self.generic_aliases.insert(name.clone(), alias);
+                        ty
+                    }
+                    // Other generic types are not tracked by their arguments
+                    None => self.resolve_type(&TypeExpr::Named(name.clone(), *span)),
+                }
+            }
+        }
+    }
+
+    /// The type `alias<args>` stands for. The alias is taken out of
+    /// `generic_aliases` meanwhile, so a recursive use is left unknown.
+    fn instantiate_alias(&mut self, alias: &TypeAlias, args: Vec<Type>, span: Span) -> Type {
+        let params = &alias.type_params;
+        if args.len() != params.len() {
+            self.error(
+                format!(
+                    "type alias `{}` expects {} type argument{}, found {}",
+                    alias.name,
+                    params.len(),
+                    if params.len() == 1 { "" } else { "s" },
+                    args.len(),
+                ),
+                span,
+            );
+        }
+        let bindings = params
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.name.clone(), args.get(i).cloned().unwrap_or(Type::Unknown)))
+            .collect();
+        let outer = std::mem::replace(&mut self.alias_args, bindings);
+        let ty = self.resolve_type(&alias.ty);
+        self.alias_args = outer;
+        ty
+    }
+
+    fn report_unknown_type(&mut self, name: &str, span: Span) {
+        if !self.reported_spans.insert(span) {
+            return;
+        }
+        let candidates = PRIMITIVE_TYPES
+            .iter()
+            .copied()
+            .chain(self.type_names.iter().map(String::as_str))
+            .chain(self.type_aliases.keys().map(String::as_str))
+            .chain(self.type_params.iter().map(String::as_str));
+        let msg = match closest_name(name, candidates) {
+            Some(suggestion) => format!("unknown type `{name}`, did you mean `{suggestion}`?"),
+            None => format!("unknown type `{name}`"),
+        };
+        self.error(msg, span);
+    }
+
+    /// Names in `names` that repeat an earlier one, with the span of the
+    /// repetition and of the first occurrence. Each repetition is returned
+    /// only the first time it is seen.
+    fn duplicate_names<'a>(
+        &mut self,
+        names: impl IntoIterator<Item = (&'a str, Span)>,
+    ) -> Vec<(&'a str, Span, Span)> {
+        let mut first: HashMap<&str, Span> = HashMap::new();
+        let mut duplicates = Vec::new();
+        for (name, span) in names {
+            match first.get(name) {
+                Some(&first_span) => {
+                    if self.reported_spans.insert(span) {
+                        duplicates.push((name, span, first_span));
+                    }
+                }
+                None => {
+                    first.insert(name, span);
+                }
+            }
+        }
+        duplicates
+    }
+
+    /// Report each repeated name in `names`, e.g. `duplicate field `name``.
+    fn report_duplicate_names<'a>(&mut self, what: &str, names: impl IntoIterator<Item = (&'a str, Span)>) {
+        for (name, span, first_span) in self.duplicate_names(names) {
+            self.error_with_label(
+                format!("duplicate {what} `{name}`"),
+                span,
+                Some((format!("`{name}` first defined here"), first_span)),
+            );
+        }
+    }
+
+    /// Report top-level items introducing the same value name, whatever
+    /// their kinds, as they share one scope in the emitted JS. Registering
+    /// the items then keeps the first binding without reporting again.
+    fn report_duplicate_declarations(&mut self, module: &Module) {
+        let mut names = Vec::new();
+        for item in &module.items {
+            match item {
+                Item::FnDecl(f) => names.push((f.name.as_str(), f.span)),
+                Item::StructDecl(s) => names.push((s.name.as_str(), s.span)),
+                Item::EnumDecl(e) => names.push((e.name.as_str(), e.span)),
+                Item::VarDecl(v) => names.push((v.name.as_str(), v.span)),
+                Item::ExternFnDecl(ef) => names.push((ef.name.as_str(), ef.span)),
+                Item::ExternStructDecl(es) => names.push((es.name.as_str(), es.span)),
+                Item::ExternTypeDecl(et) => names.push((et.name.as_str(), et.span)),
+                Item::DslBlock(dsl) => names.push((dsl.name.name.as_str(), dsl.name.span)),
+                Item::Import(imp) => {
+                    if let Some(namespace) = &imp.namespace {
+                        names.push((namespace.as_str(), imp.span));
+                    }
+                    for name in &imp.names {
+                        names.push((name.alias.as_ref().unwrap_or(&name.name).as_str(), name.span));
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.report_duplicate_names("declaration", names);
+    }
+
+    fn report_duplicate_fields(&mut self, fields: &[Field]) {
+        self.report_duplicate_names("field", fields.iter().map(|f| (f.name.as_str(), f.span)));
+    }
+
+    fn report_duplicate_params(&mut self, params: &[Param]) {
+        self.report_duplicate_names("parameter", params.iter().map(|p| (p.name.as_str(), p.span)));
+    }
+
+    /// Duplicate keys are legal in JS, where the last one wins, so they are
+    /// only a warning.
+    fn warn_duplicate_keys(&mut self, obj: &ObjectExpr) {
+        for (key, span, first_span) in self.duplicate_names(obj.fields.iter().map(|f| (f.key.as_str(), f.span))) {
+            self.lint(
+                DUPLICATE_KEY,
+                Diagnostic::warning(format!("duplicate key `{key}` in object literal; the last value wins"), span)
+                    .with_label(format!("`{key}` first defined here"), first_span),
+            );
         }
     }
 
     // ── Module check ───────────────────────────────────────
 
     fn check_module(&mut self, module: &Module) {
+        for item in &module.items {
+            let name = match item {
+                Item::StructDecl(s) => &s.name,
+                Item::EnumDecl(e) => &e.name,
+                Item::InterfaceDecl(i) => &i.name,
+                Item::TypeAlias(t) => &t.name,
+                Item::ExternStructDecl(es) => &es.name,
+                Item::ExternTypeDecl(et) => &et.name,
+                _ => continue,
+            };
+            self.type_names.insert(name.clone());
+        }
+
+        self.report_duplicate_declarations(module);
+
+        // Imports first, as declarations may name imported types
+        for item in &module.items {
+            if let Item::Import(imp) = item {
+                self.register_import(imp);
+            }
+        }
+
         // First pass: register all declarations
         for item in &module.items {
             match item {
                 Item::FnDecl(f) => self.register_fn_decl(f),
                 Item::StructDecl(s) => self.register_struct_decl(s),
                 Item::EnumDecl(e) => self.register_enum_decl(e),
+                Item::InterfaceDecl(i) => self.register_interface_decl(i),
                 Item::TypeAlias(t) => self.register_type_alias(t),
                 Item::ExternFnDecl(ef) => self.register_extern_fn_decl(ef),
                 Item::ExternStructDecl(es) => self.register_extern_struct_decl(es),
                 Item::ExternTypeDecl(et) => self.register_extern_type_decl(et),
+                Item::ImplBlock(ib) => self.register_impl_block(ib),
+                Item::VarDecl(v) => {
+                    self.pending_globals.entry(v.name.clone()).or_insert(v.span);
+                }
+                _ => {}
+            }
+        }
+        // Derived methods, once the types and the methods they may clash
+        // with are known
+        for item in &module.items {
+            match item {
+                Item::StructDecl(s) => self.register_derives(&s.name, &s.derives),
+                Item::EnumDecl(e) => self.register_derives(&e.name, &e.derives),
                 _ => {}
             }
         }
 
-        // Second pass: check bodies
+        // Second pass: module code, in the order it runs
+        self.in_async = self.options.top_level_await != TopLevelAwait::Error;
         for item in &module.items {
             match item {
-                Item::FnDecl(f) => self.check_fn_decl(f),
-                Item::VarDecl(v) => self.check_var_decl(v),
+                Item::VarDecl(v) => {
+                    self.check_var_decl(v);
+                    self.pending_globals.remove(&v.name);
+                    if v.kind == VarKind::Const {
+                        self.const_globals.insert(v.name.clone());
+                    }
+                }
                 Item::ExprStmt(e) => {
                     self.check_expr(&e.expr);
                 }
                 Item::DslBlock(dsl) => self.check_dsl_block(dsl),
+                Item::InterfaceDecl(i) => self.check_interface_decl(i),
+                Item::ExportAlias(ea) => self.check_export_alias(ea),
+                Item::StructDecl(s) => self.check_derives(&s.name, &s.derives),
+                Item::EnumDecl(e) => self.check_derives(&e.name, &e.derives),
+                _ => {}
+            }
+        }
+        self.in_async = false;
+
+        self.check_public_signatures(module);
+
+        // Third pass: function bodies, which may use any top-level binding
+        for item in &module.items {
+            match item {
+                Item::FnDecl(f) => self.check_fn_decl(f),
+                Item::ImplBlock(ib) => self.check_impl_block(ib),
                 _ => {}
             }
         }
+        self.warn_unmutated(0);
+        self.warn_unused(0);
     }
 
     fn check_dsl_block(&mut self, dsl: &DslBlock) {
         // Always type-check capture expressions
         if let DslContent::Inline { parts } = &dsl.content {
+            // Text since the last newline: a capture on a directive line
+            // (`@tools #{...}`) is an argument, others are interpolated.
+            // Component captures are attribute values and child nodes.
+            let interpolates = dsl.kind != "component";
+            let mut line = String::new();
             for part in parts {
-                if let ag_ast::DslPart::Capture(expr, _) = part {
-                    self.check_expr(expr);
+                match part {
+                    ag_ast::DslPart::Text(text, _) => match text.rfind('\n') {
+                        Some(i) => line = text[i + 1..].to_string(),
+                        None => line.push_str(text),
+                    },
+                    ag_ast::DslPart::Capture(expr, span) => {
+                        let ty = self.check_expr(expr);
+                        if interpolates && !line.trim_start().starts_with('@') {
+                            self.check_interpolated(&ty, *span, "capture expression");
+                        }
+                        line.push_str("#{}");
+                    }
                 }
             }
         }
 
+        let block = core_dsl_block(dsl);
+        if let Some(handler) = self.dsl_handlers.get(&dsl.kind) {
+            let diags = handler.validate(&block, self);
+            for d in diags {
+                self.report_dsl_diagnostic(d, dsl.span);
+            }
+            return;
+        }
+
         // Only run DSL-internal validation for inline blocks
-        let DslContent::Inline { parts } = &dsl.content else {
+        let ag_dsl_core::DslContent::Inline { parts } = &block.content else {
             return;
         };
-
-        // Convert ag_ast::DslPart to ag_dsl_core::DslPart for DSL lexers
-        let core_parts: Vec<CoreDslPart> = parts
-            .iter()
-            .map(|p| match p {
-                ag_ast::DslPart::Text(s, span) => CoreDslPart::Text(
-                    s.clone(),
-                    ag_dsl_core::Span::new(span.start, span.end),
-                ),
-                ag_ast::DslPart::Capture(expr, span) => {
-                    let boxed: Box<dyn std::any::Any> = Box::new((**expr).clone());
-                    CoreDslPart::Capture(
-                        boxed,
-                        ag_dsl_core::Span::new(span.start, span.end),
-                    )
-                }
-            })
-            .collect();
-
         match dsl.kind.as_str() {
-            "prompt" => self.check_dsl_prompt(&core_parts, dsl),
-            "agent" => self.check_dsl_agent(&core_parts, dsl),
-            "skill" => self.check_dsl_skill(&core_parts, dsl),
-            "server" => self.check_dsl_server(&core_parts, dsl),
-            "component" => self.check_dsl_component(parts, dsl),
+            "agent" => self.check_dsl_agent(parts, dsl),
+            "skill" => self.check_dsl_skill(parts, dsl),
+            "server" => self.check_dsl_server(parts, dsl),
             _ => {} // Unknown kinds are silently skipped
         }
+        if dsl.kind == "agent" {
+            self.check_agent_prompt_directives(block);
+        }
     }
 
-    fn check_dsl_prompt(&mut self, parts: &[CoreDslPart], dsl: &DslBlock) {
-        let tokens = ag_dsl_prompt::lexer::lex(parts);
-        match ag_dsl_prompt::parser::parse(&dsl.name.name, &tokens) {
-            Ok(template) => {
-                let diags = ag_dsl_prompt::validator::validate(&template);
-                for d in diags {
-                    if d.severity == ag_dsl_prompt::parser::Severity::Error {
-                        self.error(d.message, dsl.span);
-                    }
-                }
-            }
-            Err(diags) => {
-                for d in diags {
-                    self.error(d.message, dsl.span);
-                }
-            }
+    fn report_dsl_diagnostic(&mut self, d: DslDiagnostic, block_span: Span) {
+        let span = d.span.map_or(block_span, |s| Span::new(s.start, s.end));
+        match d.severity {
+            DslSeverity::Error => self.error(d.message, span),
+            DslSeverity::Warning => self.lint(DSL_VALIDATION, Diagnostic::warning(d.message, span)),
+        }
+    }
+
+    /// Check an agent's prompt directives (`@role`, `@output`) with the
+    /// prompt handler, on its text without the agent-only directive lines.
+    fn check_agent_prompt_directives(&mut self, block: ag_dsl_core::DslBlock) {
+        let Some(handler) = self.dsl_handlers.get("prompt") else {
+            return;
+        };
+        let ag_dsl_core::DslContent::Inline { mut parts } = block.content else {
+            return;
+        };
+        for name in ["tools", "skills", "agents", "on"] {
+            parts = remove_directive(parts, name);
+        }
+        let prompt = ag_dsl_core::DslBlock {
+            kind: "prompt".to_string(),
+            content: ag_dsl_core::DslContent::Inline { parts },
+            ..block
+        };
+        // Whole-block diagnostics repeat the agent parser's and validator's
+        let diags: Vec<_> = handler.validate(&prompt, self).into_iter().filter(|d| d.span.is_some()).collect();
+        for d in diags {
+            self.report_dsl_diagnostic(d, Span::new(prompt.span.start, prompt.span.end));
         }
     }
 
@@ -450,39 +1276,6 @@ impl Checker {
         }
     }
 
-    fn check_dsl_component(&mut self, parts: &[ag_ast::DslPart], dsl: &DslBlock) {
-        // Component uses ag_dsl_core::DslPart directly for parse_component
-        let core_parts: Vec<CoreDslPart> = parts
-            .iter()
-            .map(|p| match p {
-                ag_ast::DslPart::Text(s, span) => CoreDslPart::Text(
-                    s.clone(),
-                    ag_dsl_core::Span::new(span.start, span.end),
-                ),
-                ag_ast::DslPart::Capture(expr, span) => {
-                    let boxed: Box<dyn std::any::Any> = Box::new((**expr).clone());
-                    CoreDslPart::Capture(
-                        boxed,
-                        ag_dsl_core::Span::new(span.start, span.end),
-                    )
-                }
-            })
-            .collect();
-        match ag_dsl_component::parse_component(&dsl.name.name, &core_parts) {
-            Ok(meta) => {
-                let diags = ag_dsl_component::validator::validate(&meta);
-                for d in diags {
-                    if d.severity == ag_dsl_component::validator::Severity::Error {
-                        self.error(d.message, dsl.span);
-                    }
-                }
-            }
-            Err(e) => {
-                self.error(e.message, dsl.span);
-            }
-        }
-    }
-
     // ── Skill type validation ────────────────────────────────
 
     fn validate_skill_field_types(
@@ -535,51 +1328,149 @@ impl Checker {
         }
     }
 
-    fn register_fn_decl(&mut self, f: &FnDecl) {
-        let param_types: Vec<Type> = f
-            .params
+    /// Resolve declared parameters into function type parameters; those
+    /// with a default value become optional. Extern declarations describe JS
+    /// APIs, where a nullable parameter may simply be left out, so for
+    /// externs those are optional too.
+    fn declared_params(&mut self, params: &[Param], is_extern: bool) -> Vec<FnParam> {
+        self.report_duplicate_params(params);
+        params
             .iter()
             .map(|p| {
-                p.ty.as_ref()
+                let ty = p
+                    .ty
+                    .as_ref()
                     .map(|t| self.resolve_type(t))
-                    .unwrap_or(Type::Any)
-            })
-            .collect();
+                    .unwrap_or(Type::Any);
+                let optional =
+                    p.default.is_some() || (is_extern && matches!(ty, Type::Nullable(_)));
+                FnParam {
+                    name: Some(p.name.clone()),
+                    ty,
+                    optional,
+                }
+            })
+            .collect()
+    }
+
+    fn register_fn_decl(&mut self, f: &FnDecl) {
+        if f.is_const {
+            self.const_fns.insert(f.name.clone(), f.clone());
+        }
+        let outer = self.enter_fn_type_params(f);
+        let params = self.declared_params(&f.params, false);
         let mut ret_type = f
             .return_type
             .as_ref()
             .map(|t| self.resolve_type(t))
             .unwrap_or(Type::Nil);
+        self.type_params = outer;
         // async fn externally returns Promise<T>
         if f.is_async {
-            ret_type = Type::Promise(Box::new(ret_type));
+            ret_type = Type::Promise(Box::new(awaited(ret_type)));
         }
         self.scope.define(
             &f.name,
             Symbol {
-                ty: Type::Function(param_types, Box::new(ret_type)),
-                mutable: false,
+                is_const: f.is_const,
+                ..Symbol::new(Type::Function(params, Box::new(ret_type)), f.span)
             },
         );
     }
 
     fn register_struct_decl(&mut self, s: &StructDecl) {
+        self.report_duplicate_fields(&s.fields);
         let fields: Vec<(String, Type)> = s
             .fields
             .iter()
             .map(|f| (f.name.clone(), self.resolve_type(&f.ty)))
             .collect();
         let ty = Type::Struct(s.name.clone(), fields);
+        self.struct_decls.insert(s.name.clone(), s.clone());
         self.scope.define(
             &s.name,
-            Symbol {
-                ty,
-                mutable: false,
-            },
+            Symbol::new(ty, s.span),
         );
     }
 
+    /// Record the methods `@derive(...)` gives type `name`: `a.eq(b)` for
+    /// `Eq` and `x.toString()` for `Debug`. One that an `impl` block also
+    /// defines is an error, as both would be emitted.
+    fn register_derives(&mut self, name: &str, derives: &[Ident]) {
+        let Some(self_ty) = self.scope.lookup(name).map(|sym| sym.ty.clone()) else { return };
+        for derive in derives {
+            let (method, ty) = match derive.name.as_str() {
+                "Eq" => ("eq", Type::Function(vec![FnParam::required(self_ty.clone())], Box::new(Type::Bool))),
+                "Debug" => ("toString", Type::Function(vec![], Box::new(Type::Str))),
+                _ => continue,
+            };
+            let methods = self.impl_methods.entry(name.to_string()).or_default();
+            if let Some(user) = methods.iter().find(|m| m.name == method) {
+                let label = Some((format!("`{method}` defined here"), user.span));
+                self.error_with_label(
+                    format!("`@derive({})` on `{name}` conflicts with its method `{method}`", derive.name),
+                    derive.span,
+                    label,
+                );
+                continue;
+            }
+            methods.push(ImplMethod { name: method.to_string(), ty, takes_self: true, span: derive.span });
+        }
+    }
+
+    /// Check a `@derive(...)` list: only known derives, and `Eq` only when
+    /// every field supports equality.
+    fn check_derives(&mut self, name: &str, derives: &[Ident]) {
+        for derive in derives {
+            if !DERIVES.contains(&derive.name.as_str()) {
+                self.error(
+                    format!("unknown derive `{}`; supported derives are `Eq` and `Debug`", derive.name),
+                    derive.span,
+                );
+            }
+        }
+        let Some(eq) = derives.iter().find(|d| d.name == "Eq") else { return };
+        let span = eq.span;
+        let fields: Vec<(String, Type)> = match self.scope.lookup(name).map(|sym| sym.ty.clone()) {
+            Some(Type::Struct(_, fields)) => fields,
+            Some(Type::Enum(_, variants)) => variants.into_iter().flat_map(|(_, fields)| fields).collect(),
+            _ => return,
+        };
+        for (field, ty) in fields {
+            if !self.supports_eq(&ty) {
+                self.error(
+                    format!("cannot derive `Eq` for `{name}`: field `{field}` has type `{ty}`, which does not support equality"),
+                    span,
+                );
+            }
+        }
+    }
+
+    /// Whether a derived `Eq` can compare values of type `ty`: primitives,
+    /// and arrays, optionals, structs and enums of such values.
+    fn supports_eq(&self, ty: &Type) -> bool {
+        let derives_eq = |derives: &[Ident]| derives.iter().any(|d| d.name == "Eq");
+        match ty {
+            Type::Str | Type::Int | Type::Num | Type::Bool | Type::Nil => true,
+            Type::Array(inner) | Type::Nullable(inner) => self.supports_eq(inner),
+            Type::Struct(name, _) => self.struct_decls.get(name).is_some_and(|s| derives_eq(&s.derives)),
+            Type::Enum(name, _) => self.enum_decls.get(name).is_some_and(|e| derives_eq(&e.derives)),
+            _ => false,
+        }
+    }
+
     fn register_enum_decl(&mut self, e: &EnumDecl) {
+        self.report_duplicate_names("variant", e.variants.iter().map(|v| (v.name.as_str(), v.span)));
+        for v in &e.variants {
+            self.report_duplicate_fields(&v.fields);
+            // Enum values are `{ tag: "Variant", ...fields }`
+            if let Some(field) = v.fields.iter().find(|f| f.name == "tag") {
+                self.error(
+                    format!("variant `{}::{}` cannot have a field named `tag`, which holds the variant name", e.name, v.name),
+                    field.span,
+                );
+            }
+        }
         let variants: Vec<(String, Vec<(String, Type)>)> = e
             .variants
             .iter()
@@ -593,52 +1484,94 @@ impl Checker {
             })
             .collect();
         let ty = Type::Enum(e.name.clone(), variants);
+        self.enum_decls.insert(e.name.clone(), e.clone());
         self.scope.define(
             &e.name,
-            Symbol {
-                ty,
-                mutable: false,
-            },
+            Symbol::new(ty, e.span),
         );
     }
 
+    fn register_interface_decl(&mut self, i: &InterfaceDecl) {
+        if self.interfaces.contains_key(&i.name) {
+            self.error(format!("duplicate declaration `{}`", i.name), i.span);
+            return;
+        }
+        self.interfaces.insert(i.name.clone(), i.clone());
+    }
+
     fn register_type_alias(&mut self, t: &TypeAlias) {
-        let ty = self.resolve_type(&t.ty);
-        self.type_aliases.insert(t.name.clone(), ty);
+        if t.type_params.is_empty() {
+            let ty = self.resolve_type(&t.ty);
+            self.type_aliases.insert(t.name.clone(), ty);
+            return;
+        }
+        // Resolved per instantiation; resolve the body once here for its
+        // diagnostics.
+        self.report_duplicate_names("type parameter", t.type_params.iter().map(|p| (p.name.as_str(), p.span)));
+        let params = t.type_params.iter().map(|p| p.name.clone()).collect();
+        let outer = std::mem::replace(&mut self.type_params, params);
+        self.resolve_type(&t.ty);
+        self.type_params = outer;
+        self.generic_aliases.insert(t.name.clone(), t.clone());
     }
 
     fn register_extern_fn_decl(&mut self, ef: &ExternFnDecl) {
-        let param_types: Vec<Type> = ef
-            .params
-            .iter()
-            .map(|p| {
-                p.ty.as_ref()
-                    .map(|t| self.resolve_type(t))
-                    .unwrap_or(Type::Any)
-            })
-            .collect();
+        let params = self.declared_params(&ef.params, true);
         let ret_type = ef
             .return_type
             .as_ref()
             .map(|t| self.resolve_type(t))
             .unwrap_or(Type::Nil);
         let ty = if ef.variadic {
+            let param_types = params.into_iter().map(|p| p.ty).collect();
             Type::VariadicFunction(param_types, Box::new(ret_type))
         } else {
-            Type::Function(param_types, Box::new(ret_type))
+            Type::Function(params, Box::new(ret_type))
         };
-        if !self.scope.define(
+        if let Some(JsAnnotation { module: Some(module), js_name, .. }) = &ef.js_annotation {
+            let js_name = js_name.as_ref().unwrap_or(&ef.name);
+            self.check_js_binding(module, js_name, &ef.name, &ty, ef.span);
+        }
+        self.scope.define(
             &ef.name,
-            Symbol {
-                ty,
-                mutable: false,
-            },
-        ) {
-            self.error(format!("duplicate declaration `{}`", ef.name), ef.span);
+            Symbol::new(ty, ef.span),
+        );
+    }
+
+    /// Compare an extern fn `name` of type `ty`, imported as `js_name` from
+    /// `module`, with an earlier one importing the same: both names call
+    /// one JS function, so their signatures should agree.
+    fn check_js_binding(&mut self, module: &str, js_name: &str, name: &str, ty: &Type, span: Span) {
+        let key = (module.to_string(), js_name.to_string());
+        let Some((first, first_ty, first_span)) = self.js_bindings.get(&key).cloned() else {
+            self.js_bindings.insert(key, (name.to_string(), ty.clone(), span));
+            return;
+        };
+        if first == name {
+            // A duplicate declaration, reported as such
+            return;
+        }
+        if *ty == first_ty {
+            self.diagnostics.push(
+                Diagnostic::note(format!("duplicate binding for JS name `{js_name}`"), span)
+                    .with_label(format!("`{first}` also binds it"), first_span),
+            );
+        } else {
+            self.lint(
+                JS_BINDING,
+                Diagnostic::warning(
+                    format!(
+                        "`{name}` binds JS name `{js_name}` from \"{module}\" as `{ty}`, but `{first}` binds it as `{first_ty}`"
+                    ),
+                    span,
+                )
+                .with_label(format!("`{first}` declared here"), first_span),
+            );
         }
     }
 
     fn register_extern_struct_decl(&mut self, es: &ExternStructDecl) {
+        self.report_duplicate_fields(&es.fields);
         let fields: Vec<(String, Type)> = es
             .fields
             .iter()
@@ -647,51 +1580,391 @@ impl Checker {
         // Also register methods as fields with function types
         let mut all_fields = fields;
         for m in &es.methods {
-            let param_types: Vec<Type> = m
-                .params
-                .iter()
-                .map(|p| {
-                    p.ty.as_ref()
-                        .map(|t| self.resolve_type(t))
-                        .unwrap_or(Type::Any)
-                })
-                .collect();
+            let params = self.declared_params(&m.params, true);
             let ret_type = m
                 .return_type
                 .as_ref()
                 .map(|t| self.resolve_type(t))
                 .unwrap_or(Type::Nil);
-            all_fields.push((m.name.clone(), Type::Function(param_types, Box::new(ret_type))));
+            all_fields.push((m.name.clone(), Type::Function(params, Box::new(ret_type))));
         }
         let ty = Type::Struct(es.name.clone(), all_fields);
-        if !self.scope.define(
+        self.scope.define(
             &es.name,
-            Symbol {
-                ty,
-                mutable: false,
-            },
-        ) {
-            self.error(format!("duplicate declaration `{}`", es.name), es.span);
-        }
+            Symbol::new(ty, es.span),
+        );
     }
 
     fn register_extern_type_decl(&mut self, et: &ExternTypeDecl) {
         // Opaque type: register as a struct with no fields
         let ty = Type::Struct(et.name.clone(), Vec::new());
-        if !self.scope.define(
+        self.scope.define(
             &et.name,
+            Symbol::new(ty, et.span),
+        );
+    }
+
+    // ── Imports and exports ────────────────────────────────
+
+    /// Bind the names an import introduces: the imported module's exports
+    /// when it is known, `any` otherwise. Imported types go where the
+    /// module's own would; those with no runtime value are recorded for
+    /// codegen to drop.
+    fn register_import(&mut self, imp: &Import) {
+        let path = imp.path.as_static().unwrap_or_default();
+        let exports = self.imported.get(path).cloned();
+        if let Some(namespace) = &imp.namespace {
+            let ty = match &exports {
+                Some(exports) => {
+                    let mut fields: Vec<(String, Type)> = exports
+                        .iter()
+                        .filter(|(_, export)| !export.is_type_only())
+                        .map(|(name, export)| (name.clone(), export.ty.clone()))
+                        .collect();
+                    fields.sort_by(|a, b| a.0.cmp(&b.0));
+                    Type::Object(fields)
+                }
+                None => Type::Any,
+            };
+            self.define_import(namespace, Export::any(ty), imp.span);
+        }
+        for name in &imp.names {
+            let export = match &exports {
+                Some(exports) => exports.get(&name.name).cloned().unwrap_or_else(|| {
+                    self.error(format!("module `{path}` has no public `{}`", name.name), imp.span);
+                    Export::any(Type::Any)
+                }),
+                None => Export::any(Type::Any),
+            };
+            if export.is_type_only() {
+                self.rewrites.type_imports.insert(name.span);
+            }
+            let local = name.alias.as_ref().unwrap_or(&name.name);
+            match export.kind {
+                ExportKind::Value | ExportKind::Struct { .. } => self.define_import(local, export, name.span),
+                ExportKind::TypeAlias => {
+                    self.type_aliases.insert(local.clone(), export.ty);
+                }
+                ExportKind::GenericAlias(alias) => {
+                    self.generic_aliases.insert(local.clone(), *alias);
+                }
+                ExportKind::Interface(decl) => {
+                    self.type_names.insert(local.clone());
+                    self.interfaces.insert(local.clone(), *decl);
+                }
+            }
+        }
+    }
+
+    /// Imported bindings are read-only, as in JS; an imported `const` keeps
+    /// its value for folding.
+    fn define_import(&mut self, name: &str, export: Export, def: Span) {
+        self.scope.define(
+            name,
             Symbol {
-                ty,
+                ty: export.ty,
                 mutable: false,
+                frozen: true,
+                mut_decl: None,
+                local_decl: None,
+                constant: export.constant,
+                is_const: export.is_const,
+                def,
             },
-        ) {
-            self.error(format!("duplicate declaration `{}`", et.name), et.span);
+        );
+    }
+
+    /// The module's public symbols, looked up once the module is checked.
+    fn exports(&self, module: &Module) -> ModuleExports {
+        let mut exports = ModuleExports::new();
+        for item in &module.items {
+            match item {
+                Item::FnDecl(f) if f.is_pub => {
+                    if let Some(sym) = self.scope.lookup(&f.name) {
+                        exports.insert(f.name.clone(), Export::of(sym));
+                    }
+                }
+                // The enum's type carries its variants' fields in order
+                Item::EnumDecl(e) if e.is_pub => {
+                    if let Some(sym) = self.scope.lookup(&e.name) {
+                        exports.insert(e.name.clone(), Export::of(sym));
+                    }
+                }
+                Item::StructDecl(s) if s.is_pub => {
+                    if let Some(sym) = self.scope.lookup(&s.name) {
+                        let has_factory = s.fields.iter().any(|f| f.default.is_some());
+                        exports.insert(s.name.clone(), Export { kind: ExportKind::Struct { has_factory }, ..Export::of(sym) });
+                    }
+                }
+                Item::TypeAlias(t) if t.is_pub => {
+                    let export = match self.type_aliases.get(&t.name) {
+                        Some(ty) => Export { kind: ExportKind::TypeAlias, ..Export::any(ty.clone()) },
+                        None => Export { kind: ExportKind::GenericAlias(Box::new(t.clone())), ..Export::any(Type::Unknown) },
+                    };
+                    exports.insert(t.name.clone(), export);
+                }
+                Item::InterfaceDecl(i) if i.is_pub => {
+                    let export = Export { kind: ExportKind::Interface(Box::new(i.clone())), ..Export::any(Type::Unknown) };
+                    exports.insert(i.name.clone(), export);
+                }
+                Item::ExportAlias(ea) => {
+                    for name in &ea.names {
+                        if let Some(sym) = self.scope.lookup(&name.local) {
+                            exports.insert(name.exported.clone(), Export::of(sym));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        exports
+    }
+
+    /// Report private types named in the signatures of public items: an
+    /// importer could call a `pub fn` but not name its parameter types.
+    fn check_public_signatures(&mut self, module: &Module) {
+        let reexported: HashSet<&str> = module
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::ExportAlias(ea) => Some(ea.names.iter().map(|n| n.local.as_str())),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        let mut private: HashMap<&str, Span> = HashMap::new();
+        for item in &module.items {
+            let (name, span, is_pub) = match item {
+                Item::StructDecl(s) => (&s.name, s.span, s.is_pub),
+                Item::EnumDecl(e) => (&e.name, e.span, e.is_pub),
+                Item::InterfaceDecl(i) => (&i.name, i.span, i.is_pub),
+                Item::TypeAlias(t) => (&t.name, t.span, t.is_pub),
+                _ => continue,
+            };
+            if !is_pub {
+                private.insert(name, span);
+            }
+        }
+        if private.is_empty() {
+            return;
+        }
+
+        let mut named = Vec::new();
+        for item in &module.items {
+            match item {
+                Item::FnDecl(f) if f.is_pub || reexported.contains(f.name.as_str()) => {
+                    let params = f.params.iter().filter_map(|p| p.ty.as_ref());
+                    for ty in params.chain(&f.return_type) {
+                        collect_named_types(ty, &f.type_params, &mut named);
+                    }
+                }
+                Item::StructDecl(s) if s.is_pub => {
+                    s.fields.iter().for_each(|f| collect_named_types(&f.ty, &[], &mut named));
+                }
+                Item::EnumDecl(e) if e.is_pub => {
+                    for v in &e.variants {
+                        v.fields.iter().for_each(|f| collect_named_types(&f.ty, &[], &mut named));
+                    }
+                }
+                Item::TypeAlias(t) if t.is_pub => collect_named_types(&t.ty, &t.type_params, &mut named),
+                Item::InterfaceDecl(i) if i.is_pub => {
+                    for m in &i.methods {
+                        let params = m.params.iter().filter_map(|p| p.ty.as_ref());
+                        for ty in params.chain(&m.return_type) {
+                            collect_named_types(ty, &i.type_params, &mut named);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        for (name, span) in named {
+            if let Some(&decl) = private.get(name) {
+                self.error_with_label(
+                    format!("private type `{name}` appears in public signature"),
+                    span,
+                    Some((format!("`{name}` is declared here without `pub`"), decl)),
+                );
+            }
+        }
+    }
+
+    fn check_export_alias(&mut self, ea: &ExportAlias) {
+        let mut exported = HashSet::new();
+        for name in &ea.names {
+            if self.scope.lookup(&name.local).is_none() {
+                self.error(format!("cannot export undefined name `{}`", name.local), name.span);
+            }
+            if !exported.insert(name.exported.as_str()) {
+                self.error(format!("duplicate export `{}`", name.exported), name.span);
+            }
+        }
+    }
+
+    // ── Interfaces and impl blocks ─────────────────────────
+
+    fn check_interface_decl(&mut self, i: &InterfaceDecl) {
+        for parent in &i.extends {
+            if !self.interfaces.contains_key(parent) {
+                self.error(format!("unknown interface `{parent}`"), i.span);
+            }
+        }
+        // Resolve the signatures up front so unknown types are reported at
+        // the interface rather than at each `impl`.
+        let outer = self.enter_type_params(&i.name);
+        for m in &i.methods {
+            self.method_type(&m.params, m.return_type.as_ref());
+        }
+        self.type_params = outer;
+    }
+
+    /// Bring the type parameters of interface `name` into scope, returning
+    /// the previous ones for the caller to restore.
+    fn enter_type_params(&mut self, name: &str) -> Vec<String> {
+        let params: Vec<String> = self
+            .interfaces
+            .get(name)
+            .map(|i| i.type_params.iter().map(|p| p.name.clone()).collect())
+            .unwrap_or_default();
+        for p in &params {
+            self.type_bounds.remove(p);
+        }
+        std::mem::replace(&mut self.type_params, params)
+    }
+
+    /// Methods required by interface `name`, including those of the
+    /// interfaces it extends, paired with the interface declaring them.
+    fn interface_methods(&self, name: &str) -> Vec<(String, MethodSignature)> {
+        let mut methods = Vec::new();
+        let mut pending = vec![name.to_string()];
+        let mut seen = HashSet::new();
+        while let Some(name) = pending.pop() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            if let Some(decl) = self.interfaces.get(&name) {
+                methods.extend(decl.methods.iter().map(|m| (name.clone(), m.clone())));
+                pending.extend(decl.extends.iter().cloned());
+            }
+        }
+        methods
+    }
+
+    /// The type of a method as seen by callers: its parameters without
+    /// `self`, and its return type.
+    fn method_type(&mut self, params: &[Param], ret: Option<&TypeExpr>) -> Type {
+        let params: Vec<Param> = params.iter().filter(|p| p.name != "self").cloned().collect();
+        let params = self.declared_params(&params, false);
+        let ret = ret.map(|t| self.resolve_type(t)).unwrap_or(Type::Nil);
+        Type::Function(params, Box::new(ret))
+    }
+
+    /// Record the signatures of `ib`'s functions under its struct, for
+    /// `value.method()` and `Struct::function()` calls.
+    fn register_impl_block(&mut self, ib: &ImplBlock) {
+        for m in &ib.methods {
+            let outer = self.enter_fn_type_params(m);
+            let ty = match self.method_type(&m.params, m.return_type.as_ref()) {
+                Type::Function(params, ret) if m.is_async => {
+                    Type::Function(params, Box::new(Type::Promise(Box::new(awaited(*ret)))))
+                }
+                ty => ty,
+            };
+            self.type_params = outer;
+            let methods = self.impl_methods.entry(ib.target.clone()).or_default();
+            if let Some(first) = methods.iter().find(|other| other.name == m.name) {
+                let label = Some((format!("`{}` first defined here", m.name), first.span));
+                self.error_with_label(format!("duplicate method `{}` on `{}`", m.name, ib.target), m.span, label);
+                continue;
+            }
+            methods.push(ImplMethod {
+                name: m.name.clone(),
+                ty,
+                takes_self: m.params.first().is_some_and(|p| p.name == "self"),
+                span: m.span,
+            });
+        }
+    }
+
+    fn check_impl_block(&mut self, ib: &ImplBlock) {
+        if !self.struct_decls.contains_key(&ib.target) {
+            self.error(
+                format!("cannot implement methods for unknown struct `{}`", ib.target),
+                ib.span,
+            );
+        }
+        if let Some(ref iface) = ib.interface {
+            self.check_conformance(ib, iface);
+        }
+        for method in &ib.methods {
+            // `self` is the struct the block implements
+            let mut method = method.clone();
+            if let Some(param) = method.params.first_mut()
+                && param.name == "self"
+                && param.ty.is_none()
+            {
+                param.ty = Some(TypeExpr::Named(ib.target.clone(), param.span));
+            }
+            self.check_fn_decl(&method);
+        }
+    }
+
+    /// Check that `ib` provides every method interface `iface` requires,
+    /// with a matching signature.
+    fn check_conformance(&mut self, ib: &ImplBlock, iface: &str) {
+        if !self.interfaces.contains_key(iface) {
+            self.error(format!("unknown interface `{iface}`"), ib.span);
+            return;
+        }
+        for (owner, required) in self.interface_methods(iface) {
+            let label = Some((format!("`{}` declared here", required.name), required.span));
+            let Some(method) = ib.methods.iter().find(|m| m.name == required.name) else {
+                self.error_with_label(
+                    format!(
+                        "`{}` is missing method `{}` required by interface `{}`",
+                        ib.target, required.name, owner
+                    ),
+                    ib.span,
+                    label,
+                );
+                continue;
+            };
+            let takes_self = |params: &[Param]| params.first().is_some_and(|p| p.name == "self");
+            if takes_self(&required.params) != takes_self(&method.params) {
+                let expected = if takes_self(&required.params) { "take" } else { "not take" };
+                self.error_with_label(
+                    format!(
+                        "method `{}` must {expected} `self`, as declared by interface `{owner}`",
+                        method.name
+                    ),
+                    method.span,
+                    label,
+                );
+                continue;
+            }
+            let outer = self.enter_type_params(&owner);
+            let expected = self.method_type(&required.params, required.return_type.as_ref());
+            self.type_params = outer;
+            let found = self.method_type(&method.params, method.return_type.as_ref());
+            if !self.type_compatible(&expected, &found) {
+                self.error_with_label(
+                    format!(
+                        "method `{}` does not match interface `{}`: expected `{}`, found `{}`",
+                        method.name, owner, expected, found
+                    ),
+                    method.span,
+                    label,
+                );
+            }
         }
     }
 
     // ── Function check ─────────────────────────────────────
 
     fn check_fn_decl(&mut self, f: &FnDecl) {
+        let outer_type_params = self.enter_fn_type_params(f);
+        self.check_type_constraints(f);
+        self.report_duplicate_params(&f.params);
         // Register in tool registry if @tool annotated
         if let Some(ref ann) = f.tool_annotation {
             let param_types: Vec<(String, Type)> = f
@@ -735,10 +2008,12 @@ impl Checker {
             );
         }
 
-        let parent = std::mem::replace(&mut self.scope, Scope::new());
-        self.scope = Scope::child(parent);
+        let scope = self.push_scope();
         let prev_async = self.in_async;
         self.in_async = f.is_async;
+        let first_mut_decl = self.mut_decls.len();
+        let first_local_decl = self.local_decls.len();
+        let prev_loops = std::mem::take(&mut self.loop_breaks);
 
         // Check and register params
         for param in &f.params {
@@ -753,25 +2028,63 @@ impl Checker {
                 .as_ref()
                 .map(|t| self.resolve_type(t))
                 .unwrap_or(Type::Any);
+            if param.ty.is_none() && param.default.is_some() {
+                self.warn_implicit_any(&format!("parameter `{}`", param.name), &ty, param.span);
+            }
+            self.record_decl_type(param.span, &ty);
+            self.lint_shadowing(&param.name, param.span);
+            // `self` may only be assigned through with `mut self`
             self.scope.define(
                 &param.name,
                 Symbol {
                     ty,
-                    mutable: false,
+                    mutable: param.is_mut,
+                    frozen: param.name == "self" && !param.is_mut,
+                    mut_decl: None,
+                    local_decl: None,
+                    constant: None,
+                    is_const: false,
+                    def: param.span,
                 },
             );
         }
 
-        let declared_ret = f
+        let mut declared_ret = f
             .return_type
             .as_ref()
             .map(|t| self.resolve_type(t));
+        // Callers await an async fn's result, which awaits any promise it
+        // returns: `-> Promise<T>` and a `Promise<T>` result both mean `T`.
+        if f.is_async {
+            declared_ret = declared_ret.map(awaited);
+        }
+        let prev_return = std::mem::replace(&mut self.return_type, declared_ret.clone());
 
         // Check body
-        let body_type = self.check_block(&f.body);
+        let mut body_type = self.check_block_expected(&f.body, declared_ret.as_ref());
+        if f.is_async {
+            body_type = awaited(body_type);
+        }
+        // A result no one receives
+        if declared_ret.as_ref().is_none_or(|ty| *ty == Type::Nil)
+            && let Some(tail) = &f.body.tail_expr
+        {
+            self.warn_floating_promise(&body_type, tail.span());
+        }
+
+        // Every path of a function returning a value must produce one
+        let missing = declared_ret
+            .as_ref()
+            .filter(|ty| **ty != Type::Nil)
+            .and_then(|_| missing_return(&f.body));
+        if let Some(span) = missing {
+            self.error(format!("fn `{}`: not all paths return a value", f.name), span);
+        }
 
         // Check return type matches
-        if let Some(ref expected) = declared_ret {
+        if let Some(ref expected) = declared_ret
+            && missing.is_none()
+        {
             if !self.type_compatible(expected, &body_type) {
                 self.error(
                     format!(
@@ -783,62 +2096,366 @@ impl Checker {
             }
         }
 
-        // Restore scope and async state
+        self.warn_unmutated(first_mut_decl);
+        self.warn_unused(first_local_decl);
+
+        // Restore scope, async, loop and return state
         self.in_async = prev_async;
-        let child = std::mem::replace(&mut self.scope, Scope::new());
-        self.scope = *child.parent.unwrap();
+        self.loop_breaks = prev_loops;
+        self.return_type = prev_return;
+        self.pop_scope(scope);
+
+        if f.is_const {
+            let mut validator = const_fn::ConstFnValidator::new(self, f);
+            validator.block(&f.body);
+            for (msg, span) in validator.errors {
+                self.error(msg, span);
+            }
+        }
+        self.type_params = outer_type_params;
     }
 
-    // ── Variable check ─────────────────────────────────────
-
-    fn check_var_decl(&mut self, v: &VarDecl) {
-        let init_type = self.check_expr(&v.init);
+    /// Bring the type parameters of `f` into scope, after those of an
+    /// enclosing interface, returning the previous ones for the caller to
+    /// restore.
+    fn enter_fn_type_params(&mut self, f: &FnDecl) -> Vec<String> {
+        let mut params = self.type_params.clone();
+        params.extend(f.type_params.iter().map(|p| p.name.clone()));
+        for p in &f.type_params {
+            self.type_bounds.remove(&p.name);
+        }
+        for c in &f.where_clause {
+            if let TypeExpr::Named(bound, _) = &c.bound
+                && self.interfaces.contains_key(bound)
+                && f.type_params.iter().any(|p| p.name == c.type_param)
+            {
+                self.type_bounds.entry(c.type_param.clone()).or_default().push(bound.clone());
+            }
+        }
+        std::mem::replace(&mut self.type_params, params)
+    }
 
-        if let Some(ref ty_expr) = v.ty {
-            let declared = self.resolve_type(ty_expr);
-            if !self.type_compatible(&declared, &init_type) {
+    /// Check the type parameters of `f` and the bounds its `where` clause
+    /// puts on them, which must be interfaces.
+    fn check_type_constraints(&mut self, f: &FnDecl) {
+        self.report_duplicate_names("type parameter", f.type_params.iter().map(|p| (p.name.as_str(), p.span)));
+        for c in &f.where_clause {
+            if !f.type_params.iter().any(|p| p.name == c.type_param) {
                 self.error(
-                    format!(
-                        "type mismatch: expected `{}`, found `{}`",
-                        declared, init_type
-                    ),
-                    v.span,
+                    format!("`where` clause constrains `{}`, which is not a type parameter of `{}`", c.type_param, f.name),
+                    c.span,
                 );
             }
+            match &c.bound {
+                TypeExpr::Named(name, _) if self.interfaces.contains_key(name) => {}
+                bound => match self.resolve_type(bound) {
+                    // Unknown names are reported as such
+                    Type::Unknown => {}
+                    ty => self.error(format!("bound `{ty}` of `{}` is not an interface", c.type_param), c.span),
+                },
+            }
         }
+    }
 
-        let ty = v
-            .ty
-            .as_ref()
-            .map(|t| self.resolve_type(t))
-            .unwrap_or(init_type);
+    /// The module-level binding `name`, ignoring any local shadowing it.
+    fn module_symbol(&self, name: &str) -> Option<&Symbol> {
+        self.scope.frames[0].get(name)
+    }
 
-        let mutable = v.kind == VarKind::Mut;
-        if !self.scope.define(
-            &v.name,
-            Symbol {
-                ty,
-                mutable,
-            },
-        ) {
-            self.error(format!("duplicate binding `{}`", v.name), v.span);
+    /// Enter a new innermost scope. Pass the mark to [`Self::pop_scope`] to
+    /// leave it, along with any scope entered since and not yet left.
+    fn push_scope(&mut self) -> ScopeMark {
+        let mark = ScopeMark(self.scope.frames.len());
+        self.scope.frames.push(HashMap::new());
+        mark
+    }
+
+    fn pop_scope(&mut self, mark: ScopeMark) {
+        debug_assert!(mark.0 > 0 && mark.0 < self.scope.frames.len(), "scope popped twice");
+        self.scope.frames.truncate(mark.0);
+    }
+
+    /// In strict mode, warn that `what` has the unannotated type `ty` if it
+    /// involves `any`. Function types are exempt: their parameters are
+    /// reported instead.
+    fn warn_implicit_any(&mut self, what: &str, ty: &Type, span: Span) {
+        if self.options.strict_implicit_any && mentions_any(ty) {
+            self.lint(
+                IMPLICIT_ANY,
+                Diagnostic::warning(format!("{what} is implicitly `{ty}`; add a type annotation"), span),
+            );
         }
     }
 
-    // ── Expression check ───────────────────────────────────
+    /// Warn about `mut` bindings declared since `mut_decls[first]` that are
+    /// never assigned, then forget them.
+    fn warn_unmutated(&mut self, first: usize) {
+        for decl in self.mut_decls.split_off(first) {
+            if !decl.mutated {
+                self.lint(
+                    UNUSED_MUT,
+                    Diagnostic::warning(
+                        format!(
+                            "variable `{}` is declared `mut` but never mutated; consider using `let`",
+                            decl.name
+                        ),
+                        decl.span,
+                    ),
+                );
+            }
+        }
+    }
 
-    fn check_expr(&mut self, expr: &Expr) -> Type {
-        match expr {
-            Expr::Literal(lit) => match lit {
-                Literal::Int(_, _) => Type::Int,
-                Literal::Float(_, _) => Type::Num,
+    /// Warn about local variables declared since `local_decls[first]` that
+    /// are never read, then forget them.
+    fn warn_unused(&mut self, first: usize) {
+        for decl in self.local_decls.split_off(first) {
+            if !decl.used {
+                self.lint(
+                    UNUSED_VARIABLE,
+                    Diagnostic::warning(
+                        format!("variable `{}` is never used; prefix it with `_` if this is intended", decl.name),
+                        decl.span,
+                    ),
+                );
+            }
+        }
+    }
+
+    // ── Variable check ─────────────────────────────────────
+
+    fn check_var_decl(&mut self, v: &VarDecl) {
+        let declared = v.ty.as_ref().map(|t| self.resolve_type(t));
+        let init_type = self.check_expr_expected(&v.init, declared.as_ref());
+
+        if let Some(ref declared) = declared
+            && !self.type_compatible(declared, &init_type)
+        {
+            self.error(
+                format!(
+                    "type mismatch: expected `{}`, found `{}`",
+                    declared, init_type
+                ),
+                v.span,
+            );
+        }
+
+        if declared.is_none() && !matches!(init_type, Type::Function(..)) {
+            self.warn_implicit_any(&format!("`{}`", v.name), &init_type, v.span);
+        }
+        let ty = declared.unwrap_or(init_type);
+
+        if v.kind == VarKind::Const && !is_const_expr(&v.init, &self.scope) {
+            self.error("const initializer must be a constant expression", v.span);
+        }
+        let constant = if v.kind == VarKind::Const { self.const_eval(&v.init) } else { None };
+        let mutable = v.kind == VarKind::Mut;
+        let mut_decl = mutable.then(|| {
+            self.mut_decls.push(MutDecl { name: v.name.clone(), span: v.span, mutated: false });
+            self.mut_decls.len() - 1
+        });
+        // Top-level duplicates are reported up front, with the other kinds
+        let is_local = self.scope.frames.len() > 1;
+        let local_decl = (is_local && !v.name.starts_with('_')).then(|| {
+            self.local_decls.push(LocalDecl { name: v.name.clone(), span: v.span, used: false });
+            self.local_decls.len() - 1
+        });
+        self.record_decl_type(v.span, &ty);
+        self.lint_shadowing(&v.name, v.span);
+        if !self.scope.define(
+            &v.name,
+            Symbol {
+                ty,
+                mutable,
+                frozen: !mutable,
+                mut_decl,
+                local_decl,
+                constant,
+                is_const: v.kind == VarKind::Const,
+                def: v.span,
+            },
+        ) && is_local
+        {
+            self.error(format!("duplicate binding `{}`", v.name), v.span);
+        }
+    }
+
+    // ── Expression check ───────────────────────────────────
+
+    /// Check `expr` where the context expects a value of type `expected`
+    /// (an annotation, a parameter type, ...). Literals use it to report
+    /// precise errors, and struct literals to fill in defaults, also as
+    /// array elements and branch results; other expressions are checked
+    /// as usual.
+    fn check_expr_expected(&mut self, expr: &Expr, expected: Option<&Type>) -> Type {
+        let expected = match expected {
+            Some(Type::Nullable(inner)) => Some(&**inner),
+            other => other,
+        };
+        let ty = match (expr, expected) {
+            (Expr::Object(obj), Some(Type::Struct(name, fields)))
+                if self.struct_decls.contains_key(name) =>
+            {
+                self.check_struct_literal(obj, name, fields)
+            }
+            // `[]` takes its element type from the context instead of `any`.
+            (Expr::Array(arr), Some(Type::Array(elem))) if arr.elements.is_empty() => {
+                Type::Array(elem.clone())
+            }
+            (Expr::Array(arr), Some(Type::Array(elem))) => {
+                let mut ty = (**elem).clone();
+                for element in &arr.elements {
+                    let element_ty = self.check_expr_expected(element, Some(elem));
+                    if !self.type_compatible(elem, &element_ty) && self.type_compatible(elem, &ty) {
+                        ty = element_ty;
+                    }
+                }
+                Type::Array(Box::new(ty))
+            }
+            // Single quotes used to delimit strings too
+            (Expr::Literal(Literal::Char(c, span)), Some(Type::Str)) => {
+                self.error(
+                    format!(
+                        "character literal `{c:?}` is an `int` code point, not a `str`; write the string with double quotes: `{:?}`",
+                        c.to_string()
+                    ),
+                    *span,
+                );
+                Type::Str
+            }
+            (Expr::If(if_expr), Some(_)) => self.check_if(if_expr, expected),
+            (Expr::Match(m), Some(_)) => self.check_match(m, expected),
+            (Expr::Block(b), Some(_)) => self.check_block_expected(b, expected),
+            _ => return self.check_expr(expr),
+        };
+        self.record_type(expr, &ty);
+        ty
+    }
+
+    /// Check an object literal against the struct `name`: each field value
+    /// against its declared type, every field without a default or `T?`
+    /// type must be present, and unknown fields are flagged as likely
+    /// typos. Problems are reported here, so the literal then has the
+    /// struct's type.
+    fn check_struct_literal(&mut self, obj: &ObjectExpr, name: &str, fields: &[(String, Type)]) -> Type {
+        self.warn_duplicate_keys(obj);
+        for field in &obj.fields {
+            match fields.iter().find(|(n, _)| *n == field.key) {
+                Some((_, ty)) => {
+                    let value_ty = self.check_expr_expected(&field.value, Some(ty));
+                    if !self.type_compatible(ty, &value_ty) {
+                        self.error(
+                            format!(
+                                "field `{}`: expected `{}`, found `{}`",
+                                field.key, ty, value_ty
+                            ),
+                            field.span,
+                        );
+                    }
+                }
+                None => {
+                    self.check_expr(&field.value);
+                    self.lint(
+                        UNKNOWN_FIELD,
+                        Diagnostic::warning(format!("struct `{name}` has no field `{}`", field.key), field.span),
+                    );
+                }
+            }
+        }
+
+        // Defaults are filled in by the struct's factory
+        if self.struct_decls[name].fields.iter().any(|f| f.default.is_some()) {
+            self.rewrites.struct_literals.insert(obj.span, name.to_string());
+        }
+        let decl = &self.struct_decls[name];
+        let decl_span = decl.span;
+        let missing: Vec<String> = decl
+            .fields
+            .iter()
+            .zip(fields)
+            .filter(|(f, (_, ty))| f.default.is_none() && !matches!(ty, Type::Nullable(_)))
+            .filter(|(f, _)| !obj.fields.iter().any(|of| of.key == f.name))
+            .map(|(f, _)| f.name.clone())
+            .collect();
+        for field in missing {
+            self.error_with_label(
+                format!("missing field `{field}` in `{name}` literal"),
+                obj.span,
+                Some((format!("struct `{name}` declared here"), decl_span)),
+            );
+        }
+
+        Type::Struct(name.to_string(), fields.to_vec())
+    }
+
+    fn check_expr(&mut self, expr: &Expr) -> Type {
+        let ty = self.infer_expr(expr);
+        self.record_type(expr, &ty);
+        ty
+    }
+
+    fn record_type(&mut self, expr: &Expr, ty: &Type) {
+        if let Some(map) = &mut self.type_map {
+            map.types.insert(ExprKey::of(expr), ty.clone());
+        }
+    }
+
+    fn record_decl_type(&mut self, span: Span, ty: &Type) {
+        if let Some(map) = &mut self.type_map {
+            map.decl_types.insert(span, ty.clone());
+        }
+    }
+
+    fn infer_expr(&mut self, expr: &Expr) -> Type {
+        match expr {
+            Expr::Literal(lit) => match lit {
+                Literal::Int(n, text, span) => {
+                    if n.unsigned_abs() > MAX_SAFE_INTEGER {
+                        self.lint(
+                            OVERFLOWING_LITERALS,
+                            Diagnostic::warning(
+                                format!(
+                                    "integer literal `{text}` exceeds the precision of JS numbers and evaluates to `{}`",
+                                    *n as f64
+                                ),
+                                *span,
+                            ),
+                        );
+                    }
+                    Type::Int
+                }
+                Literal::Float(n, span) => {
+                    if n.is_infinite() {
+                        self.lint(
+                            OVERFLOWING_LITERALS,
+                            Diagnostic::warning("float literal is out of range and evaluates to `Infinity`", *span),
+                        );
+                    }
+                    Type::Num
+                }
                 Literal::String(_, _) => Type::Str,
+                Literal::Char(_, _) => Type::Int,
                 Literal::Bool(_, _) => Type::Bool,
                 Literal::Nil(_) => Type::Nil,
             },
             Expr::Ident(ident) => {
                 if let Some(sym) = self.scope.lookup(&ident.name) {
+                    if let Some(map) = &mut self.type_map {
+                        map.definitions.insert(ident.span, sym.def);
+                    }
+                    if let Some(id) = sym.local_decl {
+                        self.local_decls[id].used = true;
+                    }
                     sym.ty.clone()
+                } else if let Some(&def) = self.pending_globals.get(&ident.name) {
+                    if self.arrow_depth == 0 {
+                        self.error_with_label(
+                            format!("`{}` is used before its definition", ident.name),
+                            ident.span,
+                            Some((format!("`{}` is defined here", ident.name), def)),
+                        );
+                    }
+                    Type::Any
                 } else {
                     self.error(
                         format!("undefined variable `{}`", ident.name),
@@ -853,7 +2470,9 @@ impl Checker {
                 match b.op {
                     BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div
                     | BinaryOp::Mod | BinaryOp::Pow => {
-                        if matches!((&left_ty, &right_ty), (Type::Int, Type::Int)) {
+                        if self.reject_type_param_operand(&left_ty, &right_ty, "arithmetic", b.span) {
+                            Type::Unknown
+                        } else if matches!((&left_ty, &right_ty), (Type::Int, Type::Int)) {
                             Type::Int
                         } else if matches!(
                             (&left_ty, &right_ty),
@@ -868,10 +2487,48 @@ impl Checker {
                             Type::Any
                         }
                     }
-                    BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Gt
-                    | BinaryOp::Le | BinaryOp::Ge => Type::Bool,
-                    BinaryOp::And | BinaryOp::Or => Type::Bool,
+                    BinaryOp::Eq | BinaryOp::Ne => {
+                        // Disjoint only if no member of one union may equal
+                        // a member of the other
+                        let may_equal = contains_type(&left_ty, &|l| {
+                            contains_type(&right_ty, &|r| self.type_compatible(l, r) || self.type_compatible(r, l))
+                        });
+                        if !may_equal {
+                            let always = if b.op == BinaryOp::Eq { "false" } else { "true" };
+                            self.lint(
+                                DISJOINT_COMPARISON,
+                                Diagnostic::warning(
+                                    format!(
+                                        "`{left_ty}` and `{right_ty}` can never be equal, so this comparison is always {always}"
+                                    ),
+                                    b.span,
+                                ),
+                            );
+                        }
+                        Type::Bool
+                    }
+                    BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => {
+                        self.reject_type_param_operand(&left_ty, &right_ty, "ordering", b.span);
+                        Type::Bool
+                    }
+                    BinaryOp::And | BinaryOp::Or => {
+                        let op = if b.op == BinaryOp::And { "&&" } else { "||" };
+                        self.expect_bool(&left_ty, &b.left, &format!("operand of `{op}`"));
+                        self.expect_bool(&right_ty, &b.right, &format!("operand of `{op}`"));
+                        Type::Bool
+                    }
+                    BinaryOp::In => {
+                        self.check_in(b, &left_ty, &right_ty);
+                        Type::Bool
+                    }
+                }
+            }
+            Expr::Comparison(chain) => {
+                let types: Vec<Type> = chain.operands.iter().map(|operand| self.check_expr(operand)).collect();
+                for (pair, (_, span)) in types.windows(2).zip(&chain.ops) {
+                    self.reject_type_param_operand(&pair[0], &pair[1], "ordering", *span);
                 }
+                Type::Bool
             }
             Expr::Unary(u) => {
                 let inner = self.check_expr(&u.operand);
@@ -891,26 +2548,9 @@ impl Checker {
                     _ => Type::Any,
                 }
             }
-            Expr::If(if_expr) => {
-                self.check_expr(&if_expr.condition);
-                let then_ty = self.check_block(&if_expr.then_block);
-                if let Some(ref else_branch) = if_expr.else_branch {
-                    let else_ty = match else_branch {
-                        ElseBranch::Block(b) => self.check_block(b),
-                        ElseBranch::If(nested) => {
-                            self.check_expr(&Expr::If(nested.clone()))
-                        }
-                    };
-                    if self.type_compatible(&then_ty, &else_ty) {
-                        then_ty
-                    } else {
-                        Type::Union(Box::new(then_ty), Box::new(else_ty))
-                    }
-                } else {
-                    then_ty
-                }
-            }
-            Expr::Match(m) => self.check_match(m),
+            Expr::If(if_expr) => self.check_if(if_expr, None),
+            Expr::Match(m) => self.check_match(m, None),
+            Expr::Loop(l) => self.check_loop(l),
             Expr::Block(b) => self.check_block(b),
             Expr::Array(arr) => {
                 if arr.elements.is_empty() {
@@ -924,6 +2564,7 @@ impl Checker {
                 }
             }
             Expr::Object(obj) => {
+                self.warn_duplicate_keys(obj);
                 let fields: Vec<(String, Type)> = obj
                     .fields
                     .iter()
@@ -932,48 +2573,35 @@ impl Checker {
                         (f.key.clone(), ty)
                     })
                     .collect();
-                Type::Struct("anonymous".to_string(), fields)
-            }
-            Expr::Arrow(arrow) => {
-                let parent = std::mem::replace(&mut self.scope, Scope::new());
-                self.scope = Scope::child(parent);
-                let prev_async = self.in_async;
-                if arrow.is_async {
-                    self.in_async = true;
-                }
-                let param_types: Vec<Type> = arrow
-                    .params
-                    .iter()
-                    .map(|p| {
-                        let ty = p
-                            .ty
-                            .as_ref()
-                            .map(|t| self.resolve_type(t))
-                            .unwrap_or(Type::Any);
-                        self.scope.define(
-                            &p.name,
-                            Symbol {
-                                ty: ty.clone(),
-                                mutable: false,
-                            },
-                        );
-                        ty
-                    })
-                    .collect();
-                let ret = match &arrow.body {
-                    ArrowBody::Expr(e) => self.check_expr(e),
-                    ArrowBody::Block(b) => self.check_block(b),
-                };
-                self.in_async = prev_async;
-                let child = std::mem::replace(&mut self.scope, Scope::new());
-                self.scope = *child.parent.unwrap();
-                Type::Function(param_types, Box::new(ret))
+                Type::Object(fields)
             }
+            Expr::Arrow(arrow) => self.check_arrow(arrow, &[]),
             Expr::Pipe(p) => {
                 let left_ty = self.check_expr(&p.left);
-                let _right_ty = self.check_expr(&p.right);
-                // Pipe result depends on the right side function
-                Type::Any // simplified
+                match &p.right {
+                    // `a |> f(_, x)` is the call itself with `a` in place of `_`.
+                    Expr::Call(call) if call.args.iter().any(|a| matches!(a, Expr::Placeholder(_))) => {
+                        let outer = self.pipe_input.replace(left_ty);
+                        let ty = self.check_expr(&p.right);
+                        self.pipe_input = outer;
+                        ty
+                    }
+                    // `a |> f` calls `f` with `a`.
+                    right => {
+                        let right_ty = self.check_expr(right);
+                        let (param_ty, ret) = match right_ty {
+                            Type::Function(params, ret) => (params.first().map(|p| p.ty.clone()), *ret),
+                            Type::VariadicFunction(params, ret) => (params.first().cloned(), *ret),
+                            _ => return Type::Any,
+                        };
+                        if let Some(param_ty) = param_ty
+                            && !self.type_compatible(&param_ty, &left_ty)
+                        {
+                            self.error(format!("argument 1: expected `{param_ty}`, found `{left_ty}`"), p.span);
+                        }
+                        ret
+                    }
+                }
             }
             Expr::OptionalChain(oc) => {
                 let obj_ty = self.check_expr(&oc.object);
@@ -1002,379 +2630,2996 @@ impl Checker {
                 }
             }
             Expr::ErrorPropagate(ep) => self.check_expr(&ep.expr),
-            Expr::Assign(assign) => {
-                let value_ty = self.check_expr(&assign.value);
-                // Check mutability
-                if let Expr::Ident(ident) = &assign.target {
-                    if let Some(sym) = self.scope.lookup(&ident.name) {
-                        if !sym.mutable {
-                            self.error(
-                                format!("cannot assign to immutable binding `{}`", ident.name),
-                                assign.span,
-                            );
-                        }
+            Expr::Assign(assign) => self.check_assign(assign),
+            Expr::TemplateString(t) => {
+                for part in &t.parts {
+                    if let TemplatePart::Expr(e) = part {
+                        let ty = self.check_expr(e);
+                        self.check_interpolated(&ty, e.span(), "interpolated value");
+                    }
+                }
+                Type::Str
+            }
+            Expr::TaggedTemplate(tt) => {
+                let tag_ty = self.check_expr(&tt.tag);
+                for part in &tt.template.parts {
+                    if let TemplatePart::Expr(e) = part {
+                        self.check_expr(e);
                     }
                 }
-                value_ty
+                match tag_ty {
+                    Type::Function(_, ret) | Type::VariadicFunction(_, ret) => *ret,
+                    _ => Type::Any,
+                }
             }
-            Expr::TemplateString(_) => Type::Str,
-            Expr::Placeholder(_) => Type::Any,
+            Expr::Placeholder(_) => self.pipe_input.clone().unwrap_or(Type::Any),
         }
     }
 
-    fn check_call(&mut self, call: &CallExpr) -> Type {
-        let callee_ty = self.check_expr(&call.callee);
-        for arg in &call.args {
-            self.check_expr(arg);
+    /// Lint a value of type `ty` interpolated into text that will render
+    /// as `[object Object]` or `null`.
+    fn check_interpolated(&mut self, ty: &Type, span: Span, what: &str) {
+        let msg = if !is_stringifiable(ty) {
+            format!(
+                "{what} has type `{ty}` which may not stringify meaningfully; convert it explicitly, e.g. with `JSON.stringify`"
+            )
+        } else if may_be_nil(ty) {
+            format!("{what} has type `{ty}` and renders as `null` when nil; provide a default with `?? \"\"`")
+        } else {
+            return;
+        };
+        self.lint(INTERPOLATION, Diagnostic::warning(msg, span));
+    }
+
+    /// Check `x in xs` and record how codegen tests membership: arrays and
+    /// strings use `includes`, maps `Object.hasOwn`, anything else JS `in`.
+    fn check_in(&mut self, b: &BinaryExpr, left_ty: &Type, right_ty: &Type) {
+        let (expected, rewrite) = match right_ty {
+            Type::Array(elem) => ((**elem).clone(), Some(Membership::Includes)),
+            Type::Str => (Type::Str, Some(Membership::Includes)),
+            Type::Map(k, _) => ((**k).clone(), Some(Membership::HasOwn)),
+            Type::Struct(..) | Type::Object(_) => (Type::Str, None),
+            Type::Any | Type::Unknown => return,
+            _ => {
+                self.error(format!("operator `in` cannot be applied to type `{right_ty}`"), b.span);
+                return;
+            }
+        };
+        if let Some(rewrite) = rewrite {
+            self.rewrites.membership.insert(b.span, rewrite);
+        }
+        if !self.type_compatible(&expected, left_ty) {
+            self.error(
+                format!("type mismatch: `in` on `{right_ty}` expects `{expected}`, found `{left_ty}`"),
+                b.left.span(),
+            );
+        }
+    }
+
+    /// Fold a boolean expression built from literals and `const` bindings.
+    fn const_eval_expr(&mut self, expr: &Expr) -> Option<bool> {
+        match self.const_eval(expr)? {
+            Const::Bool(b) => Some(b),
+            _ => None,
         }
+    }
 
-        match &callee_ty {
-            Type::Function(param_types, ret) => {
-                if call.args.len() > param_types.len() {
-                    self.error(
-                        format!(
-                            "expected {} arguments, found {}",
-                            param_types.len(),
-                            call.args.len()
-                        ),
-                        call.span,
-                    );
+    /// Fold `expr` to a constant, if it is built from literals, `const`
+    /// bindings, operators on them and calls to `const fn`s. Folding that
+    /// runs out of calls is reported, and the value left to run time.
+    fn const_eval(&mut self, expr: &Expr) -> Option<Const> {
+        self.const_calls.set(0);
+        let value = self.const_eval_in(expr, None, 0);
+        if self.const_calls.get() > MAX_CONST_CALLS {
+            self.lint(
+                CONST_EVAL_LIMIT,
+                Diagnostic::warning(
+                    format!("gave up evaluating this constant after {MAX_CONST_CALLS} `const fn` calls; it is computed at run time"),
+                    expr.span(),
+                ),
+            );
+        }
+        value
+    }
+
+    /// Fold `expr` inside a `const fn` call `depth` calls deep, where `args`
+    /// holds the values of the function's parameters.
+    fn const_eval_in(&self, expr: &Expr, args: Option<&HashMap<String, Const>>, depth: usize) -> Option<Const> {
+        match expr {
+            Expr::Literal(lit) => Some(match lit {
+                Literal::Int(n, _, _) => Const::Num(*n as f64),
+                Literal::Float(n, _) => Const::Num(*n),
+                Literal::String(s, _) => Const::Str(s.clone()),
+                Literal::Char(c, _) => Const::Num(*c as u32 as f64),
+                Literal::Bool(b, _) => Const::Bool(*b),
+                Literal::Nil(_) => Const::Nil,
+            }),
+            Expr::Ident(ident) => match args {
+                Some(args) => args
+                    .get(&ident.name)
+                    .cloned()
+                    .or_else(|| self.module_symbol(&ident.name)?.constant.clone()),
+                None => self.scope.lookup(&ident.name)?.constant.clone(),
+            },
+            Expr::Unary(u) => match (u.op, self.const_eval_in(&u.operand, args, depth)?) {
+                (UnaryOp::Not, Const::Bool(b)) => Some(Const::Bool(!b)),
+                (UnaryOp::Neg, Const::Num(n)) => Some(Const::Num(-n)),
+                _ => None,
+            },
+            // `false && x` and `true || x` fold whatever `x` is
+            Expr::Binary(b) if matches!(b.op, BinaryOp::And | BinaryOp::Or) => {
+                let left = self.const_eval_bool(&b.left, args, depth)?;
+                if left == (b.op == BinaryOp::Or) {
+                    return Some(Const::Bool(left));
                 }
-                for (i, (arg, param_ty)) in call.args.iter().zip(param_types).enumerate() {
-                    let arg_ty = self.check_expr(arg);
-                    if !self.type_compatible(param_ty, &arg_ty) {
-                        self.error(
-                            format!(
-                                "argument {}: expected `{}`, found `{}`",
-                                i + 1, param_ty, arg_ty
-                            ),
-                            call.span,
-                        );
+                self.const_eval_bool(&b.right, args, depth).map(Const::Bool)
+            }
+            Expr::Binary(b) => {
+                let left = self.const_eval_in(&b.left, args, depth)?;
+                let right = self.const_eval_in(&b.right, args, depth)?;
+                match (b.op, left, right) {
+                    (BinaryOp::Eq, l, r) => Some(Const::Bool(l == r)),
+                    (BinaryOp::Ne, l, r) => Some(Const::Bool(l != r)),
+                    (BinaryOp::Lt, Const::Num(l), Const::Num(r)) => Some(Const::Bool(l < r)),
+                    (BinaryOp::Gt, Const::Num(l), Const::Num(r)) => Some(Const::Bool(l > r)),
+                    (BinaryOp::Le, Const::Num(l), Const::Num(r)) => Some(Const::Bool(l <= r)),
+                    (BinaryOp::Ge, Const::Num(l), Const::Num(r)) => Some(Const::Bool(l >= r)),
+                    (BinaryOp::Add, Const::Num(l), Const::Num(r)) => Some(Const::Num(l + r)),
+                    (BinaryOp::Sub, Const::Num(l), Const::Num(r)) => Some(Const::Num(l - r)),
+                    (BinaryOp::Mul, Const::Num(l), Const::Num(r)) => Some(Const::Num(l * r)),
+                    (BinaryOp::Add, Const::Str(l), Const::Str(r)) => Some(Const::Str(l + r.as_str())),
+                    _ => None,
+                }
+            }
+            // Later operands are only evaluated while the comparisons hold
+            Expr::Comparison(chain) => {
+                let mut left = self.const_eval_in(&chain.operands[0], args, depth)?;
+                for ((op, _), operand) in chain.ops.iter().zip(&chain.operands[1..]) {
+                    let right = self.const_eval_in(operand, args, depth)?;
+                    let (Const::Num(l), Const::Num(r)) = (&left, &right) else { return None };
+                    let holds = match op {
+                        BinaryOp::Lt => l < r,
+                        BinaryOp::Gt => l > r,
+                        BinaryOp::Le => l <= r,
+                        BinaryOp::Ge => l >= r,
+                        _ => return None,
+                    };
+                    if !holds {
+                        return Some(Const::Bool(false));
                     }
+                    left = right;
                 }
-                *ret.clone()
+                Some(Const::Bool(true))
             }
-            Type::VariadicFunction(param_types, ret) => {
-                // Fixed params come first; last param_type is the variadic element type
-                let (fixed, variadic_ty) = if param_types.is_empty() {
-                    (param_types.as_slice(), &Type::Any)
-                } else {
-                    let (fixed, rest) = param_types.split_at(param_types.len() - 1);
-                    (fixed, &rest[0])
+            Expr::Call(call) => {
+                let Expr::Ident(callee) = &*call.callee else { return None };
+                let is_global = match args {
+                    Some(args) => !args.contains_key(&callee.name),
+                    None => self.scope.is_module_level(&callee.name),
                 };
+                let f = self.const_fns.get(&callee.name).filter(|_| is_global)?;
+                // Only expression bodies are folded; recursion is cut off
+                // rather than evaluated to a fixpoint.
+                if !f.body.stmts.is_empty() || f.params.len() != call.args.len() || depth >= MAX_CONST_CALL_DEPTH {
+                    return None;
+                }
+                let calls = self.const_calls.get() + 1;
+                self.const_calls.set(calls);
+                if calls > MAX_CONST_CALLS {
+                    return None;
+                }
+                let values = f
+                    .params
+                    .iter()
+                    .zip(&call.args)
+                    .map(|(param, arg)| Some((param.name.clone(), self.const_eval_in(arg, args, depth)?)))
+                    .collect::<Option<HashMap<_, _>>>()?;
+                self.const_eval_in(f.body.tail_expr.as_deref()?, Some(&values), depth + 1)
+            }
+            Expr::If(i) => self.const_eval_if(i, args, depth),
+            _ => None,
+        }
+    }
 
-                // Check minimum arity (fixed params)
-                if call.args.len() < fixed.len() {
+    fn const_eval_if(&self, i: &IfExpr, args: Option<&HashMap<String, Const>>, depth: usize) -> Option<Const> {
+        let branch = if self.const_eval_bool(&i.condition, args, depth)? {
+            &i.then_block
+        } else {
+            match i.else_branch.as_ref()? {
+                ElseBranch::Block(b) => b,
+                ElseBranch::If(elif) => return self.const_eval_if(elif, args, depth),
+            }
+        };
+        if !branch.stmts.is_empty() {
+            return None;
+        }
+        self.const_eval_in(branch.tail_expr.as_deref()?, args, depth)
+    }
+
+    fn const_eval_bool(&self, expr: &Expr, args: Option<&HashMap<String, Const>>, depth: usize) -> Option<bool> {
+        match self.const_eval_in(expr, args, depth)? {
+            Const::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Warn when `cond`, an `if` condition or match guard, folds to a
+    /// constant; `what` names it in the message.
+    fn lint_constant_condition(&mut self, cond: &Expr, what: &str) {
+        if let Some(value) = self.const_eval_expr(cond) {
+            self.lint(
+                CONSTANT_CONDITION,
+                Diagnostic::warning(format!("{what} is always {value}"), cond.span()),
+            );
+        }
+    }
+
+    /// Check an `if`/`while` condition or match guard, which must be `bool`.
+    fn check_condition(&mut self, cond: &Expr) {
+        let ty = self.check_expr(cond);
+        self.expect_bool(&ty, cond, "condition");
+    }
+
+    /// Report `expr` (of type `ty`) unless it is a `bool`; `what` names the
+    /// position, e.g. "condition" or "operand of `&&`".
+    fn expect_bool(&mut self, ty: &Type, expr: &Expr, what: &str) {
+        if matches!(ty, Type::Bool | Type::Any | Type::Unknown | Type::Never) {
+            return;
+        }
+        let msg = match (expr, ty) {
+            (Expr::Assign(a), _) if a.op == AssignOp::Assign => {
+                format!("{what} must be `bool`, found an assignment; did you mean `==`?")
+            }
+            (_, Type::Nullable(inner)) if **inner == Type::Bool => format!(
+                "{what} must be `bool`, found `bool?`; compare with `== true` or check for `nil` first"
+            ),
+            (_, Type::Nullable(_)) => {
+                format!("{what} must be `bool`, found `{ty}`; compare with `nil` explicitly")
+            }
+            _ => format!("{what} must be `bool`, found `{ty}`"),
+        };
+        self.error(msg, expr.span());
+    }
+
+    /// Check an assignment; it evaluates to the target's type.
+    fn check_assign(&mut self, assign: &AssignExpr) -> Type {
+        if let Some(root) = assign_root(&assign.target)
+            && let Some(id) = self.scope.lookup(&root.name).and_then(|sym| sym.mut_decl)
+        {
+            self.mut_decls[id].mutated = true;
+        }
+        // Where the target's type comes from, shown alongside mismatches.
+        let mut label = None;
+        let target_ty = match &assign.target {
+            Expr::Ident(ident) => match self.scope.lookup(&ident.name) {
+                Some(sym) => {
+                    let (ty, mutable) = (sym.ty.clone(), sym.mutable);
+                    label = Some((format!("`{}` is declared as `{ty}` here", ident.name), sym.def));
+                    if !mutable {
+                        self.error(
+                            format!("cannot assign to immutable binding `{}`", ident.name),
+                            assign.span,
+                        );
+                    }
+                    ty
+                }
+                None => {
+                    self.error(format!("undefined variable `{}`", ident.name), ident.span);
+                    Type::Unknown
+                }
+            },
+            // `let`/`const` immutability is deep: fields and elements
+            // cannot be assigned through such a binding either.
+            target @ (Expr::Member(_) | Expr::Index(_)) => {
+                let ty = self.check_expr(target);
+                label = self.assign_target_label(target, &ty);
+                if let Some(root) = assign_root(target)
+                    && self.scope.lookup(&root.name).is_some_and(|sym| sym.frozen)
+                {
                     self.error(
                         format!(
-                            "expected at least {} arguments, found {}",
-                            fixed.len(),
-                            call.args.len()
+                            "cannot assign through immutable binding `{}`; declare it with `mut`",
+                            root.name
                         ),
-                        call.span,
+                        assign.span,
                     );
                 }
+                ty
+            }
+            _ => {
+                self.error("invalid assignment target", assign.span);
+                Type::Unknown
+            }
+        };
 
-                for (i, arg) in call.args.iter().enumerate() {
-                    let arg_ty = self.check_expr(arg);
-                    if i < fixed.len() {
-                        if !self.type_compatible(&fixed[i], &arg_ty) {
-                            self.error(
-                                format!(
-                                    "argument {}: expected `{}`, found `{}`",
-                                    i + 1, fixed[i], arg_ty
-                                ),
-                                call.span,
-                            );
-                        }
-                    } else {
-                        // Variadic args
-                        if !self.type_compatible(variadic_ty, &arg_ty) {
-                            self.error(
-                                format!(
-                                    "argument {}: expected `{}`, found `{}`",
-                                    i + 1, variadic_ty, arg_ty
-                                ),
-                                call.span,
-                            );
-                        }
-                    }
+        let value_ty = self.check_expr_expected(&assign.value, Some(&target_ty));
+        let op = match assign.op {
+            AssignOp::Assign => {
+                if !self.type_compatible(&target_ty, &value_ty) {
+                    self.error_with_label(
+                        format!("type mismatch: expected `{target_ty}`, found `{value_ty}`"),
+                        assign.span,
+                        label,
+                    );
                 }
-                *ret.clone()
+                return target_ty;
             }
-            _ => Type::Any,
+            AssignOp::AddAssign => "+=",
+            AssignOp::SubAssign => "-=",
+            AssignOp::MulAssign => "*=",
+            AssignOp::DivAssign => "/=",
+            AssignOp::ModAssign => "%=",
+        };
+        if matches!(target_ty, Type::Any | Type::Unknown) || matches!(value_ty, Type::Any | Type::Unknown) {
+            return target_ty;
         }
-    }
-
+        // `+=` also concatenates strings; every compound operator otherwise
+        // needs numbers, and the result must still fit the target.
+        let result = match (&target_ty, &value_ty) {
+            (Type::Str, Type::Str) if op == "+=" => Some(Type::Str),
+            (Type::Int, Type::Int) if op != "/=" => Some(Type::Int),
+            (Type::Num | Type::Int, Type::Num | Type::Int) => Some(Type::Num),
+            _ => None,
+        };
+        let applicable =
+            matches!(target_ty, Type::Num | Type::Int) || (op == "+=" && target_ty == Type::Str);
+        match result {
+            None if !applicable => self.error_with_label(
+                format!("operator `{op}` cannot be applied to type `{target_ty}`"),
+                assign.span,
+                label,
+            ),
+            None => self.error_with_label(
+                format!("operator `{op}` on `{target_ty}` expects a `{target_ty}` value, found `{value_ty}`"),
+                assign.span,
+                label,
+            ),
+            Some(result) if !self.type_compatible(&target_ty, &result) => self.error_with_label(
+                format!("`{op}` produces `{result}`, which cannot be assigned to `{target_ty}`"),
+                assign.span,
+                label,
+            ),
+            Some(_) => {}
+        }
+        target_ty
+    }
+
+    /// Label for where the type of a field or element assignment target is
+    /// declared: the field of a user struct, else the root binding.
+    fn assign_target_label(&self, target: &Expr, ty: &Type) -> Option<(String, Span)> {
+        if let Expr::Member(m) = target
+            && let Some(Type::Struct(name, _)) = self.place_type(&m.object)
+            && let Some(field) = self.struct_decls.get(&name).and_then(|s| s.fields.iter().find(|f| f.name == m.field))
+        {
+            return Some((format!("field `{}` is declared as `{ty}` here", field.name), field.span));
+        }
+        let root = assign_root(target)?;
+        let sym = self.scope.lookup(&root.name)?;
+        Some((format!("`{}` is declared here", root.name), sym.def))
+    }
+
+    /// Type of a binding, field or element path (`a`, `a.b`, `a[i].b`),
+    /// looked up without checking it again.
+    fn place_type(&self, expr: &Expr) -> Option<Type> {
+        match expr {
+            Expr::Ident(ident) => self.scope.lookup(&ident.name).map(|sym| sym.ty.clone()),
+            Expr::Member(m) => match self.place_type(&m.object)? {
+                Type::Struct(_, fields) | Type::Object(fields) => {
+                    fields.into_iter().find(|(name, _)| *name == m.field).map(|(_, ty)| ty)
+                }
+                _ => None,
+            },
+            Expr::Index(i) => match self.place_type(&i.object)? {
+                Type::Array(elem) => Some(*elem),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The type `for` yields when iterating a value of type `ty`, or `None`
+    /// if it is not iterable. Maps yield their keys.
+    fn element_type(&self, ty: &Type) -> Option<Type> {
+        match ty {
+            Type::Array(inner) => Some((**inner).clone()),
+            Type::Str => Some(Type::Str),
+            Type::Map(k, _) => Some((**k).clone()),
+            Type::Union(a, b) => {
+                let (a, b) = (self.element_type(a)?, self.element_type(b)?);
+                Some(if self.type_compatible(&a, &b) { a } else { Type::Union(Box::new(a), Box::new(b)) })
+            }
+            // Extern types are opaque and may well be iterable in JS.
+            Type::Struct(name, _) if !self.struct_decls.contains_key(name) => Some(Type::Any),
+            Type::Any | Type::Unknown | Type::Never => Some(Type::Any),
+            _ => None,
+        }
+    }
+
+    /// Check an arrow function. `hints` supplies parameter types for
+    /// unannotated params when the arrow is passed where a function type is
+    /// expected (e.g. an array method callback).
+    fn check_arrow(&mut self, arrow: &ArrowExpr, hints: &[FnParam]) -> Type {
+        let scope = self.push_scope();
+        // An arrow has its own async context: it may not `await` just because
+        // the enclosing function can.
+        let prev_async = std::mem::replace(&mut self.in_async, arrow.is_async);
+        self.arrow_depth += 1;
+        let prev_loops = std::mem::take(&mut self.loop_breaks);
+        let prev_return = self.return_type.take();
+        self.report_duplicate_params(&arrow.params);
+        let params: Vec<FnParam> = arrow
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let ty = p
+                    .ty
+                    .as_ref()
+                    .map(|t| self.resolve_type(t))
+                    .or_else(|| hints.get(i).map(|h| h.ty.clone()))
+                    .unwrap_or_else(|| {
+                        self.warn_implicit_any(&format!("parameter `{}`", p.name), &Type::Any, p.span);
+                        Type::Any
+                    });
+                self.record_decl_type(p.span, &ty);
+                self.lint_shadowing(&p.name, p.span);
+                self.scope.define(
+                    &p.name,
+                    Symbol::new(ty.clone(), p.span),
+                );
+                FnParam {
+                    name: Some(p.name.clone()),
+                    ty,
+                    optional: p.default.is_some(),
+                }
+            })
+            .collect();
+        let mut ret = match &arrow.body {
+            ArrowBody::Expr(e) => self.check_expr(e),
+            ArrowBody::Block(b) => self.check_block(b),
+        };
+        if arrow.is_async {
+            ret = Type::Promise(Box::new(awaited(ret)));
+        }
+        self.in_async = prev_async;
+        self.arrow_depth -= 1;
+        self.loop_breaks = prev_loops;
+        self.return_type = prev_return;
+        self.pop_scope(scope);
+        Type::Function(params, Box::new(ret))
+    }
+
+    fn check_call(&mut self, call: &CallExpr) -> Type {
+        // Method calls on arrays may refine their return type from the
+        // arguments, so remember the method name when the receiver is one.
+        let (callee_ty, array_method) = match call.callee.as_ref() {
+            Expr::Member(m) => {
+                let obj_ty = self.check_expr(&m.object);
+                let array_method = matches!(obj_ty, Type::Array(_)).then_some(m.field.as_str());
+                (self.member_type(&obj_ty, m), array_method)
+            }
+            callee => (self.check_expr(callee), None),
+        };
+        let param_hints: Vec<Type> = match &callee_ty {
+            Type::Function(params, _) => params.iter().map(|p| p.ty.clone()).collect(),
+            Type::VariadicFunction(params, _) => params.clone(),
+            _ => Vec::new(),
+        };
+        let arg_types: Vec<Type> = call
+            .args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| match (arg, param_hints.get(i)) {
+                (Expr::Arrow(arrow), Some(Type::Function(hints, _))) => {
+                    let ty = self.check_arrow(arrow, hints);
+                    self.record_type(arg, &ty);
+                    ty
+                }
+                (_, expected) => self.check_expr_expected(arg, expected),
+            })
+            .collect();
+
+        let ret = self.check_call_args(call, &callee_ty, &arg_types);
+        array_method
+            .and_then(|name| builtins::refine_array_method_return(name, &arg_types))
+            .unwrap_or(ret)
+    }
+
+    /// Check the (already evaluated) argument types against the callee's
+    /// signature and return the call's result type.
+    fn check_call_args(&mut self, call: &CallExpr, callee_ty: &Type, arg_types: &[Type]) -> Type {
+        match callee_ty {
+            Type::Function(params, ret) => {
+                let min_arity = params.iter().filter(|p| !p.optional).count();
+                let max_arity = params.len();
+                if call.args.len() > max_arity {
+                    let expected = if min_arity == max_arity {
+                        max_arity.to_string()
+                    } else {
+                        format!("at most {max_arity}")
+                    };
+                    self.error(
+                        format!("expected {expected} arguments, found {}", call.args.len()),
+                        call.span,
+                    );
+                } else if call.args.len() < min_arity {
+                    // Report the first parameter that must be supplied but wasn't.
+                    let missing = params[call.args.len()..].iter().find(|p| !p.optional);
+                    let msg = match missing.and_then(|p| p.name.as_ref()) {
+                        Some(name) => format!("missing argument `{name}`"),
+                        None => format!(
+                            "expected at least {min_arity} arguments, found {}",
+                            call.args.len()
+                        ),
+                    };
+                    self.error(msg, call.span);
+                }
+                for (i, (arg_ty, param)) in arg_types.iter().zip(params).enumerate() {
+                    let param_ty = &param.ty;
+                    if !self.type_compatible(param_ty, arg_ty) {
+                        self.error(
+                            format!(
+                                "argument {}: expected `{}`, found `{}`",
+                                i + 1, param_ty, arg_ty
+                            ),
+                            call.span,
+                        );
+                    }
+                }
+                *ret.clone()
+            }
+            Type::VariadicFunction(param_types, ret) => {
+                // Fixed params come first; last param_type is the variadic element type
+                let (fixed, variadic_ty) = if param_types.is_empty() {
+                    (param_types.as_slice(), &Type::Any)
+                } else {
+                    let (fixed, rest) = param_types.split_at(param_types.len() - 1);
+                    (fixed, &rest[0])
+                };
+
+                // Check minimum arity (fixed params)
+                if call.args.len() < fixed.len() {
+                    self.error(
+                        format!(
+                            "expected at least {} arguments, found {}",
+                            fixed.len(),
+                            call.args.len()
+                        ),
+                        call.span,
+                    );
+                }
+
+                for (i, arg_ty) in arg_types.iter().enumerate() {
+                    if i < fixed.len() {
+                        if !self.type_compatible(&fixed[i], arg_ty) {
+                            self.error(
+                                format!(
+                                    "argument {}: expected `{}`, found `{}`",
+                                    i + 1, fixed[i], arg_ty
+                                ),
+                                call.span,
+                            );
+                        }
+                    } else {
+                        // Variadic args
+                        if !self.type_compatible(variadic_ty, arg_ty) {
+                            self.error(
+                                format!(
+                                    "argument {}: expected `{}`, found `{}`",
+                                    i + 1, variadic_ty, arg_ty
+                                ),
+                                call.span,
+                            );
+                        }
+                    }
+                }
+                *ret.clone()
+            }
+            // A unit variant such as `Status::Idle` already is the value.
+            Type::Enum(name, variants)
+                if matches!(call.callee.as_ref(), Expr::Member(m)
+                    if matches!(m.object.as_ref(), Expr::Ident(id) if id.name == *name)
+                        && variants.iter().any(|(v, fields)| *v == m.field && fields.is_empty())) =>
+            {
+                if let Expr::Member(m) = call.callee.as_ref() {
+                    self.error(
+                        format!("unit variant `{}` takes no arguments", m.field),
+                        call.span,
+                    );
+                }
+                callee_ty.clone()
+            }
+            _ => Type::Any,
+        }
+    }
+
+    /// Report an operand whose type is a bounded type parameter: its
+    /// interfaces declare methods only, not `what` (e.g. "arithmetic").
+    fn reject_type_param_operand(&mut self, left: &Type, right: &Type, what: &str, span: Span) -> bool {
+        let Some(Type::Param(name, _)) = [left, right].into_iter().find(|t| matches!(t, Type::Param(..))) else {
+            return false;
+        };
+        self.error(format!("type parameter `{name}` does not support {what}; its bounds only declare methods"), span);
+        true
+    }
+
     fn check_member_access(&mut self, m: &MemberExpr) -> Type {
         let obj_ty = self.check_expr(&m.object);
-        match &obj_ty {
+        self.member_type(&obj_ty, m)
+    }
+
+    fn member_type(&mut self, obj_ty: &Type, m: &MemberExpr) -> Type {
+        match obj_ty {
+            // `Struct::function`; an extern struct's members are its JS statics
+            Type::Struct(name, _)
+                if self.struct_decls.contains_key(name)
+                    && matches!(m.object.as_ref(), Expr::Ident(id) if id.name == *name) =>
+            {
+                self.associated_fn_type(name, m)
+            }
             Type::Struct(name, fields) => {
+                if let Some((_, ty)) = fields.iter().find(|(n, _)| n == &m.field) {
+                    return ty.clone();
+                }
+                let method = self.impl_methods.get(name).and_then(|ms| ms.iter().find(|f| f.name == m.field));
+                match method {
+                    Some(method) if method.takes_self => {
+                        let ty = method.ty.clone();
+                        let js_fn = ImplBlock::method_fn_name(name, &m.field);
+                        self.rewrites.members.insert(m.span, MemberRewrite::ReceiverCall(js_fn));
+                        ty
+                    }
+                    Some(_) => {
+                        self.error(
+                            format!(
+                                "`{}` is an associated function of `{name}`, not a method; call it as `{name}::{}(...)`",
+                                m.field, m.field
+                            ),
+                            m.span,
+                        );
+                        Type::Unknown
+                    }
+                    None => {
+                        let methods = self.impl_methods.get(name).into_iter().flatten();
+                        let candidates = fields
+                            .iter()
+                            .map(|(n, _)| n.as_str())
+                            .chain(methods.filter(|f| f.takes_self).map(|f| f.name.as_str()));
+                        let msg = match closest_name(&m.field, candidates) {
+                            Some(suggestion) => format!(
+                                "field `{}` does not exist on type `{obj_ty}`, did you mean `{suggestion}`?",
+                                m.field
+                            ),
+                            None => format!("field `{}` does not exist on type `{obj_ty}`", m.field),
+                        };
+                        self.error(msg, m.span);
+                        Type::Unknown
+                    }
+                }
+            }
+            Type::Object(fields) => {
                 if let Some((_, ty)) = fields.iter().find(|(n, _)| n == &m.field) {
                     ty.clone()
                 } else {
                     self.error(
-                        format!("field `{}` does not exist on type `{}`", m.field, name),
+                        format!("field `{}` does not exist on type `{}`", m.field, obj_ty),
                         m.span,
                     );
                     Type::Unknown
                 }
             }
+            // `Enum::Variant`: unit variants are values of the enum, variants
+            // with fields are constructors taking those fields in order.
+            Type::Enum(name, variants)
+                if matches!(m.object.as_ref(), Expr::Ident(id) if id.name == *name) =>
+            {
+                match variants.iter().find(|(v, _)| *v == m.field) {
+                    Some((_, fields)) if fields.is_empty() => obj_ty.clone(),
+                    Some((_, fields)) => {
+                        let params = fields
+                            .iter()
+                            .map(|(field, ty)| FnParam {
+                                name: Some(field.clone()),
+                                ..FnParam::required(ty.clone())
+                            })
+                            .collect();
+                        Type::Function(params, Box::new(obj_ty.clone()))
+                    }
+                    None => {
+                        self.error(
+                            format!("enum `{}` has no variant `{}`", name, m.field),
+                            m.span,
+                        );
+                        Type::Unknown
+                    }
+                }
+            }
+            // A derived method of an enum value
+            Type::Enum(name, _) => match self.impl_methods.get(name).and_then(|ms| ms.iter().find(|f| f.name == m.field)) {
+                Some(method) => {
+                    let ty = method.ty.clone();
+                    let js_fn = ImplBlock::method_fn_name(name, &m.field);
+                    self.rewrites.members.insert(m.span, MemberRewrite::ReceiverCall(js_fn));
+                    ty
+                }
+                None => Type::Any,
+            },
+            // A bounded type parameter has the methods of its interfaces
+            Type::Param(name, bounds) => {
+                let method = bounds
+                    .iter()
+                    .flat_map(|b| self.interface_methods(b))
+                    .find(|(_, sig)| sig.name == m.field);
+                match method {
+                    Some((owner, sig)) => {
+                        let outer = self.enter_type_params(&owner);
+                        let ty = self.method_type(&sig.params, sig.return_type.as_ref());
+                        self.type_params = outer;
+                        ty
+                    }
+                    None => {
+                        self.error(
+                            format!(
+                                "no method `{}` on type parameter `{name}`; its bounds `{}` do not declare it",
+                                m.field,
+                                bounds.join(" + ")
+                            ),
+                            m.span,
+                        );
+                        Type::Unknown
+                    }
+                }
+            }
+            Type::Array(elem) => {
+                let member = builtins::array_member(elem, &m.field);
+                self.builtin_member(member, obj_ty, m)
+            }
+            Type::Str => self.builtin_member(builtins::str_member(&m.field), obj_ty, m),
+            Type::Map(k, v) => match builtins::map_member(k, v, &m.field) {
+                Some((js_fn, ty)) => {
+                    self.rewrites.members
+                        .insert(m.span, MemberRewrite::ReceiverCall(js_fn.to_string()));
+                    ty
+                }
+                None => {
+                    self.error(
+                        format!("member `{}` does not exist on type `{}`", m.field, obj_ty),
+                        m.span,
+                    );
+                    Type::Unknown
+                }
+            },
             _ => Type::Any,
         }
     }
 
-    fn check_match(&mut self, m: &MatchExpr) -> Type {
-        let subject_ty = self.check_expr(&m.subject);
-        let mut result_ty: Option<Type> = None;
-
-        for arm in &m.arms {
-            // Enter new scope for pattern bindings
-            let parent = std::mem::replace(&mut self.scope, Scope::new());
-            self.scope = Scope::child(parent);
-
-            self.bind_pattern(&arm.pattern, &subject_ty);
-
-            if let Some(ref guard) = arm.guard {
-                self.check_expr(guard);
-            }
-
-            let arm_ty = self.check_expr(&arm.body);
+    /// The type of `Struct::function`, the associated function `m.field` of
+    /// struct `name`.
+    fn associated_fn_type(&mut self, name: &str, m: &MemberExpr) -> Type {
+        let methods = self.impl_methods.get(name).map(Vec::as_slice).unwrap_or_default();
+        match methods.iter().find(|f| f.name == m.field) {
+            Some(f) if !f.takes_self => {
+                let ty = f.ty.clone();
+                let js_fn = ImplBlock::method_fn_name(name, &m.field);
+                self.rewrites.associated_fns.insert(m.span, js_fn);
+                ty
+            }
+            Some(_) => {
+                self.error(
+                    format!("`{}` is a method of `{name}` and takes `self`; call it on a value: `x.{}(...)`", m.field, m.field),
+                    m.span,
+                );
+                Type::Unknown
+            }
+            None => {
+                let candidates = methods.iter().filter(|f| !f.takes_self).map(|f| f.name.as_str());
+                let msg = match closest_name(&m.field, candidates) {
+                    Some(suggestion) => format!(
+                        "no associated function `{}` on `{name}`, did you mean `{suggestion}`?",
+                        m.field
+                    ),
+                    None => format!("no associated function `{}` on `{name}`", m.field),
+                };
+                self.error(msg, m.span);
+                Type::Unknown
+            }
+        }
+    }
+
+    /// Resolve a builtin member lookup, recording a rename when the JS name
+    /// differs and reporting unknown members.
+    fn builtin_member(
+        &mut self,
+        member: Option<builtins::BuiltinMember>,
+        obj_ty: &Type,
+        m: &MemberExpr,
+    ) -> Type {
+        match member {
+            Some((js_name, ty)) => {
+                if js_name != m.field {
+                    self.rewrites.members
+                        .insert(m.span, MemberRewrite::Rename(js_name.to_string()));
+                }
+                ty
+            }
+            None => {
+                self.error(
+                    format!("member `{}` does not exist on type `{}`", m.field, obj_ty),
+                    m.span,
+                );
+                Type::Unknown
+            }
+        }
+    }
+
+    fn check_if(&mut self, if_expr: &IfExpr, expected: Option<&Type>) -> Type {
+        self.check_condition(&if_expr.condition);
+        self.lint_constant_condition(&if_expr.condition, "condition");
+        let then_ty = self.check_block_expected(&if_expr.then_block, expected);
+        let Some(else_branch) = &if_expr.else_branch else { return then_ty };
+        let else_ty = match else_branch {
+            ElseBranch::Block(b) => self.check_block_expected(b, expected),
+            ElseBranch::If(nested) => self.check_if(nested, expected),
+        };
+        if self.type_compatible(&then_ty, &else_ty) {
+            then_ty
+        } else if self.type_compatible(&else_ty, &then_ty) {
+            else_ty
+        } else {
+            Type::Union(Box::new(then_ty), Box::new(else_ty))
+        }
+    }
+
+    fn check_match(&mut self, m: &MatchExpr, expected: Option<&Type>) -> Type {
+        let subject_ty = self.check_expr(&m.subject);
+        let mut result_ty: Option<Type> = None;
+
+        for arm in &m.arms {
+            // Enter new scope for pattern bindings
+            let scope = self.push_scope();
+
+            self.bind_pattern(&arm.pattern, &subject_ty);
+
+            if let Some(ref guard) = arm.guard {
+                self.check_condition(guard);
+                self.lint_constant_condition(guard, "match guard");
+            }
+
+            let arm_ty = match &arm.body {
+                MatchArmBody::Expr(e) => self.check_expr_expected(e, expected),
+                MatchArmBody::Block(b) => self.check_block_expected(b, expected),
+            };
+
+            // Restore scope
+            self.pop_scope(scope);
+
+            if let Some(ref existing) = result_ty {
+                if !self.type_compatible(existing, &arm_ty) {
+                    result_ty = Some(Type::Union(
+                        Box::new(existing.clone()),
+                        Box::new(arm_ty),
+                    ));
+                }
+            } else {
+                result_ty = Some(arm_ty);
+            }
+        }
+
+        result_ty.unwrap_or(Type::Nil)
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern, subject_ty: &Type) {
+        match pattern {
+            Pattern::Ident(name, span) => {
+                self.scope.define(
+                    name,
+                    Symbol::new(subject_ty.clone(), *span),
+                );
+            }
+            Pattern::Enum(ep) => {
+                // Bind enum variant fields
+                if let Type::Enum(enum_name, variants) = subject_ty {
+                    if ep.enum_name != *enum_name {
+                        self.error(
+                            format!(
+                                "pattern `{}::{}` does not match type `{}`",
+                                ep.enum_name, ep.variant, enum_name
+                            ),
+                            ep.span,
+                        );
+                        return;
+                    }
+                    let Some((_, fields)) = variants.iter().find(|(n, _)| n == &ep.variant)
+                    else {
+                        let label = self
+                            .enum_decls
+                            .get(enum_name)
+                            .map(|e| (format!("enum `{enum_name}` declared here"), e.span));
+                        self.error_with_label(
+                            format!("enum `{}` has no variant `{}`", enum_name, ep.variant),
+                            ep.span,
+                            label,
+                        );
+                        return;
+                    };
+                    self.variant_fields.insert(
+                        (enum_name.clone(), ep.variant.clone()),
+                        fields.iter().map(|(name, _)| name.clone()).collect(),
+                    );
+                    // `Enum::Variant` without bindings matches any field values.
+                    if !ep.bindings.is_empty() && ep.bindings.len() != fields.len() {
+                        let label = self.enum_decls.get(enum_name).and_then(|e| {
+                            let v = e.variants.iter().find(|v| v.name == ep.variant)?;
+                            Some((format!("variant `{}` declared here", v.name), v.span))
+                        });
+                        self.error_with_label(
+                            format!(
+                                "variant `{}::{}` has {} field(s), but the pattern binds {}",
+                                enum_name,
+                                ep.variant,
+                                fields.len(),
+                                ep.bindings.len()
+                            ),
+                            ep.span,
+                            label,
+                        );
+                    }
+                    for (binding, (_, ty)) in ep.bindings.iter().zip(fields) {
+                        self.scope.define(
+                            binding,
+                            Symbol::new(ty.clone(), ep.span),
+                        );
+                    }
+                }
+            }
+            Pattern::Struct(sp) => {
+                if let Type::Struct(struct_name, fields) = subject_ty {
+                    for field_name in &sp.fields {
+                        if let Some((_, ty)) = fields.iter().find(|(n, _)| n == field_name) {
+                            self.scope.define(
+                                field_name,
+                                Symbol::new(ty.clone(), sp.span),
+                            );
+                        } else {
+                            let label = self
+                                .struct_decls
+                                .get(struct_name)
+                                .map(|d| (format!("struct `{struct_name}` declared here"), d.span));
+                            self.error_with_label(
+                                format!("struct `{struct_name}` has no field `{field_name}`"),
+                                sp.span,
+                                label,
+                            );
+                        }
+                    }
+                } else if let Type::Object(fields) = subject_ty {
+                    for field_name in &sp.fields {
+                        if let Some((_, ty)) = fields.iter().find(|(n, _)| n == field_name) {
+                            self.scope.define(
+                                field_name,
+                                Symbol::new(ty.clone(), sp.span),
+                            );
+                        } else {
+                            self.error(
+                                format!("field `{field_name}` does not exist on type `{subject_ty}`"),
+                                sp.span,
+                            );
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // ── Block check ────────────────────────────────────────
+
+    fn check_block(&mut self, block: &Block) -> Type {
+        self.check_block_expected(block, None)
+    }
+
+    /// Check `block`, whose value the context expects to be of type
+    /// `expected`.
+    fn check_block_expected(&mut self, block: &Block, expected: Option<&Type>) -> Type {
+        let scope = self.push_scope();
+
+        // Everything after a diverging statement is reported as one
+        // unreachable region, but still checked.
+        let mut diverged = false;
+        let mut unreachable: Option<Span> = None;
+        for stmt in &block.stmts {
+            if diverged {
+                unreachable = Some(unreachable.map_or(stmt.span(), |s| s.to(stmt.span())));
+            }
+            self.check_stmt(stmt);
+            diverged |= stmt_diverges(stmt);
+        }
+
+        let ty = if let Some(ref tail) = block.tail_expr {
+            if diverged {
+                unreachable = Some(unreachable.map_or(tail.span(), |s| s.to(tail.span())));
+            }
+            self.check_expr_expected(tail, expected)
+        } else {
+            Type::Nil
+        };
+        // A block that always leaves early produces no value of its own
+        let ty = if diverged { Type::Never } else { ty };
+        if let Some(span) = unreachable {
+            self.lint(UNREACHABLE_CODE, Diagnostic::warning("unreachable code", span));
+        }
+
+        self.pop_scope(scope);
+
+        ty
+    }
+
+    /// Check a loop body and return the `break`s that exit it.
+    fn check_loop_body(&mut self, body: &Block) -> Vec<(Option<Type>, Span)> {
+        self.loop_breaks.push(Vec::new());
+        self.check_block(body);
+        self.loop_breaks.pop().unwrap_or_default()
+    }
+
+    /// Check a `for`/`while` body; only `loop` can be broken out of with a
+    /// value.
+    fn check_cond_loop_body(&mut self, body: &Block) {
+        for (value, span) in self.check_loop_body(body) {
+            if value.is_some() {
+                self.error("`break` with a value is only allowed inside `loop`", span);
+            }
+        }
+    }
+
+    /// `loop` evaluates to the unified type of its `break` values (`nil` for
+    /// a bare `break`) and never completes without a `break`.
+    fn check_loop(&mut self, l: &LoopExpr) -> Type {
+        let breaks = self.check_loop_body(&l.body);
+        let mut result: Option<Type> = None;
+        for (value, span) in breaks {
+            let ty = value.unwrap_or(Type::Nil);
+            result = Some(match result {
+                None => ty,
+                Some(acc) if self.type_compatible(&acc, &ty) => acc,
+                Some(acc) if self.type_compatible(&ty, &acc) => ty,
+                Some(acc) => {
+                    self.error(
+                        format!("`break` value has type `{ty}`, expected `{acc}`"),
+                        span,
+                    );
+                    acc
+                }
+            });
+        }
+        result.unwrap_or(Type::Never)
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::VarDecl(v) => self.check_var_decl(v),
+            Stmt::ExprStmt(e) => {
+                let ty = self.check_expr(&e.expr);
+                if !matches!(e.expr, Expr::Assign(_)) {
+                    self.warn_floating_promise(&ty, e.expr.span());
+                }
+            }
+            Stmt::Return(r) => {
+                if let Some(ref val) = r.value {
+                    let expected = self.return_type.clone();
+                    let mut ty = self.check_expr_expected(val, expected.as_ref());
+                    if self.in_async {
+                        ty = awaited(ty);
+                    }
+                    if let Some(expected) = expected
+                        && !self.type_compatible(&expected, &ty)
+                    {
+                        self.error(format!("return type mismatch: expected `{expected}`, found `{ty}`"), val.span());
+                    }
+                }
+            }
+            Stmt::If(if_expr) => {
+                self.check_expr(&Expr::If(Box::new(if_expr.clone())));
+            }
+            Stmt::For(f) => {
+                let iter_ty = self.check_expr(&f.iter);
+                // Maps iterate their keys, which needs JS `for...in`; that
+                // would iterate an array's or string's indices instead.
+                if contains_type(&iter_ty, &|t| matches!(t, Type::Map(..))) {
+                    self.rewrites.for_in.insert(f.span);
+                    if contains_type(&iter_ty, &|t| matches!(t, Type::Array(_) | Type::Str)) {
+                        self.lint(
+                            FOR_IN_ARRAY,
+                            Diagnostic::warning(
+                                format!(
+                                    "`for` over `{iter_ty}` compiles to `for...in`, which yields the indices of arrays and strings, not their elements"
+                                ),
+                                f.iter.span(),
+                            ),
+                        );
+                    }
+                }
+                let elem_ty = self.element_type(&iter_ty).unwrap_or_else(|| {
+                    self.error(format!("type `{iter_ty}` is not iterable"), f.iter.span());
+                    Type::Unknown
+                });
+                let scope = self.push_scope();
+                self.lint_shadowing(&f.binding, f.span);
+                self.scope.define(
+                    &f.binding,
+                    Symbol::new(elem_ty, f.span),
+                );
+                self.check_cond_loop_body(&f.body);
+                self.pop_scope(scope);
+            }
+            Stmt::ForRange(fr) => {
+                let scope = self.push_scope();
+                self.check_var_decl(&fr.init);
+                self.check_condition(&fr.condition);
+                self.check_cond_loop_body(&fr.body);
+                // The update may step a `let` counter, which the body cannot assign
+                if let Some(sym) = self.scope.frames.last_mut().and_then(|symbols| symbols.get_mut(&fr.init.name)) {
+                    sym.mutable = true;
+                    sym.frozen = false;
+                }
+                self.check_expr(&fr.update);
+                self.pop_scope(scope);
+            }
+            Stmt::While(w) => {
+                self.check_condition(&w.condition);
+                match self.const_eval_expr(&w.condition) {
+                    Some(false) => self.lint(
+                        CONSTANT_CONDITION,
+                        Diagnostic::warning("condition is always false", w.condition.span()),
+                    ),
+                    Some(true) => self.lint(
+                        WHILE_TRUE,
+                        Diagnostic::warning("condition is always true; use `loop` instead", w.condition.span()),
+                    ),
+                    None => {}
+                }
+                self.check_cond_loop_body(&w.body);
+            }
+            Stmt::Loop(l) => {
+                self.check_loop(l);
+            }
+            Stmt::Break(b) => {
+                let value = b.value.as_ref().map(|v| self.check_expr(v));
+                match self.loop_breaks.last_mut() {
+                    Some(breaks) => breaks.push((value, b.span)),
+                    None => self.error("`break` outside of a loop", b.span),
+                }
+            }
+            Stmt::Match(m) => {
+                self.check_match(m, None);
+            }
+            Stmt::TryCatch(tc) => {
+                self.check_block(&tc.try_block);
+                let scope = self.push_scope();
+                self.lint_shadowing(&tc.catch_binding, tc.span);
+                self.scope.define(
+                    &tc.catch_binding,
+                    Symbol::new(Type::Any, tc.span),
+                );
+                self.check_block(&tc.catch_block);
+                self.pop_scope(scope);
+            }
+        }
+    }
+}
+
+// ── Suggestions ────────────────────────────────────────────
+
+const PRIMITIVE_TYPES: &[&str] = &["str", "num", "int", "bool", "nil", "any"];
+
+/// The candidate closest to `name` by case-insensitive edit distance, if
+/// it is close enough to be a plausible typo.
+fn closest_name<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let max_distance = (name.chars().count() / 3).max(2);
+    candidates
+        .map(|c| (edit_distance(&name, &c.to_lowercase()), c))
+        .filter(|&(d, _)| d <= max_distance)
+        .min_by_key(|&(d, c)| (d, c))
+        .map(|(_, c)| c)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// `dsl` as handlers see it, with its captures type-erased.
+fn core_dsl_block(dsl: &DslBlock) -> ag_dsl_core::DslBlock {
+    let content = match &dsl.content {
+        DslContent::Inline { parts } => ag_dsl_core::DslContent::Inline {
+            parts: parts
+                .iter()
+                .map(|p| match p {
+                    ag_ast::DslPart::Text(s, span) => {
+                        CoreDslPart::Text(s.clone(), ag_dsl_core::Span::new(span.start, span.end))
+                    }
+                    ag_ast::DslPart::Capture(expr, span) => {
+                        let boxed: Box<dyn std::any::Any> = Box::new((**expr).clone());
+                        CoreDslPart::Capture(boxed, ag_dsl_core::Span::new(span.start, span.end))
+                    }
+                })
+                .collect(),
+        },
+        DslContent::FileRef { path, span } => ag_dsl_core::DslContent::FileRef {
+            path: path.clone(),
+            span: ag_dsl_core::Span::new(span.start, span.end),
+        },
+    };
+    ag_dsl_core::DslBlock {
+        kind: dsl.kind.clone(),
+        name: dsl.name.name.clone(),
+        content,
+        span: ag_dsl_core::Span::new(dsl.span.start, dsl.span.end),
+    }
+}
+
+/// Whether `ty`, or a member of it if it is a union, satisfies `pred`.
+fn contains_type(ty: &Type, pred: &dyn Fn(&Type) -> bool) -> bool {
+    match ty {
+        Type::Union(a, b) => contains_type(a, pred) || contains_type(b, pred),
+        _ => pred(ty),
+    }
+}
+
+/// The type names mentioned in `ty`, other than the type parameters `params`.
+fn collect_named_types<'t>(ty: &'t TypeExpr, params: &[TypeParam], out: &mut Vec<(&'t str, Span)>) {
+    match ty {
+        TypeExpr::Named(name, span) => {
+            if !params.iter().any(|p| p.name == *name) {
+                out.push((name, *span));
+            }
+        }
+        TypeExpr::Array(inner, _) | TypeExpr::Nullable(inner, _) | TypeExpr::Promise(inner, _) => {
+            collect_named_types(inner, params, out)
+        }
+        TypeExpr::Map(a, b, _) | TypeExpr::Union(a, b, _) => {
+            collect_named_types(a, params, out);
+            collect_named_types(b, params, out);
+        }
+        TypeExpr::Function(ft) => {
+            for ty in ft.params.iter().chain(std::iter::once(&*ft.ret)) {
+                collect_named_types(ty, params, out);
+            }
+        }
+        TypeExpr::Object(ot) => ot.fields.iter().for_each(|f| collect_named_types(&f.ty, params, out)),
+        TypeExpr::Generic(name, args, span) => {
+            if !params.iter().any(|p| p.name == *name) {
+                out.push((name, *span));
+            }
+            args.iter().for_each(|a| collect_named_types(a, params, out));
+        }
+    }
+}
+
+/// The variable a member/index assignment target is rooted at, e.g. `cfg`
+/// for `cfg.retries[0]`. `None` when rooted at a temporary such as a call.
+fn assign_root(target: &Expr) -> Option<&Ident> {
+    match target {
+        Expr::Ident(ident) => Some(ident),
+        Expr::Member(m) => assign_root(&m.object),
+        Expr::Index(i) => assign_root(&i.object),
+        _ => None,
+    }
+}
+
+/// The type an `await` of `ty` produces: `ty` without its `Promise`
+/// wrappers, as awaiting a promise of a promise awaits both.
+fn awaited(ty: Type) -> Type {
+    match ty {
+        Type::Promise(inner) => awaited(*inner),
+        ty => ty,
+    }
+}
+
+/// Whether non-nil values of type `ty` read well interpolated into text:
+/// primitives, arrays of them, and objects with a `toString` method.
+fn is_stringifiable(ty: &Type) -> bool {
+    match ty {
+        Type::Str | Type::Int | Type::Num | Type::Bool | Type::Nil | Type::Any | Type::Unknown | Type::Never => true,
+        Type::Nullable(inner) | Type::Array(inner) => is_stringifiable(inner),
+        Type::Union(a, b) => is_stringifiable(a) && is_stringifiable(b),
+        Type::Struct(_, fields) | Type::Object(fields) => fields
+            .iter()
+            .any(|(name, ty)| name == "toString" && matches!(ty, Type::Function(..) | Type::Any)),
+        _ => false,
+    }
+}
+
+/// Whether a value of type `ty` may be `nil`.
+fn may_be_nil(ty: &Type) -> bool {
+    match ty {
+        Type::Nil | Type::Nullable(_) => true,
+        Type::Union(a, b) => may_be_nil(a) || may_be_nil(b),
+        _ => false,
+    }
+}
+
+/// Whether `ty` is or contains `any`.
+fn mentions_any(ty: &Type) -> bool {
+    match ty {
+        Type::Any => true,
+        Type::Array(inner) | Type::Nullable(inner) | Type::Promise(inner) => mentions_any(inner),
+        Type::Map(k, v) | Type::Union(k, v) => mentions_any(k) || mentions_any(v),
+        Type::Object(fields) => fields.iter().any(|(_, ty)| mentions_any(ty)),
+        _ => false,
+    }
+}
+
+// ── Reachability ───────────────────────────────────────────
+
+/// Whether control never continues past `stmt`: `ret`, `break`, or an
+/// `if`/`match` whose every branch diverges.
+fn stmt_diverges(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Return(_) | Stmt::Break(_) => true,
+        Stmt::ExprStmt(e) => expr_diverges(&e.expr),
+        Stmt::If(i) => if_diverges(i),
+        Stmt::Match(m) => match_diverges(m),
+        _ => false,
+    }
+}
+
+fn expr_diverges(expr: &Expr) -> bool {
+    match expr {
+        Expr::Block(b) => block_diverges(b),
+        Expr::If(i) => if_diverges(i),
+        Expr::Match(m) => match_diverges(m),
+        _ => false,
+    }
+}
+
+fn block_diverges(block: &Block) -> bool {
+    block.stmts.iter().any(stmt_diverges) || block.tail_expr.as_deref().is_some_and(expr_diverges)
+}
+
+fn if_diverges(i: &IfExpr) -> bool {
+    block_diverges(&i.then_block)
+        && match &i.else_branch {
+            Some(ElseBranch::Block(b)) => block_diverges(b),
+            Some(ElseBranch::If(nested)) => if_diverges(nested),
+            None => false,
+        }
+}
+
+fn match_diverges(m: &MatchExpr) -> bool {
+    !m.arms.is_empty()
+        && m.arms.iter().all(|arm| match &arm.body {
+            MatchArmBody::Expr(e) => expr_diverges(e),
+            MatchArmBody::Block(b) => block_diverges(b),
+        })
+}
+
+/// The span of a path through `block` that ends without producing a value:
+/// it has no tail expression and does not end in `ret`, a diverging
+/// `if`/`match`, or a `loop` that is never broken out of.
+fn missing_return(block: &Block) -> Option<Span> {
+    if block.stmts.iter().any(|s| stmt_diverges(s) || matches!(s, Stmt::Loop(l) if !breaks_out(&l.body))) {
+        return None;
+    }
+    if let Some(tail) = &block.tail_expr {
+        return expr_missing_return(tail);
+    }
+    match block.stmts.last() {
+        Some(Stmt::If(i)) => if_missing_return(i),
+        Some(Stmt::Match(m)) => match_missing_return(m),
+        Some(Stmt::TryCatch(tc)) => {
+            missing_return(&tc.try_block).or_else(|| missing_return(&tc.catch_block))
+        }
+        Some(Stmt::ExprStmt(e)) if matches!(e.expr, Expr::If(_) | Expr::Match(_) | Expr::Block(_)) => {
+            expr_missing_return(&e.expr)
+        }
+        _ => Some(block.span),
+    }
+}
+
+/// Like [`missing_return`] for a tail expression, which yields a value
+/// unless it is an `if`/`match`/block with a branch that does not.
+fn expr_missing_return(expr: &Expr) -> Option<Span> {
+    match expr {
+        Expr::Block(b) => missing_return(b),
+        Expr::If(i) => if_missing_return(i),
+        Expr::Match(m) => match_missing_return(m),
+        _ => None,
+    }
+}
+
+fn if_missing_return(i: &IfExpr) -> Option<Span> {
+    missing_return(&i.then_block).or_else(|| match &i.else_branch {
+        Some(ElseBranch::Block(b)) => missing_return(b),
+        Some(ElseBranch::If(nested)) => if_missing_return(nested),
+        None => Some(i.span),
+    })
+}
+
+/// Arms are checked for exhaustiveness separately; here each must return.
+fn match_missing_return(m: &MatchExpr) -> Option<Span> {
+    m.arms.iter().find_map(|arm| match &arm.body {
+        MatchArmBody::Expr(e) => expr_missing_return(e),
+        MatchArmBody::Block(b) => missing_return(b),
+    })
+}
+
+/// Whether `block` contains a `break` for its enclosing loop (one not
+/// nested in an inner loop).
+fn breaks_out(block: &Block) -> bool {
+    block.stmts.iter().any(|stmt| match stmt {
+        Stmt::Break(_) => true,
+        Stmt::ExprStmt(e) => expr_breaks_out(&e.expr),
+        Stmt::If(i) => if_breaks_out(i),
+        Stmt::Match(m) => match_breaks_out(m),
+        Stmt::TryCatch(tc) => breaks_out(&tc.try_block) || breaks_out(&tc.catch_block),
+        _ => false,
+    }) || block.tail_expr.as_deref().is_some_and(expr_breaks_out)
+}
+
+fn expr_breaks_out(expr: &Expr) -> bool {
+    match expr {
+        Expr::Block(b) => breaks_out(b),
+        Expr::If(i) => if_breaks_out(i),
+        Expr::Match(m) => match_breaks_out(m),
+        _ => false,
+    }
+}
+
+fn match_breaks_out(m: &MatchExpr) -> bool {
+    m.arms.iter().any(|arm| match &arm.body {
+        MatchArmBody::Expr(e) => expr_breaks_out(e),
+        MatchArmBody::Block(b) => breaks_out(b),
+    })
+}
+
+fn if_breaks_out(i: &IfExpr) -> bool {
+    breaks_out(&i.then_block)
+        || match &i.else_branch {
+            Some(ElseBranch::Block(b)) => breaks_out(b),
+            Some(ElseBranch::If(nested)) => if_breaks_out(nested),
+            None => false,
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ag_parser;
+
+    fn check_src(src: &str) -> Vec<Diagnostic> {
+        let parsed = ag_parser::parse(src);
+        assert!(
+            parsed.diagnostics.is_empty(),
+            "parse errors: {:?}",
+            parsed.diagnostics
+        );
+        let result = check(&parsed.module);
+        result.diagnostics
+    }
+
+    fn assert_no_errors(src: &str) {
+        let diags = check_src(src);
+        assert!(diags.is_empty(), "unexpected errors: {:?}", diags);
+    }
+
+    fn assert_has_error(src: &str, msg_contains: &str) {
+        let diags = check_src(src);
+        assert!(
+            diags.iter().any(|d| d.message.contains(msg_contains)),
+            "expected error containing '{}', got: {:?}",
+            msg_contains,
+            diags
+        );
+    }
+
+    fn assert_has_warning(src: &str, code: &str) {
+        let diags = check_src(src);
+        assert!(
+            diags.iter().any(|d| d.severity == Severity::Warning && d.code == Some(code)),
+            "expected `{}` warning, got: {:?}",
+            code,
+            diags
+        );
+    }
+
+    #[test]
+    fn type_mismatch() {
+        assert_has_error(r#"let x: int = "hello""#, "type mismatch");
+    }
+
+    #[test]
+    fn int_to_num_widening() {
+        assert_no_errors("let x: num = 42");
+    }
+
+    #[test]
+    fn any_escapes_checking() {
+        // any should be compatible with everything
+        assert_no_errors("let x: any = 42");
+    }
+
+    #[test]
+    fn infer_let_type() {
+        assert_no_errors("let x = 42");
+    }
+
+    #[test]
+    fn undefined_variable() {
+        assert_has_error("fn f() -> int { y }", "undefined variable `y`");
+    }
+
+    #[test]
+    fn duplicate_binding() {
+        assert_has_error("fn f() { let x = 1; let x = 2 }", "duplicate binding `x`");
+        assert_has_error("let x = 1\nlet x = 2", "duplicate declaration `x`");
+    }
+
+    #[test]
+    fn duplicate_top_level_declarations() {
+        let decls = [
+            "fn x() {}",
+            "extern fn x()",
+            "let x = 1",
+            "import { x } from \"./a\"",
+            "import { y as x } from \"./a\"",
+            "import * as x from \"./b\"",
+            "enum x { A }",
+            "struct x { a: int }",
+            "extern struct x {}",
+            "extern type x",
+            "@prompt x <<EOF\nhi\nEOF",
+        ];
+        for first in decls {
+            for second in decls {
+                let src = format!("{first}\n{second}\n");
+                let diags = check_src(&src);
+                assert_eq!(diags.len(), 1, "{src}: {:?}", diags);
+                assert_eq!(diags[0].message, "duplicate declaration `x`", "{src}");
+                assert!(diags[0].span.start as usize > first.len(), "{src}");
+                assert_eq!(diags[0].labels[0].message, "`x` first defined here");
+                assert!((diags[0].labels[0].span.start as usize) < first.len(), "{src}");
+            }
+        }
+        assert_no_errors("fn x() {}\nfn f() -> int { let x = 1\n x }");
+    }
+
+    #[test]
+    fn reassign_immutable() {
+        assert_has_error("fn f() { let x = 1; x = 2 }", "cannot assign to immutable binding `x`");
+    }
+
+    #[test]
+    fn reassign_mutable() {
+        assert_no_errors("fn f() -> int { mut x = 1; x = 2\n x }");
+    }
+
+    #[test]
+    fn assign_through_immutable_binding() {
+        assert_has_error(
+            "fn f() { let cfg = { retries: 1 }; cfg.retries = 3 }",
+            "cannot assign through immutable binding `cfg`",
+        );
+        assert_has_error(
+            "fn f() { let xs = [1]; xs[0] = 2 }",
+            "cannot assign through immutable binding `xs`",
+        );
+        assert_has_error(
+            "const CFG = { limits: { max: 1 } }\nfn f() { CFG.limits.max = 2 }",
+            "cannot assign through immutable binding `CFG`",
+        );
+        assert_has_error(
+            "fn f() { mut cfg = { retries: 1 }; cfg.retrys = 3 }",
+            "field `retrys` does not exist",
+        );
+    }
+
+    #[test]
+    fn assign_through_mutable_binding_or_param() {
+        assert_no_errors("fn f() { mut cfg = { retries: 1 }; cfg.retries = 3 }");
+        assert_no_errors("fn f() { mut xs = [1]; xs[0] = 2 }");
+        assert_no_errors("fn f(m: {str: int}) { m[\"a\"] = 1 }");
+        assert_has_error("fn f(n: int) { n = 1 }", "cannot assign to immutable binding `n`");
+    }
+
+    #[test]
+    fn compound_assignment_types() {
+        assert_no_errors("fn f() -> num { mut s = \"a\"; s += \"b\"; mut n = 1; n += 2; n *= 3; mut x = 1.5; x /= 2\n s.len + n + x }");
+        assert_no_errors("fn f() -> int { mut n = 7; n %= 3\n n }");
+        assert_has_error(
+            "fn f() { mut s = \"a\"; s += 1 }",
+            "operator `+=` on `str` expects a `str` value, found `int`",
+        );
+        assert_has_error("fn f() { mut s = \"a\"; s -= \"b\" }", "operator `-=` cannot be applied to type `str`");
+        assert_has_error("fn f() { mut b = true; b += true }", "operator `+=` cannot be applied to type `bool`");
+        assert_has_error("fn f() { mut n = 1; n /= 2 }", "`/=` produces `num`, which cannot be assigned to `int`");
+        assert_has_error("fn f() { mut n = 1; n = \"x\" }", "type mismatch: expected `int`, found `str`");
+    }
+
+    #[test]
+    fn assignment_mismatch_points_at_declaration() {
+        let src = "mut n = 1\nn = \"hello\"\nlet m = n * 2";
+        let diags = check_src(src);
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "type mismatch: expected `int`, found `str`");
+        assert_eq!(diags[0].labels[0].message, "`n` is declared as `int` here");
+        assert_eq!(diags[0].labels[0].span.start, 0);
+
+        let diags = check_src("fn f() -> str { mut s = \"a\"; s += 1\n s }");
+        assert_eq!(diags[0].labels[0].message, "`s` is declared as `str` here");
+
+        let src = "struct P { x: int }\nfn f(p: P) -> P { mut q = p; q.x = \"a\"; q.x -= 0.5\n q }";
+        let diags = check_src(src);
+        assert_eq!(diags.len(), 2, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "type mismatch: expected `int`, found `str`");
+        assert_eq!(diags[0].labels[0].message, "field `x` is declared as `int` here");
+        assert_eq!(diags[0].labels[0].span.start, src.find("x: int").unwrap() as u32);
+        assert_eq!(diags[1].message, "`-=` produces `num`, which cannot be assigned to `int`");
+
+        let diags = check_src("fn f() { mut xs = [1]; xs[0] = true }");
+        assert_eq!(diags[0].message, "type mismatch: expected `int`, found `bool`");
+        assert_eq!(diags[0].labels[0].message, "`xs` is declared here");
+    }
+
+    #[test]
+    fn assignment_evaluates_to_target_type() {
+        assert_has_error("fn f() -> str { mut n = 1; n += 2 }", "expected `str`, found `int`");
+    }
+
+    #[test]
+    fn assign_to_undefined_variable() {
+        assert_has_error("fn f() { missing = 1 }", "undefined variable `missing`");
+    }
+
+    #[test]
+    fn nullable_assignment() {
+        assert_no_errors("let x: str? = nil");
+    }
+
+    #[test]
+    fn return_type_mismatch() {
+        assert_has_error(
+            r#"fn foo() -> int { "hello" }"#,
+            "return type mismatch",
+        );
+    }
+
+    #[test]
+    fn call_argument_checked_once() {
+        let diags = check_src("fn g(x: int) -> int { x }\nfn f() -> int { g(y) }");
+        assert_eq!(diags.len(), 1, "expected one diagnostic, got: {:?}", diags);
+        assert!(diags[0].message.contains("undefined variable `y`"));
+
+        let diags = check_src("fn f(xs: [int]) { xs.push(1, y) }");
+        assert_eq!(diags.len(), 1, "expected one diagnostic, got: {:?}", diags);
+    }
+
+    #[test]
+    fn arity_with_default_params() {
+        let greet = "fn greet(name: str, loud: bool = false) -> str { name }\n";
+        assert_no_errors(&format!("{greet}fn f() {{ greet(\"Bo\") }}"));
+        assert_no_errors(&format!("{greet}fn f() {{ greet(\"Bo\", true) }}"));
+        assert_has_error(&format!("{greet}fn f() {{ greet() }}"), "missing argument `name`");
+        assert_has_error(
+            &format!("{greet}fn f() {{ greet(\"Bo\", true, 1) }}"),
+            "expected at most 2 arguments, found 3",
+        );
+    }
+
+    #[test]
+    fn arity_too_few_arguments() {
+        assert_has_error(
+            "fn add(a: int, b: int) -> int { a + b }\nfn f() { add(1) }",
+            "missing argument `b`",
+        );
+        assert_has_error(
+            "fn f(xs: [int]) -> str { \"\".pad_start() }",
+            "expected at least 1 arguments, found 0",
+        );
+        assert_no_errors("fn f(xs: [int]) -> str { xs.join() }");
+    }
+
+    #[test]
+    fn arity_variadic_extern() {
+        let log = "extern fn log(prefix: str, ...args: any)\n";
+        assert_no_errors(&format!("{log}fn f() {{ log(\"a\", 1, 2) }}"));
+        assert_has_error(&format!("{log}fn f() {{ log() }}"), "expected at least 1 arguments");
+    }
+
+    #[test]
+    fn extern_nullable_params_are_optional() {
+        let fetch = "extern fn fetch(input: str, init: any?) -> any\n";
+        assert_no_errors(&format!("{fetch}fn f() {{ fetch(\"u\") }}"));
+        assert_has_error(&format!("{fetch}fn f() {{ fetch() }}"), "missing argument `input`");
+    }
+
+    #[test]
+    fn valid_function_return() {
+        assert_no_errors("fn add(a: int, b: int) -> int { a + b }");
+    }
+
+    // ── Builtin member tests ──
+
+    #[test]
+    fn array_len_is_int() {
+        assert_no_errors("fn f(xs: [str]) -> int { xs.len }");
+        assert_has_error("fn f(xs: [str]) -> str { xs.len }", "return type mismatch");
+    }
+
+    #[test]
+    fn array_push_checks_element_type() {
+        assert_no_errors("fn f(xs: [int]) { xs.push(1, 2) }");
+        assert_has_error(
+            r#"fn f(xs: [int]) { xs.push("a") }"#,
+            "argument 1: expected `int`, found `str`",
+        );
+    }
+
+    #[test]
+    fn array_pop_and_find_are_nullable() {
+        assert_no_errors("fn f(xs: [int]) -> int? { xs.pop() }");
+        assert_has_error("fn f(xs: [int]) -> int { xs.find((x) => x > 1) }", "return type mismatch");
+    }
+
+    #[test]
+    fn array_map_uses_callback_return_type() {
+        assert_no_errors(r#"fn f(xs: [int]) -> [str] { xs.map((x) => "n") }"#);
+        assert_no_errors("fn f(xs: [int]) -> [int] { xs.map((x) => x * 2) }");
+        assert_has_error(
+            r#"fn f(xs: [int]) -> [int] { xs.map((x) => "n") }"#,
+            "return type mismatch",
+        );
+    }
+
+    #[test]
+    fn array_callback_params_take_element_type() {
+        assert_has_error(
+            "struct P { name: str }\nfn f(ps: [P]) -> [P] { ps.filter((p) => p.age > 1) }",
+            "field `age` does not exist on type `P`",
+        );
+    }
+
+    #[test]
+    fn array_methods_typed() {
+        assert_no_errors(
+            r#"fn f(xs: [str]) -> bool { let s: str = xs.join(", "); let _ys: [str] = xs.slice(0, 1).concat(xs); xs.includes(s) }"#,
+        );
+    }
+
+    #[test]
+    fn str_members_typed() {
+        assert_no_errors(
+            r#"fn f(s: str) -> [str] { let _n: int = s.len; let _ok: bool = s.trim().starts_with("a"); s.split(",") }"#,
+        );
+        assert_has_error(r#"fn f(s: str) -> str { s.split(",") }"#, "return type mismatch");
+    }
+
+    #[test]
+    fn str_unknown_member() {
+        assert_has_error(
+            r#"fn f(s: str) { s.startsWith("a") }"#,
+            "member `startsWith` does not exist on type `str`",
+        );
+    }
+
+    #[test]
+    fn builtin_member_renames_recorded() {
+        let result = check_full(r#"fn f(s: str, xs: [int]) { s.starts_with("a"); xs.len; s.trim() }"#);
+        let mut renames: Vec<&str> = result
+            .rewrites
+            .members
+            .values()
+            .map(|r| match r {
+                MemberRewrite::Rename(name) => name.as_str(),
+                other => panic!("unexpected rewrite {other:?}"),
+            })
+            .collect();
+        renames.sort();
+        assert_eq!(renames, vec!["length", "startsWith"]);
+    }
+
+    #[test]
+    fn map_members_typed() {
+        assert_no_errors(
+            r#"fn f(m: {str: int}) -> [int] { let _ks: [str] = m.keys(); let _ok: bool = m.has("a"); m.values() }"#,
+        );
+        assert_has_error(
+            "fn f(m: {str: int}) { m.get(1) }",
+            "member `get` does not exist on type `{str: int}`",
+        );
+    }
+
+    #[test]
+    fn map_for_over_keys_and_index() {
+        assert_no_errors(
+            "fn f(m: {str: int}) -> int { mut total = 0; for k in m.keys() { total = total + m[k] }\n total }",
+        );
+    }
+
+    #[test]
+    fn await_in_sync_arrow_inside_async_fn() {
+        assert_has_error(
+            "extern fn load(u: str) -> Promise<str>\nasync fn f() { let g = (u: str) => await load(u) }",
+            "await can only be used inside async functions",
+        );
+    }
+
+    #[test]
+    fn await_in_async_arrow_inside_sync_fn() {
+        assert_no_errors(
+            "extern fn load(u: str) -> Promise<str>\nfn f() { let _g = async fn(u: str) { await load(u) } }",
+        );
+        assert_no_errors(
+            "extern fn load(u: str) -> Promise<str>\nfn f() -> Promise<str> { let g = async fn(u: str) { await load(u) }\n g(\"a\") }",
+        );
+        assert_no_errors(
+            "extern fn load(u: str) -> Promise<str>\nfn f() -> Promise<str> { let g = async (u: str) => await load(u)\n g(\"a\") }",
+        );
+    }
+
+    #[test]
+    fn top_level_await_follows_the_option() {
+        let src = "extern fn load(u: str) -> Promise<str>\nlet data = await load(\"a\")\nlet s: str = data\nfn f() { await load(data) }";
+        let parsed = ag_parser::parse(src);
+        let check_await = |top_level_await| {
+            let options = CheckerOptions { top_level_await, ..Default::default() };
+            check_with_options(&parsed.module, options).diagnostics
+        };
+
+        let diags = check_await(TopLevelAwait::Error);
+        assert_eq!(diags.len(), 2, "{:?}", diags);
+        assert!(diags.iter().all(|d| d.message == "await can only be used inside async functions"));
+        for mode in [TopLevelAwait::Allow, TopLevelAwait::WrapInMain] {
+            // Function bodies still need to be async
+            let diags = check_await(mode);
+            assert_eq!(diags.len(), 1, "{:?}", diags);
+            assert_eq!(diags[0].message, "await can only be used inside async functions");
+            assert_eq!(diags[0].span.start as usize, src.find("await load(data)").unwrap());
+        }
+    }
+
+    #[test]
+    fn function_types_ignore_parameter_names() {
+        let named = |name: &str, optional: bool| FnParam { name: Some(name.to_string()), ty: Type::Int, optional };
+        let f = |p: FnParam| Type::Function(vec![p], Box::new(Type::Str));
+        assert_eq!(f(named("x", false)), f(named("y", false)));
+        assert_eq!(f(named("x", false)), f(FnParam::required(Type::Int)));
+        assert_ne!(f(named("x", false)), f(named("x", true)));
+    }
+
+    #[test]
+    fn extern_fns_binding_one_js_name() {
+        let same = "@js(\"my-lib\", name = \"sameFn\")\nextern fn a(x: str) -> int\n\
+                    @js(\"my-lib\", name = \"sameFn\")\nextern fn b(y: str) -> int\n\
+                    @js(\"other-lib\", name = \"sameFn\")\nextern fn c(y: int) -> int";
+        let diags = check_src(same);
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].severity, Severity::Note);
+        assert_eq!(diags[0].message, "duplicate binding for JS name `sameFn`");
+        assert_eq!(diags[0].labels[0].message, "`a` also binds it");
+
+        let differ = "@js(\"my-lib\", name = \"sameFn\")\nextern fn a(x: str) -> int\n\
+                      @js(\"my-lib\")\nextern fn sameFn(x: int) -> int";
+        let diags = check_src(differ);
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].severity, Severity::Warning);
+        assert_eq!(diags[0].code, Some("js-binding"));
+        assert_eq!(
+            diags[0].message,
+            "`sameFn` binds JS name `sameFn` from \"my-lib\" as `(int) -> int`, but `a` binds it as `(str) -> int`"
+        );
+    }
+
+    #[test]
+    fn floating_promises() {
+        let load = "extern fn fetchData() -> Promise<str>\n";
+        let diags = check_src(&format!("{load}async fn f() {{ fetchData()\n let _x = 1 }}"));
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "this `Promise<str>` is never awaited");
+        assert_eq!(diags[0].code, Some("floating-promise"));
+        assert!(!diags[0].is_error());
+        assert_has_error(&format!("{load}fn f() {{ fetchData() }}"), "is never awaited");
+
+        // Awaited, returned or bound
+        assert_no_errors(&format!(
+            "{load}async fn f() {{ await fetchData()\n let p = fetchData()\n mut _q = p\n _q = fetchData() }}"
+        ));
+        assert_no_errors(&format!("{load}async fn f() {{ fetchData() }}"));
+        assert_no_errors(&format!("{load}fn f() -> Promise<str> {{ fetchData() }}"));
+    }
+
+    #[test]
+    fn async_fn_returns_promises_flattened() {
+        let load = "extern fn fetchData() -> Promise<str>\n";
+        assert_no_errors(&format!(
+            "{load}async fn f(c: bool) -> str {{ if c {{ ret fetchData() }}\n fetchData() }}\n\
+             async fn g() -> Promise<str> {{ await fetchData() }}\n\
+             async fn h() -> int {{ let s: str = await f(true)\n let t: str = await g()\n s.len + t.len }}"
+        ));
+    }
+
+    #[test]
+    fn in_operator_types() {
+        assert_no_errors(
+            "fn f(m: {str: int}, xs: [int], s: str) -> bool { \"a\" in m && 1 in xs && \"x\" not in s }",
+        );
+        assert_no_errors("fn f(v: str?) { if v is not nil { } }");
+        assert_has_error("fn f(xs: [int]) -> bool { \"a\" in xs }", "`in` on `[int]` expects `int`, found `str`");
+        assert_has_error("fn f(n: int) -> bool { 1 in n }", "operator `in` cannot be applied to type `int`");
+    }
+
+    #[test]
+    fn conditions_must_be_bool() {
+        assert_no_errors("fn f(a: int, b: bool) { if a > 0 && b { } while !b || a == 1 { } }");
+        assert_has_error(r#"fn f() { while "str" { } }"#, "condition must be `bool`, found `str`");
+        assert_has_error("fn f(a: bool) -> bool { a && 5 }", "operand of `&&` must be `bool`, found `int`");
+        assert_has_error(
+            "fn f(x: int) -> int { match x { n if n => n, _ => 0 } }",
+            "condition must be `bool`, found `int`",
+        );
+        assert_no_errors("fn f(x: any) { if x { } }");
+    }
+
+    #[test]
+    fn assignment_in_condition_suggests_eq() {
+        assert_has_error(
+            "fn f() { mut x = 0; if x = 1 { } }",
+            "found an assignment; did you mean `==`?",
+        );
+    }
+
+    #[test]
+    fn nullable_conditions_need_explicit_check() {
+        assert_has_error(
+            "fn f(b: bool?) { if b { } }",
+            "found `bool?`; compare with `== true` or check for `nil` first",
+        );
+        assert_has_error(
+            "fn f(s: str?) { if s { } }",
+            "found `str?`; compare with `nil` explicitly",
+        );
+        assert_no_errors("fn f(b: bool?) { if b == true { } if b != nil { } }");
+    }
+
+    #[test]
+    fn for_in_element_types() {
+        assert_no_errors("fn f(s: str) { for c in s { let _x: str = c } }");
+        assert_no_errors("fn f(xs: [int]) -> int { mut t = 0; for x in xs { t += x }\n t }");
+        assert_no_errors("fn f(m: {str: int}) -> int { mut t = 0; for k in m { let _s: str = k; t += m[k] }\n t }");
+        assert_has_error("fn f(s: str) { for c in s { let x: int = c } }", "type mismatch");
+        assert_has_error("fn f(m: {str: int}) { for k in m { let v: int = k } }", "type mismatch");
+    }
+
+    #[test]
+    fn for_in_over_array_or_map_warns() {
+        let diags = check_src("fn f(xs: [str] | {str: int}) { for k in xs { } }");
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(
+            diags[0].message,
+            "`for` over `[str] | {str: int}` compiles to `for...in`, which yields the indices of arrays and strings, not their elements"
+        );
+        assert_eq!(diags[0].code, Some("for-in-array"));
+        assert_no_errors("fn f(xs: [str], m: {str: int}) { for x in xs { } for k in m { } }");
+    }
+
+    #[test]
+    fn for_range_loops() {
+        assert_no_errors("fn f(n: int) -> int { mut t = 0; for let i = 0; i < n; i += 1 { t += i }\n t }");
+        assert_no_errors("fn f() { for mut i = 10; i > 0; i -= 2 { } }");
+        assert_has_error("fn f() { for let i = 0; i < 3; i += 1 { i = 5 } }", "cannot assign to immutable binding `i`");
+        assert_has_error("fn f() { for let i = 0; i; i += 1 { } }", "condition must be `bool`, found `int`");
+        assert_has_error("fn f() { for let i = 0; i < 3; i += 1 { }\n i }", "undefined variable `i`");
+    }
+
+    #[test]
+    fn for_in_non_iterable() {
+        assert_has_error("fn f(n: int) { for x in n { } }", "type `int` is not iterable");
+        assert_has_error(
+            "struct P { x: int }\nfn f(p: P) { for x in p { } }",
+            "type `P` is not iterable",
+        );
+        assert_no_errors("fn f(x: any) { for y in x { } }");
+    }
+
+    #[test]
+    fn object_literal_as_map() {
+        assert_no_errors(r#"let m: {str: int} = {a: 1, b: 2}"#);
+        assert_has_error(r#"let m: {str: int} = {a: 1, b: "x"}"#, "type mismatch");
+    }
+
+    const STATUS: &str = "enum Status { Idle, Active(since: int, by: str) }\n";
+
+    #[test]
+    fn enum_variant_construction() {
+        assert_no_errors(&format!("{STATUS}fn f() -> Status {{ Status::Active(1, \"bo\") }}"));
+        assert_no_errors(&format!("{STATUS}fn f() -> Status {{ Status::Idle }}"));
+        assert_no_errors(&format!("{STATUS}let s: Status = Status::Idle"));
+        assert_has_error(
+            "enum Event { Key(tag: str) }",
+            "variant `Event::Key` cannot have a field named `tag`, which holds the variant name",
+        );
+    }
+
+    #[test]
+    fn enum_variant_construction_errors() {
+        assert_has_error(
+            &format!("{STATUS}fn f() -> Status {{ Status::Active(1) }}"),
+            "missing argument `by`",
+        );
+        assert_has_error(
+            &format!("{STATUS}fn f() -> Status {{ Status::Active(\"x\", \"bo\") }}"),
+            "argument 1: expected `int`, found `str`",
+        );
+        assert_has_error(
+            &format!("{STATUS}fn f() -> Status {{ Status::Paused }}"),
+            "enum `Status` has no variant `Paused`",
+        );
+        assert_has_error(
+            &format!("{STATUS}fn f() -> Status {{ Status::Idle(1) }}"),
+            "unit variant `Idle` takes no arguments",
+        );
+        let diags = check_src(&format!("{STATUS}struct Job {{ status: Status }}\nfn f(j: Job) {{ j.status(1) }}"));
+        assert!(!diags.iter().any(|d| d.message.contains("unit variant")), "got: {:?}", diags);
+        assert_has_error(&format!("{STATUS}let n: int = Status::Idle"), "type mismatch");
+    }
+
+    const RESULT: &str = "enum Res { Ok(value: int), Error(code: int, msg: str) }\n";
+
+    #[test]
+    fn enum_pattern_bindings_match_variant() {
+        assert_no_errors(&format!(
+            "{RESULT}fn f(r: Res) -> int {{ match r {{ Res::Ok(v) => v, Res::Error(c, m) => c }} }}"
+        ));
+        assert_no_errors(&format!(
+            "{RESULT}fn f(r: Res) -> int {{ match r {{ Res::Error => 0, _ => 1 }} }}"
+        ));
+    }
+
+    #[test]
+    fn enum_pattern_binding_count_mismatch() {
+        let diags = check_src(&format!(
+            "{RESULT}fn f(r: Res) -> int {{ match r {{ Res::Error(c) => c, _ => 0 }} }}"
+        ));
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(
+            diags[0].message,
+            "variant `Res::Error` has 2 field(s), but the pattern binds 1"
+        );
+        assert_eq!(diags[0].labels[0].message, "variant `Error` declared here");
+    }
+
+    #[test]
+    fn enum_pattern_unknown_variant() {
+        let diags = check_src(&format!(
+            "{RESULT}fn f(r: Res) -> int {{ match r {{ Res::Eror(c, m) => c, _ => 0 }} }}"
+        ));
+        assert!(
+            diags.iter().any(|d| d.message == "enum `Res` has no variant `Eror`"
+                && d.labels[0].message == "enum `Res` declared here"),
+            "got: {:?}",
+            diags
+        );
+        assert_has_error(
+            &format!("{RESULT}enum Other {{ A }}\nfn f(r: Res) {{ match r {{ Other::A => 1, _ => 0 }} }}"),
+            "pattern `Other::A` does not match type `Res`",
+        );
+    }
+
+    #[test]
+    fn struct_pattern_unknown_field() {
+        let diags = check_src(
+            "struct P { x: int }\nfn f(p: P) -> int { match p { { x, y } => x } }",
+        );
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "struct `P` has no field `y`");
+        assert_eq!(diags[0].labels[0].message, "struct `P` declared here");
+    }
+
+    const SHAPE: &str = "interface Shape { fn area(self) -> num }\nstruct Sq { side: num }\n";
+
+    #[test]
+    fn impl_satisfies_interface() {
+        assert_no_errors(&format!(
+            "{SHAPE}impl Shape for Sq {{ fn area(self) -> num {{ self.side * self.side }} }}"
+        ));
+    }
+
+    #[test]
+    fn impl_missing_interface_method() {
+        let diags = check_src(&format!("{SHAPE}impl Shape for Sq {{ fn perimeter(self) -> num {{ 4.0 }} }}"));
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(
+            diags[0].message,
+            "`Sq` is missing method `area` required by interface `Shape`"
+        );
+        assert_eq!(diags[0].labels[0].message, "`area` declared here");
+    }
+
+    #[test]
+    fn impl_method_signature_mismatch() {
+        assert_has_error(
+            &format!("{SHAPE}impl Shape for Sq {{ fn area(self) -> str {{ \"big\" }} }}"),
+            "method `area` does not match interface `Shape`: expected `() -> num`, found `() -> str`",
+        );
+        assert_has_error(
+            &format!("{SHAPE}impl Shape for Sq {{ fn area() -> num {{ 1.0 }} }}"),
+            "method `area` must take `self`",
+        );
+    }
+
+    #[test]
+    fn impl_inherits_extended_interface_methods() {
+        let src = "interface Named { fn name(self) -> str }\ninterface Shape extends Named { fn area(self) -> num }\nstruct Sq { side: num }\n";
+        assert_has_error(
+            &format!("{src}impl Shape for Sq {{ fn area(self) -> num {{ self.side }} }}"),
+            "`Sq` is missing method `name` required by interface `Named`",
+        );
+        assert_has_error("interface A extends B { }", "unknown interface `B`");
+        assert_has_error(
+            "struct Sq { side: num }\nimpl Drawable for Sq { }",
+            "unknown interface `Drawable`",
+        );
+    }
+
+    #[test]
+    fn impl_self_has_struct_type() {
+        assert_has_error(
+            "struct Sq { side: num }\nimpl Sq { fn area(self) -> num { self.width } }",
+            "field `width` does not exist on type `Sq`",
+        );
+    }
+
+    const SQ_IMPL: &str = "struct Sq { side: num }\n\
+                           impl Sq {\n\
+                           fn unit() -> Sq { { side: 1.0 } }\n\
+                           fn area(self) -> num { self.side * self.side }\n\
+                           fn scale(self, by: num) -> Sq { { side: self.side * by } }\n\
+                           fn grow(mut self, by: num) { self.side += by }\n\
+                           }\n";
+
+    #[test]
+    fn const_initializers_are_constant() {
+        assert_no_errors("const X = 1 + 2");
+        assert_no_errors(
+            "enum Level { Low, High(n: int) }\nconst fn kib(n: int) -> int = n * 1024\nconst X = 1\nconst A = -X * 2\n\
+             const B = [A, kib(4)]\nconst C = { a: B[0], level: Level::High(A) }\nconst D = \"${C.a}!\"\nconst E = Level::Low",
+        );
+        let src = "fn random() -> num { 4.0 }\nconst X = random()";
+        let diags = check_src(src);
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!(diags[0].message, "const initializer must be a constant expression");
+        assert_eq!(diags[0].span.start, span_of(src, "const X").start);
+        assert_has_error("let n = 1\nconst X = n + 1", "const initializer must be a constant expression");
+        assert_has_error("mut n = 1\nconst X = [n]", "const initializer must be a constant expression");
+        assert_has_error("fn f() { const X = (x: int) => x }", "const initializer must be a constant expression");
+        assert_has_error("fn f(n: int) { const X = n }", "const initializer must be a constant expression");
+        // A value of an enum type is not the enum
+        assert_has_error(
+            "enum Level { Low, High(n: int) }\nfn f(l: Level) -> any { const X = l.n\n X }",
+            "const initializer must be a constant expression",
+        );
+    }
+
+    #[test]
+    fn impl_methods_resolve() {
+        assert_no_errors(&format!(
+            "{SQ_IMPL}let s = Sq::unit()\nlet a: num = s.area()\nlet b: num = s.scale(2.0).area()\nlet f: () -> num = s.area"
+        ));
+        assert_has_error(&format!("{SQ_IMPL}let a: str = Sq::unit().area()"), "expected `str`, found `num`");
+        assert_has_error(&format!("{SQ_IMPL}let a = Sq::unit().scale(\"x\")"), "argument 1: expected `num`, found `str`");
+        assert_has_error(&format!("{SQ_IMPL}let a = Sq::unit().scale()"), "missing argument `by`");
+    }
+
+    #[test]
+    fn impl_method_call_styles() {
+        assert_has_error(
+            &format!("{SQ_IMPL}let a = Sq::area()"),
+            "`area` is a method of `Sq` and takes `self`; call it on a value: `x.area(...)`",
+        );
+        assert_has_error(
+            &format!("{SQ_IMPL}let s = Sq::unit().unit()"),
+            "`unit` is an associated function of `Sq`, not a method; call it as `Sq::unit(...)`",
+        );
+        assert_has_error(
+            &format!("{SQ_IMPL}let a = Sq::unit().aera()"),
+            "field `aera` does not exist on type `Sq`, did you mean `area`?",
+        );
+        assert_has_error(
+            &format!("{SQ_IMPL}let a = Sq::unti()"),
+            "no associated function `unti` on `Sq`, did you mean `unit`?",
+        );
+        assert_no_errors("extern struct JSON { fn stringify(v: any) -> str }\nlet s: str = JSON.stringify(1)");
+    }
+
+    #[test]
+    fn impl_self_is_immutable_without_mut() {
+        assert_has_error(
+            "struct Sq { side: num }\nimpl Sq { fn grow(self) { self.side += 1.0 } }",
+            "cannot assign through immutable binding `self`",
+        );
+        assert_no_errors("struct Sq { side: num }\nimpl Sq { fn grow(mut self) { self.side += 1.0 } }");
+        assert_has_error(
+            "struct Sq { side: num }\nimpl Sq { fn a(self) -> num { 1.0 } }\nimpl Sq { fn a(self) -> num { 2.0 } }",
+            "duplicate method `a` on `Sq`",
+        );
+    }
+
+    #[test]
+    fn tagged_template_returns_tag_result() {
+        assert_no_errors(
+            "fn html(strings: [str], values: any) -> int { 1 }\nlet n: int = html`<b>${1}</b>`",
+        );
+        assert_has_error(
+            "fn html(strings: [str], values: any) -> int { 1 }\nlet s: str = html`<b></b>`",
+            "expected `str`, found `int`",
+        );
+        assert_has_error("let s = html`<b>${missing}</b>`", "undefined variable `missing`");
+    }
+
+    #[test]
+    fn export_alias_names() {
+        assert_no_errors("fn internalHelper() {}\npub use { internalHelper as helper }");
+        assert_has_error("pub use { missing as helper }", "cannot export undefined name `missing`");
+        assert_has_error(
+            "fn a() {}\nfn b() {}\npub use { a as x, b as x }",
+            "duplicate export `x`",
+        );
+    }
+
+    const USER: &str = "struct User { name: str, age: int, nick: str?, admin: bool = false }\n";
+
+    #[test]
+    fn struct_literal_complete() {
+        assert_no_errors(&format!(r#"{USER}let u: User = {{ name: "Bo", age: 3 }}"#));
+        assert_no_errors(&format!(
+            r#"{USER}let u: User = {{ name: "Bo", age: 3, nick: "b", admin: true }}"#
+        ));
+    }
+
+    #[test]
+    fn struct_literal_missing_field() {
+        let diags = check_src(&format!(r#"{USER}let u: User = {{ name: "Bo" }}"#));
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "missing field `age` in `User` literal");
+        assert!(diags[0].is_error());
+        assert_eq!(diags[0].labels.len(), 1);
+        assert_eq!(diags[0].labels[0].span.start, 0, "label should point at the struct");
+    }
+
+    #[test]
+    fn struct_literal_unknown_field_warns() {
+        let diags = check_src(&format!(r#"{USER}let u: User = {{ name: "Bo", age: 3, agee: 4 }}"#));
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "struct `User` has no field `agee`");
+        assert_eq!(diags[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn unmutated_mut_warns() {
+        let diags = check_src("fn f() -> int { mut x = 1; x + 1 }");
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(
+            diags[0].message,
+            "variable `x` is declared `mut` but never mutated; consider using `let`"
+        );
+        assert_eq!(diags[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn mutated_mut_does_not_warn() {
+        assert_no_errors("fn f() -> int { mut x = 1; x = 2; x }");
+        assert_no_errors("fn f(c: bool) -> int { mut x = 1; if c { x += 1 }\n x }");
+        assert_no_errors("fn f() -> int { mut p = { x: 1 }; p.x = 2; p.x }");
+        assert_no_errors("mut count = 0\nfn bump() { count += 1 }");
+    }
+
+    #[test]
+    fn strict_implicit_any_warnings() {
+        let src = "fn f(x = 1) -> int { 1 }\nlet xs = []\nlet g = (a) => a\nlet ys: [int] = []\nlet n = [1, 2].map((v) => v + 1)";
+        assert_no_errors(src);
+
+        let parsed = ag_parser::parse(src);
+        let options = CheckerOptions { strict_implicit_any: true, ..Default::default() };
+        let diags = check_with_options(&parsed.module, options).diagnostics;
+        let messages: Vec<&str> = diags.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "parameter `x` is implicitly `any`; add a type annotation",
+                "`xs` is implicitly `[any]`; add a type annotation",
+                "parameter `a` is implicitly `any`; add a type annotation",
+            ]
+        );
+        assert!(diags.iter().all(|d| d.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn constant_conditions() {
+        assert_has_error("fn f() { if 1 == 2 { } }", "condition is always false");
+        assert_has_error("fn f() { if true && false { } }", "condition is always false");
+        assert_has_error("const DEBUG = false\nfn f() { if DEBUG { } }", "condition is always false");
+        assert_has_error("const LEVEL = 2\nfn f() { if LEVEL * 2 > 5 { } }", "condition is always false");
+        assert_has_error("fn f() { while !true { } }", "condition is always false");
+        assert_has_error("const DEBUG = true\nfn f() { if DEBUG { } }", "condition is always true");
+        assert_has_error("fn f() { if 1 == 1 { } }", "condition is always true");
+        assert_has_error("fn f(n: int) -> int { match n { x if true => x, _ => 0 } }", "match guard is always true");
+        assert_no_errors("let debug = false\nfn f(x: bool) { if debug == x { } if false || x { } }");
+        assert_no_errors("fn f(n: int) -> int { match n { x if x > 1 => x, _ => 0 } }");
+    }
+
+    #[test]
+    fn disjoint_comparisons() {
+        let src = "fn f(s: str, n: int) -> bool { s == n }";
+        let diags = check_src(src);
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "`str` and `int` can never be equal, so this comparison is always false");
+        assert_eq!(diags[0].code, Some("disjoint-comparison"));
+        assert_eq!(diags[0].span.start, span_of(src, "==").start);
+        assert_has_error("fn f(s: str) -> bool { s != true }", "so this comparison is always true");
+        assert_has_error("fn f() -> bool { \"a\" == 1 }", "`str` and `int` can never be equal");
+
+        assert_no_errors(
+            "fn f(a: int, b: num, s: str?, x: any, u: str | int) -> bool {\n\
+             a == b && s == nil && s == \"a\" && x == 1 && u == 1 && u != \"a\"\n}",
+        );
+        assert_no_errors("fn m(a: int | str, b: str | bool) -> bool { a == b }");
+        assert_has_error("fn m(a: int | str, b: bool | nil) -> bool { a == b }", "can never be equal");
+    }
+
+    #[test]
+    fn char_literal_where_string_expected() {
+        assert_has_error(
+            "let s: str = 'a'",
+            "character literal `'a'` is an `int` code point, not a `str`; write the string with double quotes: `\"a\"`",
+        );
+        assert_has_error("fn f(s: str) {}\nf('\\n')", "write the string with double quotes: `\"\\n\"`");
+        assert_no_errors("let c: int = 'a'");
+    }
+
+    #[test]
+    fn overflowing_literals() {
+        let diags = check_src("let a = 9007199254740993\nlet b = 1e999");
+        let messages: Vec<_> = diags.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "integer literal `9007199254740993` exceeds the precision of JS numbers and evaluates to `9007199254740992`",
+                "float literal is out of range and evaluates to `Infinity`",
+            ]
+        );
+        assert_eq!(diags[0].code, Some("overflowing-literals"));
+        assert_no_errors("let a = 9007199254740991\nlet b = -9007199254740991\nlet c = 1e308");
+    }
+
+    #[test]
+    fn while_true_suggests_loop() {
+        let diags = check_src("fn f() { while true { break } }");
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "condition is always true; use `loop` instead");
+        assert_eq!(diags[0].code, Some("while-true"));
+    }
+
+    #[test]
+    fn private_types_in_public_signatures() {
+        let src = "struct User { name: str }\npub fn get_user(id: int) -> User? { nil }";
+        let diags = check_src(src);
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "private type `User` appears in public signature");
+        assert_eq!(diags[0].span.start, src.rfind("User").unwrap() as u32);
+        assert_eq!(diags[0].labels[0].message, "`User` is declared here without `pub`");
+        assert_eq!(diags[0].labels[0].span.start, 0);
+
+        assert_has_error(
+            "enum Role { Admin }\npub struct User { roles: [Role] }",
+            "private type `Role` appears in public signature",
+        );
+        assert_has_error(
+            "type Id = int\nfn find(id: Id) {}\npub use { find }",
+            "private type `Id` appears in public signature",
+        );
+        assert_no_errors(
+            "pub struct User { name: str }\nstruct Cache { n: int }\npub type Id = int\n\
+             pub fn get_user(id: Id) -> User { { name: \"a\" } }\nfn cached(c: Cache) {}\n\
+             pub interface Repo<T> { fn get(self, id: Id) -> T }",
+        );
+    }
+
+    #[test]
+    fn const_fn_bodies() {
+        assert_no_errors(
+            "const SCALE = 10\nenum Sign { Neg, Pos(n: int) }\n\
+             const fn clamp(x: int, lo: int, hi: int) -> int { if x < lo { lo } else if x > hi { hi } else { x } }\n\
+             const fn scaled(x: int) -> int { let y = clamp(x, 0, 100)\n y * SCALE }\n\
+             const fn sign(x: int) -> Sign = if x < 0 { Sign::Neg } else { Sign::Pos(x) }\n\
+             const fn total(xs: [int]) -> int { mut sum = 0\n for x in xs { sum += x }\n sum }",
+        );
+        assert_has_error(
+            "fn log(s: str) {}\nconst fn f(x: int) -> int { log(\"hi\")\n x }",
+            "const fn `f`: cannot call `log`, which is not a `const fn`",
+        );
+        assert_has_error(
+            "extern fn now() -> int\nconst fn f() -> int { now() }",
+            "const fn `f`: cannot call `now`, which is not a `const fn`",
+        );
+        assert_has_error("let base = 1\nconst fn f() -> int { base }", "const fn `f`: cannot use `base`, which is not constant");
+        assert_has_error("const fn f() -> int { missing }", "const fn `f`: cannot use `missing`, which is not constant");
+        assert_has_error(
+            "enum Sign { Neg, Pos }\nlet s = Sign::Neg\nconst fn f() -> Sign { s }",
+            "const fn `f`: cannot use `s`, which is not constant",
+        );
+        assert_has_error("const fn f() -> int { missing() }", "const fn `f`: cannot call `missing`, which is not a `const fn`");
+        assert_has_error("mut count = 0\nconst fn f() { count += 1 }", "const fn `f`: cannot assign to `count`, which is not a local binding");
+        assert_has_error("const fn f(g: (int) -> int) -> int { g(1) }", "const fn `f`: cannot call parameter `g`");
+        assert_has_error("const fn f(s: str) -> [str] { s.split(\",\") }", "const fn `f`: cannot call method `split`");
+        assert_has_error(
+            "const fn f(n: int) -> int { mut i = n\n while i > 0 { i -= 1 }\n i }",
+            "const fn `f`: `while` loops are not allowed, as they may not terminate",
+        );
+    }
+
+    #[test]
+    fn const_fn_calls_fold() {
+        let src = "const fn double(x: int) -> int = x * 2\nconst fn max(a: int, b: int) -> int { if a > b { a } else { b } }\nconst LIMIT = max(double(3), 5)\n";
+        assert_has_error(&format!("{src}fn f() {{ if LIMIT != 6 {{ }} }}"), "condition is always false");
+        assert_has_error(&format!("{src}fn f() {{ if double(LIMIT) != 12 {{ }} }}"), "condition is always false");
+        // A local shadowing the function is not folded
+        assert_no_errors(&format!("{src}fn f(double: (int) -> int) {{ if double(1) == 2 {{ }} }}"));
+
+        // Folding stops after a bounded number of calls, not only a bounded depth
+        let diags = check_src("const fn fib(n: int) -> int = if n < 2 { n } else { fib(n - 1) + fib(n - 2) }\nconst X = fib(32)");
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!(diags[0].severity, Severity::Warning);
+        assert!(diags[0].message.starts_with("gave up evaluating this constant after 10000 `const fn` calls"), "{:?}", diags);
+        assert_eq!(diags[0].code, Some("const-eval-limit"));
+        assert_no_errors("const fn fib(n: int) -> int = if n < 2 { n } else { fib(n - 1) + fib(n - 2) }\nconst X = fib(10)");
+    }
+
+    #[test]
+    fn imports_bind_any_without_a_project() {
+        assert_no_errors("import { helper, fmt as format } from \"./util\"\nimport * as path from \"path\"\nlet x: int = helper(format(path.sep))");
+        assert_has_error("import { helper } from \"./a\"\nimport { helper } from \"./b\"", "duplicate declaration `helper`");
+        assert_no_errors("import \"./setup\"\nimport \"./setup\"");
+        assert_has_error("import { VERSION } from \"./version\"\nVERSION = \"new\"", "cannot assign to immutable binding `VERSION`");
+    }
+
+    #[test]
+    fn derive_checks() {
+        assert_no_errors(
+            "@derive(Eq, Debug) struct Point { x: int, y: int }\n\
+             @derive(Eq) enum Shape { Dot(at: Point), Path(points: [Point], label: str?) }",
+        );
+        let diags = check_src("@derive(Eq, Hash) struct P { x: int }");
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!(diags[0].message, "unknown derive `Hash`; supported derives are `Eq` and `Debug`");
+        assert_eq!(diags[0].span, Span::new(12, 16));
+        assert_has_error(
+            "struct Inner { x: int }\n@derive(Eq) struct Outer { inner: Inner }",
+            "cannot derive `Eq` for `Outer`: field `inner` has type `Inner`, which does not support equality",
+        );
+        assert_has_error(
+            "@derive(Eq) struct P { tags: {str: int} }",
+            "field `tags` has type `{str: int}`, which does not support equality",
+        );
+        assert_no_errors("@derive(Debug) struct P { tags: {str: int} }");
+    }
+
+    #[test]
+    fn derived_methods() {
+        assert_no_errors(
+            "@derive(Eq, Debug) struct Point { x: int, y: int }\n@derive(Eq, Debug) enum Dir { Up, Down }\n\
+             fn f(a: Point, b: Point) -> bool { a.eq(b) }\nfn g(p: Point) -> str { p.toString() }\n\
+             fn h(d: Dir) -> bool { d.eq(Dir::Up) && d.toString() != \"\" }",
+        );
+        assert_has_error("@derive(Eq) struct Point { x: int }\nfn f(a: Point) -> bool { a.eq(1) }", "argument 1: expected `Point`, found `int`");
+        assert_has_error("@derive(Debug) struct Point { x: int }\nfn f(a: Point, b: Point) -> bool { a.eq(b) }", "field `eq` does not exist");
+
+        let src = "@derive(Eq) struct Point { x: int }\nimpl Point { fn eq(self, other: Point) -> bool { true } }";
+        let diags = check_src(src);
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!(diags[0].message, "`@derive(Eq)` on `Point` conflicts with its method `eq`");
+        assert_eq!(diags[0].span, Span::new(8, 10));
+    }
+
+    #[test]
+    fn lint_levels() {
+        let src = "fn f() -> int { let x = 1; 2 }";
+        let parsed = ag_parser::parse(src);
+        let with_level = |level| {
+            let options = CheckerOptions {
+                lint_levels: HashMap::from([("unused-variable".to_string(), level)]),
+                ..Default::default()
+            };
+            check_with_options(&parsed.module, options)
+        };
+
+        let result = check(&parsed.module);
+        assert_eq!(result.diagnostics.len(), 1);
+        assert_eq!(result.diagnostics[0].code, Some("unused-variable"));
+        assert!(!result.has_errors());
+
+        let result = with_level(LintLevel::Deny);
+        assert_eq!(result.diagnostics.len(), 1);
+        assert!(result.has_errors());
+
+        let result = with_level(LintLevel::Allow);
+        assert!(result.diagnostics.is_empty(), "got: {:?}", result.diagnostics);
+    }
+
+    #[test]
+    fn unused_variables() {
+        let diags = check_src("fn f(unused: int) -> int {\n    let a = 1\n    mut b = 2\n    b = 3\n    let _c = 4\n    0\n}");
+        let messages: Vec<&str> = diags.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "variable `a` is never used; prefix it with `_` if this is intended",
+                "variable `b` is never used; prefix it with `_` if this is intended",
+            ]
+        );
+        assert_eq!(diags[0].span.start, 31);
+        // Reads in closures and templates count; module-level bindings may be used elsewhere
+        assert_no_errors("fn f() -> int { let a = 1\n let g = () => a\n g() }\nlet top = 1");
+        assert_no_errors("fn f() -> str { let name = \"x\"\n `Hi ${name}` }");
+    }
+
+    #[test]
+    fn fn_type_params_and_where_clauses() {
+        assert_no_errors(
+            "interface Named { fn name(self) -> str }\n\
+             fn first<T>(xs: [T]) -> T? where T: Named { xs[0] }\n\
+             fn pair<A, B>(a: A, b: B) -> [any] = [a, b]",
+        );
+        assert_has_error("fn f<T>(x: T) -> U { x }", "unknown type `U`");
+        assert_has_error(
+            "interface Named { fn name(self) -> str }\nfn f<T>(x: T) where U: Named { }",
+            "`where` clause constrains `U`, which is not a type parameter of `f`",
+        );
+        assert_has_error(
+            "struct P { x: int }\nfn f<T>(x: T) where T: P { }",
+            "bound `P` of `T` is not an interface",
+        );
+        assert_has_error("fn f<T>(x: T) where T: Nmaed { }", "unknown type `Nmaed`");
+        assert_has_error("fn f<T, T>(x: T) { }", "duplicate type parameter `T`");
+
+        // Bounded type parameters have the methods of their interfaces
+        let named = "interface Named { fn name(self) -> str }\n\
+                     interface Sized extends Named { fn size(self, unit: str) -> int }\n";
+        assert_no_errors(&format!(
+            "{named}fn label<T>(x: T) -> str where T: Sized {{ `${{x.name()}}: ${{x.size(\"kb\")}}` }}\n\
+             fn same<T>(a: T, b: T) -> bool where T: Named {{ a == b }}"
+        ));
+        assert_has_error(
+            &format!("{named}fn f<T>(x: T) -> str where T: Named {{ x.title() }}"),
+            "no method `title` on type parameter `T`; its bounds `Named` do not declare it",
+        );
+        assert_has_error(
+            &format!("{named}fn f<T>(x: T) -> int where T: Sized {{ x.size(1) }}"),
+            "expected `str`, found `int`",
+        );
+        assert_has_error(
+            &format!("{named}fn f<T>(x: T) -> int where T: Named {{ x.name() }}"),
+            "return type mismatch: expected `int`, found `str`",
+        );
+        assert_has_error(
+            &format!("{named}fn f<T>(a: T, b: T) where T: Named {{ a + b }}"),
+            "type parameter `T` does not support arithmetic; its bounds only declare methods",
+        );
+        assert_has_error(
+            &format!("{named}fn f<T>(a: T, b: T) -> bool where T: Named {{ a < b }}"),
+            "type parameter `T` does not support ordering; its bounds only declare methods",
+        );
+        // An unbounded parameter, or the same name in another function, is unchecked
+        assert_no_errors(&format!(
+            "{named}fn f<T>(x: T) where T: Named {{ }}\nfn g<T>(x: T) -> any {{ x.anything + 1 }}"
+        ));
+    }
+
+    #[test]
+    fn shadowing_lint() {
+        let src = "fn f(c: bool) -> int {\n    let x = 1\n    if c { let x = 2; ret x }\n    \
+                   let _n = [1].map((x) => x + 1)\n    \
+                   match x { x => { let _y = x } }\n    \
+                   for _n in [1] { let _n = 2 }\n    x\n}";
+        let parsed = ag_parser::parse(src);
+        assert!(check(&parsed.module).diagnostics.is_empty());
 
-            // Restore scope
-            let child = std::mem::replace(&mut self.scope, Scope::new());
-            self.scope = *child.parent.unwrap();
+        let options = CheckerOptions {
+            lint_levels: HashMap::from([("shadowing".to_string(), LintLevel::Warn)]),
+            ..Default::default()
+        };
+        let diags = check_with_options(&parsed.module, options).diagnostics;
+        assert_eq!(diags.len(), 2, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "`x` shadows a binding from an outer scope");
+        assert_eq!(diags[0].code, Some("shadowing"));
+        assert_eq!(diags[0].span.start, span_of(src, "let x = 2").start);
+        assert_eq!(diags[0].labels[0].span.start, span_of(src, "let x = 1").start);
+        assert_eq!(diags[1].span.start, span_of(src, "x) =>").start);
+        assert_has_error("fn f() { let x = 1\n let x = 2 }", "duplicate binding `x`");
+    }
 
-            if let Some(ref existing) = result_ty {
-                if !self.type_compatible(existing, &arm_ty) {
-                    result_ty = Some(Type::Union(
-                        Box::new(existing.clone()),
-                        Box::new(arm_ty),
-                    ));
-                }
-            } else {
-                result_ty = Some(arm_ty);
-            }
-        }
+    fn span_of(src: &str, needle: &str) -> Span {
+        let start = src.find(needle).expect("needle in source") as u32;
+        Span::new(start, start + needle.len() as u32)
+    }
 
-        result_ty.unwrap_or(Type::Nil)
+    #[test]
+    fn type_map_records_expression_types() {
+        let src = "struct Inner { name: str }\n\
+                   struct Outer { inner: Inner }\n\
+                   fn wrap(n: str) -> [str] { [n] }\n\
+                   fn len(xs: [str]) -> int { xs.len }\n\
+                   fn f(o: Outer) -> int { o.inner.name |> wrap |> len(_) }";
+        let parsed = ag_parser::parse(src);
+        let options = CheckerOptions { record_types: true, ..Default::default() };
+        let result = check_with_options(&parsed.module, options);
+        assert!(result.diagnostics.is_empty(), "got: {:?}", result.diagnostics);
+        let map = result.type_map.expect("type map requested");
+        let ty = |expr: &Expr| map.type_of(expr).map(|t| t.to_string());
+
+        let Item::FnDecl(f) = &parsed.module.items[4] else { panic!("expected fn") };
+        let outer = f.body.tail_expr.as_deref().unwrap();
+        let Expr::Pipe(outer_pipe) = outer else { panic!("expected pipe") };
+        let Expr::Pipe(inner_pipe) = &outer_pipe.left else { panic!("expected pipe") };
+        let Expr::Member(name) = &inner_pipe.left else { panic!("expected member") };
+        assert_eq!(ty(outer).as_deref(), Some("int"));
+        assert_eq!(ty(&outer_pipe.left).as_deref(), Some("[str]"));
+        assert_eq!(ty(&inner_pipe.left).as_deref(), Some("str"));
+        assert_eq!(ty(&name.object).as_deref(), Some("Inner"));
+
+        let Item::FnDecl(wrap) = &parsed.module.items[2] else { panic!("expected fn") };
+        assert_eq!(map.decl_type(wrap.params[0].span), Some(&Type::Str));
     }
 
-    fn bind_pattern(&mut self, pattern: &Pattern, subject_ty: &Type) {
-        match pattern {
-            Pattern::Ident(name, _) => {
-                self.scope.define(
-                    name,
-                    Symbol {
-                        ty: subject_ty.clone(),
-                        mutable: false,
-                    },
-                );
-            }
-            Pattern::Enum(ep) => {
-                // Bind enum variant fields
-                if let Type::Enum(_, variants) = subject_ty {
-                    if let Some((_, fields)) = variants.iter().find(|(n, _)| n == &ep.variant) {
-                        for (binding, (_, ty)) in ep.bindings.iter().zip(fields) {
-                            self.scope.define(
-                                binding,
-                                Symbol {
-                                    ty: ty.clone(),
-                                    mutable: false,
-                                },
-                            );
-                        }
-                    }
-                }
-            }
-            Pattern::Struct(sp) => {
-                if let Type::Struct(_, fields) = subject_ty {
-                    for field_name in &sp.fields {
-                        if let Some((_, ty)) = fields.iter().find(|(n, _)| n == field_name) {
-                            self.scope.define(
-                                field_name,
-                                Symbol {
-                                    ty: ty.clone(),
-                                    mutable: false,
-                                },
-                            );
-                        }
-                    }
-                }
-            }
-            _ => {}
+    #[test]
+    fn type_map_keys_each_expression_by_its_extent() {
+        let src = "fn f(a: int, b: int, xs: [int]) -> bool { a < b <= b + 1 && a not in xs }";
+        let parsed = ag_parser::parse(src);
+        let options = CheckerOptions { record_types: true, ..Default::default() };
+        let result = check_with_options(&parsed.module, options);
+        assert!(result.diagnostics.is_empty(), "got: {:?}", result.diagnostics);
+        let map = result.type_map.unwrap();
+
+        let Item::FnDecl(f) = &parsed.module.items[0] else { panic!("expected fn") };
+        let Some(Expr::Binary(and)) = f.body.tail_expr.as_deref() else { panic!("expected &&") };
+        let Expr::Comparison(chain) = and.left.as_ref() else { panic!("expected chain") };
+        let last = &chain.operands[2];
+        // `a not in xs` is `!(a in xs)`, both spanning the `not in`
+        let Expr::Unary(not) = and.right.as_ref() else { panic!("expected not in") };
+        assert_eq!(not.span, not.operand.span());
+        assert_eq!(ExprKey::of(last).span, span_of(src, "b + 1"));
+        assert_eq!(map.type_of(last), Some(&Type::Int));
+        assert_eq!(ExprKey::of(&and.left).span, span_of(src, "a < b <= b + 1"));
+
+        let nodes = [&*and.left, &chain.operands[1], &*and.right, &*not.operand, last];
+        let keys: HashSet<ExprKey> = nodes.iter().map(|e| ExprKey::of(e)).collect();
+        assert_eq!(keys.len(), nodes.len());
+        for node in nodes {
+            assert!(map.type_of(node).is_some(), "no type for {node:?}");
         }
+        assert_eq!(ExprKey::of(&and.right).span, span_of(src, "a not in xs"));
     }
 
-    // ── Block check ────────────────────────────────────────
+    #[test]
+    fn pipe_checks_input_and_returns_result() {
+        let src = "fn double(n: int) -> int { n * 2 }\nfn add(a: int, b: int) -> int { a + b }\nlet x = 42 |> double\nlet y = 1 |> add(2, _)";
+        let parsed = ag_parser::parse(src);
+        let options = CheckerOptions { record_types: true, ..Default::default() };
+        let result = check_with_options(&parsed.module, options);
+        assert!(result.diagnostics.is_empty(), "got: {:?}", result.diagnostics);
+        let map = result.type_map.unwrap();
+        let (Item::VarDecl(x), Item::VarDecl(y)) = (&parsed.module.items[2], &parsed.module.items[3]) else {
+            panic!("expected lets")
+        };
+        assert_eq!(map.type_of(&x.init), Some(&Type::Int));
+        assert_eq!(map.type_of(&y.init), Some(&Type::Int));
 
-    fn check_block(&mut self, block: &Block) -> Type {
-        let parent = std::mem::replace(&mut self.scope, Scope::new());
-        self.scope = Scope::child(parent);
+        let diags = check_src("fn double(n: int) -> int { n * 2 }\nlet x = \"a\" |> double");
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "argument 1: expected `int`, found `str`");
+        assert_has_error(
+            "fn add(a: int, b: int) -> int { a + b }\nlet y = \"a\" |> add(2, _)",
+            "argument 2: expected `int`, found `str`",
+        );
+        assert_no_errors("fn show(x: any) -> str { \"\" }\nlet s = 1 |> show |> show");
+    }
 
-        for stmt in &block.stmts {
-            self.check_stmt(stmt);
-        }
+    #[test]
+    fn type_map_records_definitions() {
+        let src = "let limit = 3\nfn f(n: int) -> bool { n > limit }";
+        let parsed = ag_parser::parse(src);
+        let options = CheckerOptions { record_types: true, ..Default::default() };
+        let map = check_with_options(&parsed.module, options).type_map.unwrap();
 
-        let ty = if let Some(ref tail) = block.tail_expr {
-            self.check_expr(tail)
-        } else {
-            Type::Nil
+        let (Item::VarDecl(limit), Item::FnDecl(f)) = (&parsed.module.items[0], &parsed.module.items[1]) else {
+            panic!("expected a let and a fn")
         };
+        assert_eq!(map.definition_of(span_of(src, "limit ")), None);
+        let limit_use = src.rfind("limit").unwrap() as u32;
+        assert_eq!(map.definition_of(Span::new(limit_use, limit_use + 5)), Some(limit.span));
+        let n_use = span_of(src, "n >").start;
+        assert_eq!(map.definition_of(Span::new(n_use, n_use + 1)), Some(f.params[0].span));
 
-        let child = std::mem::replace(&mut self.scope, Scope::new());
-        self.scope = *child.parent.unwrap();
+        assert!(check(&parsed.module).type_map.is_none());
+    }
 
-        ty
+    #[test]
+    fn lints_are_known() {
+        assert!(LINTS.contains(&"unreachable-code"));
+        let diags = check_src("fn f() { ret;\n let x = 1 }");
+        assert!(!diags.is_empty());
+        assert!(diags.iter().all(|d| d.code.is_some_and(|c| LINTS.contains(&c))), "got: {:?}", diags);
     }
 
-    fn check_stmt(&mut self, stmt: &Stmt) {
-        match stmt {
-            Stmt::VarDecl(v) => self.check_var_decl(v),
-            Stmt::ExprStmt(e) => {
-                self.check_expr(&e.expr);
-            }
-            Stmt::Return(r) => {
-                if let Some(ref val) = r.value {
-                    self.check_expr(val);
-                }
-            }
-            Stmt::If(if_expr) => {
-                self.check_expr(&Expr::If(Box::new(if_expr.clone())));
-            }
-            Stmt::For(f) => {
-                let iter_ty = self.check_expr(&f.iter);
-                let elem_ty = match iter_ty {
-                    Type::Array(inner) => *inner,
-                    _ => Type::Any,
-                };
-                let parent = std::mem::replace(&mut self.scope, Scope::new());
-                self.scope = Scope::child(parent);
-                self.scope.define(
-                    &f.binding,
-                    Symbol {
-                        ty: elem_ty,
-                        mutable: false,
-                    },
-                );
-                self.check_block(&f.body);
-                let child = std::mem::replace(&mut self.scope, Scope::new());
-                self.scope = *child.parent.unwrap();
-            }
-            Stmt::While(w) => {
-                self.check_expr(&w.condition);
-                self.check_block(&w.body);
-            }
-            Stmt::Match(m) => {
-                self.check_match(m);
-            }
-            Stmt::TryCatch(tc) => {
-                self.check_block(&tc.try_block);
-                let parent = std::mem::replace(&mut self.scope, Scope::new());
-                self.scope = Scope::child(parent);
-                self.scope.define(
-                    &tc.catch_binding,
-                    Symbol {
-                        ty: Type::Any,
-                        mutable: false,
-                    },
-                );
-                self.check_block(&tc.catch_block);
-                let child = std::mem::replace(&mut self.scope, Scope::new());
-                self.scope = *child.parent.unwrap();
-            }
-        }
+    #[test]
+    fn top_level_use_before_definition() {
+        let src = "let a = b + 1\nlet b = 2";
+        let diags = check_src(src);
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "`b` is used before its definition");
+        assert_eq!(diags[0].span.start, 8);
+        assert_eq!(diags[0].labels[0].span.start, src.find("let b").unwrap() as u32);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use ag_parser;
+    #[test]
+    fn functions_may_use_later_bindings() {
+        assert_no_errors("fn f() -> int { limit * 2 }\nlet limit = 10");
+        assert_no_errors("let g = () => limit\nlet limit = 10");
+    }
 
-    fn check_src(src: &str) -> Vec<Diagnostic> {
-        let parsed = ag_parser::parse(src);
-        assert!(
-            parsed.diagnostics.is_empty(),
-            "parse errors: {:?}",
-            parsed.diagnostics
+    #[test]
+    fn dsl_capture_before_definition() {
+        assert_has_error(
+            "@prompt system <<EOF\nHello #{name}\nEOF\nlet name = \"Bo\"",
+            "`name` is used before its definition",
         );
-        let result = check(&parsed.module);
-        result.diagnostics
+        assert_no_errors("let name = \"Bo\"\n@prompt system <<EOF\nHello #{name}\nEOF\n");
     }
 
-    fn assert_no_errors(src: &str) {
+    #[test]
+    fn unknown_type_name_reported_once() {
+        let diags = check_src("fn f(x: Strig) -> int { 1 }");
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "unknown type `Strig`, did you mean `str`?");
+        assert_eq!(diags[0].span.start, 8);
+
+        let diags = check_src("struct User { name: str }\nlet users: [Usr] = []\nfn g(m: {str: Qwerty}) {}");
+        let messages: Vec<&str> = diags.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            ["unknown type `Usr`, did you mean `User`?", "unknown type `Qwerty`"]
+        );
+    }
+
+    #[test]
+    fn known_type_names_are_not_reported() {
+        assert_no_errors(
+            "fn f(u: User, id: ID) -> Shape? { nil }\nstruct User { name: str }\ntype ID = str\ninterface Shape { fn area(self) -> num }",
+        );
+        assert_no_errors("interface Box<T> { fn get(self) -> T }");
+        assert_has_error("interface Box<T> { fn get(self) -> U }", "unknown type `U`");
+    }
+
+    #[test]
+    fn generic_type_aliases_substitute_arguments() {
+        let pair = "type Pair<T> = { first: T, second: T }\n";
+        assert_no_errors(&format!("{pair}let p: Pair<int> = {{ first: 1, second: 2 }}\nlet n: int = p.first"));
+        assert_has_error(&format!("{pair}let p: Pair<int> = {{ first: 1, second: \"b\" }}"), "found `{ first: int, second: str }`");
+        assert_has_error(&format!("{pair}let p: Pair<int> = {{ first: 1, second: 2 }}\nlet s: str = p.second"), "expected `str`, found `int`");
+        assert_no_errors("type Pair<T> = { first: T, second: T }\ntype Both<A> = Pair<[A]>\nlet b: Both<str> = { first: [\"a\"], second: [] }");
+
+        assert_has_error(&format!("{pair}let p: Pair<int, str> = {{ first: 1, second: 2 }}"), "type alias `Pair` expects 1 type argument, found 2");
+        assert_has_error(&format!("{pair}let p: Pair = {{ first: 1, second: 2 }}"), "type alias `Pair` expects 1 type argument, found 0");
+        assert_has_error("type Pair<T> = { first: T, second: U }", "unknown type `U`");
+        assert_has_error("type Pair<T, T> = { first: T }", "duplicate type parameter `T`");
+        // A recursive alias is left unresolved instead of expanding forever
+        assert_no_errors("type List<T> = { head: T, tail: List<T>? }\nlet l: List<int> = { head: 1, tail: nil }");
+    }
+
+    #[test]
+    fn empty_array_takes_annotated_element_type() {
+        assert_no_errors("let items: [str] = []\nlet tags: [str]? = []");
+        let parsed = ag_parser::parse("let items: [str] = []");
+        let Item::VarDecl(v) = &parsed.module.items[0] else { panic!("expected var decl") };
+        let mut checker = Checker::new();
+        let expected = Type::Array(Box::new(Type::Str));
+        assert_eq!(checker.check_expr_expected(&v.init, Some(&expected)).to_string(), "[str]");
+        assert_eq!(checker.check_expr(&v.init).to_string(), "[any]");
+    }
+
+    #[test]
+    fn unreachable_after_ret_reported_once() {
+        let src = "fn f() -> int {\n  ret 1\n  let a = 2\n  let b = 3\n  a + b\n}";
         let diags = check_src(src);
-        assert!(diags.is_empty(), "unexpected errors: {:?}", diags);
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "unreachable code");
+        assert_eq!(diags[0].severity, Severity::Warning);
+        let start = src.find("let a").unwrap() as u32;
+        let tail = src.find("a + b").unwrap() as u32;
+        assert_eq!(diags[0].span.start, start);
+        assert!(diags[0].span.end > tail, "span should cover the tail: {:?}", diags[0].span);
     }
 
-    fn assert_has_error(src: &str, msg_contains: &str) {
+    #[test]
+    fn unreachable_after_break_and_diverging_match() {
+        assert_has_warning("fn f() { loop { break;\n  let x = 1 } }", "unreachable-code");
+        assert_has_warning(
+            "fn f(x: int) -> int {\n  match x { 1 => { ret 1 }, _ => { ret 2 } }\n  let y = 3\n  y\n}",
+            "unreachable-code",
+        );
+        assert_no_errors("fn f(x: int) -> int {\n  match x { 1 => { ret 1 }, _ => 2 }\n  3\n}");
+        assert_no_errors("fn f(x: bool) -> int {\n  if x { ret 1 }\n  2\n}");
+    }
+
+    #[test]
+    fn missing_return_reported() {
+        let src = "fn f(x: int) -> int { if x > 0 { ret 1 } }";
         let diags = check_src(src);
-        assert!(
-            diags.iter().any(|d| d.message.contains(msg_contains)),
-            "expected error containing '{}', got: {:?}",
-            msg_contains,
-            diags
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "fn `f`: not all paths return a value");
+        assert_eq!(diags[0].span.start, src.find("if").unwrap() as u32);
+
+        assert_has_error(
+            "fn f(x: int) -> int { if x > 0 { 1 } else { log(x); } }\nextern fn log(x: int)",
+            "not all paths return a value",
+        );
+        assert_has_error(
+            "fn f(x: int) -> int { match x { 1 => { ret 1 }, _ => { log(x); } } }\nextern fn log(x: int)",
+            "not all paths return a value",
+        );
+        assert_has_error(
+            "fn f(x: int) -> int { while x > 0 { ret 1 } }",
+            "not all paths return a value",
         );
+        assert_has_error("fn f(xs: [int]) -> int { for x in xs { ret x } }", "not all paths return a value");
     }
 
     #[test]
-    fn type_mismatch() {
-        assert_has_error(r#"let x: int = "hello""#, "type mismatch");
+    fn all_paths_return() {
+        assert_no_errors("fn f(x: int) -> int { if x > 0 { ret 1 } else if x < 0 { -1 } else { 0 } }");
+        assert_no_errors("fn f(x: int) -> int { match x { 1 => { ret 1 }, _ => 2 } }");
+        assert_no_errors("fn f(x: int) -> int { loop { if x > 0 { ret 1 } } }");
+        assert_no_errors("fn f(x: int) -> int { if x > 0 { ret 1 }\n ret 0 }");
+        assert_no_errors("fn f(x: int) { if x > 0 { ret } }");
     }
 
     #[test]
-    fn int_to_num_widening() {
-        assert_no_errors("let x: num = 42");
+    fn ret_values_match_the_return_type() {
+        assert_has_error("fn f(c: bool) -> int { if c { ret \"s\" }\n 1 }", "return type mismatch: expected `int`, found `str`");
+        let load = "extern fn fetchData() -> Promise<str>\n";
+        assert_has_error(
+            &format!("{load}async fn f() -> int {{ ret fetchData() }}"),
+            "return type mismatch: expected `int`, found `str`",
+        );
+        assert_no_errors(&format!("{load}async fn f() -> str {{ ret fetchData() }}"));
+        // Arrows have no declared return type to match
+        assert_no_errors("fn f() -> int { let _g = () => { ret \"s\" }\n 1 }");
     }
 
     #[test]
-    fn any_escapes_checking() {
-        // any should be compatible with everything
-        assert_no_errors("let x: any = 42");
+    fn duplicate_names_in_declarations() {
+        let diags = check_src("struct User { name: str, name: int }");
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "duplicate field `name`");
+        assert_eq!(diags[0].span.start, 25);
+        assert_eq!(diags[0].labels[0].message, "`name` first defined here");
+        assert_eq!(diags[0].labels[0].span.start, 14);
+
+        assert_has_error("enum E { A, A }", "duplicate variant `A`");
+        assert_has_error("enum E { A(x: int, x: str) }", "duplicate field `x`");
+        assert_has_error("fn f(x: int, x: str) {}", "duplicate parameter `x`");
+        assert_has_error("extern fn g(a: int, a: int)", "duplicate parameter `a`");
+        assert_has_error("let h = (y: int, y: int) => y", "duplicate parameter `y`");
+        assert_has_error("fn k(o: { a: int, a: str }) {}", "duplicate field `a`");
+        assert_eq!(check_src("fn f(x: int, x: str) {}").len(), 1);
     }
 
     #[test]
-    fn infer_let_type() {
-        assert_no_errors("let x = 42");
+    fn duplicate_object_keys_warn() {
+        let diags = check_src("let o = { a: 1, a: 2 }");
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].severity, Severity::Warning);
+        assert_eq!(diags[0].message, "duplicate key `a` in object literal; the last value wins");
     }
 
     #[test]
-    fn undefined_variable() {
-        assert_has_error("fn f() -> int { y }", "undefined variable `y`");
+    fn object_type_fits_struct_structurally() {
+        assert_no_errors(&format!(r#"{USER}let u: User = {{ name: "A", age: 30 }}"#));
+        assert_no_errors(&format!(
+            r#"{USER}fn greet(u: User) -> str {{ u.name }}
+let o = {{ name: "A", age: 30, admin: true, extra: true }}
+greet(o)"#
+        ));
+        // Only a literal checked against the struct gets its defaults
+        assert_has_error(
+            &format!(
+                r#"{USER}fn greet(u: User) -> str {{ u.name }}
+let o = {{ name: "A", age: 30 }}
+greet(o)"#
+            ),
+            "argument 1: expected `User`, found `{ name: str, age: int }`",
+        );
+        assert_has_error(
+            &format!(
+                r#"{USER}fn greet(u: User) -> str {{ u.name }}
+let o = {{ name: "A" }}
+greet(o)"#
+            ),
+            "argument 1: expected `User`, found `{ name: str }`",
+        );
+        assert_has_error(
+            "let o = { name: \"A\" }\nlet n = o.age",
+            "field `age` does not exist on type `{ name: str }`",
+        );
     }
 
     #[test]
-    fn duplicate_binding() {
-        assert_has_error("let x = 1\nlet x = 2", "duplicate binding `x`");
+    fn struct_literal_field_type_and_call_arg() {
+        assert_has_error(
+            &format!(r#"{USER}let u: User = {{ name: "Bo", age: "3" }}"#),
+            "field `age`: expected `int`, found `str`",
+        );
+        assert_has_error(
+            &format!("{USER}fn greet(u: User) {{ }}\nfn f() {{ greet({{ name: \"Bo\" }}) }}"),
+            "missing field `age` in `User` literal",
+        );
     }
 
     #[test]
-    fn reassign_immutable() {
-        assert_has_error("fn f() { let x = 1; x = 2 }", "cannot assign to immutable binding `x`");
+    fn array_unknown_member() {
+        assert_has_error(
+            "fn f(xs: [int]) { xs.frobnicate() }",
+            "member `frobnicate` does not exist on type `[int]`",
+        );
     }
 
+    // ── Loop tests ──
+
     #[test]
-    fn reassign_mutable() {
-        assert_no_errors("fn f() { mut x = 1; x = 2 }");
+    fn while_let_binds_pattern() {
+        assert_no_errors(
+            "enum Opt { Some(value: int), None }\nfn next() -> Opt { Opt::None }\nfn f() { mut total = 0; while let Opt::Some(v) = next() { total = total + v } }",
+        );
     }
 
     #[test]
-    fn nullable_assignment() {
-        assert_no_errors("let x: str? = nil");
+    fn loop_without_break_diverges() {
+        assert_no_errors("fn f() -> int { loop { } }");
+        assert_has_error("fn f() { let x: int = loop { break } }", "type mismatch");
+        assert_no_errors("fn f(c: bool) { let _x = loop { if c { break } } }");
     }
 
     #[test]
-    fn return_type_mismatch() {
+    fn break_outside_loop() {
+        assert_has_error("fn f() { break }", "`break` outside of a loop");
         assert_has_error(
-            r#"fn foo() -> int { "hello" }"#,
-            "return type mismatch",
+            "fn f() { while true { let g = () => { break } } }",
+            "`break` outside of a loop",
         );
+        assert_no_errors("fn f(c: bool) { while c { if c { break } } }");
     }
 
     #[test]
-    fn valid_function_return() {
-        assert_no_errors("fn add(a: int, b: int) -> int { a + b }");
+    fn loop_break_value_types() {
+        assert_no_errors("fn f() -> int { loop { break 1 } }");
+        assert_no_errors("fn f(n: int) -> int { let x = loop { if n > 0 { break n }\n break 0 }\n x }");
+        assert_has_error("fn f() -> str { loop { break 1 } }", "type mismatch");
+        assert_has_error(
+            "fn f() { let x = loop { if true { break 1 }\n break \"a\" } }",
+            "`break` value has type `str`, expected `int`",
+        );
+        assert_has_error(
+            "fn f() { while true { break 1 } }",
+            "`break` with a value is only allowed inside `loop`",
+        );
     }
 
     // ── DSL capture tests ──
@@ -1503,6 +5748,10 @@ mod tests {
             "@component c <<EOF\n/**\n * @param {string} x\n * @param {number} x\n */\nexport default function C({ x }) { return <div/> }\nEOF\n",
             "duplicate prop",
         );
+        assert_has_error(
+            "@component Card <<EOF\nfunction Card() { return <div/> }\nEOF\n",
+            "no `export default` function",
+        );
     }
 
     #[test]
@@ -1530,6 +5779,126 @@ mod tests {
         );
     }
 
+    #[test]
+    fn dsl_handler_diagnostics_have_source_spans() {
+        let src = "struct Source { url: str }\n@prompt p <<EOF\n@role system\nHi\n@role sytem\n@output {\n  refs: [Source]\n  score: Score\n}\nEOF\n";
+        let diags = check_src(src);
+        assert_eq!(diags.len(), 2, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "unknown role `sytem`, expected `system`, `user` or `assistant`");
+        assert_eq!(diags[0].code, Some("dsl-validation"));
+        assert_eq!(diags[0].span, span_of(src, "@role sytem"));
+        assert_eq!(diags[1].message, "@output field `score` has unknown type `Score`");
+        assert!(diags[1].is_error());
+        assert_eq!(diags[1].span, span_of(src, "@output {"));
+    }
+
+    #[test]
+    fn agent_prompt_directives_are_validated() {
+        let src = "fn search(q: str) -> str { q }\n@agent a <<EOF\n@role system\nUse these:\n@tools #{[search]}\n@on init #{() => {}}\n@role sytem\nHi\nEOF\n";
+        let diags = check_src(src);
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "unknown role `sytem`, expected `system`, `user` or `assistant`");
+        assert_eq!(diags[0].span, span_of(src, "@role sytem"));
+    }
+
+    #[test]
+    fn dsl_handlers_are_pluggable() {
+        struct Graphql;
+
+        impl DslHandler for Graphql {
+            fn handle(
+                &self,
+                _block: &ag_dsl_core::DslBlock,
+                _ctx: &mut dyn ag_dsl_core::CodegenContext,
+            ) -> Result<Vec<swc_ecma_ast::ModuleItem>, ag_dsl_core::DslError> {
+                Ok(Vec::new())
+            }
+
+            fn validate(
+                &self,
+                block: &ag_dsl_core::DslBlock,
+                ctx: &dyn ag_dsl_core::ValidateContext,
+            ) -> Vec<ag_dsl_core::DslDiagnostic> {
+                let mut diags = vec![ag_dsl_core::DslDiagnostic::warning(format!("query `{}`", block.name), None)];
+                if !ctx.is_type("User") {
+                    diags.push(ag_dsl_core::DslDiagnostic::error("no `User` type", None));
+                }
+                diags
+            }
+        }
+
+        let src = "struct User { name: str }\n@graphql q <<EOF\nquery { users }\nEOF\n";
+        let parsed = ag_parser::parse(src);
+        let mut handlers = builtin_dsl_handlers();
+        handlers.insert("graphql".to_string(), Box::new(Graphql));
+        let diags = check_with_handlers(&parsed.module, CheckerOptions::default(), handlers).diagnostics;
+        assert_eq!(diags.len(), 1, "got: {:?}", diags);
+        assert_eq!(diags[0].message, "query `q`");
+        assert_eq!(diags[0].span.start, span_of(src, "@graphql").start);
+
+        // Without a handler, the prompt is not validated
+        let parsed = ag_parser::parse("@prompt p <<EOF\n@role sytem\nEOF\n");
+        let diags = check_with_handlers(&parsed.module, CheckerOptions::default(), HashMap::new()).diagnostics;
+        assert!(diags.is_empty(), "got: {:?}", diags);
+    }
+
+    #[test]
+    fn interpolated_values_should_stringify() {
+        let src = "struct User { name: str }\n\
+                   extern struct Id { fn toString() -> str }\n\
+                   extern fn make_id() -> Id\n\
+                   let u = User { name: \"a\" }\n\
+                   let id = make_id()\n\
+                   let n: int? = 1\n\
+                   @prompt p <<EOF\n@role system\nHello #{u.name} (#{id}), #{n} and #{u}.\nEOF\n";
+        let diags = check_src(src);
+        assert_eq!(diags.len(), 2, "got: {:?}", diags);
+        assert_eq!(
+            diags[0].message,
+            "capture expression has type `int?` and renders as `null` when nil; provide a default with `?? \"\"`"
+        );
+        assert_eq!(
+            diags[1].message,
+            "capture expression has type `User` which may not stringify meaningfully; convert it explicitly, e.g. with `JSON.stringify`"
+        );
+        assert_eq!(diags[1].code, Some("interpolation"));
+        assert!(!diags[1].is_error());
+
+        // Template strings, but not tagged templates, which get the values
+        let src = "struct User { name: str }\n\
+                   enum Role { Admin, Guest }\n\
+                   fn tag(strings: [str], values: [any]) -> str { \"\" }\n\
+                   fn f(u: User, r: Role, m: {str: int}, us: [User], name: str?, ns: [int], x: any) -> str {\n\
+                   let _a = `${u.name} ${ns} ${x} ${name ?? \"\"}`\n\
+                   let _b = tag`${u}`\n\
+                   `${u} ${r} ${m} ${us} ${name}`\n\
+                   }\n";
+        let diags = check_src(src);
+        let messages: Vec<&str> = diags.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "interpolated value has type `User` which may not stringify meaningfully; convert it explicitly, e.g. with `JSON.stringify`",
+                "interpolated value has type `Role` which may not stringify meaningfully; convert it explicitly, e.g. with `JSON.stringify`",
+                "interpolated value has type `{str: int}` which may not stringify meaningfully; convert it explicitly, e.g. with `JSON.stringify`",
+                "interpolated value has type `[User]` which may not stringify meaningfully; convert it explicitly, e.g. with `JSON.stringify`",
+                "interpolated value has type `str?` and renders as `null` when nil; provide a default with `?? \"\"`",
+            ]
+        );
+
+        let options = CheckerOptions {
+            lint_levels: HashMap::from([("interpolation".to_string(), LintLevel::Allow)]),
+            ..Default::default()
+        };
+        let parsed = ag_parser::parse("fn f(name: str?) -> str { `${name}` }");
+        assert!(check_with_options(&parsed.module, options).diagnostics.is_empty());
+
+        // Directive arguments are not interpolated
+        assert_no_errors(
+            "fn lookup(q: str) -> str { q }\n@agent a <<EOF\n@tools #{[lookup]}\n@role system\nHi\nEOF\n",
+        );
+    }
+
     // ── Skill type validation tests ──
 
     #[test]
@@ -1655,7 +6024,7 @@ mod tests {
 
     #[test]
     fn json_schema_non_serializable_fn() {
-        let ty = Type::Function(vec![Type::Int], Box::new(Type::Int));
+        let ty = Type::Function(vec![FnParam::required(Type::Int)], Box::new(Type::Int));
         assert_eq!(type_to_json_schema(&ty), JsonSchema::Any);
     }
 
@@ -0,0 +1,344 @@
+//! Checking a program made of several modules linked by relative imports.
+
+use std::collections::{HashMap, HashSet};
+
+use ag_ast::{Diagnostic, Item, Module, Rewrites};
+
+use crate::{check_with_imports, CheckResult, CheckerOptions, Export, ModuleExports};
+
+/// Supplies module sources to [`check_project`], so that embedders control
+/// how (and whether) files are read.
+pub trait FileLoader {
+    /// The source of the module at `path`, or `None` if there is no such
+    /// module. Paths are `/`-separated, normalized, and end in `.ag`.
+    fn load(&self, path: &str) -> Option<String>;
+}
+
+/// A module of a project, checked against the modules it imports.
+pub struct ProjectModule {
+    pub path: String,
+    pub module: Module,
+    /// Parse, import and checker diagnostics, in source order.
+    pub result: CheckResult,
+    /// The module's public symbols (`pub fn`s, `pub use` aliases and `pub`
+    /// types).
+    pub exports: HashMap<String, Export>,
+}
+
+/// The modules reachable from an entry module through relative imports.
+pub struct ModuleGraph {
+    /// In dependency order: each module comes after the modules it imports,
+    /// so the entry module is last.
+    pub modules: Vec<ProjectModule>,
+}
+
+impl ModuleGraph {
+    pub fn get(&self, path: &str) -> Option<&ProjectModule> {
+        self.modules.iter().find(|m| m.path == path)
+    }
+
+    /// Whether any module has an error diagnostic.
+    pub fn has_errors(&self) -> bool {
+        self.modules.iter().any(|m| m.result.has_errors())
+    }
+}
+
+/// Load the module at `entry` and every module it imports with a relative
+/// path (`./util`, `../lib/http.ag`), then check them in dependency order.
+/// Other imports (`std:` modules, npm packages) introduce `any` bindings.
+/// Returns `None` if the entry module cannot be loaded.
+pub fn check_project(entry: &str, loader: &dyn FileLoader, options: CheckerOptions) -> Option<ModuleGraph> {
+    let entry = resolve_path("", entry);
+    let source = loader.load(&entry)?;
+    let mut project = Project {
+        loader,
+        options,
+        stack: Vec::new(),
+        checked: HashMap::new(),
+        unparsed: HashSet::new(),
+        modules: Vec::new(),
+    };
+    project.visit(entry, &source);
+    Some(ModuleGraph { modules: project.modules })
+}
+
+struct Project<'a> {
+    loader: &'a dyn FileLoader,
+    options: CheckerOptions,
+    /// Modules being loaded, importer first, for detecting cycles.
+    stack: Vec<String>,
+    /// Index into `modules` of each module checked so far, by path.
+    checked: HashMap<String, usize>,
+    /// Indices of modules with parse errors, which are not checked.
+    unparsed: HashSet<usize>,
+    modules: Vec<ProjectModule>,
+}
+
+impl Project<'_> {
+    /// Parse and check the module at `path` after the modules it imports;
+    /// returns its index in `modules`.
+    fn visit(&mut self, path: String, source: &str) -> usize {
+        self.stack.push(path.clone());
+        let parsed = ag_parser::parse(source);
+        let parsed_ok = !parsed.has_errors();
+        let mut diagnostics = parsed.diagnostics;
+
+        let mut imported: HashMap<String, ModuleExports> = HashMap::new();
+        for item in &parsed.module.items {
+            let Item::Import(imp) = item else { continue };
+            let Some(spec) = imp.path.as_static().filter(|spec| is_relative(spec)) else {
+                continue;
+            };
+            let target = resolve_path(&path, spec);
+            if let Some(start) = self.stack.iter().position(|p| *p == target) {
+                let mut cycle = self.stack[start..].to_vec();
+                cycle.push(target);
+                diagnostics.push(Diagnostic::error(format!("import cycle: {}", cycle.join(" -> ")), imp.span));
+                continue;
+            }
+            let index = match self.checked.get(&target) {
+                Some(&index) => index,
+                None => match self.loader.load(&target) {
+                    Some(source) => self.visit(target, &source),
+                    None => {
+                        diagnostics.push(Diagnostic::error(format!("cannot find module `{spec}`"), imp.span));
+                        continue;
+                    }
+                },
+            };
+            // A module that failed to parse has unknown exports; its
+            // importers see `any` rather than a cascade of missing names.
+            if !self.unparsed.contains(&index) {
+                imported.insert(spec.to_string(), self.modules[index].exports.clone());
+            }
+        }
+
+        let (mut result, exports) = if parsed_ok {
+            check_with_imports(&parsed.module, self.options.clone(), imported)
+        } else {
+            let result = CheckResult {
+                diagnostics: Vec::new(),
+                tool_registry: HashMap::new(),
+                rewrites: Rewrites::default(),
+                variant_fields: HashMap::new(),
+                type_map: None,
+            };
+            (result, ModuleExports::new())
+        };
+        result.diagnostics.extend(diagnostics);
+        result.diagnostics.sort_by_key(|d| d.span.start);
+
+        self.stack.pop();
+        self.modules.push(ProjectModule {
+            path: path.clone(),
+            module: parsed.module,
+            result,
+            exports,
+        });
+        let index = self.modules.len() - 1;
+        self.checked.insert(path, index);
+        if !parsed_ok {
+            self.unparsed.insert(index);
+        }
+        index
+    }
+}
+
+fn is_relative(spec: &str) -> bool {
+    spec.starts_with("./") || spec.starts_with("../")
+}
+
+/// The path of the module `spec` refers to from the module at `from`:
+/// joined onto `from`'s directory, with `.` and `..` segments folded and
+/// `.ag` appended when missing.
+fn resolve_path(from: &str, spec: &str) -> String {
+    let mut parts: Vec<&str> = if spec.starts_with('/') {
+        vec![""]
+    } else {
+        let mut dir: Vec<&str> = from.split('/').collect();
+        dir.pop();
+        dir
+    };
+    for segment in spec.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." if parts.last().is_some_and(|p| !p.is_empty() && *p != "..") => {
+                parts.pop();
+            }
+            _ => parts.push(segment),
+        }
+    }
+    let path = parts.join("/");
+    if path.ends_with(".ag") { path } else { format!("{path}.ag") }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MemoryLoader(HashMap<&'static str, &'static str>);
+
+    impl FileLoader for MemoryLoader {
+        fn load(&self, path: &str) -> Option<String> {
+            self.0.get(path).map(|s| s.to_string())
+        }
+    }
+
+    fn check_files(files: &[(&'static str, &'static str)]) -> ModuleGraph {
+        let loader = MemoryLoader(files.iter().copied().collect());
+        check_project(files[0].0, &loader, CheckerOptions::default()).expect("entry module loads")
+    }
+
+    fn messages(graph: &ModuleGraph, path: &str) -> Vec<String> {
+        graph.get(path).unwrap().result.diagnostics.iter().map(|d| d.message.clone()).collect()
+    }
+
+    #[test]
+    fn imported_functions_keep_their_types() {
+        let graph = check_files(&[
+            ("main.ag", "import { helper, shout as loud } from \"./lib/util\"\nlet n: int = helper(\"x\")\nlet s: str = loud(\"y\")"),
+            ("lib/util.ag", "pub fn helper(s: str) -> int { 1 }\nfn shout(s: str) -> str { s }\npub use { shout }"),
+        ]);
+        assert!(!graph.has_errors(), "got: {:?}", messages(&graph, "main.ag"));
+        let paths: Vec<_> = graph.modules.iter().map(|m| m.path.as_str()).collect();
+        assert_eq!(paths, ["lib/util.ag", "main.ag"]);
+
+        let graph = check_files(&[
+            ("main.ag", "import { helper } from \"./util.ag\"\nhelper(1)"),
+            ("util.ag", "pub fn helper(s: str) -> int { 1 }"),
+        ]);
+        assert_eq!(messages(&graph, "main.ag"), ["argument 1: expected `str`, found `int`"]);
+    }
+
+    #[test]
+    fn imported_consts_are_read_only_constants() {
+        let graph = check_files(&[
+            ("main.ag", "import { LIMIT } from \"./config\"\nconst DOUBLE = LIMIT * 2\nLIMIT = 3\nfn f() { if DOUBLE != 20 { } }"),
+            ("config.ag", "const LIMIT = 10\npub use { LIMIT }"),
+        ]);
+        assert_eq!(
+            messages(&graph, "main.ag"),
+            ["cannot assign to immutable binding `LIMIT`", "condition is always false"]
+        );
+    }
+
+    #[test]
+    fn imported_enums_keep_their_field_order() {
+        let graph = check_files(&[
+            ("main.ag", "import { Res } from \"./res\"\nfn f(r: Res) -> int { match r { Res::Err(c, m) => c, _ => 0 } }"),
+            ("res.ag", "pub enum Res { Ok(value: int), Err(code: int, msg: str) }"),
+        ]);
+        assert!(!graph.has_errors(), "got: {:?}", messages(&graph, "main.ag"));
+        let fields = &graph.get("main.ag").unwrap().result.variant_fields;
+        assert_eq!(fields[&("Res".to_string(), "Err".to_string())], ["code", "msg"]);
+    }
+
+    #[test]
+    fn imported_type_aliases_and_interfaces() {
+        let graph = check_files(&[
+            (
+                "main.ag",
+                "import { Id, Pair, Named, load } from \"./model\"\nlet i: Id = load(1)\nlet p: Pair<str> = { a: \"x\", b: \"y\" }\n\
+                 struct User { id: Id, name: str }\nimpl Named for User { fn name(self) -> str { self.name } }\n\
+                 fn show<T>(x: T) -> str where T: Named { x.name() }\nlet bad: Id = \"x\"",
+            ),
+            ("model.ag", "pub type Id = int\npub type Pair<T> = { a: T, b: T }\npub interface Named { fn name(self) -> str }\npub fn load(id: Id) -> Id { id }"),
+        ]);
+        assert_eq!(messages(&graph, "main.ag"), ["type mismatch: expected `int`, found `str`"]);
+        // Only `load` exists at runtime
+        let module = graph.get("main.ag").unwrap();
+        let Item::Import(imp) = &module.module.items[0] else { panic!("expected import") };
+        let erased: Vec<&str> = imp
+            .names
+            .iter()
+            .filter(|n| module.result.rewrites.type_imports.contains(&n.span))
+            .map(|n| n.name.as_str())
+            .collect();
+        assert_eq!(erased, ["Id", "Pair", "Named"]);
+    }
+
+    #[test]
+    fn imported_structs() {
+        let graph = check_files(&[
+            (
+                "main.ag",
+                "import { User, Config, find } from \"./users\"\nimport { Secret } from \"./users\"\n\
+                 fn greet(u: User) -> str { u.name }\nlet u: User = find(1)\nlet c: Config = { retries: 1 }\nlet n: int = u.name",
+            ),
+            (
+                "users.ag",
+                "pub struct User { id: int, name: str }\npub struct Config { retries: int = 3 }\nstruct Secret { key: str }\n\
+                 pub fn find(id: int) -> User { { id: id, name: \"x\" } }",
+            ),
+        ]);
+        assert_eq!(
+            messages(&graph, "main.ag"),
+            ["module `./users` has no public `Secret`", "type mismatch: expected `int`, found `str`"]
+        );
+        // `User` is erased; `Config` has a factory
+        let module = graph.get("main.ag").unwrap();
+        let Item::Import(imp) = &module.module.items[0] else { panic!("expected import") };
+        let erased: Vec<&str> = imp
+            .names
+            .iter()
+            .filter(|n| module.result.rewrites.type_imports.contains(&n.span))
+            .map(|n| n.name.as_str())
+            .collect();
+        assert_eq!(erased, ["User"]);
+    }
+
+    #[test]
+    fn unknown_imported_name() {
+        let graph = check_files(&[
+            ("main.ag", "import { helper, private } from \"./util\"\nhelper()"),
+            ("util.ag", "pub fn helper() {}\nfn private() {}"),
+        ]);
+        assert_eq!(messages(&graph, "main.ag"), ["module `./util` has no public `private`"]);
+    }
+
+    #[test]
+    fn unresolved_module_path() {
+        let graph = check_files(&[("src/main.ag", "import { helper } from \"../missing\"\nhelper()")]);
+        assert_eq!(messages(&graph, "src/main.ag"), ["cannot find module `../missing`"]);
+    }
+
+    #[test]
+    fn import_cycles_are_reported() {
+        let graph = check_files(&[
+            ("a.ag", "import { b } from \"./b\"\npub fn a() { b() }"),
+            ("b.ag", "import { a } from \"./a\"\npub fn b() { a() }"),
+        ]);
+        assert_eq!(messages(&graph, "b.ag"), ["import cycle: a.ag -> b.ag -> a.ag"]);
+        assert!(messages(&graph, "a.ag").is_empty());
+    }
+
+    #[test]
+    fn non_relative_imports_are_any() {
+        let graph = check_files(&[("main.ag", "import { readFile } from \"node:fs\"\nimport * as path from \"path\"\nreadFile(path.join(1))")]);
+        assert!(!graph.has_errors(), "got: {:?}", messages(&graph, "main.ag"));
+    }
+
+    #[test]
+    fn unparsable_dependency() {
+        let graph = check_files(&[
+            ("main.ag", "import { helper } from \"./util\"\nhelper()"),
+            ("util.ag", "pub fn helper( {}"),
+        ]);
+        assert!(messages(&graph, "main.ag").is_empty());
+        assert!(graph.get("util.ag").unwrap().result.has_errors());
+    }
+
+    #[test]
+    fn missing_entry() {
+        let loader = MemoryLoader(HashMap::new());
+        assert!(check_project("main.ag", &loader, CheckerOptions::default()).is_none());
+    }
+
+    #[test]
+    fn resolves_paths() {
+        assert_eq!(resolve_path("main.ag", "./util"), "util.ag");
+        assert_eq!(resolve_path("src/a/main.ag", "../b/./c.ag"), "src/b/c.ag");
+        assert_eq!(resolve_path("main.ag", "../shared/x"), "../shared/x.ag");
+        assert_eq!(resolve_path("", "/abs/main.ag"), "/abs/main.ag");
+    }
+}
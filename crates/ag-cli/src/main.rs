@@ -3,13 +3,18 @@ use std::fs;
 use std::path::Path;
 use std::process;
 
+/// File name of the runtime helpers that `build --runtime` writes next to
+/// the output.
+const RUNTIME_FILE: &str = "runtime.js";
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
     if args.len() < 2 {
         eprintln!("Usage: asc <command> <file.ag> [options]");
         eprintln!("Commands:");
-        eprintln!("  build <file.ag> [-o <output>]  Compile to JavaScript");
+        eprintln!("  build <file.ag> [-o <output>] [--runtime]");
+        eprintln!("                                 Compile to JavaScript");
         eprintln!("  check <file.ag>                Type check only");
         process::exit(1);
     }
@@ -27,7 +32,7 @@ fn main() {
 
 fn cmd_build(args: &[String]) {
     if args.is_empty() {
-        eprintln!("Usage: asc build <file.ag> [-o <output>]");
+        eprintln!("Usage: asc build <file.ag> [-o <output>] [--runtime]");
         process::exit(1);
     }
 
@@ -45,12 +50,13 @@ fn cmd_build(args: &[String]) {
         }
     };
 
-    // Lex + Parse
-    let parsed = ag_parser::parse(&source);
-    if !parsed.diagnostics.is_empty() {
-        for diag in &parsed.diagnostics {
-            print_diagnostic(input_path, &source, diag);
-        }
+    // Lex + Parse, keeping `///` comments for the JSDoc of exported
+    // declarations
+    let parsed = ag_parser::parse_with_options(&source, ag_parser::ParseOptions { preserve_comments: true });
+    for diag in &parsed.diagnostics {
+        print_diagnostic(input_path, &source, diag);
+    }
+    if parsed.has_errors() {
         process::exit(1);
     }
 
@@ -65,20 +71,47 @@ fn cmd_build(args: &[String]) {
 
     // Type check
     let checked = ag_checker::check(&module);
-    if !checked.diagnostics.is_empty() {
-        for diag in &checked.diagnostics {
-            print_diagnostic(input_path, &source, diag);
-        }
+    for diag in &checked.diagnostics {
+        print_diagnostic(input_path, &source, diag);
+    }
+    if checked.has_errors() {
         process::exit(1);
     }
 
-    // Codegen
-    let js = ag_codegen::codegen_with_tools(&module, checked.tool_registry);
+    // Codegen; with `--runtime`, the shared helpers are imported from a
+    // runtime module written next to the output
+    let runtime = args.iter().any(|a| a == "--runtime");
+    let options = ag_codegen::CodegenOptions {
+        runtime_module: runtime.then(|| format!("./{RUNTIME_FILE}")),
+        ..Default::default()
+    };
+    let translator = ag_codegen::Translator::builder()
+        .with_builtin_handlers()
+        .with_options(options)
+        .with_tool_registry(checked.tool_registry)
+        .with_rewrites(checked.rewrites)
+        .with_variant_fields(checked.variant_fields)
+        .build();
+    let js = match translator.codegen(&module) {
+        Ok(js) => js,
+        Err(e) => {
+            let (line, col) = offset_to_line_col(&source, e.span.start as usize);
+            eprintln!("{}:{}:{}: error: {}", input_path, line, col, e.message);
+            process::exit(1);
+        }
+    };
 
     if let Err(e) = fs::write(&output_path, &js) {
         eprintln!("error: cannot write '{}': {}", output_path, e);
         process::exit(1);
     }
+    if runtime {
+        let runtime_path = Path::new(&output_path).with_file_name(RUNTIME_FILE);
+        if let Err(e) = fs::write(&runtime_path, ag_codegen::RUNTIME_JS) {
+            eprintln!("error: cannot write '{}': {}", runtime_path.display(), e);
+            process::exit(1);
+        }
+    }
 
     eprintln!("compiled {} -> {}", input_path, output_path);
 }
@@ -99,10 +132,10 @@ fn cmd_check(args: &[String]) {
     };
 
     let parsed = ag_parser::parse(&source);
-    if !parsed.diagnostics.is_empty() {
-        for diag in &parsed.diagnostics {
-            print_diagnostic(input_path, &source, diag);
-        }
+    for diag in &parsed.diagnostics {
+        print_diagnostic(input_path, &source, diag);
+    }
+    if parsed.has_errors() {
         process::exit(1);
     }
 
@@ -116,10 +149,10 @@ fn cmd_check(args: &[String]) {
     }
 
     let checked = ag_checker::check(&module);
-    if !checked.diagnostics.is_empty() {
-        for diag in &checked.diagnostics {
-            print_diagnostic(input_path, &source, diag);
-        }
+    for diag in &checked.diagnostics {
+        print_diagnostic(input_path, &source, diag);
+    }
+    if checked.has_errors() {
         process::exit(1);
     }
 
@@ -140,6 +173,7 @@ fn parse_output_flag(args: &[String]) -> Option<String> {
 fn resolve_std_imports(module: &mut ag_ast::Module) -> Result<(), Vec<String>> {
     let mut errors = Vec::new();
     let mut injected_items = Vec::new();
+    let mut injected_names = std::collections::HashSet::new();
 
     let std_imports: Vec<(usize, ag_ast::Import)> = module
         .items
@@ -147,7 +181,7 @@ fn resolve_std_imports(module: &mut ag_ast::Module) -> Result<(), Vec<String>> {
         .enumerate()
         .filter_map(|(i, item)| {
             if let ag_ast::Item::Import(imp) = item {
-                if imp.path.starts_with("std:") {
+                if imp.path.as_static().is_some_and(|path| path.starts_with("std:")) {
                     return Some((i, imp.clone()));
                 }
             }
@@ -156,12 +190,14 @@ fn resolve_std_imports(module: &mut ag_ast::Module) -> Result<(), Vec<String>> {
         .collect();
 
     for (_, imp) in &std_imports {
-        let std_path = &imp.path[4..]; // strip "std:" prefix
+        let Some(std_path) = imp.path.as_static().and_then(|path| path.strip_prefix("std:")) else {
+            continue;
+        };
         match ag_stdlib::resolve_std_module(std_path) {
             Some(source) => {
-                let parsed = ag_parser::parse(source);
-                if !parsed.diagnostics.is_empty() {
-                    for diag in &parsed.diagnostics {
+                let mut parsed = ag_parser::parse(source);
+                if parsed.has_errors() {
+                    for diag in parsed.diagnostics.iter().filter(|d| d.is_error()) {
                         errors.push(format!(
                             "error in stdlib module `{}`: {}",
                             std_path, diag.message
@@ -169,36 +205,35 @@ fn resolve_std_imports(module: &mut ag_ast::Module) -> Result<(), Vec<String>> {
                     }
                     continue;
                 }
+                // Stdlib modules share declarations by importing each other
+                if let Err(nested) = resolve_std_imports(&mut parsed.module) {
+                    for err in nested {
+                        errors.push(format!("error in stdlib module `{}`: {}", std_path, err));
+                    }
+                    continue;
+                }
 
-                // If selective import, only inject requested names
+                // If selective import, only inject requested names, plus
+                // the types their signatures mention so that those resolve.
                 if !imp.names.is_empty() {
-                    let requested: std::collections::HashSet<&str> =
-                        imp.names.iter().map(|n| n.name.as_str()).collect();
-                    for item in parsed.module.items {
-                        let name = match &item {
-                            ag_ast::Item::ExternFnDecl(ef) => Some(ef.name.as_str()),
-                            ag_ast::Item::ExternStructDecl(es) => Some(es.name.as_str()),
-                            ag_ast::Item::ExternTypeDecl(et) => Some(et.name.as_str()),
-                            _ => None,
-                        };
-                        if let Some(n) = name {
-                            if requested.contains(n) {
-                                injected_items.push(item);
-                            }
+                    let mut pending: Vec<String> =
+                        imp.names.iter().map(|n| n.name.clone()).collect();
+                    while let Some(name) = pending.pop() {
+                        if injected_names.contains(&name) {
+                            continue;
+                        }
+                        let item = parsed.module.items.iter().find(|item| {
+                            std_item_name(item).is_some_and(|n| n == name)
+                        });
+                        if let Some(item) = item {
+                            referenced_type_names(item, &mut pending);
+                            injected_items.push(item.clone());
+                            injected_names.insert(name);
                         }
                     }
                     // Check for unknown imports
-                    let available: std::collections::HashSet<String> = injected_items
-                        .iter()
-                        .filter_map(|item| match item {
-                            ag_ast::Item::ExternFnDecl(ef) => Some(ef.name.clone()),
-                            ag_ast::Item::ExternStructDecl(es) => Some(es.name.clone()),
-                            ag_ast::Item::ExternTypeDecl(et) => Some(et.name.clone()),
-                            _ => None,
-                        })
-                        .collect();
                     for name in &imp.names {
-                        if !available.contains(&name.name) {
+                        if !injected_names.contains(&name.name) {
                             errors.push(format!(
                                 "`{}` is not exported by module `std:{}`",
                                 name.name, std_path
@@ -208,13 +243,10 @@ fn resolve_std_imports(module: &mut ag_ast::Module) -> Result<(), Vec<String>> {
                 } else {
                     // Import all declarations
                     for item in parsed.module.items {
-                        match &item {
-                            ag_ast::Item::ExternFnDecl(_)
-                            | ag_ast::Item::ExternStructDecl(_)
-                            | ag_ast::Item::ExternTypeDecl(_) => {
-                                injected_items.push(item);
-                            }
-                            _ => {}
+                        if let Some(name) = std_item_name(&item)
+                            && injected_names.insert(name.to_string())
+                        {
+                            injected_items.push(item);
                         }
                     }
                 }
@@ -247,9 +279,73 @@ fn resolve_std_imports(module: &mut ag_ast::Module) -> Result<(), Vec<String>> {
     Ok(())
 }
 
+/// Name of a stdlib module item that can be imported.
+fn std_item_name(item: &ag_ast::Item) -> Option<&str> {
+    match item {
+        ag_ast::Item::StructDecl(s) => Some(&s.name),
+        ag_ast::Item::ExternFnDecl(ef) => Some(&ef.name),
+        ag_ast::Item::ExternStructDecl(es) => Some(&es.name),
+        ag_ast::Item::ExternTypeDecl(et) => Some(&et.name),
+        _ => None,
+    }
+}
+
+/// Collect the named types a stdlib declaration's signature refers to.
+fn referenced_type_names(item: &ag_ast::Item, out: &mut Vec<String>) {
+    fn walk(ty: &ag_ast::TypeExpr, out: &mut Vec<String>) {
+        use ag_ast::TypeExpr;
+        match ty {
+            TypeExpr::Named(name, _) => out.push(name.clone()),
+            TypeExpr::Array(inner, _) | TypeExpr::Nullable(inner, _) | TypeExpr::Promise(inner, _) => {
+                walk(inner, out)
+            }
+            TypeExpr::Map(a, b, _) | TypeExpr::Union(a, b, _) => {
+                walk(a, out);
+                walk(b, out);
+            }
+            TypeExpr::Function(ft) => {
+                ft.params.iter().for_each(|p| walk(p, out));
+                walk(&ft.ret, out);
+            }
+            TypeExpr::Object(ot) => ot.fields.iter().for_each(|f| walk(&f.ty, out)),
+            TypeExpr::Generic(name, args, _) => {
+                out.push(name.clone());
+                args.iter().for_each(|a| walk(a, out));
+            }
+        }
+    }
+    let signature = |params: &[ag_ast::Param], ret: &Option<ag_ast::TypeExpr>, out: &mut Vec<String>| {
+        params.iter().filter_map(|p| p.ty.as_ref()).for_each(|t| walk(t, out));
+        if let Some(t) = ret {
+            walk(t, out);
+        }
+    };
+    match item {
+        ag_ast::Item::StructDecl(s) => s.fields.iter().for_each(|f| walk(&f.ty, out)),
+        ag_ast::Item::ExternFnDecl(ef) => signature(&ef.params, &ef.return_type, out),
+        ag_ast::Item::ExternStructDecl(es) => {
+            es.fields.iter().for_each(|f| walk(&f.ty, out));
+            for m in &es.methods {
+                signature(&m.params, &m.return_type, out);
+            }
+        }
+        _ => {}
+    }
+}
+
 fn print_diagnostic(file: &str, source: &str, diag: &ag_ast::Diagnostic) {
     let (line, col) = offset_to_line_col(source, diag.span.start as usize);
-    eprintln!("{}:{}:{}: error: {}", file, line, col, diag.message);
+    let severity = match diag.severity {
+        ag_ast::Severity::Error => "error",
+        ag_ast::Severity::Warning => "warning",
+        ag_ast::Severity::Note => "note",
+    };
+    let code = diag.code.map(|code| format!(" [{code}]")).unwrap_or_default();
+    eprintln!("{}:{}:{}: {}: {}{}", file, line, col, severity, diag.message, code);
+    for label in &diag.labels {
+        let (line, col) = offset_to_line_col(source, label.span.start as usize);
+        eprintln!("{}:{}:{}: note: {}", file, line, col, label.message);
+    }
 }
 
 fn offset_to_line_col(source: &str, offset: usize) -> (usize, usize) {
@@ -81,6 +81,18 @@ pub fn greet(name: str) -> str {
     assert!(js.contains("export function greet(name)"));
 }
 
+#[test]
+fn build_doc_comments() {
+    let (js, _, code) = build_ag(r#"
+/// Greets someone.
+pub fn greet(name: str) -> str {
+    "hi"
+}
+"#);
+    assert_eq!(code, 0);
+    assert!(js.contains("/** Greets someone. */ export function greet(name)"), "got: {}", js);
+}
+
 #[test]
 fn build_async_function() {
     let (js, _, code) = build_ag(r#"
@@ -130,8 +142,9 @@ fn describe(x: num) -> str {
 }
 "#);
     assert_eq!(code, 0);
-    // match compiles to if-else chain
-    assert!(js.contains("if"));
+    // a match over literals compiles to a switch
+    assert!(js.contains("switch"));
+    assert!(js.contains("case 0:"));
 }
 
 #[test]
@@ -236,6 +249,28 @@ let x: num = "bad"
     assert!(stderr.contains("error:"));
 }
 
+#[test]
+fn warnings_do_not_fail_check() {
+    let (stderr, code) = check_ag(r#"
+struct User { name: str }
+let u: User = { name: "Bo", nmae: "typo" }
+"#);
+    assert_eq!(code, 0, "stderr: {}", stderr);
+    assert!(stderr.contains("test.ag:3:"));
+    assert!(stderr.contains("warning: struct `User` has no field `nmae`"));
+}
+
+#[test]
+fn error_shows_secondary_label() {
+    let (stderr, code) = check_ag(r#"
+struct User { name: str, age: int }
+let u: User = { name: "Bo" }
+"#);
+    assert_ne!(code, 0);
+    assert!(stderr.contains("error: missing field `age` in `User` literal"));
+    assert!(stderr.contains("test.ag:2:1: note: struct `User` declared here"));
+}
+
 // ── CLI usage tests ──
 
 #[test]
@@ -617,6 +652,60 @@ fn main() {
     assert!(js.contains(r#"@agentscript/stdlib/http/client"#));
 }
 
+#[test]
+fn build_http_client_with_web_fetch() {
+    let (js, stderr, code) = build_ag(r#"
+import { fetch, Response } from "std:web/fetch"
+import { get } from "std:http/client"
+
+async fn status(url: str) -> int {
+    let a: Response = await fetch(url)
+    let b: Response = await get(url)
+    a.status + b.status
+}
+"#);
+    assert_eq!(code, 0, "{stderr}");
+    assert!(js.contains(r#"@agentscript/stdlib/http/client"#));
+    assert!(!js.contains(r#"from "std:"#));
+}
+
+#[test]
+fn build_with_runtime_writes_runtime_module() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("test.ag");
+    let output = dir.path().join("out").join("test.js");
+    std::fs::create_dir(output.parent().unwrap()).unwrap();
+    std::fs::write(&input, "enum Shape { Circle(r: num) }\nlet c = Shape::Circle(1.0)\n").unwrap();
+
+    let result = asc_binary()
+        .args(["build", input.to_str().unwrap(), "-o", output.to_str().unwrap(), "--runtime"])
+        .output()
+        .unwrap();
+    assert_eq!(result.status.code(), Some(0), "{}", String::from_utf8_lossy(&result.stderr));
+    let js = std::fs::read_to_string(&output).unwrap();
+    assert!(js.contains(r#"import * as __ag from "./runtime.js""#), "{js}");
+    let runtime = std::fs::read_to_string(dir.path().join("out").join("runtime.js")).unwrap();
+    assert!(runtime.contains("export function variant("));
+
+    // A user binding named `__ag` would shadow the import
+    std::fs::write(&input, "enum Shape { Circle(r: num) }\nlet __ag = Shape::Circle(1.0)\n").unwrap();
+    let result = asc_binary()
+        .args(["build", input.to_str().unwrap(), "-o", output.to_str().unwrap(), "--runtime"])
+        .output()
+        .unwrap();
+    assert_eq!(result.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("test.ag:2:1: error: `__ag` clashes with the runtime helpers import"), "{stderr}");
+}
+
+#[test]
+fn build_reports_lexer_warnings_without_failing() {
+    let (js, stderr, code) = build_ag("let s = \"\\uD800\"\n");
+    assert_eq!(code, 0, "{stderr}");
+    assert!(stderr.contains("warning: `\\uD800` is not a valid Unicode code point"), "{stderr}");
+    assert!(js.contains("const s ="), "{js}");
+}
+
 #[test]
 fn build_fn_expression() {
     let (js, _, code) = build_ag(r#"
@@ -0,0 +1,351 @@
+//! Functions generated for `@derive(...)` annotations. Struct and enum
+//! values are plain objects, so derived traits compile to top-level
+//! functions named like impl methods (`Point_eq`, `Point_toString`).
+
+use std::collections::{HashMap, HashSet};
+
+use ag_ast::{EnumDecl, Field, ImplBlock, Item, Module, StructDecl, TypeExpr};
+use ag_dsl_core::swc_helpers::{binding_ident, bool_lit, expr_or_spread, ident, str_lit};
+use swc_common::{SyntaxContext, DUMMY_SP};
+use swc_ecma_ast as swc;
+
+/// Which of the module's types derive which traits, for emitting the
+/// functions of one declaration that call those of another.
+pub(crate) struct Derives<'a> {
+    aliases: HashMap<&'a str, &'a TypeExpr>,
+    eq: HashSet<&'a str>,
+    debug: HashSet<&'a str>,
+}
+
+impl<'a> Derives<'a> {
+    pub(crate) fn new(module: &'a Module) -> Self {
+        let mut derives = Self {
+            aliases: HashMap::new(),
+            eq: HashSet::new(),
+            debug: HashSet::new(),
+        };
+        for item in &module.items {
+            let (name, names) = match item {
+                Item::StructDecl(s) => (&s.name, &s.derives),
+                Item::EnumDecl(e) => (&e.name, &e.derives),
+                Item::TypeAlias(t) => {
+                    derives.aliases.insert(&t.name, &t.ty);
+                    continue;
+                }
+                _ => continue,
+            };
+            for derive in names {
+                match derive.name.as_str() {
+                    "Eq" => derives.eq.insert(name),
+                    "Debug" => derives.debug.insert(name),
+                    _ => false,
+                };
+            }
+        }
+        derives
+    }
+
+    /// The functions derived for a struct or enum declaration, exported
+    /// with a `pub` one.
+    pub(crate) fn items_for(&self, item: &Item) -> Vec<swc::ModuleItem> {
+        let (decls, is_pub, span) = match item {
+            Item::StructDecl(s) => (self.struct_fns(s), s.is_pub, s.span),
+            Item::EnumDecl(e) => (self.enum_fns(e), e.is_pub, e.span),
+            _ => return Vec::new(),
+        };
+        let mut items = Vec::new();
+        for decl in decls {
+            super::push_decl(&mut items, swc::Decl::Fn(decl), is_pub, span);
+        }
+        items
+    }
+
+    fn struct_fns(&self, s: &StructDecl) -> Vec<swc::FnDecl> {
+        let mut fns = Vec::new();
+        if self.eq.contains(s.name.as_str()) {
+            // function Point_eq(a, b) { return a.x === b.x && a.y === b.y; }
+            let body = self.fields_eq(&s.fields, "a", "b");
+            fns.push(fn_decl(&ImplBlock::method_fn_name(&s.name, "eq"), &["a", "b"], body));
+        }
+        if self.debug.contains(s.name.as_str()) {
+            // function Point_toString(self) { return "Point { x: " + self.x + ", y: " + self.y + " }"; }
+            let body = if s.fields.is_empty() {
+                str_lit(&format!("{} {{}}", s.name))
+            } else {
+                concat(vec![
+                    str_lit(&format!("{} {{ ", s.name)),
+                    self.fields_debug(&s.fields),
+                    str_lit(" }"),
+                ])
+            };
+            fns.push(fn_decl(&ImplBlock::method_fn_name(&s.name, "toString"), &["self"], body));
+        }
+        fns
+    }
+
+    fn enum_fns(&self, e: &EnumDecl) -> Vec<swc::FnDecl> {
+        let mut fns = Vec::new();
+        if self.eq.contains(e.name.as_str()) {
+            // a.tag === b.tag && (a.tag !== "Variant" || <fields equal>) && ...
+            let mut terms = vec![strict_eq(member(var("a"), "tag"), member(var("b"), "tag"))];
+            for v in e.variants.iter().filter(|v| !v.fields.is_empty()) {
+                let other_variant = bin(swc::BinaryOp::NotEqEq, member(var("a"), "tag"), str_lit(&v.name));
+                terms.push(bin(swc::BinaryOp::LogicalOr, other_variant, self.fields_eq(&v.fields, "a", "b")));
+            }
+            fns.push(fn_decl(&ImplBlock::method_fn_name(&e.name, "eq"), &["a", "b"], and_all(terms)));
+        }
+        if self.debug.contains(e.name.as_str()) {
+            // self.tag === "Variant" ? "Enum::Variant(x: " + self.x + ")" : ... : "Enum::" + self.tag
+            let mut body = concat(vec![str_lit(&format!("{}::", e.name)), member(var("self"), "tag")]);
+            for v in e.variants.iter().rev().filter(|v| !v.fields.is_empty()) {
+                let text = concat(vec![
+                    str_lit(&format!("{}::{}(", e.name, v.name)),
+                    self.fields_debug(&v.fields),
+                    str_lit(")"),
+                ]);
+                body = swc::Expr::Cond(swc::CondExpr {
+                    span: DUMMY_SP,
+                    test: Box::new(strict_eq(member(var("self"), "tag"), str_lit(&v.name))),
+                    cons: Box::new(text),
+                    alt: Box::new(body),
+                });
+            }
+            fns.push(fn_decl(&ImplBlock::method_fn_name(&e.name, "toString"), &["self"], body));
+        }
+        fns
+    }
+
+    fn fields_eq(&self, fields: &[Field], a: &str, b: &str) -> swc::Expr {
+        let terms = fields
+            .iter()
+            .map(|f| self.eq_expr(&f.ty, member(var(a), &f.name), member(var(b), &f.name), 0))
+            .collect();
+        and_all(terms)
+    }
+
+    /// `x: <x>, y: <y>`, reading the fields of `self`.
+    fn fields_debug(&self, fields: &[Field]) -> swc::Expr {
+        let mut parts = Vec::new();
+        for (i, f) in fields.iter().enumerate() {
+            let sep = if i == 0 { "" } else { ", " };
+            parts.push(str_lit(&format!("{sep}{}: ", f.name)));
+            parts.push(self.debug_expr(&f.ty, member(var("self"), &f.name), 0));
+        }
+        concat(parts)
+    }
+
+    fn resolve<'t>(&'t self, ty: &'t TypeExpr) -> &'t TypeExpr {
+        match ty {
+            TypeExpr::Named(name, _) => match self.aliases.get(name.as_str()) {
+                Some(aliased) => self.resolve(aliased),
+                None => ty,
+            },
+            _ => ty,
+        }
+    }
+
+    /// Whether values of type `ty` are equal exactly when `===` says so.
+    fn is_strict_eq(&self, ty: &TypeExpr) -> bool {
+        match self.resolve(ty) {
+            TypeExpr::Named(name, _) => !self.eq.contains(name.as_str()),
+            TypeExpr::Nullable(inner, _) => self.is_strict_eq(inner),
+            _ => false,
+        }
+    }
+
+    /// Compare `l` and `r`, both of type `ty`. `depth` names the callback
+    /// parameters of nested array comparisons apart.
+    fn eq_expr(&self, ty: &TypeExpr, l: swc::Expr, r: swc::Expr, depth: usize) -> swc::Expr {
+        if self.is_strict_eq(ty) {
+            return strict_eq(l, r);
+        }
+        match self.resolve(ty) {
+            TypeExpr::Named(name, _) => call(var(&ImplBlock::method_fn_name(name, "eq")), vec![l, r]),
+            // l.length === r.length && l.every((x, i) => <x equals r[i]>)
+            TypeExpr::Array(inner, _) => {
+                let (x, i) = (format!("x{depth}"), format!("i{depth}"));
+                let elem_eq = self.eq_expr(inner, var(&x), index(r.clone(), var(&i)), depth + 1);
+                let every = call(member(l.clone(), "every"), vec![arrow(&[&x, &i], elem_eq)]);
+                and_all(vec![strict_eq(member(l, "length"), member(r, "length")), every])
+            }
+            // l === r || (l != null && r != null && <l equals r>)
+            TypeExpr::Nullable(inner, _) => {
+                let both = and_all(vec![
+                    not_nil(l.clone()),
+                    not_nil(r.clone()),
+                    self.eq_expr(inner, l.clone(), r.clone(), depth),
+                ]);
+                bin(swc::BinaryOp::LogicalOr, strict_eq(l, r), paren(both))
+            }
+            _ => strict_eq(l, r),
+        }
+    }
+
+    /// Text for `value` of type `ty`: strings quoted, `nil` as `nil`, and
+    /// types deriving `Debug` through their `toString`.
+    fn debug_expr(&self, ty: &TypeExpr, value: swc::Expr, depth: usize) -> swc::Expr {
+        match self.resolve(ty) {
+            TypeExpr::Named(name, _) => match name.as_str() {
+                "int" | "num" | "bool" => value,
+                "str" => json_stringify(value),
+                _ if self.debug.contains(name.as_str()) => {
+                    call(var(&ImplBlock::method_fn_name(name, "toString")), vec![value])
+                }
+                _ => json_stringify(value),
+            },
+            // "[" + value.map((x) => <x>).join(", ") + "]"
+            TypeExpr::Array(inner, _) => {
+                let x = format!("x{depth}");
+                let mapped = call(
+                    member(value, "map"),
+                    vec![arrow(&[&x], self.debug_expr(inner, var(&x), depth + 1))],
+                );
+                let joined = call(member(mapped, "join"), vec![str_lit(", ")]);
+                concat(vec![str_lit("["), joined, str_lit("]")])
+            }
+            // (value == null ? "nil" : <value>)
+            TypeExpr::Nullable(inner, _) => paren(swc::Expr::Cond(swc::CondExpr {
+                span: DUMMY_SP,
+                test: Box::new(bin(swc::BinaryOp::EqEq, value.clone(), null())),
+                cons: Box::new(str_lit("nil")),
+                alt: Box::new(self.debug_expr(inner, value, depth)),
+            })),
+            _ => json_stringify(value),
+        }
+    }
+}
+
+// ── SWC builders ───────────────────────────────────────────
+
+pub(crate) fn fn_decl(name: &str, params: &[&str], ret: swc::Expr) -> swc::FnDecl {
+    swc::FnDecl {
+        ident: ident(name),
+        declare: false,
+        function: Box::new(swc::Function {
+            params: params
+                .iter()
+                .map(|p| swc::Param {
+                    span: DUMMY_SP,
+                    decorators: Vec::new(),
+                    pat: swc::Pat::Ident(binding_ident(p)),
+                })
+                .collect(),
+            decorators: Vec::new(),
+            span: DUMMY_SP,
+            ctxt: SyntaxContext::empty(),
+            body: Some(swc::BlockStmt {
+                span: DUMMY_SP,
+                ctxt: SyntaxContext::empty(),
+                stmts: vec![swc::Stmt::Return(swc::ReturnStmt {
+                    span: DUMMY_SP,
+                    arg: Some(Box::new(ret)),
+                })],
+            }),
+            is_generator: false,
+            is_async: false,
+            type_params: None,
+            return_type: None,
+        }),
+    }
+}
+
+fn arrow(params: &[&str], body: swc::Expr) -> swc::Expr {
+    swc::Expr::Arrow(swc::ArrowExpr {
+        span: DUMMY_SP,
+        ctxt: SyntaxContext::empty(),
+        params: params.iter().map(|p| swc::Pat::Ident(binding_ident(p))).collect(),
+        body: Box::new(swc::BlockStmtOrExpr::Expr(Box::new(body))),
+        is_async: false,
+        is_generator: false,
+        type_params: None,
+        return_type: None,
+    })
+}
+
+pub(crate) fn var(name: &str) -> swc::Expr {
+    swc::Expr::Ident(ident(name))
+}
+
+fn null() -> swc::Expr {
+    swc::Expr::Lit(swc::Lit::Null(swc::Null { span: DUMMY_SP }))
+}
+
+pub(crate) fn member(obj: swc::Expr, field: &str) -> swc::Expr {
+    swc::Expr::Member(swc::MemberExpr {
+        span: DUMMY_SP,
+        obj: Box::new(obj),
+        prop: swc::MemberProp::Ident(swc::IdentName {
+            span: DUMMY_SP,
+            sym: field.into(),
+        }),
+    })
+}
+
+fn index(obj: swc::Expr, idx: swc::Expr) -> swc::Expr {
+    swc::Expr::Member(swc::MemberExpr {
+        span: DUMMY_SP,
+        obj: Box::new(obj),
+        prop: swc::MemberProp::Computed(swc::ComputedPropName {
+            span: DUMMY_SP,
+            expr: Box::new(idx),
+        }),
+    })
+}
+
+pub(crate) fn call(callee: swc::Expr, args: Vec<swc::Expr>) -> swc::Expr {
+    swc::Expr::Call(swc::CallExpr {
+        span: DUMMY_SP,
+        ctxt: SyntaxContext::empty(),
+        callee: swc::Callee::Expr(Box::new(callee)),
+        args: args.into_iter().map(expr_or_spread).collect(),
+        type_args: None,
+    })
+}
+
+fn json_stringify(value: swc::Expr) -> swc::Expr {
+    call(member(var("JSON"), "stringify"), vec![value])
+}
+
+fn bin(op: swc::BinaryOp, left: swc::Expr, right: swc::Expr) -> swc::Expr {
+    swc::Expr::Bin(swc::BinExpr {
+        span: DUMMY_SP,
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    })
+}
+
+fn paren(expr: swc::Expr) -> swc::Expr {
+    swc::Expr::Paren(swc::ParenExpr {
+        span: DUMMY_SP,
+        expr: Box::new(expr),
+    })
+}
+
+fn strict_eq(left: swc::Expr, right: swc::Expr) -> swc::Expr {
+    bin(swc::BinaryOp::EqEqEq, left, right)
+}
+
+fn not_nil(value: swc::Expr) -> swc::Expr {
+    bin(swc::BinaryOp::NotEq, value, null())
+}
+
+/// `a && b && ...`, or `true` for no terms. `||` terms are parenthesized.
+fn and_all(terms: Vec<swc::Expr>) -> swc::Expr {
+    terms
+        .into_iter()
+        .map(|term| match term {
+            swc::Expr::Bin(swc::BinExpr { op: swc::BinaryOp::LogicalOr, .. }) => paren(term),
+            _ => term,
+        })
+        .reduce(|acc, term| bin(swc::BinaryOp::LogicalAnd, acc, term))
+        .unwrap_or_else(|| bool_lit(true))
+}
+
+/// `a + b + ...` over string parts.
+fn concat(parts: Vec<swc::Expr>) -> swc::Expr {
+    parts
+        .into_iter()
+        .reduce(|acc, part| bin(swc::BinaryOp::Add, acc, part))
+        .unwrap_or_else(|| str_lit(""))
+}
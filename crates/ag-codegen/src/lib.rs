@@ -1,12 +1,20 @@
+mod derive;
+mod split;
+mod tail_call;
 mod tool_schema;
+mod ts_comments;
+mod walk;
 
 use std::any::Any;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 
 use ag_ast::*;
-use ag_dsl_core::swc_helpers::{ident, binding_ident, expr_or_spread};
+use ag_dsl_core::swc_helpers::{bool_lit, ident, binding_ident, expr_or_spread, make_prop, str_lit};
+use swc_common::comments::{Comment as SwcComment, CommentKind as SwcCommentKind, Comments, SingleThreadedComments};
 use swc_common::sync::Lrc;
-use swc_common::{SourceMap, SyntaxContext, DUMMY_SP};
+use swc_common::source_map::DefaultSourceMapGenConfig;
+use swc_common::{BytePos, FileName, LineCol, SourceMap, SyntaxContext, DUMMY_SP};
 use swc_ecma_ast as swc;
 use swc_ecma_codegen::text_writer::JsWriter;
 use swc_ecma_codegen::Emitter;
@@ -15,19 +23,275 @@ use swc_ecma_codegen::Emitter;
 
 pub use ag_dsl_core::DslHandler;
 
+/// The shared runtime helpers, to be emitted as the module that the
+/// `runtime_module` option points at.
+pub const RUNTIME_JS: &str = include_str!("runtime.js");
+
+/// The name modules import the runtime helpers as.
+const RUNTIME_NS: &str = "__ag";
+
 #[derive(Debug, Clone)]
 pub struct CodegenError {
     pub message: String,
     pub span: Span,
 }
 
+/// User-facing switches that change the emitted JavaScript.
+#[derive(Debug, Clone, Default)]
+pub struct CodegenOptions {
+    /// Emit `nil` as `undefined` rather than `null`.
+    pub nil_to_undefined: bool,
+    /// Compile self-recursive tail calls to loops, so that deep recursion
+    /// does not overflow the stack.
+    pub optimize_tail_calls: bool,
+    /// Emit the declared types of variables and function signatures as
+    /// JSDoc comments (`/** @type {string} */`), for `// @ts-check`.
+    pub ts_comments: bool,
+    /// Have [`Translator::codegen_split`] emit each `pub fn` to a file of
+    /// its own, when the module's functions are independent.
+    pub split_output: bool,
+    /// Point relative imports of `.ag` modules at the files they compile
+    /// to, for imports and `@js` externs alike.
+    pub import_rewrite: Option<ImportRewrite>,
+    /// Import path of the shared runtime helpers ([`RUNTIME_JS`]). When
+    /// set, enum constructors, variant tests, range patterns and `?` checks
+    /// call its helpers rather than being emitted inline, and a module
+    /// that uses any imports them as `__ag`.
+    pub runtime_module: Option<String>,
+    /// How module code that awaits is emitted. `Allow` and `Error` emit
+    /// it as-is, for ES modules; `WrapInMain` moves it into a function so
+    /// that CommonJS targets can run it too.
+    pub top_level_await: TopLevelAwait,
+}
+
+/// How relative import paths are rewritten to the emitted files:
+/// `"./util.ag"` → `"./util.js"`. Package and builtin specifiers (`"zod"`,
+/// `"node:fs"`) are never rewritten.
+#[derive(Debug, Clone)]
+pub struct ImportRewrite {
+    /// Extension of the emitted files, without the dot: `"js"` or `"mjs"`.
+    pub extension: String,
+    /// Also add the extension to relative paths without one: `"./util"`.
+    pub extensionless: bool,
+}
+
+impl ImportRewrite {
+    /// `path` as it should be imported from the emitted JavaScript.
+    pub fn rewrite(&self, path: &str) -> String {
+        if !(path.starts_with("./") || path.starts_with("../")) {
+            return path.to_string();
+        }
+        if let Some(stem) = path.strip_suffix(".ag") {
+            return format!("{stem}.{}", self.extension);
+        }
+        let file = path.rsplit('/').next().unwrap_or(path);
+        if self.extensionless && !file.is_empty() && !file.contains('.') {
+            return format!("{path}.{}", self.extension);
+        }
+        path.to_string()
+    }
+}
+
+/// Checker-derived information consulted by the `translate_*` functions.
+#[derive(Default)]
+struct TranslateCtx {
+    options: CodegenOptions,
+    /// Constructs that are emitted differently from the source, keyed by
+    /// span (e.g. `s.len` → `s.length`).
+    rewrites: Rewrites,
+    /// Result variable of each enclosing `loop` (innermost last), or `None`
+    /// for loops whose value is unused; `break <value>` assigns to it.
+    loop_results: RefCell<Vec<Option<String>>>,
+    /// Identifiers used in the module, which temporaries must not shadow.
+    reserved: RefCell<HashSet<String>>,
+    /// Number of temporaries handed out so far, per base name.
+    temp_counts: RefCell<HashMap<&'static str, usize>>,
+    /// Constructs that cannot be translated, reported once the module is
+    /// done.
+    errors: RefCell<Vec<CodegenError>>,
+    /// Field names of the module's enum variants, keyed by enum and variant
+    /// name, for binding pattern positions to fields.
+    enum_fields: RefCell<HashMap<(String, String), Vec<String>>>,
+    /// Whether emitted nodes carry their source positions, for a source map.
+    source_spans: Cell<bool>,
+    /// Bodies of the `@type` comments of variable declarations and the
+    /// signature comments of methods, by the position they are attached at.
+    type_comments: RefCell<Vec<(BytePos, String)>>,
+    /// Values of the pattern bindings of the match arm whose guard is being
+    /// translated, by name. The guard is tested before they are declared.
+    guard_bindings: RefCell<HashMap<String, swc::Expr>>,
+    /// Statements to emit in front of the statement being translated: the
+    /// checks with which its `?` operators return early. `None` where such
+    /// a `return` would not leave the function, or would run whether or
+    /// not the operand is evaluated.
+    hoisted: RefCell<Option<Vec<swc::Stmt>>>,
+    /// Temporaries to declare at the top of the function (or module) being
+    /// translated, for use where nothing can be hoisted.
+    temp_decls: RefCell<Vec<String>>,
+    /// Whether a function body is being translated.
+    in_function: Cell<bool>,
+    /// Whether the module calls a runtime helper, and so imports them.
+    runtime_used: Cell<bool>,
+}
+
+impl TranslateCtx {
+    /// A fresh temporary variable name (`_match`, `_match1`, ...), unique
+    /// within the module and distinct from the module's own identifiers.
+    fn fresh(&self, base: &'static str) -> String {
+        let mut counts = self.temp_counts.borrow_mut();
+        let n = counts.entry(base).or_default();
+        loop {
+            let name = if *n == 0 { base.to_string() } else { format!("{base}{n}") };
+            *n += 1;
+            if !self.reserved.borrow().contains(&name) {
+                return name;
+            }
+        }
+    }
+
+    /// Declare the temporary `name`, which is assigned where its value is
+    /// computed: in front of the statement being translated, or else at
+    /// the top of the enclosing function or module.
+    fn declare_temp(&self, name: &str) {
+        match self.hoisted.borrow_mut().as_mut() {
+            Some(hoisted) => hoisted.push(let_decl_stmt(name)),
+            None => self.temp_decls.borrow_mut().push(name.to_string()),
+        }
+    }
+
+    fn error(&self, message: impl Into<String>, span: Span) {
+        self.errors.borrow_mut().push(CodegenError { message: message.into(), span });
+    }
+
+    /// A call of the runtime helper `name`, or `None` when lowerings are
+    /// emitted inline.
+    fn runtime_call(&self, name: &str, args: Vec<swc::Expr>) -> Option<swc::Expr> {
+        self.options.runtime_module.as_ref()?;
+        self.runtime_used.set(true);
+        let helper = swc::Expr::Member(swc::MemberExpr {
+            span: DUMMY_SP,
+            obj: Box::new(swc::Expr::Ident(ident(RUNTIME_NS))),
+            prop: swc::MemberProp::Ident(swc::IdentName { span: DUMMY_SP, sym: name.into() }),
+        });
+        Some(swc::Expr::Call(swc::CallExpr {
+            span: DUMMY_SP,
+            ctxt: SyntaxContext::empty(),
+            callee: swc::Callee::Expr(Box::new(helper)),
+            args: args.into_iter().map(expr_or_spread).collect(),
+            type_args: None,
+        }))
+    }
+
+    /// The path the emitted JavaScript imports the module at `path` from.
+    fn import_path(&self, path: &str) -> String {
+        match &self.options.import_rewrite {
+            Some(rewrite) => rewrite.rewrite(path),
+            None => path.to_string(),
+        }
+    }
+
+    /// The position of `span` in the source file registered for the source
+    /// map, which starts at position 1, or no position without one.
+    fn span(&self, span: Span) -> swc_common::Span {
+        if self.source_spans.get() {
+            swc_common::Span::new(BytePos(span.start + 1), BytePos(span.end + 1))
+        } else {
+            DUMMY_SP
+        }
+    }
+
+    /// Like [`TranslateCtx::span`], but positioned at [`item_pos`] when TS
+    /// comments are emitted, as comments are attached by position.
+    fn anchor(&self, span: Span) -> swc_common::Span {
+        if self.options.ts_comments && !self.source_spans.get() {
+            let pos = item_pos(span);
+            swc_common::Span::new(pos, pos)
+        } else {
+            self.span(span)
+        }
+    }
+
+    /// Run `f` for the body of a function, which `?` returns from, and
+    /// declare the temporaries it needs at the top of the body.
+    fn in_function(&self, f: impl FnOnce() -> swc::BlockStmtOrExpr) -> swc::BlockStmtOrExpr {
+        let outer = self.hoisted.replace(Some(Vec::new()));
+        let outer_fn = self.in_function.replace(true);
+        let outer_temps = self.temp_decls.take();
+        let body = f();
+        let temps = self.temp_decls.replace(outer_temps);
+        self.in_function.set(outer_fn);
+        self.hoisted.replace(outer);
+        if temps.is_empty() {
+            return body;
+        }
+        let mut stmts: Vec<swc::Stmt> = temps.iter().map(|name| let_decl_stmt(name)).collect();
+        match body {
+            swc::BlockStmtOrExpr::BlockStmt(block) => stmts.extend(block.stmts),
+            swc::BlockStmtOrExpr::Expr(value) => {
+                stmts.push(swc::Stmt::Return(swc::ReturnStmt { span: DUMMY_SP, arg: Some(value) }))
+            }
+        }
+        swc::BlockStmtOrExpr::BlockStmt(swc::BlockStmt { span: DUMMY_SP, ctxt: SyntaxContext::empty(), stmts })
+    }
+
+    /// Run `f` for an expression that `?` cannot return early from: one
+    /// inside an IIFE, or one that is evaluated conditionally.
+    /// Run `f` with `names` bound anew, so that they no longer stand for
+    /// the guard bindings of the same name: in
+    /// `n if some(xs, (n: int) => n > 3)` the arrow's `n` is its own.
+    fn shadowing<'n, T>(&self, names: impl IntoIterator<Item = &'n str>, f: impl FnOnce() -> T) -> T {
+        if self.guard_bindings.borrow().is_empty() {
+            return f();
+        }
+        let outer = self.guard_bindings.borrow().clone();
+        for name in names {
+            self.guard_bindings.borrow_mut().remove(name);
+        }
+        let result = f();
+        self.guard_bindings.replace(outer);
+        result
+    }
+
+    fn without_early_return<T>(&self, f: impl FnOnce() -> T) -> T {
+        let outer = self.hoisted.replace(None);
+        let result = f();
+        self.hoisted.replace(outer);
+        result
+    }
+
+    /// Give `stmt` the position of `span`, unless it has one already.
+    fn locate(&self, stmt: &mut swc::Stmt, span: Span) {
+        let target = match stmt {
+            swc::Stmt::Block(b) => &mut b.span,
+            swc::Stmt::Expr(e) => &mut e.span,
+            swc::Stmt::Return(r) => &mut r.span,
+            swc::Stmt::If(i) => &mut i.span,
+            swc::Stmt::While(w) => &mut w.span,
+            swc::Stmt::For(f) => &mut f.span,
+            swc::Stmt::ForIn(f) => &mut f.span,
+            swc::Stmt::ForOf(f) => &mut f.span,
+            swc::Stmt::Try(t) => &mut t.span,
+            swc::Stmt::Break(b) => &mut b.span,
+            swc::Stmt::Continue(c) => &mut c.span,
+            swc::Stmt::Decl(swc::Decl::Var(v)) => &mut v.span,
+            swc::Stmt::Decl(swc::Decl::Fn(f)) => &mut f.function.span,
+            _ => return,
+        };
+        if target.is_dummy() {
+            *target = self.span(span);
+        }
+    }
+}
+
 /// Bridges the host compiler's expression translator to the DSL system.
-pub struct AgCodegenContext;
+pub struct AgCodegenContext<'a> {
+    cx: &'a TranslateCtx,
+}
 
-impl ag_dsl_core::CodegenContext for AgCodegenContext {
+impl ag_dsl_core::CodegenContext for AgCodegenContext<'_> {
     fn translate_expr(&mut self, expr: &dyn Any) -> swc::Expr {
         if let Some(ag_expr) = expr.downcast_ref::<ag_ast::Expr>() {
-            translate_expr(ag_expr)
+            translate_expr(self.cx, ag_expr)
         } else {
             swc::Expr::Ident(ident("undefined"))
         }
@@ -35,7 +299,7 @@ impl ag_dsl_core::CodegenContext for AgCodegenContext {
 
     fn translate_block(&mut self, block: &dyn Any) -> Vec<swc::Stmt> {
         if let Some(ag_block) = block.downcast_ref::<ag_ast::Block>() {
-            translate_block_with_implicit_return(ag_block).stmts
+            translate_block_with_implicit_return(self.cx, ag_block).stmts
         } else {
             Vec::new()
         }
@@ -84,6 +348,9 @@ fn convert_dsl_block(dsl: &ag_ast::DslBlock) -> ag_dsl_core::DslBlock {
 pub struct Translator {
     handlers: HashMap<String, Box<dyn ag_dsl_core::DslHandler>>,
     tool_registry: HashMap<String, ToolSchemaInfo>,
+    /// See [`Translator::set_variant_fields`].
+    variant_fields: HashMap<(String, String), Vec<String>>,
+    cx: TranslateCtx,
 }
 
 impl Translator {
@@ -91,6 +358,8 @@ impl Translator {
         Self {
             handlers: HashMap::new(),
             tool_registry: HashMap::new(),
+            variant_fields: HashMap::new(),
+            cx: TranslateCtx::default(),
         }
     }
 
@@ -98,13 +367,81 @@ impl Translator {
         self.tool_registry = registry;
     }
 
+    /// Rewrites computed by the checker, such as builtin members emitted
+    /// under their JS names.
+    pub fn set_rewrites(&mut self, rewrites: Rewrites) {
+        self.cx.rewrites = rewrites;
+    }
+
+    /// Field names of enum variants in declaration order, keyed by enum and
+    /// variant name, as the checker found them; needed to bind the fields
+    /// of variants of enums declared in other modules.
+    pub fn set_variant_fields(&mut self, fields: HashMap<(String, String), Vec<String>>) {
+        self.variant_fields = fields;
+    }
+
+    pub fn set_options(&mut self, options: CodegenOptions) {
+        self.cx.options = options;
+    }
+
     pub fn register_dsl_handler(&mut self, kind: &str, handler: Box<dyn ag_dsl_core::DslHandler>) {
         self.handlers.insert(kind.to_string(), handler);
     }
 
+    /// A [`TranslatorBuilder`], for configuring a translator in one expression.
+    pub fn builder() -> TranslatorBuilder {
+        TranslatorBuilder::new()
+    }
+
     pub fn codegen(&self, module: &Module) -> Result<String, CodegenError> {
         let swc_module = self.translate_module(module)?;
-        Ok(emit(&swc_module))
+        let comments = doc_comments(module, &self.cx.options, self.cx.type_comments.take());
+        Ok(emit(&swc_module, &comments, Lrc::default(), None))
+    }
+
+    /// Like [`Translator::codegen`], as `(filename, content)` pairs. With
+    /// `split_output`, a module with several independent `pub fn`s becomes
+    /// one file per function, `<name>.js`, each holding the private
+    /// functions it uses; otherwise the module is the single `index.js`.
+    /// Files are named, and import each other, with the `import_rewrite`
+    /// extension when one is set.
+    pub fn codegen_split(&self, module: &Module) -> Result<Vec<(String, String)>, CodegenError> {
+        let extension = self.cx.options.import_rewrite.as_ref().map_or("js", |r| r.extension.as_str());
+        let parts = if self.cx.options.split_output { split::split_module(module, extension) } else { None };
+        let Some(parts) = parts else {
+            return Ok(vec![(format!("index.{extension}"), self.codegen(module)?)]);
+        };
+        parts
+            .into_iter()
+            .map(|(name, part)| Ok((format!("{name}.{extension}"), self.codegen(&part)?)))
+            .collect()
+    }
+
+    /// Like [`Translator::codegen`], also returning a source map (as JSON)
+    /// from the JavaScript back to `source`, the text `module` was parsed
+    /// from, named `filename` in the map. Statements, declarations and
+    /// calls are mapped.
+    pub fn codegen_with_sourcemap(
+        &self,
+        module: &Module,
+        source: &str,
+        filename: &str,
+    ) -> Result<(String, String), CodegenError> {
+        self.cx.source_spans.set(true);
+        let swc_module = self.translate_module(module);
+        self.cx.source_spans.set(false);
+        let swc_module = swc_module?;
+
+        let cm: Lrc<SourceMap> = Lrc::default();
+        cm.new_source_file(Lrc::new(FileName::Real(filename.into())), source.to_string());
+        let mut mappings = Vec::new();
+        let comments = doc_comments(module, &self.cx.options, self.cx.type_comments.take());
+        let js = emit(&swc_module, &comments, cm.clone(), Some(&mut mappings));
+        let mut map = Vec::new();
+        cm.build_source_map(&mappings, None, DefaultSourceMapGenConfig)
+            .to_writer(&mut map)
+            .expect("writing to a Vec cannot fail");
+        Ok((js, String::from_utf8(map).expect("source maps are UTF-8")))
     }
 
     fn translate_module(&self, module: &Module) -> Result<swc::Module, CodegenError> {
@@ -118,6 +455,7 @@ impl Translator {
                             js_externs.insert(ef.name.clone(), JsExternInfo {
                                 module: module_name.clone(),
                                 js_name: ann.js_name.clone(),
+                                span: ef.span,
                             });
                         }
                     }
@@ -128,6 +466,7 @@ impl Translator {
                             js_externs.insert(es.name.clone(), JsExternInfo {
                                 module: module_name.clone(),
                                 js_name: ann.js_name.clone(),
+                                span: es.span,
                             });
                         }
                     }
@@ -138,6 +477,7 @@ impl Translator {
                             js_externs.insert(et.name.clone(), JsExternInfo {
                                 module: module_name.clone(),
                                 js_name: ann.js_name.clone(),
+                                span: et.span,
                             });
                         }
                     }
@@ -146,60 +486,106 @@ impl Translator {
             }
         }
 
+        let mut enum_fields = self.variant_fields.clone();
+        enum_fields.extend(
+            module
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    Item::EnumDecl(e) => Some(e),
+                    _ => None,
+                })
+                .flat_map(|e| {
+                    e.variants.iter().map(|v| {
+                        let fields = v.fields.iter().map(|f| f.name.clone()).collect();
+                        ((e.name.clone(), v.name.clone()), fields)
+                    })
+                }),
+        );
+        *self.cx.enum_fields.borrow_mut() = enum_fields;
+
         // Collect referenced identifiers
         let mut referenced = std::collections::HashSet::new();
         for item in &module.items {
             collect_referenced_idents(item, &mut referenced);
         }
-
-        // Generate import statements for referenced @js externs, grouped by module
-        let mut module_imports: HashMap<String, Vec<(String, Option<String>)>> = HashMap::new();
-        for (ag_name, info) in &js_externs {
-            if referenced.contains(ag_name) {
-                let entry = module_imports.entry(info.module.clone()).or_default();
-                entry.push((ag_name.clone(), info.js_name.clone()));
+        let mut reserved = referenced.clone();
+        for item in &module.items {
+            if let Item::ImplBlock(ib) = item {
+                ib.methods.iter().for_each(|m| collect_idents_block(&m.body, &mut reserved));
             }
+            collect_declared_idents(item, &mut reserved);
         }
+        reserved.insert(RUNTIME_NS.to_string());
+        *self.cx.reserved.borrow_mut() = reserved;
+        self.cx.temp_counts.borrow_mut().clear();
+        self.cx.temp_decls.take();
+        self.cx.type_comments.take();
+        self.cx.runtime_used.set(false);
 
-        let mut body = Vec::new();
+        // Imports for referenced @js externs join the module's own imports,
+        // sorted by JS name so that the aliases of one function are adjacent
+        let mut externs: Vec<(&str, &JsExternInfo)> = js_externs
+            .iter()
+            .filter(|(ag_name, _)| referenced.contains(*ag_name))
+            .map(|(ag_name, info)| (ag_name.as_str(), info))
+            .collect();
+        externs.sort_by_key(|&(ag_name, info)| (info.js_name.as_deref().unwrap_or(ag_name), ag_name));
+        let mut imports = merge_imports(&self.cx, module, &externs)?;
 
-        // Emit merged import statements at the top
-        let mut sorted_modules: Vec<_> = module_imports.keys().cloned().collect();
-        sorted_modules.sort();
-        for module_path in sorted_modules {
-            let names = &module_imports[&module_path];
-            let specifiers: Vec<swc::ImportSpecifier> = names.iter().map(|(ag_name, js_name)| {
-                swc::ImportSpecifier::Named(swc::ImportNamedSpecifier {
-                    span: DUMMY_SP,
-                    local: ident(ag_name),
-                    imported: js_name.as_ref().map(|jn| {
-                        swc::ModuleExportName::Ident(ident(jn))
-                    }),
-                    is_type_only: false,
-                })
-            }).collect();
-            body.push(swc::ModuleItem::ModuleDecl(swc::ModuleDecl::Import(
-                swc::ImportDecl {
-                    span: DUMMY_SP,
-                    specifiers,
-                    src: Box::new(swc::Str {
-                        span: DUMMY_SP,
-                        value: module_path.into(),
-                        raw: None,
-                    }),
-                    type_only: false,
-                    with: None,
-                    phase: Default::default(),
-                },
-            )));
-        }
+        let mut body: Vec<swc::ModuleItem> =
+            imports.leading.into_iter().map(swc::ModuleItem::ModuleDecl).collect();
+
+        // With `WrapInMain`, module code from the first statement that
+        // awaits onward runs in `async function main()`, called at the end;
+        // its variables are still declared at the top level
+        let main_from = match self.cx.options.top_level_await {
+            TopLevelAwait::WrapInMain => module.items.iter().position(item_awaits),
+            TopLevelAwait::Allow | TopLevelAwait::Error => None,
+        };
+        let mut main_body: Vec<swc::Stmt> = Vec::new();
 
         // Second pass: translate items
-        for item in &module.items {
+        let derives = derive::Derives::new(module);
+        for (index, item) in module.items.iter().enumerate() {
+            if main_from.is_some_and(|from| index >= from)
+                && matches!(item, Item::VarDecl(_) | Item::ExprStmt(_))
+            {
+                let mut items = Vec::new();
+                translate_item_into(&self.cx, item, &mut items, &self.tool_registry);
+                let mut stmts: Vec<swc::Stmt> = items
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        swc::ModuleItem::Stmt(stmt) => Some(stmt),
+                        swc::ModuleItem::ModuleDecl(_) => None,
+                    })
+                    .collect();
+                if let Item::VarDecl(v) = item {
+                    let span = assign_declared(&mut stmts, &v.name);
+                    body.push(stmt_to_module_item(swc::Stmt::Decl(swc::Decl::Var(Box::new(swc::VarDecl {
+                        span,
+                        ctxt: SyntaxContext::empty(),
+                        kind: swc::VarDeclKind::Let,
+                        declare: false,
+                        decls: vec![swc::VarDeclarator {
+                            span: DUMMY_SP,
+                            name: swc::Pat::Ident(binding_ident(&v.name)),
+                            init: None,
+                            definite: false,
+                        }],
+                    })))));
+                }
+                main_body.extend(stmts);
+                continue;
+            }
             match item {
+                Item::StructDecl(_) | Item::EnumDecl(_) => {
+                    translate_item_into(&self.cx, item, &mut body, &self.tool_registry);
+                    body.extend(derives.items_for(item));
+                }
                 Item::DslBlock(dsl) => {
                     if let Some(handler) = self.handlers.get(&dsl.kind) {
-                        let mut ctx = AgCodegenContext;
+                        let mut ctx = AgCodegenContext { cx: &self.cx };
                         let core_block = convert_dsl_block(dsl);
                         let items = handler.handle(&core_block, &mut ctx).map_err(|e| {
                             CodegenError {
@@ -218,23 +604,131 @@ impl Translator {
                         });
                     }
                 }
+                Item::Import(Import { path: ImportPath::Dynamic(template), .. }) => {
+                    return Err(CodegenError {
+                        message: "dynamic import paths are not supported in static imports".to_string(),
+                        span: template.span,
+                    });
+                }
+                Item::Import(_) => {
+                    if let Some(decl) = imports.in_place.remove(&index).flatten() {
+                        body.push(swc::ModuleItem::ModuleDecl(decl));
+                    }
+                }
                 other => {
-                    translate_item_into(other, &mut body, &self.tool_registry);
+                    translate_item_into(&self.cx, other, &mut body, &self.tool_registry);
                 }
             }
         }
 
+        // Temporaries of module code, after the imports
+        let temps = self.cx.temp_decls.take();
+        let first = body
+            .iter()
+            .position(|item| !matches!(item, swc::ModuleItem::ModuleDecl(swc::ModuleDecl::Import(_))))
+            .unwrap_or(body.len());
+        body.splice(first..first, temps.iter().map(|name| stmt_to_module_item(let_decl_stmt(name))));
+
+        if main_from.is_some() {
+            let mut reserved = self.cx.reserved.borrow_mut();
+            for item in &module.items {
+                match item {
+                    Item::FnDecl(f) => reserved.insert(f.name.clone()),
+                    Item::VarDecl(v) => reserved.insert(v.name.clone()),
+                    _ => continue,
+                };
+            }
+            drop(reserved);
+            let main = self.cx.fresh("main");
+            body.push(stmt_to_module_item(swc::Stmt::Decl(swc::Decl::Fn(swc::FnDecl {
+                ident: ident(&main),
+                declare: false,
+                function: Box::new(swc::Function {
+                    params: vec![],
+                    decorators: vec![],
+                    span: DUMMY_SP,
+                    ctxt: SyntaxContext::empty(),
+                    body: Some(swc::BlockStmt { span: DUMMY_SP, ctxt: SyntaxContext::empty(), stmts: main_body }),
+                    is_generator: false,
+                    is_async: true,
+                    type_params: None,
+                    return_type: None,
+                }),
+            }))));
+            body.push(stmt_to_module_item(call_main(&main)));
+        }
+        if let Some(error) = self.cx.errors.take().into_iter().next() {
+            return Err(error);
+        }
+        if self.cx.runtime_used.get()
+            && let Some(path) = &self.cx.options.runtime_module
+        {
+            if let Some(span) = module.items.iter().find_map(binds_runtime_ns) {
+                return Err(CodegenError {
+                    message: format!(
+                        "`{RUNTIME_NS}` clashes with the runtime helpers import; rename this binding"
+                    ),
+                    span,
+                });
+            }
+            let runtime = swc::ImportDecl {
+                span: DUMMY_SP,
+                specifiers: vec![swc::ImportSpecifier::Namespace(swc::ImportStarAsSpecifier {
+                    span: DUMMY_SP,
+                    local: ident(RUNTIME_NS),
+                })],
+                src: Box::new(swc::Str { span: DUMMY_SP, value: path.as_str().into(), raw: None }),
+                type_only: false,
+                with: None,
+                phase: Default::default(),
+            };
+            body.insert(0, swc::ModuleItem::ModuleDecl(swc::ModuleDecl::Import(runtime)));
+        }
         Ok(swc::Module {
             span: DUMMY_SP,
             body,
-            shebang: None,
+            shebang: module.shebang.as_deref().map(Into::into),
         })
     }
 }
 
+/// Whether the module code `item` awaits outside a function.
+fn item_awaits(item: &Item) -> bool {
+    let is_await = |e: &Expr| matches!(e, Expr::Await(_));
+    match item {
+        Item::VarDecl(v) => walk::expr_any(&v.init, &is_await),
+        Item::ExprStmt(e) => walk::expr_any(&e.expr, &is_await),
+        _ => false,
+    }
+}
+
+/// Turn the declaration of `name` in `stmts` into an assignment to it, for
+/// a variable declared elsewhere, and return the declaration's span.
+fn assign_declared(stmts: &mut Vec<swc::Stmt>, name: &str) -> swc_common::Span {
+    let position = stmts.iter().position(|stmt| {
+        matches!(stmt, swc::Stmt::Decl(swc::Decl::Var(decl))
+            if matches!(&decl.decls[0].name, swc::Pat::Ident(id) if *id.sym == *name))
+    });
+    let Some(position) = position else { return DUMMY_SP };
+    let swc::Stmt::Decl(swc::Decl::Var(mut decl)) = stmts.remove(position) else { unreachable!() };
+    if let Some(init) = decl.decls[0].init.take() {
+        stmts.insert(position, swc::Stmt::Expr(swc::ExprStmt {
+            span: DUMMY_SP,
+            expr: Box::new(swc::Expr::Assign(swc::AssignExpr {
+                span: DUMMY_SP,
+                op: swc::AssignOp::Assign,
+                left: swc::AssignTarget::Simple(swc::SimpleAssignTarget::Ident(binding_ident(name))),
+                right: init,
+            })),
+        }));
+    }
+    decl.span
+}
+
 struct JsExternInfo {
     module: String,
     js_name: Option<String>,
+    span: Span,
 }
 
 fn collect_referenced_idents(item: &Item, set: &mut std::collections::HashSet<String>) {
@@ -259,6 +753,7 @@ fn collect_idents_expr(expr: &Expr, set: &mut std::collections::HashSet<String>)
     match expr {
         Expr::Ident(id) => { set.insert(id.name.clone()); }
         Expr::Binary(b) => { collect_idents_expr(&b.left, set); collect_idents_expr(&b.right, set); }
+        Expr::Comparison(c) => { for operand in &c.operands { collect_idents_expr(operand, set); } }
         Expr::Unary(u) => collect_idents_expr(&u.operand, set),
         Expr::Call(c) => {
             collect_idents_expr(&c.callee, set);
@@ -279,10 +774,14 @@ fn collect_idents_expr(expr: &Expr, set: &mut std::collections::HashSet<String>)
         Expr::Match(m) => {
             collect_idents_expr(&m.subject, set);
             for arm in &m.arms {
-                collect_idents_expr(&arm.body, set);
+                match &arm.body {
+                    MatchArmBody::Expr(e) => collect_idents_expr(e, set),
+                    MatchArmBody::Block(b) => collect_idents_block(b, set),
+                }
                 if let Some(ref g) = arm.guard { collect_idents_expr(g, set); }
             }
         }
+        Expr::Loop(l) => collect_idents_block(&l.body, set),
         Expr::Block(b) => collect_idents_block(b, set),
         Expr::Array(a) => { for e in &a.elements { collect_idents_expr(e, set); } }
         Expr::Object(o) => { for f in &o.fields { collect_idents_expr(&f.value, set); } }
@@ -303,6 +802,12 @@ fn collect_idents_expr(expr: &Expr, set: &mut std::collections::HashSet<String>)
                 if let TemplatePart::Expr(e) = p { collect_idents_expr(e, set); }
             }
         }
+        Expr::TaggedTemplate(tt) => {
+            collect_idents_expr(&tt.tag, set);
+            for p in &tt.template.parts {
+                if let TemplatePart::Expr(e) = p { collect_idents_expr(e, set); }
+            }
+        }
         _ => {}
     }
 }
@@ -315,7 +820,15 @@ fn collect_idents_block(block: &Block, set: &mut std::collections::HashSet<Strin
             Stmt::Return(r) => { if let Some(ref v) = r.value { collect_idents_expr(v, set); } }
             Stmt::If(i) => collect_idents_expr(&Expr::If(Box::new(i.clone())), set),
             Stmt::For(f) => { collect_idents_expr(&f.iter, set); collect_idents_block(&f.body, set); }
+            Stmt::ForRange(fr) => {
+                collect_idents_expr(&fr.init.init, set);
+                collect_idents_expr(&fr.condition, set);
+                collect_idents_expr(&fr.update, set);
+                collect_idents_block(&fr.body, set);
+            }
             Stmt::While(w) => { collect_idents_expr(&w.condition, set); collect_idents_block(&w.body, set); }
+            Stmt::Loop(l) => collect_idents_block(&l.body, set),
+            Stmt::Break(b) => { if let Some(ref v) = b.value { collect_idents_expr(v, set); } }
             Stmt::Match(m) => collect_idents_expr(&Expr::Match(Box::new(m.clone())), set),
             Stmt::TryCatch(tc) => { collect_idents_block(&tc.try_block, set); collect_idents_block(&tc.catch_block, set); }
         }
@@ -325,62 +838,329 @@ fn collect_idents_block(block: &Block, set: &mut std::collections::HashSet<Strin
     }
 }
 
+/// The span of `item` if it binds [`RUNTIME_NS`], which would shadow the
+/// runtime helpers import.
+fn binds_runtime_ns(item: &Item) -> Option<Span> {
+    let span = match item {
+        Item::Import(imp) => {
+            let mut names = imp.names.iter().map(|n| n.alias.as_ref().unwrap_or(&n.name));
+            let binds = imp.namespace.as_deref() == Some(RUNTIME_NS)
+                || names.any(|name| name == RUNTIME_NS);
+            return binds.then_some(imp.span);
+        }
+        Item::FnDecl(f) => f.span,
+        Item::VarDecl(v) => v.span,
+        Item::ExprStmt(e) => e.span,
+        Item::ImplBlock(ib) => ib.span,
+        _ => return None,
+    };
+    let mut declared = std::collections::HashSet::new();
+    collect_declared_idents(item, &mut declared);
+    declared.contains(RUNTIME_NS).then_some(span)
+}
+
+/// Collect the names `item` declares anywhere inside it: functions,
+/// parameters, variables and pattern bindings. Temporaries must not clash
+/// with them even where they are never read.
+fn collect_declared_idents(item: &Item, set: &mut std::collections::HashSet<String>) {
+    match item {
+        Item::FnDecl(f) => {
+            set.insert(f.name.clone());
+            set.extend(f.params.iter().map(|p| p.name.clone()));
+            collect_declared_block(&f.body, set);
+        }
+        Item::ImplBlock(ib) => {
+            for m in &ib.methods {
+                set.extend(m.params.iter().map(|p| p.name.clone()));
+                collect_declared_block(&m.body, set);
+            }
+        }
+        Item::VarDecl(v) => {
+            set.insert(v.name.clone());
+            collect_declared_expr(&v.init, set);
+        }
+        Item::ExprStmt(e) => collect_declared_expr(&e.expr, set),
+        _ => {}
+    }
+}
+
+pub(crate) fn collect_declared_block(block: &Block, set: &mut std::collections::HashSet<String>) {
+    collect_declared_stmts(block, set);
+    let declared = RefCell::new(std::mem::take(set));
+    walk::block_any(block, &|e| declare_in_expr(e, &declared));
+    *set = declared.into_inner();
+}
+
+fn collect_declared_expr(expr: &Expr, set: &mut std::collections::HashSet<String>) {
+    let declared = RefCell::new(std::mem::take(set));
+    walk::expr_any(expr, &|e| declare_in_expr(e, &declared));
+    *set = declared.into_inner();
+}
+
+/// Add the names `expr` itself declares, or the function it builds, to
+/// `declared`; never holds, so that [`walk::expr_any`] visits every
+/// expression.
+fn declare_in_expr(expr: &Expr, declared: &RefCell<std::collections::HashSet<String>>) -> bool {
+    let mut declared = declared.borrow_mut();
+    match expr {
+        Expr::Arrow(ar) => {
+            declared.extend(ar.params.iter().map(|p| p.name.clone()));
+            match &ar.body {
+                ArrowBody::Expr(e) => collect_declared_expr(e, &mut declared),
+                ArrowBody::Block(b) => collect_declared_block(b, &mut declared),
+            }
+        }
+        Expr::Match(m) => collect_declared_arms(m, &mut declared),
+        Expr::If(i) => collect_declared_if(i, &mut declared),
+        Expr::Loop(l) => collect_declared_stmts(&l.body, &mut declared),
+        Expr::Block(b) => collect_declared_stmts(b, &mut declared),
+        _ => {}
+    }
+    false
+}
+
+/// The names declared by the statements of `block` and of the blocks
+/// nested in them; not those in their expressions.
+fn collect_declared_stmts(block: &Block, set: &mut std::collections::HashSet<String>) {
+    for stmt in &block.stmts {
+        match stmt {
+            Stmt::VarDecl(v) => {
+                set.insert(v.name.clone());
+            }
+            Stmt::For(f) => {
+                set.insert(f.binding.clone());
+                collect_declared_stmts(&f.body, set);
+            }
+            Stmt::ForRange(fr) => {
+                set.insert(fr.init.name.clone());
+                collect_declared_stmts(&fr.body, set);
+            }
+            Stmt::TryCatch(tc) => {
+                set.insert(tc.catch_binding.clone());
+                collect_declared_stmts(&tc.try_block, set);
+                collect_declared_stmts(&tc.catch_block, set);
+            }
+            Stmt::While(w) => collect_declared_stmts(&w.body, set),
+            Stmt::Loop(l) => collect_declared_stmts(&l.body, set),
+            Stmt::If(i) => collect_declared_if(i, set),
+            Stmt::Match(m) => collect_declared_arms(m, set),
+            Stmt::ExprStmt(_) | Stmt::Return(_) | Stmt::Break(_) => {}
+        }
+    }
+}
+
+fn collect_declared_if(i: &IfExpr, set: &mut std::collections::HashSet<String>) {
+    collect_declared_stmts(&i.then_block, set);
+    match &i.else_branch {
+        Some(ElseBranch::Block(b)) => collect_declared_stmts(b, set),
+        Some(ElseBranch::If(elif)) => collect_declared_if(elif, set),
+        None => {}
+    }
+}
+
+fn collect_declared_arms(m: &MatchExpr, set: &mut std::collections::HashSet<String>) {
+    for arm in &m.arms {
+        match &arm.pattern {
+            Pattern::Ident(name, _) => {
+                set.insert(name.clone());
+            }
+            Pattern::Struct(sp) => set.extend(sp.fields.iter().cloned()),
+            Pattern::Enum(ep) => set.extend(ep.bindings.iter().cloned()),
+            Pattern::Literal(_) | Pattern::Wildcard(_) | Pattern::Range(..) => {}
+        }
+        if let MatchArmBody::Block(b) = &arm.body {
+            collect_declared_stmts(b, set);
+        }
+    }
+}
+
+// ── Translator builder ────────────────────────────────────
+
+/// Fluent construction of a [`Translator`]:
+///
+/// ```ignore
+/// let translator = TranslatorBuilder::new()
+///     .with_handler("prompt", Box::new(PromptDslHandler))
+///     .with_options(CodegenOptions { nil_to_undefined: true, ..Default::default() })
+///     .build();
+/// ```
+pub struct TranslatorBuilder {
+    translator: Translator,
+}
+
+impl TranslatorBuilder {
+    pub fn new() -> Self {
+        Self { translator: Translator::new() }
+    }
+
+    pub fn with_handler(mut self, kind: &str, handler: Box<dyn ag_dsl_core::DslHandler>) -> Self {
+        self.translator.register_dsl_handler(kind, handler);
+        self
+    }
+
+    /// The handlers for the built-in `@prompt`, `@agent` and `@server` blocks.
+    pub fn with_builtin_handlers(self) -> Self {
+        self.with_handler("prompt", Box::new(ag_dsl_prompt::handler::PromptDslHandler))
+            .with_handler("agent", Box::new(ag_dsl_agent::handler::AgentDslHandler))
+            .with_handler("server", Box::new(ag_dsl_server::handler::ServerDslHandler))
+            .with_handler("component", Box::new(ag_dsl_component::handler::ComponentDslHandler))
+    }
+
+    pub fn with_options(mut self, options: CodegenOptions) -> Self {
+        self.translator.set_options(options);
+        self
+    }
+
+    pub fn with_tool_registry(mut self, registry: HashMap<String, ToolSchemaInfo>) -> Self {
+        self.translator.set_tool_registry(registry);
+        self
+    }
+
+    /// See [`Translator::set_rewrites`].
+    pub fn with_rewrites(mut self, rewrites: Rewrites) -> Self {
+        self.translator.set_rewrites(rewrites);
+        self
+    }
+
+    /// See [`Translator::set_variant_fields`].
+    pub fn with_variant_fields(mut self, fields: HashMap<(String, String), Vec<String>>) -> Self {
+        self.translator.set_variant_fields(fields);
+        self
+    }
+
+    pub fn build(self) -> Translator {
+        self.translator
+    }
+}
+
+impl Default for TranslatorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // ── Legacy API (keeps existing code working) ──────────────
 
 pub fn codegen(module: &Module) -> String {
-    let mut translator = Translator::new();
-    translator.register_dsl_handler(
-        "prompt",
-        Box::new(ag_dsl_prompt::handler::PromptDslHandler),
-    );
-    translator.register_dsl_handler(
-        "agent",
-        Box::new(ag_dsl_agent::handler::AgentDslHandler),
-    );
-    translator.register_dsl_handler(
-        "server",
-        Box::new(ag_dsl_server::handler::ServerDslHandler),
-    );
+    let translator = TranslatorBuilder::new().with_builtin_handlers().build();
     translator.codegen(module).unwrap_or_else(|e| {
         panic!("codegen error: {}", e.message)
     })
 }
 
 pub fn codegen_with_tools(module: &Module, tool_registry: HashMap<String, ToolSchemaInfo>) -> String {
-    let mut translator = Translator::new();
-    translator.set_tool_registry(tool_registry);
-    translator.register_dsl_handler(
-        "prompt",
-        Box::new(ag_dsl_prompt::handler::PromptDslHandler),
-    );
-    translator.register_dsl_handler(
-        "agent",
-        Box::new(ag_dsl_agent::handler::AgentDslHandler),
-    );
-    translator.register_dsl_handler(
-        "server",
-        Box::new(ag_dsl_server::handler::ServerDslHandler),
-    );
+    codegen_with_type_info(module, tool_registry, Rewrites::default(), HashMap::new())
+}
+
+/// Like [`codegen_with_tools`], additionally applying the checker's
+/// rewrites (e.g. `starts_with` → `startsWith`) and the field order of the
+/// enum variants it saw.
+pub fn codegen_with_type_info(
+    module: &Module,
+    tool_registry: HashMap<String, ToolSchemaInfo>,
+    rewrites: Rewrites,
+    variant_fields: HashMap<(String, String), Vec<String>>,
+) -> String {
+    let translator = TranslatorBuilder::new()
+        .with_builtin_handlers()
+        .with_tool_registry(tool_registry)
+        .with_rewrites(rewrites)
+        .with_variant_fields(variant_fields)
+        .build();
     translator.codegen(module).unwrap_or_else(|e| {
         panic!("codegen error: {}", e.message)
     })
 }
 
-fn emit(module: &swc::Module) -> String {
-    let cm: Lrc<SourceMap> = Lrc::new(SourceMap::default());
+/// Print `module`, recording the positions of its nodes into `mappings`
+/// when given.
+fn emit(
+    module: &swc::Module,
+    comments: &SingleThreadedComments,
+    cm: Lrc<SourceMap>,
+    mappings: Option<&mut Vec<(BytePos, LineCol)>>,
+) -> String {
     let mut buf = Vec::new();
     {
         let mut emitter = Emitter {
             cfg: swc_ecma_codegen::Config::default(),
             cm: cm.clone(),
-            comments: None,
-            wr: JsWriter::new(cm, "\n", &mut buf, None),
+            comments: Some(comments),
+            wr: JsWriter::new(cm, "\n", &mut buf, mappings),
         };
         emitter.emit_module(module).unwrap();
     }
     String::from_utf8(buf).unwrap()
 }
 
+/// JSDoc comments for exported functions, enums and struct factories,
+/// built from the `///` comments preceding them (present when the module
+/// was parsed with comments preserved), attached at each declaration's
+/// [`item_pos`]. With `ts_comments`, every function's comment also gets
+/// its signature, and the variable `type_comments` are added.
+fn doc_comments(module: &Module, options: &CodegenOptions, type_comments: Vec<(BytePos, String)>) -> SingleThreadedComments {
+    let comments = SingleThreadedComments::default();
+    for (index, item) in module.items.iter().enumerate() {
+        let (is_pub, span) = match item {
+            Item::FnDecl(f) => (f.is_pub, f.span),
+            Item::EnumDecl(e) => (e.is_pub, e.span),
+            // Only a struct with defaults is emitted, as its factory
+            Item::StructDecl(s) if s.fields.iter().any(|f| f.default.is_some()) => (s.is_pub, s.span),
+            _ => continue,
+        };
+        let mut lines: Vec<String> = Vec::new();
+        if is_pub {
+            lines.extend(module.doc_comments(index).map(|c| {
+                let text = c.text.trim_start_matches("///");
+                text.strip_prefix(' ').unwrap_or(text).trim_end().to_string()
+            }));
+        }
+        if options.ts_comments
+            && let Item::FnDecl(f) = item
+        {
+            lines.extend(ts_comments::fn_lines(f, None, options));
+        }
+        if lines.is_empty() {
+            continue;
+        }
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        comments.add_leading(
+            item_pos(span),
+            SwcComment { kind: SwcCommentKind::Block, span: DUMMY_SP, text: jsdoc(&lines).into() },
+        );
+    }
+    for (pos, text) in type_comments {
+        comments.add_leading(pos, SwcComment { kind: SwcCommentKind::Block, span: DUMMY_SP, text: text.into() });
+    }
+    comments
+}
+
+/// The body of a `/** ... */` comment (without the outer `/*` and `*/`).
+fn jsdoc(lines: &[&str]) -> String {
+    let lines: Vec<String> = lines.iter().map(|l| l.replace("*/", "*\\/")).collect();
+    match lines.as_slice() {
+        [line] => format!("* {line} "),
+        _ => {
+            let mut text = "*\n".to_string();
+            for line in &lines {
+                if line.is_empty() {
+                    text.push_str(" *\n");
+                } else {
+                    text.push_str(&format!(" * {line}\n"));
+                }
+            }
+            text.push(' ');
+            text
+        }
+    }
+}
+
+/// Position an emitted top-level declaration is given so that comments can
+/// be attached to it. Offset by one: position 0 means "no position".
+fn item_pos(span: Span) -> BytePos {
+    BytePos(span.start + 1)
+}
+
 // ── Helpers ────────────────────────────────────────────────
 
 fn stmt_to_module_item(stmt: swc::Stmt) -> swc::ModuleItem {
@@ -389,19 +1169,20 @@ fn stmt_to_module_item(stmt: swc::Stmt) -> swc::ModuleItem {
 
 // ── Module translation ─────────────────────────────────────
 
-fn translate_item_into(item: &Item, body: &mut Vec<swc::ModuleItem>, tool_registry: &HashMap<String, ToolSchemaInfo>) {
+fn translate_item_into(cx: &TranslateCtx, item: &Item, body: &mut Vec<swc::ModuleItem>, tool_registry: &HashMap<String, ToolSchemaInfo>) {
     match item {
         Item::FnDecl(f) => {
             if f.is_pub {
+                let pos = item_pos(f.span);
                 body.push(swc::ModuleItem::ModuleDecl(swc::ModuleDecl::ExportDecl(
                     swc::ExportDecl {
-                        span: DUMMY_SP,
-                        decl: swc::Decl::Fn(translate_fn_decl(f)),
+                        span: swc_common::Span::new(pos, pos),
+                        decl: swc::Decl::Fn(translate_fn_decl(cx, f)),
                     },
                 )));
             } else {
                 body.push(stmt_to_module_item(swc::Stmt::Decl(swc::Decl::Fn(
-                    translate_fn_decl(f),
+                    translate_fn_decl(cx, f),
                 ))));
             }
             // Emit tool schema if this is a @tool function
@@ -435,19 +1216,49 @@ fn translate_item_into(item: &Item, body: &mut Vec<swc::ModuleItem>, tool_regist
             }
         }
         Item::VarDecl(v) => {
-            body.push(stmt_to_module_item(translate_var_decl_stmt(v)));
+            let mut stmts = Vec::new();
+            translate_var_decl_into(cx, v, &mut stmts);
+            if let Some(first) = stmts.first_mut() {
+                cx.locate(first, v.span);
+            }
+            body.extend(stmts.into_iter().map(stmt_to_module_item));
         }
         Item::Import(imp) => {
-            body.push(swc::ModuleItem::ModuleDecl(translate_import(imp)));
+            body.push(swc::ModuleItem::ModuleDecl(translate_import(cx, imp)));
+        }
+        Item::ExportAlias(ea) => {
+            body.push(swc::ModuleItem::ModuleDecl(translate_export_alias(ea)));
+        }
+        Item::ImplBlock(ib) => {
+            for method in &ib.methods {
+                let f = FnDecl {
+                    name: ImplBlock::method_fn_name(&ib.target, &method.name),
+                    ..method.clone()
+                };
+                let decl = translate_fn_decl(cx, &f);
+                if cx.options.ts_comments {
+                    let lines = ts_comments::fn_lines(method, Some(&ib.target), &cx.options);
+                    if !lines.is_empty() {
+                        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+                        cx.type_comments.borrow_mut().push((decl.function.span.lo, jsdoc(&lines)));
+                    }
+                }
+                body.push(stmt_to_module_item(swc::Stmt::Decl(swc::Decl::Fn(decl))));
+            }
+        }
+        Item::EnumDecl(e) => push_decl(body, translate_enum_decl(cx, e), e.is_pub, e.span),
+        Item::StructDecl(s) => {
+            if let Some(factory) = translate_struct_factory(cx, s) {
+                push_decl(body, factory, s.is_pub, s.span);
+            }
         }
-        // Struct, Enum, TypeAlias, Extern declarations are erased
-        Item::StructDecl(_) | Item::EnumDecl(_) | Item::TypeAlias(_)
+        // Interface, TypeAlias, Extern declarations are erased
+        Item::InterfaceDecl(_) | Item::TypeAlias(_)
         | Item::ExternFnDecl(_) | Item::ExternStructDecl(_) | Item::ExternTypeDecl(_) => {}
         Item::ExprStmt(e) => {
-            body.push(stmt_to_module_item(swc::Stmt::Expr(swc::ExprStmt {
-                span: DUMMY_SP,
-                expr: Box::new(translate_expr(&e.expr)),
-            })));
+            let mut stmt = translate_expr_stmt(cx, &e.expr);
+            cx.locate(&mut stmt, e.span);
+            body.push(stmt_to_module_item(stmt));
         }
         Item::DslBlock(_) => {
             // Handled by Translator; legacy codegen() registers prompt handler
@@ -457,30 +1268,183 @@ fn translate_item_into(item: &Item, body: &mut Vec<swc::ModuleItem>, tool_regist
 
 // ── Variable declarations ──────────────────────────────────
 
-fn translate_var_decl_stmt(v: &VarDecl) -> swc::Stmt {
-    let kind = match v.kind {
-        VarKind::Let => swc::VarDeclKind::Const,
-        VarKind::Mut => swc::VarDeclKind::Let,
-        VarKind::Const => swc::VarDeclKind::Const,
-    };
+/// Translate a variable declaration into `out`. A `loop` initializer is
+/// hoisted in front of the declaration so that `ret` and `await` in its
+/// body keep their meaning:
+/// `let _loop_result; while (true) { ... } const x = _loop_result;`.
+/// A `match` initializer assigns the variable from each arm instead:
+/// `let x; { const _match = ...; if (...) { x = ...; } }`. With
+/// `ts_comments`, the declaration of `x` gets its `@type` comment.
+fn translate_var_decl_into(cx: &TranslateCtx, v: &VarDecl, out: &mut Vec<swc::Stmt>) {
+    let first = out.len();
+    translate_var_decl_parts(cx, v, out);
+    if cx.options.ts_comments
+        && let Some(ty) = &v.ty
+    {
+        let decl = out[first..].iter_mut().find_map(|stmt| match stmt {
+            swc::Stmt::Decl(swc::Decl::Var(decl))
+                if matches!(&decl.decls[0].name, swc::Pat::Ident(id) if *id.sym == *v.name) =>
+            {
+                Some(decl)
+            }
+            _ => None,
+        });
+        if let Some(decl) = decl {
+            decl.span = cx.anchor(v.span);
+            cx.type_comments.borrow_mut().push((decl.span.lo, ts_comments::var_comment(ty, &cx.options)));
+        }
+    }
+}
 
-    swc::Stmt::Decl(swc::Decl::Var(Box::new(swc::VarDecl {
+fn translate_var_decl_parts(cx: &TranslateCtx, v: &VarDecl, out: &mut Vec<swc::Stmt>) {
+    match &v.init {
+        Expr::Loop(l) => {
+            let result = translate_value_loop(cx, l, out);
+            out.push(var_decl_stmt(v.kind, &v.name, swc::Expr::Ident(ident(&result))));
+        }
+        Expr::Match(m) => {
+            out.push(swc::Stmt::Decl(swc::Decl::Var(Box::new(swc::VarDecl {
+                span: DUMMY_SP,
+                ctxt: SyntaxContext::empty(),
+                kind: swc::VarDeclKind::Let,
+                declare: false,
+                decls: vec![swc::VarDeclarator {
+                    span: DUMMY_SP,
+                    name: swc::Pat::Ident(binding_ident(&v.name)),
+                    init: None,
+                    definite: false,
+                }],
+            }))));
+            out.push(scoped_stmt(translate_match_stmt(cx, m, Some(&v.name))));
+        }
+        _ => out.push(translate_var_decl_stmt(cx, v)),
+    }
+}
+
+fn translate_var_decl_stmt(cx: &TranslateCtx, v: &VarDecl) -> swc::Stmt {
+    var_decl_stmt(v.kind, &v.name, translate_expr(cx, &v.init))
+}
+
+fn var_decl_stmt(kind: VarKind, name: &str, init: swc::Expr) -> swc::Stmt {
+    swc::Stmt::Decl(swc::Decl::Var(var_decl(kind, name, init)))
+}
+
+fn var_decl(kind: VarKind, name: &str, init: swc::Expr) -> Box<swc::VarDecl> {
+    let kind = match kind {
+        VarKind::Let => swc::VarDeclKind::Const,
+        VarKind::Mut => swc::VarDeclKind::Let,
+        VarKind::Const => swc::VarDeclKind::Const,
+    };
+
+    Box::new(swc::VarDecl {
         span: DUMMY_SP,
         ctxt: SyntaxContext::empty(),
         kind,
         declare: false,
         decls: vec![swc::VarDeclarator {
             span: DUMMY_SP,
-            name: swc::Pat::Ident(binding_ident(&v.name)),
-            init: Some(Box::new(translate_expr(&v.init))),
+            name: swc::Pat::Ident(binding_ident(name)),
+            init: Some(Box::new(init)),
             definite: false,
         }],
-    })))
+    })
+}
+
+// ── Struct and enum declarations ───────────────────────────
+
+/// Push `decl` onto `body`, exported if `is_pub`.
+fn push_decl(body: &mut Vec<swc::ModuleItem>, decl: swc::Decl, is_pub: bool, span: Span) {
+    if is_pub {
+        let pos = item_pos(span);
+        body.push(swc::ModuleItem::ModuleDecl(swc::ModuleDecl::ExportDecl(swc::ExportDecl {
+            span: swc_common::Span::new(pos, pos),
+            decl,
+        })));
+    } else {
+        body.push(stmt_to_module_item(swc::Stmt::Decl(decl)));
+    }
+}
+
+/// The factory filling in the defaults of a struct that declares any,
+/// which its literals are built with:
+/// `function Config(fields) { return { retries: 3, ...fields }; }`.
+fn translate_struct_factory(cx: &TranslateCtx, s: &StructDecl) -> Option<swc::Decl> {
+    let defaults: Vec<swc::PropOrSpread> = s
+        .fields
+        .iter()
+        .filter_map(|f| Some(make_prop(&f.name, translate_expr(cx, f.default.as_ref()?))))
+        .collect();
+    if defaults.is_empty() {
+        return None;
+    }
+    let fields = swc::PropOrSpread::Spread(swc::SpreadElement {
+        dot3_token: DUMMY_SP,
+        expr: Box::new(swc::Expr::Ident(ident("fields"))),
+    });
+    let value = swc::Expr::Object(swc::ObjectLit {
+        span: DUMMY_SP,
+        props: defaults.into_iter().chain([fields]).collect(),
+    });
+    Some(swc::Decl::Fn(derive::fn_decl(&s.name, &["fields"], value)))
+}
+
+/// The runtime object of an enum, holding a tagged value for each unit
+/// variant and a constructor taking the fields in order for the others:
+/// `const Status = { Pending: { tag: "Pending" }, Active: (since)=>({ tag: "Active", since }) };`
+/// With the runtime helpers, constructors are `__ag.variant("Active", ["since"])`.
+fn translate_enum_decl(cx: &TranslateCtx, e: &EnumDecl) -> swc::Decl {
+    let props = e
+        .variants
+        .iter()
+        .map(|v| {
+            if !v.fields.is_empty() {
+                let fields = swc::Expr::Array(swc::ArrayLit {
+                    span: DUMMY_SP,
+                    elems: v.fields.iter().map(|f| Some(expr_or_spread(str_lit(&f.name)))).collect(),
+                });
+                if let Some(constructor) = cx.runtime_call("variant", vec![str_lit(&v.name), fields]) {
+                    return make_prop(&v.name, constructor);
+                }
+            }
+            let tag = make_prop("tag", str_lit(&v.name));
+            let value = swc::Expr::Object(swc::ObjectLit {
+                span: DUMMY_SP,
+                props: std::iter::once(tag)
+                    .chain(v.fields.iter().map(|f| {
+                        swc::PropOrSpread::Prop(Box::new(swc::Prop::Shorthand(ident(&f.name))))
+                    }))
+                    .collect(),
+            });
+            let value = if v.fields.is_empty() {
+                value
+            } else {
+                swc::Expr::Arrow(swc::ArrowExpr {
+                    span: DUMMY_SP,
+                    ctxt: SyntaxContext::empty(),
+                    params: v.fields.iter().map(|f| swc::Pat::Ident(binding_ident(&f.name))).collect(),
+                    body: Box::new(swc::BlockStmtOrExpr::Expr(Box::new(swc::Expr::Paren(swc::ParenExpr {
+                        span: DUMMY_SP,
+                        expr: Box::new(value),
+                    })))),
+                    is_async: false,
+                    is_generator: false,
+                    type_params: None,
+                    return_type: None,
+                })
+            };
+            make_prop(&v.name, value)
+        })
+        .collect();
+    swc::Decl::Var(var_decl(
+        VarKind::Const,
+        &e.name,
+        swc::Expr::Object(swc::ObjectLit { span: DUMMY_SP, props }),
+    ))
 }
 
 // ── Function declarations ──────────────────────────────────
 
-fn translate_fn_decl(f: &FnDecl) -> swc::FnDecl {
+fn translate_fn_decl(cx: &TranslateCtx, f: &FnDecl) -> swc::FnDecl {
     let params: Vec<swc::Param> = f
         .params
         .iter()
@@ -489,7 +1453,7 @@ fn translate_fn_decl(f: &FnDecl) -> swc::FnDecl {
                 swc::Pat::Assign(swc::AssignPat {
                     span: DUMMY_SP,
                     left: Box::new(swc::Pat::Ident(binding_ident(&p.name))),
-                    right: Box::new(translate_expr(default)),
+                    right: Box::new(cx.without_early_return(|| translate_expr(cx, default))),
                 })
             } else {
                 swc::Pat::Ident(binding_ident(&p.name))
@@ -502,7 +1466,16 @@ fn translate_fn_decl(f: &FnDecl) -> swc::FnDecl {
         })
         .collect();
 
-    let body = translate_block_with_implicit_return(&f.body);
+    let body = cx.in_function(|| {
+        let body = cx
+            .options
+            .optimize_tail_calls
+            .then(|| tail_call::loop_body(cx, f))
+            .flatten()
+            .unwrap_or_else(|| translate_block_with_implicit_return(cx, &f.body));
+        swc::BlockStmtOrExpr::BlockStmt(body)
+    });
+    let swc::BlockStmtOrExpr::BlockStmt(body) = body else { unreachable!("a block body stays a block") };
 
     swc::FnDecl {
         ident: ident(&f.name),
@@ -510,7 +1483,7 @@ fn translate_fn_decl(f: &FnDecl) -> swc::FnDecl {
         function: Box::new(swc::Function {
             params,
             decorators: Vec::new(),
-            span: DUMMY_SP,
+            span: cx.anchor(f.span),
             ctxt: SyntaxContext::empty(),
             body: Some(body),
             is_generator: false,
@@ -523,17 +1496,20 @@ fn translate_fn_decl(f: &FnDecl) -> swc::FnDecl {
 
 // ── Block translation ──────────────────────────────────────
 
-fn translate_block(block: &Block) -> swc::BlockStmt {
+fn translate_block(cx: &TranslateCtx, block: &Block) -> swc::BlockStmt {
     let mut stmts = Vec::new();
-    for stmt in &block.stmts {
-        stmts.push(translate_stmt(stmt));
-    }
-    if let Some(ref tail) = block.tail_expr {
-        stmts.push(swc::Stmt::Expr(swc::ExprStmt {
-            span: DUMMY_SP,
-            expr: Box::new(translate_expr(tail)),
-        }));
-    }
+    cx.shadowing(block_lets(block), || {
+        for stmt in &block.stmts {
+            translate_stmt_into(cx, stmt, &mut stmts);
+        }
+        if let Some(ref tail) = block.tail_expr {
+            with_hoisted(cx, &mut stmts, |out| {
+                let mut stmt = translate_expr_stmt(cx, tail);
+                cx.locate(&mut stmt, tail.span());
+                out.push(stmt);
+            });
+        }
+    });
     swc::BlockStmt {
         span: DUMMY_SP,
         ctxt: SyntaxContext::empty(),
@@ -541,17 +1517,129 @@ fn translate_block(block: &Block) -> swc::BlockStmt {
     }
 }
 
-fn translate_block_with_implicit_return(block: &Block) -> swc::BlockStmt {
+/// The names `block` declares with `let`, `mut` or `const`.
+fn block_lets(block: &Block) -> impl Iterator<Item = &str> {
+    block.stmts.iter().filter_map(|stmt| match stmt {
+        Stmt::VarDecl(v) => Some(v.name.as_str()),
+        _ => None,
+    })
+}
+
+/// The statements of `block`, with its tail expression assigned to `result`.
+fn translate_block_assigning(cx: &TranslateCtx, block: &Block, result: &str) -> Vec<swc::Stmt> {
     let mut stmts = Vec::new();
-    for stmt in &block.stmts {
-        stmts.push(translate_stmt(stmt));
+    cx.shadowing(block_lets(block), || {
+        for stmt in &block.stmts {
+            translate_stmt_into(cx, stmt, &mut stmts);
+        }
+        if let Some(ref tail) = block.tail_expr {
+            with_hoisted(cx, &mut stmts, |out| out.push(assign_stmt(result, translate_expr(cx, tail))));
+        }
+    });
+    stmts
+}
+
+fn block_stmt(stmts: Vec<swc::Stmt>) -> swc::Stmt {
+    swc::Stmt::Block(swc::BlockStmt {
+        span: DUMMY_SP,
+        ctxt: SyntaxContext::empty(),
+        stmts,
+    })
+}
+
+/// `stmts` as one statement, in a block if they declare temporaries.
+fn scoped_stmt(mut stmts: Vec<swc::Stmt>) -> swc::Stmt {
+    if stmts.len() == 1 && !matches!(stmts[0], swc::Stmt::Decl(_)) {
+        stmts.remove(0)
+    } else {
+        block_stmt(stmts)
     }
-    if let Some(ref tail) = block.tail_expr {
-        stmts.push(swc::Stmt::Return(swc::ReturnStmt {
+}
+
+/// `main().catch((e)=>{ console.error(e); process.exitCode = 1; });`, so
+/// that an error thrown by the module code wrapped into `main` is reported
+/// and fails the process rather than being an unhandled rejection.
+fn call_main(main: &str) -> swc::Stmt {
+    use derive::{call, member, var};
+    let report = swc::Stmt::Expr(swc::ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(call(member(var("console"), "error"), vec![var("e")])),
+    });
+    let exit_code = swc::Stmt::Expr(swc::ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(swc::Expr::Assign(swc::AssignExpr {
             span: DUMMY_SP,
-            arg: Some(Box::new(translate_expr(tail))),
-        }));
-    }
+            op: swc::AssignOp::Assign,
+            left: swc::AssignTarget::Simple(swc::SimpleAssignTarget::Member(swc::MemberExpr {
+                span: DUMMY_SP,
+                obj: Box::new(var("process")),
+                prop: swc::MemberProp::Ident(swc::IdentName { span: DUMMY_SP, sym: "exitCode".into() }),
+            })),
+            right: Box::new(swc::Expr::Lit(swc::Lit::Num(swc::Number { span: DUMMY_SP, value: 1.0, raw: None }))),
+        })),
+    });
+    let handler = swc::Expr::Arrow(swc::ArrowExpr {
+        span: DUMMY_SP,
+        ctxt: SyntaxContext::empty(),
+        params: vec![swc::Pat::Ident(binding_ident("e"))],
+        body: Box::new(swc::BlockStmtOrExpr::BlockStmt(swc::BlockStmt {
+            span: DUMMY_SP,
+            ctxt: SyntaxContext::empty(),
+            stmts: vec![report, exit_code],
+        })),
+        is_async: false,
+        is_generator: false,
+        type_params: None,
+        return_type: None,
+    });
+    let started = call(var(main), Vec::new());
+    swc::Stmt::Expr(swc::ExprStmt { span: DUMMY_SP, expr: Box::new(call(member(started, "catch"), vec![handler])) })
+}
+
+/// `name = value;`
+fn assign_stmt(name: &str, value: swc::Expr) -> swc::Stmt {
+    swc::Stmt::Expr(swc::ExprStmt { span: DUMMY_SP, expr: Box::new(assign_expr(name, value)) })
+}
+
+fn assign_expr(name: &str, value: swc::Expr) -> swc::Expr {
+    swc::Expr::Assign(swc::AssignExpr {
+        span: DUMMY_SP,
+        op: swc::AssignOp::Assign,
+        left: swc::AssignTarget::Simple(swc::SimpleAssignTarget::Ident(binding_ident(name))),
+        right: Box::new(value),
+    })
+}
+
+/// `let name;`, for a variable assigned later.
+fn let_decl_stmt(name: &str) -> swc::Stmt {
+    swc::Stmt::Decl(swc::Decl::Var(Box::new(swc::VarDecl {
+        span: DUMMY_SP,
+        ctxt: SyntaxContext::empty(),
+        kind: swc::VarDeclKind::Let,
+        declare: false,
+        decls: vec![swc::VarDeclarator {
+            span: DUMMY_SP,
+            name: swc::Pat::Ident(binding_ident(name)),
+            init: None,
+            definite: false,
+        }],
+    })))
+}
+
+fn translate_block_with_implicit_return(cx: &TranslateCtx, block: &Block) -> swc::BlockStmt {
+    let mut stmts = Vec::new();
+    cx.shadowing(block_lets(block), || {
+        for stmt in &block.stmts {
+            translate_stmt_into(cx, stmt, &mut stmts);
+        }
+        if let Some(ref tail) = block.tail_expr {
+            let first = stmts.len();
+            with_hoisted(cx, &mut stmts, |out| translate_return_into(cx, tail, out));
+            if let Some(first) = stmts.get_mut(first) {
+                cx.locate(first, tail.span());
+            }
+        }
+    });
     swc::BlockStmt {
         span: DUMMY_SP,
         ctxt: SyntaxContext::empty(),
@@ -561,22 +1649,57 @@ fn translate_block_with_implicit_return(block: &Block) -> swc::BlockStmt {
 
 // ── Statement translation ──────────────────────────────────
 
-fn translate_stmt(stmt: &Stmt) -> swc::Stmt {
+/// Translate a block statement into `out`; declarations may expand into
+/// several JS statements.
+fn translate_stmt_into(cx: &TranslateCtx, stmt: &Stmt, out: &mut Vec<swc::Stmt>) {
+    let first = out.len();
+    with_hoisted(cx, out, |out| match stmt {
+        Stmt::VarDecl(v) => translate_var_decl_into(cx, v, out),
+        Stmt::Return(ReturnStmt { value: Some(value), .. }) => translate_return_into(cx, value, out),
+        _ => out.push(translate_stmt(cx, stmt)),
+    });
+    if let Some(first) = out.get_mut(first) {
+        cx.locate(first, stmt.span());
+    }
+}
+
+/// Push the statements that `f` pushes onto `out`, preceded by the checks
+/// hoisted from the `?` operators in them:
+/// `let x = parse(s)?` → `const _tmp = parse(s); if (_tmp instanceof Error)
+/// return _tmp; const x = _tmp;`.
+fn with_hoisted(cx: &TranslateCtx, out: &mut Vec<swc::Stmt>, f: impl FnOnce(&mut Vec<swc::Stmt>)) {
+    let outer = cx.hoisted.borrow_mut().as_mut().map(std::mem::take);
+    let first = out.len();
+    f(out);
+    if let Some(outer) = outer {
+        let hoisted = cx.hoisted.replace(Some(outer)).unwrap_or_default();
+        out.splice(first..first, hoisted);
+    }
+}
+
+/// The statement built by `f`, in a block with the checks hoisted from it
+/// if there are any, so that they only run where the statement does.
+fn stmt_with_hoisted(cx: &TranslateCtx, f: impl FnOnce() -> swc::Stmt) -> swc::Stmt {
+    let mut stmts = Vec::new();
+    with_hoisted(cx, &mut stmts, |out| out.push(f()));
+    if stmts.len() == 1 {
+        stmts.remove(0)
+    } else {
+        block_stmt(stmts)
+    }
+}
+
+fn translate_stmt(cx: &TranslateCtx, stmt: &Stmt) -> swc::Stmt {
     match stmt {
-        Stmt::VarDecl(v) => translate_var_decl_stmt(v),
-        Stmt::ExprStmt(e) => swc::Stmt::Expr(swc::ExprStmt {
-            span: DUMMY_SP,
-            expr: Box::new(translate_expr(&e.expr)),
-        }),
+        Stmt::VarDecl(v) => translate_var_decl_stmt(cx, v),
+        Stmt::ExprStmt(e) => translate_expr_stmt(cx, &e.expr),
         Stmt::Return(r) => swc::Stmt::Return(swc::ReturnStmt {
             span: DUMMY_SP,
-            arg: r.value.as_ref().map(|v| Box::new(translate_expr(v))),
+            arg: r.value.as_ref().map(|v| Box::new(translate_expr(cx, v))),
         }),
-        Stmt::If(if_expr) => translate_if_stmt(if_expr),
-        Stmt::For(f) => swc::Stmt::ForOf(swc::ForOfStmt {
-            span: DUMMY_SP,
-            is_await: false,
-            left: swc::ForHead::VarDecl(Box::new(swc::VarDecl {
+        Stmt::If(if_expr) => translate_if_stmt(cx, if_expr),
+        Stmt::For(f) => {
+            let left = swc::ForHead::VarDecl(Box::new(swc::VarDecl {
                 span: DUMMY_SP,
                 ctxt: SyntaxContext::empty(),
                 kind: swc::VarDeclKind::Const,
@@ -587,128 +1710,378 @@ fn translate_stmt(stmt: &Stmt) -> swc::Stmt {
                     init: None,
                     definite: false,
                 }],
-            })),
-            right: Box::new(translate_expr(&f.iter)),
-            body: Box::new(swc::Stmt::Block(translate_block(&f.body))),
-        }),
-        Stmt::While(w) => swc::Stmt::While(swc::WhileStmt {
-            span: DUMMY_SP,
-            test: Box::new(translate_expr(&w.condition)),
-            body: Box::new(swc::Stmt::Block(translate_block(&w.body))),
-        }),
-        Stmt::Match(m) => {
-            let expr = translate_match(m);
-            swc::Stmt::Expr(swc::ExprStmt {
-                span: DUMMY_SP,
-                expr: Box::new(expr),
+            }));
+            let right = Box::new(translate_expr(cx, &f.iter));
+            let body = Box::new(swc::Stmt::Block(cx.shadowing([f.binding.as_str()], || translate_block(cx, &f.body))));
+            if cx.rewrites.for_in.contains(&f.span) {
+                swc::Stmt::ForIn(swc::ForInStmt { span: DUMMY_SP, left, right, body })
+            } else {
+                swc::Stmt::ForOf(swc::ForOfStmt { span: DUMMY_SP, is_await: false, left, right, body })
+            }
+        }
+        // The counter is a JS `let` even when declared with `let`, as the
+        // update assigns it.
+        Stmt::ForRange(fr) => {
+            let init = translate_expr(cx, &fr.init.init);
+            cx.shadowing([fr.init.name.as_str()], || {
+                swc::Stmt::For(swc::ForStmt {
+                    span: DUMMY_SP,
+                    init: Some(swc::VarDeclOrExpr::VarDecl(var_decl(VarKind::Mut, &fr.init.name, init))),
+                    test: Some(Box::new(cx.without_early_return(|| translate_expr(cx, &fr.condition)))),
+                    update: Some(Box::new(cx.without_early_return(|| translate_expr(cx, &fr.update)))),
+                    body: Box::new(swc::Stmt::Block(translate_block(cx, &fr.body))),
+                })
             })
         }
+        Stmt::While(w) => translate_while(cx, w),
+        Stmt::Loop(l) => translate_loop(cx, l, None),
+        Stmt::Break(b) => translate_break(cx, b),
+        Stmt::Match(m) => scoped_stmt(translate_match_stmt(cx, m, None)),
         Stmt::TryCatch(tc) => swc::Stmt::Try(Box::new(swc::TryStmt {
             span: DUMMY_SP,
-            block: translate_block(&tc.try_block),
+            block: translate_block(cx, &tc.try_block),
             handler: Some(swc::CatchClause {
                 span: DUMMY_SP,
                 param: Some(swc::Pat::Ident(binding_ident(&tc.catch_binding))),
-                body: translate_block(&tc.catch_block),
+                body: cx.shadowing([tc.catch_binding.as_str()], || translate_block(cx, &tc.catch_block)),
             }),
             finalizer: None,
         })),
     }
 }
 
-fn translate_if_stmt(if_expr: &IfExpr) -> swc::Stmt {
-    let alt = if_expr.else_branch.as_ref().map(|eb| {
+/// Push statements returning the value of `expr` onto `out`. An `if` or
+/// `match` returns from each branch rather than from an IIFE:
+/// `const _match = x; if (_match === 1) { return "one"; } else { return "many"; }`.
+fn translate_return_into(cx: &TranslateCtx, expr: &Expr, out: &mut Vec<swc::Stmt>) {
+    match expr {
+        Expr::If(i) if !is_ternary(i) => out.push(translate_returning_if(cx, i)),
+        Expr::Match(m) => out.push(scoped_stmt(translate_match_chain(cx, m, |body| match body {
+            MatchArmBody::Block(b) => translate_block_with_implicit_return(cx, b).stmts,
+            MatchArmBody::Expr(other) => {
+                let mut stmts = Vec::new();
+                with_hoisted(cx, &mut stmts, |out| translate_return_into(cx, other, out));
+                stmts
+            }
+        }))),
+        Expr::Loop(l) => {
+            let result = translate_value_loop(cx, l, out);
+            out.push(swc::Stmt::Return(swc::ReturnStmt {
+                span: DUMMY_SP,
+                arg: Some(Box::new(swc::Expr::Ident(ident(&result)))),
+            }));
+        }
+        Expr::Block(b) if !b.stmts.is_empty() => out.push(swc::Stmt::Block(translate_block_with_implicit_return(cx, b))),
+        _ => out.push(swc::Stmt::Return(swc::ReturnStmt {
+            span: DUMMY_SP,
+            arg: Some(Box::new(translate_expr(cx, expr))),
+        })),
+    }
+}
+
+/// Whether `i` is emitted as a ternary: every branch is a single
+/// expression that needs no statements of its own.
+fn is_ternary(i: &IfExpr) -> bool {
+    let propagates = |e: &Expr| matches!(e, Expr::ErrorPropagate(_));
+    let simple = |b: &Block| {
+        b.stmts.is_empty()
+            && b.tail_expr.as_deref().is_some_and(|tail| {
+                !matches!(tail, Expr::If(_) | Expr::Match(_) | Expr::Loop(_) | Expr::Block(_))
+                    && !walk::expr_any(tail, &propagates)
+            })
+    };
+    simple(&i.then_block)
+        && match &i.else_branch {
+            Some(ElseBranch::Block(b)) => simple(b),
+            Some(ElseBranch::If(elif)) => is_ternary(elif),
+            None => false,
+        }
+}
+
+/// An `if` statement returning the value of each branch.
+fn translate_returning_if(cx: &TranslateCtx, i: &IfExpr) -> swc::Stmt {
+    let alt = i.else_branch.as_ref().map(|eb| {
         Box::new(match eb {
-            ElseBranch::Block(b) => swc::Stmt::Block(translate_block(b)),
-            ElseBranch::If(nested) => translate_if_stmt(nested),
+            ElseBranch::Block(b) => swc::Stmt::Block(translate_block_with_implicit_return(cx, b)),
+            ElseBranch::If(elif) => stmt_with_hoisted(cx, || translate_returning_if(cx, elif)),
         })
     });
-
     swc::Stmt::If(swc::IfStmt {
         span: DUMMY_SP,
-        test: Box::new(translate_expr(&if_expr.condition)),
-        cons: Box::new(swc::Stmt::Block(translate_block(&if_expr.then_block))),
+        test: Box::new(translate_expr(cx, &i.condition)),
+        cons: Box::new(swc::Stmt::Block(translate_block_with_implicit_return(cx, &i.then_block))),
         alt,
     })
 }
 
-// ── Expression translation ─────────────────────────────────
+/// `while cond { ... }`. A condition with `?` is tested at the top of a
+/// `while (true)` loop, after the checks hoisted from it.
+fn translate_while(cx: &TranslateCtx, w: &WhileStmt) -> swc::Stmt {
+    let mut stmts = Vec::new();
+    let mut test = None;
+    with_hoisted(cx, &mut stmts, |_| test = Some(translate_expr(cx, &w.condition)));
+    let test = test.expect("condition is translated");
+    let mut body = translate_block(cx, &w.body);
+    let test = if stmts.is_empty() {
+        test
+    } else {
+        // if (!(cond)) break;
+        stmts.push(swc::Stmt::If(swc::IfStmt {
+            span: DUMMY_SP,
+            test: Box::new(swc::Expr::Unary(swc::UnaryExpr {
+                span: DUMMY_SP,
+                op: swc::UnaryOp::Bang,
+                arg: Box::new(swc::Expr::Paren(swc::ParenExpr { span: DUMMY_SP, expr: Box::new(test) })),
+            })),
+            cons: Box::new(swc::Stmt::Break(swc::BreakStmt { span: DUMMY_SP, label: None })),
+            alt: None,
+        }));
+        stmts.append(&mut body.stmts);
+        body.stmts = stmts;
+        bool_lit(true)
+    };
+    swc::Stmt::While(swc::WhileStmt {
+        span: DUMMY_SP,
+        test: Box::new(test),
+        body: Box::new(swc::Stmt::Block(body)),
+    })
+}
 
-fn translate_expr(expr: &Expr) -> swc::Expr {
+/// An expression whose value is discarded. Control flow is emitted as plain
+/// statements rather than IIFEs so `break` inside it reaches the enclosing
+/// loop.
+fn translate_expr_stmt(cx: &TranslateCtx, expr: &Expr) -> swc::Stmt {
     match expr {
-        Expr::Literal(lit) => translate_literal(lit),
-        Expr::Ident(id) => swc::Expr::Ident(ident(&id.name)),
-        Expr::Binary(b) => translate_binary(b),
-        Expr::Unary(u) => translate_unary(u),
-        Expr::Call(c) => translate_call(c),
-        Expr::Member(m) => translate_member(m),
-        Expr::Index(i) => swc::Expr::Member(swc::MemberExpr {
+        Expr::If(if_expr) => translate_if_stmt(cx, if_expr),
+        Expr::Match(m) => scoped_stmt(translate_match_stmt(cx, m, None)),
+        Expr::Loop(l) => translate_loop(cx, l, None),
+        Expr::Block(b) => swc::Stmt::Block(translate_block(cx, b)),
+        _ => swc::Stmt::Expr(swc::ExprStmt {
+            span: DUMMY_SP,
+            expr: Box::new(translate_expr(cx, expr)),
+        }),
+    }
+}
+
+/// Statement-position match: a plain if-else chain, to be wrapped in a
+/// block that scopes the subject temporary. Each arm's value is assigned
+/// to `result`, or discarded without one.
+fn translate_match_stmt(cx: &TranslateCtx, m: &MatchExpr, result: Option<&str>) -> Vec<swc::Stmt> {
+    translate_match_chain(cx, m, |body| match (body, result) {
+        (MatchArmBody::Block(b), Some(result)) => translate_block_assigning(cx, b, result),
+        (MatchArmBody::Block(b), None) => translate_block(cx, b).stmts,
+        (MatchArmBody::Expr(other), result) => {
+            let mut stmts = Vec::new();
+            with_hoisted(cx, &mut stmts, |out| {
+                out.push(match result {
+                    Some(result) => assign_stmt(result, translate_expr(cx, other)),
+                    None => translate_expr_stmt(cx, other),
+                })
+            });
+            stmts
+        }
+    })
+}
+
+/// `loop { ... }` → `while (true) { ... }`. `break <value>` in the body
+/// assigns to `result` when the loop's value is used.
+fn translate_loop(cx: &TranslateCtx, l: &LoopExpr, result: Option<String>) -> swc::Stmt {
+    cx.loop_results.borrow_mut().push(result);
+    let body = translate_block(cx, &l.body);
+    cx.loop_results.borrow_mut().pop();
+    swc::Stmt::While(swc::WhileStmt {
+        span: DUMMY_SP,
+        test: Box::new(swc::Expr::Lit(swc::Lit::Bool(swc::Bool {
+            span: DUMMY_SP,
+            value: true,
+        }))),
+        body: Box::new(swc::Stmt::Block(body)),
+    })
+}
+
+/// Push `let _loop_result; while (true) { ... }` onto `out` and return the
+/// name of the variable holding the loop's value.
+fn translate_value_loop(cx: &TranslateCtx, l: &LoopExpr, out: &mut Vec<swc::Stmt>) -> String {
+    let result = cx.fresh("_loop_result");
+    out.push(swc::Stmt::Decl(swc::Decl::Var(Box::new(swc::VarDecl {
+        span: DUMMY_SP,
+        ctxt: SyntaxContext::empty(),
+        kind: swc::VarDeclKind::Let,
+        declare: false,
+        decls: vec![swc::VarDeclarator {
+            span: DUMMY_SP,
+            name: swc::Pat::Ident(binding_ident(&result)),
+            init: None,
+            definite: false,
+        }],
+    }))));
+    out.push(translate_loop(cx, l, Some(result.clone())));
+    result
+}
+
+/// `break <value>` → `{ _loop_result = value; break; }`, or just evaluates
+/// the value when the enclosing loop's result is unused.
+fn translate_break(cx: &TranslateCtx, b: &BreakStmt) -> swc::Stmt {
+    let brk = swc::Stmt::Break(swc::BreakStmt {
+        span: DUMMY_SP,
+        label: None,
+    });
+    let Some(value) = &b.value else {
+        return brk;
+    };
+    let value = translate_expr(cx, value);
+    let result = cx.loop_results.borrow().last().cloned().flatten();
+    let expr = match result {
+        Some(name) => swc::Expr::Assign(swc::AssignExpr {
             span: DUMMY_SP,
-            obj: Box::new(translate_expr(&i.object)),
-            prop: swc::MemberProp::Computed(swc::ComputedPropName {
+            op: swc::AssignOp::Assign,
+            left: swc::AssignTarget::Simple(swc::SimpleAssignTarget::Ident(binding_ident(
+                &name,
+            ))),
+            right: Box::new(value),
+        }),
+        None => value,
+    };
+    swc::Stmt::Block(swc::BlockStmt {
+        span: DUMMY_SP,
+        ctxt: SyntaxContext::empty(),
+        stmts: vec![
+            swc::Stmt::Expr(swc::ExprStmt {
                 span: DUMMY_SP,
-                expr: Box::new(translate_expr(&i.index)),
+                expr: Box::new(expr),
             }),
-        }),
+            brk,
+        ],
+    })
+}
+
+fn translate_if_stmt(cx: &TranslateCtx, if_expr: &IfExpr) -> swc::Stmt {
+    let alt = if_expr.else_branch.as_ref().map(|eb| {
+        Box::new(match eb {
+            ElseBranch::Block(b) => swc::Stmt::Block(translate_block(cx, b)),
+            ElseBranch::If(nested) => stmt_with_hoisted(cx, || translate_if_stmt(cx, nested)),
+        })
+    });
+
+    swc::Stmt::If(swc::IfStmt {
+        span: DUMMY_SP,
+        test: Box::new(translate_expr(cx, &if_expr.condition)),
+        cons: Box::new(swc::Stmt::Block(translate_block(cx, &if_expr.then_block))),
+        alt,
+    })
+}
+
+// ── Expression translation ─────────────────────────────────
+
+fn translate_expr(cx: &TranslateCtx, expr: &Expr) -> swc::Expr {
+    match expr {
+        Expr::Literal(lit) => translate_literal(cx, lit),
+        Expr::Ident(id) => match cx.guard_bindings.borrow().get(&id.name) {
+            Some(value) => value.clone(),
+            None => swc::Expr::Ident(ident(&id.name)),
+        },
+        Expr::Binary(b) => translate_binary(cx, b),
+        Expr::Comparison(c) => translate_comparison_chain(cx, c),
+        Expr::Unary(u) => translate_unary(cx, u),
+        Expr::Call(c) => translate_call(cx, c),
+        Expr::Member(m) => translate_member(cx, m),
+        Expr::Index(i) => swc::Expr::Member(translate_index(cx, i)),
+        Expr::If(_) | Expr::Match(_)
+            if cx.hoisted.borrow().is_some() && walk::expr_any(expr, &|e| matches!(e, Expr::ErrorPropagate(_))) =>
+        {
+            translate_hoisted_branches(cx, expr)
+        }
         Expr::If(if_expr) => {
             // Translate as ternary if simple, else IIFE
             if let Some(ref else_branch) = if_expr.else_branch {
-                let alt_expr = match else_branch {
-                    ElseBranch::Block(b) => block_to_expr(b),
-                    ElseBranch::If(nested) => translate_expr(&Expr::If(nested.clone())),
-                };
+                let test = translate_expr(cx, &if_expr.condition);
+                let (cons, alt) = cx.without_early_return(|| {
+                    let alt = match else_branch {
+                        ElseBranch::Block(b) => block_to_expr(cx, b),
+                        ElseBranch::If(nested) => translate_expr(cx, &Expr::If(nested.clone())),
+                    };
+                    (block_to_expr(cx, &if_expr.then_block), alt)
+                });
                 swc::Expr::Cond(swc::CondExpr {
                     span: DUMMY_SP,
-                    test: Box::new(translate_expr(&if_expr.condition)),
-                    cons: Box::new(block_to_expr(&if_expr.then_block)),
-                    alt: Box::new(alt_expr),
+                    test: Box::new(test),
+                    cons: Box::new(cons),
+                    alt: Box::new(alt),
                 })
             } else {
                 // IIFE for if without else
-                let body = translate_block_with_implicit_return(&if_expr.then_block);
-                let if_stmt = swc::Stmt::If(swc::IfStmt {
-                    span: DUMMY_SP,
-                    test: Box::new(translate_expr(&if_expr.condition)),
-                    cons: Box::new(swc::Stmt::Block(body)),
-                    alt: None,
-                });
-                make_iife(vec![if_stmt])
+                cx.without_early_return(|| {
+                    let body = translate_block_with_implicit_return(cx, &if_expr.then_block);
+                    let if_stmt = swc::Stmt::If(swc::IfStmt {
+                        span: DUMMY_SP,
+                        test: Box::new(translate_expr(cx, &if_expr.condition)),
+                        cons: Box::new(swc::Stmt::Block(body)),
+                        alt: None,
+                    });
+                    make_iife(vec![if_stmt])
+                })
             }
         }
-        Expr::Match(m) => translate_match(m),
-        Expr::Block(b) => block_to_expr(b),
-        Expr::Array(arr) => swc::Expr::Array(swc::ArrayLit {
-            span: DUMMY_SP,
-            elems: arr
-                .elements
-                .iter()
-                .map(|e| Some(expr_or_spread(translate_expr(e))))
-                .collect(),
-        }),
-        Expr::Object(obj) => swc::Expr::Object(swc::ObjectLit {
-            span: DUMMY_SP,
-            props: obj
-                .fields
-                .iter()
-                .map(|f| {
-                    swc::PropOrSpread::Prop(Box::new(swc::Prop::KeyValue(swc::KeyValueProp {
-                        key: swc::PropName::Ident(swc::IdentName {
-                            span: DUMMY_SP,
-                            sym: f.key.clone().into(),
-                        }),
-                        value: Box::new(translate_expr(&f.value)),
-                    })))
-                })
-                .collect(),
+        Expr::Match(m) => translate_match(cx, m),
+        Expr::Loop(l) => cx.without_early_return(|| {
+            let mut stmts = Vec::new();
+            let result = translate_value_loop(cx, l, &mut stmts);
+            stmts.push(swc::Stmt::Return(swc::ReturnStmt {
+                span: DUMMY_SP,
+                arg: Some(Box::new(swc::Expr::Ident(ident(&result)))),
+            }));
+            make_iife(stmts)
         }),
-        Expr::Arrow(arrow) => translate_arrow(arrow),
-        Expr::Pipe(p) => translate_pipe(p),
+        Expr::Block(b) => block_to_expr(cx, b),
+        Expr::Array(arr) => {
+            let elements: Vec<&Expr> = arr.elements.iter().collect();
+            swc::Expr::Array(swc::ArrayLit {
+                span: DUMMY_SP,
+                elems: translate_in_order(cx, &elements)
+                    .into_iter()
+                    .map(|e| Some(expr_or_spread(e)))
+                    .collect(),
+            })
+        }
+        Expr::Object(obj) => {
+            let values: Vec<&Expr> = obj.fields.iter().map(|f| &f.value).collect();
+            let lit = swc::Expr::Object(swc::ObjectLit {
+                span: DUMMY_SP,
+                props: obj
+                    .fields
+                    .iter()
+                    .zip(translate_in_order(cx, &values))
+                    .map(|(f, value)| {
+                        swc::PropOrSpread::Prop(Box::new(swc::Prop::KeyValue(swc::KeyValueProp {
+                            key: swc::PropName::Ident(swc::IdentName {
+                                span: DUMMY_SP,
+                                sym: f.key.clone().into(),
+                            }),
+                            value: Box::new(value),
+                        })))
+                    })
+                    .collect(),
+            });
+            match cx.rewrites.struct_literals.get(&obj.span) {
+                // `Config({ verbose: true })`
+                Some(name) => swc::Expr::Call(swc::CallExpr {
+                    span: DUMMY_SP,
+                    ctxt: SyntaxContext::empty(),
+                    callee: swc::Callee::Expr(Box::new(swc::Expr::Ident(ident(name)))),
+                    args: vec![expr_or_spread(lit)],
+                    type_args: None,
+                }),
+                _ => lit,
+            }
+        }
+        Expr::Arrow(arrow) => translate_arrow(cx, arrow),
+        Expr::Pipe(p) => translate_pipe(cx, p),
         Expr::OptionalChain(oc) => swc::Expr::OptChain(swc::OptChainExpr {
             span: DUMMY_SP,
             optional: true,
             base: Box::new(swc::OptChainBase::Member(swc::MemberExpr {
                 span: DUMMY_SP,
-                obj: Box::new(translate_expr(&oc.object)),
+                obj: Box::new(translate_expr(cx, &oc.object)),
                 prop: swc::MemberProp::Ident(swc::IdentName {
                     span: DUMMY_SP,
                     sym: oc.field.clone().into(),
@@ -718,26 +2091,44 @@ fn translate_expr(expr: &Expr) -> swc::Expr {
         Expr::NullishCoalesce(nc) => swc::Expr::Bin(swc::BinExpr {
             span: DUMMY_SP,
             op: swc::BinaryOp::NullishCoalescing,
-            left: Box::new(translate_expr(&nc.left)),
-            right: Box::new(translate_expr(&nc.right)),
+            left: Box::new(translate_expr(cx, &nc.left)),
+            right: Box::new(cx.without_early_return(|| translate_expr(cx, &nc.right))),
         }),
         Expr::Await(a) => swc::Expr::Await(swc::AwaitExpr {
             span: DUMMY_SP,
-            arg: Box::new(translate_expr(&a.expr)),
+            arg: Box::new(translate_expr(cx, &a.expr)),
         }),
-        Expr::ErrorPropagate(ep) => translate_error_propagate(ep),
-        Expr::Assign(assign) => translate_assign(assign),
-        Expr::TemplateString(ts) => translate_template_string(ts),
+        Expr::ErrorPropagate(ep) => translate_error_propagate(cx, ep),
+        Expr::Assign(assign) => translate_assign(cx, assign),
+        Expr::TemplateString(ts) => translate_template_string(cx, ts),
+        Expr::TaggedTemplate(tt) => {
+            let operands: Vec<&Expr> = std::iter::once(&*tt.tag).chain(template_exprs(&tt.template)).collect();
+            let mut values = translate_in_order(cx, &operands);
+            let tag = values.remove(0);
+            swc::Expr::TaggedTpl(swc::TaggedTpl {
+                span: DUMMY_SP,
+                ctxt: SyntaxContext::empty(),
+                tag: Box::new(tag),
+                type_params: None,
+                tpl: Box::new(translate_tpl(&tt.template, values)),
+            })
+        }
         Expr::Placeholder(_) => swc::Expr::Ident(ident("undefined")),
     }
 }
 
-fn translate_literal(lit: &Literal) -> swc::Expr {
+fn translate_literal(cx: &TranslateCtx, lit: &Literal) -> swc::Expr {
     match lit {
-        Literal::Int(val, _) => swc::Expr::Lit(swc::Lit::Num(swc::Number {
+        // `-42` is emitted as `-(42)`, as JS has no negative literals
+        Literal::Int(val, text, span) if *val < 0 => {
+            negate(translate_literal(cx, &Literal::Int(-val, text[1..].to_string(), *span)))
+        }
+        Literal::Float(val, span) if val.is_sign_negative() => negate(translate_literal(cx, &Literal::Float(-val, *span))),
+        Literal::Int(val, text, _) => swc::Expr::Lit(swc::Lit::Num(swc::Number {
             span: DUMMY_SP,
             value: *val as f64,
-            raw: None,
+            // Beyond 2^53 the f64 value is rounded; keep the digits as written
+            raw: (val.unsigned_abs() > MAX_SAFE_INTEGER).then(|| text.as_str().into()),
         })),
         Literal::Float(val, _) => swc::Expr::Lit(swc::Lit::Num(swc::Number {
             span: DUMMY_SP,
@@ -749,16 +2140,88 @@ fn translate_literal(lit: &Literal) -> swc::Expr {
             value: s.clone().into(),
             raw: None,
         })),
+        // `'a'` → `97`
+        Literal::Char(c, _) => swc::Expr::Lit(swc::Lit::Num(swc::Number {
+            span: DUMMY_SP,
+            value: *c as u32 as f64,
+            raw: None,
+        })),
         Literal::Bool(b, _) => swc::Expr::Lit(swc::Lit::Bool(swc::Bool {
             span: DUMMY_SP,
             value: *b,
         })),
+        Literal::Nil(_) if cx.options.nil_to_undefined => swc::Expr::Ident(ident("undefined")),
         Literal::Nil(_) => swc::Expr::Lit(swc::Lit::Null(swc::Null { span: DUMMY_SP })),
     }
 }
 
-fn translate_binary(b: &BinaryExpr) -> swc::Expr {
-    let op = match b.op {
+fn translate_binary(cx: &TranslateCtx, b: &BinaryExpr) -> swc::Expr {
+    if b.op == BinaryOp::In {
+        return translate_in(cx, b);
+    }
+    let op = swc_binary_op(b.op);
+    let (left, right) = match b.op {
+        // The right operand is evaluated conditionally
+        BinaryOp::And | BinaryOp::Or => (
+            translate_expr(cx, &b.left),
+            cx.without_early_return(|| translate_expr(cx, &b.right)),
+        ),
+        _ => translate_operands(cx, b),
+    };
+    swc::Expr::Bin(swc::BinExpr {
+        span: DUMMY_SP,
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    })
+}
+
+/// `a < f() <= c` → `a < (_cmp = f()) && _cmp <= c`. A middle operand
+/// other than a name or literal is assigned to a temporary where it is
+/// first compared, so that every operand runs once, in order, and only
+/// while the comparisons before it hold.
+fn translate_comparison_chain(cx: &TranslateCtx, chain: &ComparisonChain) -> swc::Expr {
+    let last = chain.operands.len() - 1;
+    let mut first_two = translate_in_order(cx, &[&chain.operands[0], &chain.operands[1]]).into_iter();
+    let mut left = first_two.next().expect("two operands");
+    let mut comparisons = Vec::new();
+    for (i, (op, _)) in chain.ops.iter().enumerate() {
+        let operand = &chain.operands[i + 1];
+        let value = match first_two.next() {
+            Some(value) => value,
+            None => cx.without_early_return(|| translate_expr(cx, operand)),
+        };
+        let (right, next) = if i + 1 < last && !matches!(operand, Expr::Ident(_) | Expr::Literal(_)) {
+            let tmp = cx.fresh("_cmp");
+            cx.declare_temp(&tmp);
+            let assign = swc::Expr::Paren(swc::ParenExpr { span: DUMMY_SP, expr: Box::new(assign_expr(&tmp, value)) });
+            (assign, swc::Expr::Ident(ident(&tmp)))
+        } else {
+            (value.clone(), value)
+        };
+        comparisons.push(swc::Expr::Bin(swc::BinExpr {
+            span: DUMMY_SP,
+            op: swc_binary_op(*op),
+            left: Box::new(left),
+            right: Box::new(right),
+        }));
+        left = next;
+    }
+    comparisons
+        .into_iter()
+        .reduce(|acc, cmp| {
+            swc::Expr::Bin(swc::BinExpr {
+                span: DUMMY_SP,
+                op: swc::BinaryOp::LogicalAnd,
+                left: Box::new(acc),
+                right: Box::new(cmp),
+            })
+        })
+        .expect("a chain has comparisons")
+}
+
+fn swc_binary_op(op: BinaryOp) -> swc::BinaryOp {
+    match op {
         BinaryOp::Add => swc::BinaryOp::Add,
         BinaryOp::Sub => swc::BinaryOp::Sub,
         BinaryOp::Mul => swc::BinaryOp::Mul,
@@ -773,16 +2236,85 @@ fn translate_binary(b: &BinaryExpr) -> swc::Expr {
         BinaryOp::Ge => swc::BinaryOp::GtEq,
         BinaryOp::And => swc::BinaryOp::LogicalAnd,
         BinaryOp::Or => swc::BinaryOp::LogicalOr,
+        BinaryOp::In => swc::BinaryOp::In,
+    }
+}
+
+fn translate_operands(cx: &TranslateCtx, b: &BinaryExpr) -> (swc::Expr, swc::Expr) {
+    let mut operands = translate_in_order(cx, &[&b.left, &b.right]);
+    let right = operands.pop().expect("two operands");
+    (operands.pop().expect("two operands"), right)
+}
+
+/// Translate `exprs`, which JS evaluates left to right. An operand followed
+/// by one with a `?` is saved to a temporary first, so that it still runs
+/// before the checks hoisted from the later one.
+fn translate_in_order(cx: &TranslateCtx, exprs: &[&Expr]) -> Vec<swc::Expr> {
+    let propagates = |e: &Expr| matches!(e, Expr::ErrorPropagate(_));
+    let mut out = Vec::with_capacity(exprs.len());
+    for (i, expr) in exprs.iter().enumerate() {
+        let value = translate_expr(cx, expr);
+        let spill = !matches!(expr, Expr::Literal(_) | Expr::Ident(_) | Expr::ErrorPropagate(_))
+            && exprs[i + 1..].iter().any(|e| walk::expr_any(e, &propagates));
+        match cx.hoisted.borrow_mut().as_mut() {
+            Some(hoisted) if spill => {
+                let tmp = cx.fresh("_tmp");
+                hoisted.push(var_decl_stmt(VarKind::Let, &tmp, value));
+                out.push(swc::Expr::Ident(ident(&tmp)));
+            }
+            _ => out.push(value),
+        }
+    }
+    out
+}
+
+/// `x in xs`: `xs.includes(x)` for arrays and strings, `Object.hasOwn(m, k)`
+/// for maps, and JS `in` otherwise.
+fn translate_in(cx: &TranslateCtx, b: &BinaryExpr) -> swc::Expr {
+    let (left, right) = translate_operands(cx, b);
+    let (callee, args) = match cx.rewrites.membership.get(&b.span) {
+        Some(Membership::Includes) => (
+            swc::Expr::Member(swc::MemberExpr {
+                span: DUMMY_SP,
+                obj: Box::new(right),
+                prop: swc::MemberProp::Ident(swc::IdentName {
+                    span: DUMMY_SP,
+                    sym: "includes".into(),
+                }),
+            }),
+            vec![expr_or_spread(left)],
+        ),
+        Some(Membership::HasOwn) => (
+            dotted_path("Object.hasOwn"),
+            vec![expr_or_spread(right), expr_or_spread(left)],
+        ),
+        _ => {
+            return swc::Expr::Bin(swc::BinExpr {
+                span: DUMMY_SP,
+                op: swc::BinaryOp::In,
+                left: Box::new(left),
+                right: Box::new(right),
+            });
+        }
     };
-    swc::Expr::Bin(swc::BinExpr {
+    swc::Expr::Call(swc::CallExpr {
         span: DUMMY_SP,
-        op,
-        left: Box::new(translate_expr(&b.left)),
-        right: Box::new(translate_expr(&b.right)),
+        ctxt: SyntaxContext::empty(),
+        callee: swc::Callee::Expr(Box::new(callee)),
+        args,
+        type_args: None,
+    })
+}
+
+fn negate(arg: swc::Expr) -> swc::Expr {
+    swc::Expr::Unary(swc::UnaryExpr {
+        span: DUMMY_SP,
+        op: swc::UnaryOp::Minus,
+        arg: Box::new(arg),
     })
 }
 
-fn translate_unary(u: &UnaryExpr) -> swc::Expr {
+fn translate_unary(cx: &TranslateCtx, u: &UnaryExpr) -> swc::Expr {
     let op = match u.op {
         UnaryOp::Not => swc::UnaryOp::Bang,
         UnaryOp::Neg => swc::UnaryOp::Minus,
@@ -790,49 +2322,142 @@ fn translate_unary(u: &UnaryExpr) -> swc::Expr {
     swc::Expr::Unary(swc::UnaryExpr {
         span: DUMMY_SP,
         op,
-        arg: Box::new(translate_expr(&u.operand)),
+        arg: Box::new(translate_expr(cx, &u.operand)),
     })
 }
 
-fn translate_call(c: &CallExpr) -> swc::Expr {
+fn translate_call(cx: &TranslateCtx, c: &CallExpr) -> swc::Expr {
+    let (callee, receiver) = match c.callee.as_ref() {
+        // `m.keys()` → `Object.keys(m)`
+        Expr::Member(m) => match cx.rewrites.members.get(&m.span) {
+            Some(MemberRewrite::ReceiverCall(func)) => (dotted_path(func), Some(&*m.object)),
+            _ => (translate_expr(cx, &c.callee), None),
+        },
+        callee => (translate_expr(cx, callee), None),
+    };
+    let args: Vec<&Expr> = receiver.into_iter().chain(&c.args).collect();
+    let args: Vec<swc::ExprOrSpread> = translate_in_order(cx, &args).into_iter().map(expr_or_spread).collect();
     swc::Expr::Call(swc::CallExpr {
-        span: DUMMY_SP,
+        // The call's own span starts at its arguments
+        span: cx.span(Span::new(c.callee.span().start, c.span.end)),
         ctxt: SyntaxContext::empty(),
-        callee: swc::Callee::Expr(Box::new(translate_expr(&c.callee))),
-        args: c.args.iter().map(|a| expr_or_spread(translate_expr(a))).collect(),
+        callee: swc::Callee::Expr(Box::new(callee)),
+        args,
         type_args: None,
     })
 }
 
-fn translate_member(m: &MemberExpr) -> swc::Expr {
+/// Build a member chain from a dotted JS path such as `Object.keys`.
+fn dotted_path(path: &str) -> swc::Expr {
+    let mut segments = path.split('.');
+    let first = segments.next().unwrap_or_default();
+    segments.fold(swc::Expr::Ident(ident(first)), |obj, seg| {
+        swc::Expr::Member(swc::MemberExpr {
+            span: DUMMY_SP,
+            obj: Box::new(obj),
+            prop: swc::MemberProp::Ident(swc::IdentName {
+                span: DUMMY_SP,
+                sym: seg.into(),
+            }),
+        })
+    })
+}
+
+fn translate_member(cx: &TranslateCtx, m: &MemberExpr) -> swc::Expr {
     // Check if this is an enum variant construction: Enum::Variant or Enum::Variant(...)
     // We detect this pattern: Member { object: Ident(EnumName), field: VariantName }
     // For now, just do regular member access
-    swc::Expr::Member(swc::MemberExpr {
+    if let Some(name) = cx.rewrites.associated_fns.get(&m.span) {
+        return swc::Expr::Ident(ident(name));
+    }
+    let field = match cx.rewrites.members.get(&m.span) {
+        Some(MemberRewrite::Rename(name)) => name,
+        // Not called directly: bind the receiver, `Object.keys.bind(null, m)`
+        Some(MemberRewrite::ReceiverCall(func)) => {
+            let bind = swc::Expr::Member(swc::MemberExpr {
+                span: DUMMY_SP,
+                obj: Box::new(dotted_path(func)),
+                prop: swc::MemberProp::Ident(swc::IdentName {
+                    span: DUMMY_SP,
+                    sym: "bind".into(),
+                }),
+            });
+            return swc::Expr::Call(swc::CallExpr {
+                span: DUMMY_SP,
+                ctxt: SyntaxContext::empty(),
+                callee: swc::Callee::Expr(Box::new(bind)),
+                args: vec![
+                    expr_or_spread(swc::Expr::Lit(swc::Lit::Null(swc::Null { span: DUMMY_SP }))),
+                    expr_or_spread(translate_expr(cx, &m.object)),
+                ],
+                type_args: None,
+            });
+        }
+        None => &m.field,
+    };
+    swc::Expr::Member(member_expr(cx, &m.object, field))
+}
+
+/// `object.field`, with `field` already mapped to its JS name.
+fn member_expr(cx: &TranslateCtx, object: &Expr, field: &str) -> swc::MemberExpr {
+    swc::MemberExpr {
         span: DUMMY_SP,
-        obj: Box::new(translate_expr(&m.object)),
+        obj: Box::new(translate_expr(cx, object)),
         prop: swc::MemberProp::Ident(swc::IdentName {
             span: DUMMY_SP,
-            sym: m.field.clone().into(),
+            sym: field.into(),
         }),
-    })
+    }
+}
+
+/// `object[index]`
+fn translate_index(cx: &TranslateCtx, i: &IndexExpr) -> swc::MemberExpr {
+    let mut operands = translate_in_order(cx, &[&i.object, &i.index]);
+    let index = operands.pop().expect("two operands");
+    index_expr(operands.pop().expect("two operands"), index)
+}
+
+fn index_expr(object: swc::Expr, index: swc::Expr) -> swc::MemberExpr {
+    swc::MemberExpr {
+        span: DUMMY_SP,
+        obj: Box::new(object),
+        prop: swc::MemberProp::Computed(swc::ComputedPropName {
+            span: DUMMY_SP,
+            expr: Box::new(index),
+        }),
+    }
 }
 
-fn translate_arrow(arrow: &ArrowExpr) -> swc::Expr {
+fn translate_arrow(cx: &TranslateCtx, arrow: &ArrowExpr) -> swc::Expr {
     let params: Vec<swc::Pat> = arrow
         .params
         .iter()
         .map(|p| swc::Pat::Ident(binding_ident(&p.name)))
         .collect();
 
-    let body = match &arrow.body {
+    let names = arrow.params.iter().map(|p| p.name.as_str());
+    let body = cx.shadowing(names, || cx.in_function(|| match &arrow.body {
         ArrowBody::Expr(e) => {
-            swc::BlockStmtOrExpr::Expr(Box::new(translate_expr(e)))
+            let mut stmts = Vec::new();
+            let mut value = None;
+            with_hoisted(cx, &mut stmts, |_| value = Some(translate_expr(cx, e)));
+            let value = value.expect("body is translated");
+            if stmts.is_empty() {
+                swc::BlockStmtOrExpr::Expr(Box::new(value))
+            } else {
+                // `(s) => parse(s)?` needs statements to return early from
+                stmts.push(swc::Stmt::Return(swc::ReturnStmt { span: DUMMY_SP, arg: Some(Box::new(value)) }));
+                swc::BlockStmtOrExpr::BlockStmt(swc::BlockStmt {
+                    span: DUMMY_SP,
+                    ctxt: SyntaxContext::empty(),
+                    stmts,
+                })
+            }
         }
         ArrowBody::Block(b) => {
-            swc::BlockStmtOrExpr::BlockStmt(translate_block_with_implicit_return(b))
+            swc::BlockStmtOrExpr::BlockStmt(translate_block_with_implicit_return(cx, b))
         }
-    };
+    }));
 
     swc::Expr::Arrow(swc::ArrowExpr {
         span: DUMMY_SP,
@@ -846,8 +2471,8 @@ fn translate_arrow(arrow: &ArrowExpr) -> swc::Expr {
     })
 }
 
-fn translate_pipe(p: &PipeExpr) -> swc::Expr {
-    let left = translate_expr(&p.left);
+fn translate_pipe(cx: &TranslateCtx, p: &PipeExpr) -> swc::Expr {
+    let left = translate_expr(cx, &p.left);
 
     // Check if right side is a call with placeholder
     match &p.right {
@@ -862,14 +2487,14 @@ fn translate_pipe(p: &PipeExpr) -> swc::Expr {
                         if matches!(a, Expr::Placeholder(_)) {
                             expr_or_spread(left.clone())
                         } else {
-                            expr_or_spread(translate_expr(a))
+                            expr_or_spread(translate_expr(cx, a))
                         }
                     })
                     .collect();
                 swc::Expr::Call(swc::CallExpr {
                     span: DUMMY_SP,
                     ctxt: SyntaxContext::empty(),
-                    callee: swc::Callee::Expr(Box::new(translate_expr(&call.callee))),
+                    callee: swc::Callee::Expr(Box::new(translate_expr(cx, &call.callee))),
                     args,
                     type_args: None,
                 })
@@ -881,7 +2506,7 @@ fn translate_pipe(p: &PipeExpr) -> swc::Expr {
                 swc::Expr::Call(swc::CallExpr {
                     span: DUMMY_SP,
                     ctxt: SyntaxContext::empty(),
-                    callee: swc::Callee::Expr(Box::new(translate_expr(&p.right))),
+                    callee: swc::Callee::Expr(Box::new(translate_expr(cx, &p.right))),
                     args: vec![expr_or_spread(left)],
                     type_args: None,
                 })
@@ -892,7 +2517,7 @@ fn translate_pipe(p: &PipeExpr) -> swc::Expr {
             swc::Expr::Call(swc::CallExpr {
                 span: DUMMY_SP,
                 ctxt: SyntaxContext::empty(),
-                callee: swc::Callee::Expr(Box::new(translate_expr(&p.right))),
+                callee: swc::Callee::Expr(Box::new(translate_expr(cx, &p.right))),
                 args: vec![expr_or_spread(left)],
                 type_args: None,
             })
@@ -902,7 +2527,7 @@ fn translate_pipe(p: &PipeExpr) -> swc::Expr {
             swc::Expr::Call(swc::CallExpr {
                 span: DUMMY_SP,
                 ctxt: SyntaxContext::empty(),
-                callee: swc::Callee::Expr(Box::new(translate_expr(&p.right))),
+                callee: swc::Callee::Expr(Box::new(translate_expr(cx, &p.right))),
                 args: vec![expr_or_spread(left)],
                 type_args: None,
             })
@@ -910,77 +2535,120 @@ fn translate_pipe(p: &PipeExpr) -> swc::Expr {
     }
 }
 
-fn translate_error_propagate(ep: &ErrorPropagateExpr) -> swc::Expr {
-    // expr? → (()=>{ const _tmp = expr; if (_tmp instanceof Error) return _tmp; return _tmp; })()
-    let tmp = "_tmp";
-    let inner = translate_expr(&ep.expr);
-
-    let body = swc::BlockStmt {
-        span: DUMMY_SP,
-        ctxt: SyntaxContext::empty(),
-        stmts: vec![
-            // const _tmp = expr;
-            swc::Stmt::Decl(swc::Decl::Var(Box::new(swc::VarDecl {
-                span: DUMMY_SP,
-                ctxt: SyntaxContext::empty(),
-                kind: swc::VarDeclKind::Const,
-                declare: false,
-                decls: vec![swc::VarDeclarator {
-                    span: DUMMY_SP,
-                    name: swc::Pat::Ident(binding_ident(tmp)),
-                    init: Some(Box::new(inner)),
-                    definite: false,
-                }],
-            }))),
-            // if (_tmp instanceof Error) return _tmp;
-            swc::Stmt::If(swc::IfStmt {
-                span: DUMMY_SP,
-                test: Box::new(swc::Expr::Bin(swc::BinExpr {
-                    span: DUMMY_SP,
-                    op: swc::BinaryOp::InstanceOf,
-                    left: Box::new(swc::Expr::Ident(ident(tmp))),
-                    right: Box::new(swc::Expr::Ident(ident("Error"))),
-                })),
-                cons: Box::new(swc::Stmt::Return(swc::ReturnStmt {
-                    span: DUMMY_SP,
-                    arg: Some(Box::new(swc::Expr::Ident(ident(tmp)))),
-                })),
-                alt: None,
-            }),
-            // return _tmp;
-            swc::Stmt::Return(swc::ReturnStmt {
-                span: DUMMY_SP,
-                arg: Some(Box::new(swc::Expr::Ident(ident(tmp)))),
-            }),
-        ],
+/// `expr?` → `_tmp`, after `const _tmp = expr; if (_tmp instanceof Error)
+/// return _tmp;` is hoisted in front of the enclosing statement.
+fn translate_error_propagate(cx: &TranslateCtx, ep: &ErrorPropagateExpr) -> swc::Expr {
+    let inner = translate_expr(cx, &ep.expr);
+    let mut hoisted = cx.hoisted.borrow_mut();
+    let Some(hoisted) = hoisted.as_mut() else {
+        if cx.in_function.get() {
+            cx.error(
+                "`?` cannot return early from here, as its operand is not always evaluated; bind the value with `let` first",
+                ep.span,
+            );
+        } else {
+            cx.error("`?` outside of a function has nothing to return from", ep.span);
+        }
+        return inner;
     };
-
-    make_iife(body.stmts)
+    let tmp = &cx.fresh("_tmp");
+    hoisted.push(var_decl_stmt(VarKind::Let, tmp, inner));
+    let test = cx
+        .runtime_call("isError", vec![swc::Expr::Ident(ident(tmp))])
+        .unwrap_or_else(|| {
+            swc::Expr::Bin(swc::BinExpr {
+                span: DUMMY_SP,
+                op: swc::BinaryOp::InstanceOf,
+                left: Box::new(swc::Expr::Ident(ident(tmp))),
+                right: Box::new(swc::Expr::Ident(ident("Error"))),
+            })
+        });
+    hoisted.push(swc::Stmt::If(swc::IfStmt {
+        span: DUMMY_SP,
+        test: Box::new(test),
+        cons: Box::new(swc::Stmt::Return(swc::ReturnStmt {
+            span: DUMMY_SP,
+            arg: Some(Box::new(swc::Expr::Ident(ident(tmp)))),
+        })),
+        alt: None,
+    }));
+    swc::Expr::Ident(ident(tmp))
 }
 
-fn translate_assign(assign: &AssignExpr) -> swc::Expr {
+fn translate_assign(cx: &TranslateCtx, assign: &AssignExpr) -> swc::Expr {
     let op = match assign.op {
         AssignOp::Assign => swc::AssignOp::Assign,
         AssignOp::AddAssign => swc::AssignOp::AddAssign,
         AssignOp::SubAssign => swc::AssignOp::SubAssign,
         AssignOp::MulAssign => swc::AssignOp::MulAssign,
         AssignOp::DivAssign => swc::AssignOp::DivAssign,
+        AssignOp::ModAssign => swc::AssignOp::ModAssign,
+    };
+
+    // The target's operands are evaluated before the value
+    let operands: Vec<&Expr> = match &assign.target {
+        Expr::Member(m) => vec![&m.object, &assign.value],
+        Expr::Index(i) => vec![&i.object, &i.index, &assign.value],
+        _ => vec![&assign.value],
+    };
+    let mut values = translate_in_order(cx, &operands).into_iter();
+    let right = values.next_back().expect("the value is an operand");
+    let mut operand = || values.next().expect("one value per operand");
+
+    let left = match &assign.target {
+        // `obj.field = v`; a renamed builtin field is assigned by its JS
+        // name (`xs.len = 0` → `xs.length = 0`).
+        Expr::Member(m) => {
+            let field = match cx.rewrites.members.get(&m.span) {
+                Some(MemberRewrite::Rename(name)) => name,
+                _ => &m.field,
+            };
+            swc::SimpleAssignTarget::Member(swc::MemberExpr {
+                span: DUMMY_SP,
+                obj: Box::new(operand()),
+                prop: swc::MemberProp::Ident(swc::IdentName {
+                    span: DUMMY_SP,
+                    sym: field.as_str().into(),
+                }),
+            })
+        }
+        // `arr[i] = v`, `m["k"] = v`
+        Expr::Index(_) => {
+            let object = operand();
+            swc::SimpleAssignTarget::Member(index_expr(object, operand()))
+        }
+        Expr::Ident(id) => swc::SimpleAssignTarget::Ident(binding_ident(&id.name)),
+        // Rejected by the checker, but code may be generated unchecked
+        target => {
+            cx.error("invalid assignment target", target.span());
+            swc::SimpleAssignTarget::Invalid(swc::Invalid { span: DUMMY_SP })
+        }
     };
 
     swc::Expr::Assign(swc::AssignExpr {
         span: DUMMY_SP,
         op,
-        left: swc::AssignTarget::Simple(swc::SimpleAssignTarget::Ident(binding_ident(
-            match &assign.target {
-                Expr::Ident(id) => &id.name,
-                _ => "_",
-            },
-        ))),
-        right: Box::new(translate_expr(&assign.value)),
+        left: swc::AssignTarget::Simple(left),
+        right: Box::new(right),
     })
 }
 
-fn translate_template_string(ts: &TemplateStringExpr) -> swc::Expr {
+fn translate_template_string(cx: &TranslateCtx, ts: &TemplateStringExpr) -> swc::Expr {
+    let values = translate_in_order(cx, &template_exprs(ts).collect::<Vec<_>>());
+    swc::Expr::Tpl(translate_tpl(ts, values))
+}
+
+/// The interpolated expressions of `ts`, in order.
+fn template_exprs(ts: &TemplateStringExpr) -> impl Iterator<Item = &Expr> {
+    ts.parts.iter().filter_map(|part| match part {
+        TemplatePart::Expr(e) => Some(e),
+        TemplatePart::String(_) => None,
+    })
+}
+
+/// The template literal for `ts`, with `values` as its interpolations.
+fn translate_tpl(ts: &TemplateStringExpr, values: Vec<swc::Expr>) -> swc::Tpl {
+    let mut values = values.into_iter();
     let mut quasis = Vec::new();
     let mut exprs: Vec<Box<swc::Expr>> = Vec::new();
 
@@ -1000,7 +2668,7 @@ fn translate_template_string(ts: &TemplateStringExpr) -> swc::Expr {
                 });
                 i += 1;
             }
-            TemplatePart::Expr(e) => {
+            TemplatePart::Expr(_) => {
                 // If no string before this expr, add empty quasis
                 if quasis.len() == exprs.len() {
                     quasis.push(swc::TplElement {
@@ -1010,7 +2678,7 @@ fn translate_template_string(ts: &TemplateStringExpr) -> swc::Expr {
                         raw: "".into(),
                     });
                 }
-                exprs.push(Box::new(translate_expr(e)));
+                exprs.push(Box::new(values.next().expect("one value per interpolation")));
                 i += 1;
             }
         }
@@ -1031,17 +2699,73 @@ fn translate_template_string(ts: &TemplateStringExpr) -> swc::Expr {
         last.tail = true;
     }
 
-    swc::Expr::Tpl(swc::Tpl {
+    swc::Tpl {
         span: DUMMY_SP,
         exprs,
         quasis,
+    }
+}
+
+/// An `if` or `match` in value position whose branches use `?`, as
+/// statements hoisted in front of the enclosing one that assign its value
+/// to a variable, so that `?` can return from the function:
+/// `f(if ok { parse(s)? } else { 0 })` → `let _result; if (ok) { ...
+/// _result = _tmp; } else { _result = 0; } f(_result);`.
+fn translate_hoisted_branches(cx: &TranslateCtx, expr: &Expr) -> swc::Expr {
+    let result = cx.fresh("_result");
+    let stmt = match expr {
+        Expr::If(i) => translate_assigning_if(cx, i, &result),
+        Expr::Match(m) => scoped_stmt(translate_match_stmt(cx, m, Some(&result))),
+        _ => unreachable!("only an `if` or `match` has branches"),
+    };
+    let mut hoisted = cx.hoisted.borrow_mut();
+    let hoisted = hoisted.as_mut().expect("statements can be hoisted");
+    hoisted.push(let_decl_stmt(&result));
+    hoisted.push(stmt);
+    swc::Expr::Ident(ident(&result))
+}
+
+/// An `if` statement assigning the value of each branch to `result`.
+fn translate_assigning_if(cx: &TranslateCtx, i: &IfExpr, result: &str) -> swc::Stmt {
+    let alt = i.else_branch.as_ref().map(|eb| {
+        Box::new(match eb {
+            ElseBranch::Block(b) => block_stmt(translate_block_assigning(cx, b, result)),
+            ElseBranch::If(elif) => stmt_with_hoisted(cx, || translate_assigning_if(cx, elif, result)),
+        })
+    });
+    swc::Stmt::If(swc::IfStmt {
+        span: DUMMY_SP,
+        test: Box::new(translate_expr(cx, &i.condition)),
+        cons: Box::new(block_stmt(translate_block_assigning(cx, &i.then_block, result))),
+        alt,
     })
 }
 
-fn translate_match(m: &MatchExpr) -> swc::Expr {
+fn translate_match(cx: &TranslateCtx, m: &MatchExpr) -> swc::Expr {
     // Translate match to IIFE with if-else chain
-    let subject_var = "_match";
-    let subject = translate_expr(&m.subject);
+    cx.without_early_return(|| {
+        make_iife(translate_match_chain(cx, m, |body| match body {
+            MatchArmBody::Block(b) => translate_block_with_implicit_return(cx, b).stmts,
+            MatchArmBody::Expr(e) => vec![swc::Stmt::Return(swc::ReturnStmt {
+                span: DUMMY_SP,
+                arg: Some(Box::new(translate_expr(cx, e))),
+            })],
+        }))
+    })
+}
+
+/// Lower a match to `const _match = subject;` followed by an if-else chain,
+/// emitting each arm body with `arm_body`.
+fn translate_match_chain(
+    cx: &TranslateCtx,
+    m: &MatchExpr,
+    arm_body: impl Fn(&MatchArmBody) -> Vec<swc::Stmt>,
+) -> Vec<swc::Stmt> {
+    if let Some(switch) = translate_match_switch(cx, m, &arm_body) {
+        return switch;
+    }
+    let subject_var = &cx.fresh("_match");
+    let subject = translate_expr(cx, &m.subject);
 
     let mut stmts: Vec<swc::Stmt> = vec![swc::Stmt::Decl(swc::Decl::Var(Box::new(
         swc::VarDecl {
@@ -1062,17 +2786,12 @@ fn translate_match(m: &MatchExpr) -> swc::Expr {
     let mut else_stmt: Option<Box<swc::Stmt>> = None;
 
     for arm in m.arms.iter().rev() {
-        let body_expr = translate_expr(&arm.body);
-        let return_stmt = swc::Stmt::Return(swc::ReturnStmt {
-            span: DUMMY_SP,
-            arg: Some(Box::new(body_expr)),
-        });
-
-        let (condition, bindings) = translate_pattern_to_condition(&arm.pattern, subject_var);
+        let (condition, bindings) = translate_pattern_to_condition(cx, &arm.pattern, subject_var);
+        let guard = arm.guard.as_ref().map(|guard| translate_guard(cx, guard, &bindings));
 
         let mut body_stmts: Vec<swc::Stmt> = Vec::new();
         // Add bindings
-        for (name, init_expr) in bindings {
+        for (name, init_expr) in bindings.clone() {
             body_stmts.push(swc::Stmt::Decl(swc::Decl::Var(Box::new(swc::VarDecl {
                 span: DUMMY_SP,
                 ctxt: SyntaxContext::empty(),
@@ -1086,17 +2805,18 @@ fn translate_match(m: &MatchExpr) -> swc::Expr {
                 }],
             }))));
         }
-        body_stmts.push(return_stmt);
+        let names: Vec<&str> = bindings.iter().map(|(name, _)| name.as_str()).collect();
+        body_stmts.extend(cx.shadowing(names, || arm_body(&arm.body)));
 
         match condition {
             Some(mut cond) => {
                 // Add guard to condition
-                if let Some(ref guard) = arm.guard {
+                if let Some(guard) = guard {
                     cond = swc::Expr::Bin(swc::BinExpr {
                         span: DUMMY_SP,
                         op: swc::BinaryOp::LogicalAnd,
                         left: Box::new(cond),
-                        right: Box::new(translate_expr(guard)),
+                        right: Box::new(guard),
                     });
                 }
                 let if_stmt = swc::Stmt::If(swc::IfStmt {
@@ -1113,10 +2833,10 @@ fn translate_match(m: &MatchExpr) -> swc::Expr {
             }
             None => {
                 // Wildcard or catch-all — just the body
-                if let Some(ref guard) = arm.guard {
+                if let Some(guard) = guard {
                     let if_stmt = swc::Stmt::If(swc::IfStmt {
                         span: DUMMY_SP,
-                        test: Box::new(translate_expr(guard)),
+                        test: Box::new(guard),
                         cons: Box::new(swc::Stmt::Block(swc::BlockStmt {
                             span: DUMMY_SP,
                             ctxt: SyntaxContext::empty(),
@@ -1140,10 +2860,110 @@ fn translate_match(m: &MatchExpr) -> swc::Expr {
         stmts.push(*chain);
     }
 
-    make_iife(stmts)
+    stmts
+}
+
+/// A match over literals as a `switch`, with a catch-all arm as its
+/// `default`; `None` when an arm has another pattern or a guard, or would
+/// `break` an enclosing loop from inside the `switch`. Each case ends in a
+/// `break` unless its body already leaves it:
+/// `switch (x) { case 1: log("one"); break; default: log("other"); }`.
+fn translate_match_switch(
+    cx: &TranslateCtx,
+    m: &MatchExpr,
+    arm_body: &impl Fn(&MatchArmBody) -> Vec<swc::Stmt>,
+) -> Option<Vec<swc::Stmt>> {
+    // Arms after a catch-all are unreachable
+    let end = m
+        .arms
+        .iter()
+        .position(|arm| matches!(arm.pattern, Pattern::Wildcard(_) | Pattern::Ident(..)))
+        .map_or(m.arms.len(), |i| i + 1);
+    let arms = &m.arms[..end];
+    let literals = arms.iter().filter(|arm| matches!(arm.pattern, Pattern::Literal(_))).count();
+    let switchable = |arm: &MatchArm| {
+        arm.guard.is_none()
+            && matches!(arm.pattern, Pattern::Literal(_) | Pattern::Wildcard(_) | Pattern::Ident(..))
+            && !walk::arm_breaks(&arm.body)
+    };
+    // A single case reads better as an `if`
+    if literals < 2 || !arms.iter().all(switchable) {
+        return None;
+    }
+
+    let mut stmts = Vec::new();
+    let subject = translate_expr(cx, &m.subject);
+    let binds = arms.iter().any(|arm| matches!(arm.pattern, Pattern::Ident(..)));
+    let discriminant = if binds {
+        let subject_var = cx.fresh("_match");
+        stmts.push(var_decl_stmt(VarKind::Let, &subject_var, subject));
+        swc::Expr::Ident(ident(&subject_var))
+    } else {
+        subject
+    };
+
+    let mut seen = HashSet::new();
+    let mut cases = Vec::new();
+    for arm in arms {
+        let test = match &arm.pattern {
+            // A repeated literal never matches
+            Pattern::Literal(lit) if !seen.insert(literal_key(lit)) => continue,
+            Pattern::Literal(lit) => Some(Box::new(translate_literal(cx, lit))),
+            _ => None,
+        };
+        let mut body = Vec::new();
+        let mut names = None;
+        if let Pattern::Ident(name, _) = &arm.pattern {
+            body.push(var_decl_stmt(VarKind::Let, name, discriminant.clone()));
+            names = Some(name.as_str());
+        }
+        body.extend(cx.shadowing(names, || arm_body(&arm.body)));
+        if test.is_some()
+            && !matches!(body.last(), Some(swc::Stmt::Return(_) | swc::Stmt::Break(_) | swc::Stmt::Continue(_)))
+        {
+            body.push(swc::Stmt::Break(swc::BreakStmt { span: DUMMY_SP, label: None }));
+        }
+        // Declarations are scoped to their case
+        if body.iter().any(|stmt| matches!(stmt, swc::Stmt::Decl(_))) {
+            body = vec![block_stmt(body)];
+        }
+        cases.push(swc::SwitchCase { span: DUMMY_SP, test, cons: body });
+    }
+    stmts.push(swc::Stmt::Switch(swc::SwitchStmt {
+        span: DUMMY_SP,
+        discriminant: Box::new(discriminant),
+        cases,
+    }));
+    Some(stmts)
+}
+
+/// Equal for literals that compile to the same JS value (`97` and `'a'`).
+fn literal_key(lit: &Literal) -> String {
+    match lit {
+        Literal::Int(n, _, _) => (*n as f64).to_string(),
+        Literal::Float(n, _) => n.to_string(),
+        Literal::Char(c, _) => (*c as u32 as f64).to_string(),
+        Literal::String(s, _) => format!("{s:?}"),
+        Literal::Bool(b, _) => b.to_string(),
+        Literal::Nil(_) => "nil".to_string(),
+    }
+}
+
+/// The test for an arm's guard. The arm's `bindings` are only declared
+/// once the test passes, so the guard reads their values in place:
+/// `Status::Error(code) if code > 400` tests `_match.code > 400`. Those of
+/// an enclosing guard stay in place unless the arm rebinds them.
+fn translate_guard(cx: &TranslateCtx, guard: &Expr, bindings: &[(String, swc::Expr)]) -> swc::Expr {
+    let mut inner = cx.guard_bindings.borrow().clone();
+    inner.extend(bindings.iter().cloned());
+    let outer = cx.guard_bindings.replace(inner);
+    let test = cx.without_early_return(|| translate_expr(cx, guard));
+    cx.guard_bindings.replace(outer);
+    test
 }
 
 fn translate_pattern_to_condition(
+    cx: &TranslateCtx,
     pattern: &Pattern,
     subject_var: &str,
 ) -> (Option<swc::Expr>, Vec<(String, swc::Expr)>) {
@@ -1153,7 +2973,7 @@ fn translate_pattern_to_condition(
                 span: DUMMY_SP,
                 op: swc::BinaryOp::EqEqEq,
                 left: Box::new(swc::Expr::Ident(ident(subject_var))),
-                right: Box::new(translate_literal(lit)),
+                right: Box::new(translate_literal(cx, lit)),
             });
             (Some(cond), Vec::new())
         }
@@ -1165,28 +2985,34 @@ fn translate_pattern_to_condition(
         Pattern::Wildcard(_) => (None, Vec::new()),
         Pattern::Enum(ep) => {
             // Check tag field
-            let cond = swc::Expr::Bin(swc::BinExpr {
-                span: DUMMY_SP,
-                op: swc::BinaryOp::EqEqEq,
-                left: Box::new(swc::Expr::Member(swc::MemberExpr {
+            let subject = swc::Expr::Ident(ident(subject_var));
+            let cond = match cx.runtime_call("matchTag", vec![subject.clone(), str_lit(&ep.variant)]) {
+                Some(call) => call,
+                None => swc::Expr::Bin(swc::BinExpr {
                     span: DUMMY_SP,
-                    obj: Box::new(swc::Expr::Ident(ident(subject_var))),
-                    prop: swc::MemberProp::Ident(swc::IdentName {
+                    op: swc::BinaryOp::EqEqEq,
+                    left: Box::new(swc::Expr::Member(swc::MemberExpr {
                         span: DUMMY_SP,
-                        sym: "tag".into(),
-                    }),
-                })),
-                right: Box::new(swc::Expr::Lit(swc::Lit::Str(swc::Str {
-                    span: DUMMY_SP,
-                    value: ep.variant.clone().into(),
-                    raw: None,
-                }))),
-            });
-            // Bind variant fields
+                        obj: Box::new(subject),
+                        prop: swc::MemberProp::Ident(swc::IdentName {
+                            span: DUMMY_SP,
+                            sym: "tag".into(),
+                        }),
+                    })),
+                    right: Box::new(str_lit(&ep.variant)),
+                }),
+            };
+            // Bind variant fields by position; without the checker, variants
+            // of enums from other modules are assumed to name them like the
+            // bindings
+            let enum_fields = cx.enum_fields.borrow();
+            let fields = enum_fields.get(&(ep.enum_name.clone(), ep.variant.clone()));
             let bindings: Vec<(String, swc::Expr)> = ep
                 .bindings
                 .iter()
-                .map(|b| {
+                .enumerate()
+                .map(|(i, b)| {
+                    let field = fields.and_then(|fields| fields.get(i)).unwrap_or(b);
                     (
                         b.clone(),
                         swc::Expr::Member(swc::MemberExpr {
@@ -1194,7 +3020,7 @@ fn translate_pattern_to_condition(
                             obj: Box::new(swc::Expr::Ident(ident(subject_var))),
                             prop: swc::MemberProp::Ident(swc::IdentName {
                                 span: DUMMY_SP,
-                                sym: b.clone().into(),
+                                sym: field.clone().into(),
                             }),
                         }),
                     )
@@ -1222,22 +3048,30 @@ fn translate_pattern_to_condition(
                 .collect();
             (None, bindings)
         }
-        Pattern::Range(from, to, _) => {
-            let cond = swc::Expr::Bin(swc::BinExpr {
-                span: DUMMY_SP,
-                op: swc::BinaryOp::LogicalAnd,
-                left: Box::new(swc::Expr::Bin(swc::BinExpr {
-                    span: DUMMY_SP,
-                    op: swc::BinaryOp::GtEq,
-                    left: Box::new(swc::Expr::Ident(ident(subject_var))),
-                    right: Box::new(translate_expr(from)),
-                })),
-                right: Box::new(swc::Expr::Bin(swc::BinExpr {
+        Pattern::Range(from, to, inclusive, _) => {
+            let subject = || swc::Expr::Ident(ident(subject_var));
+            let (from, to) = (translate_expr(cx, from), translate_expr(cx, to));
+            let mut args = vec![subject(), from.clone(), to.clone()];
+            if *inclusive {
+                args.push(bool_lit(true));
+            }
+            let cond = cx.runtime_call("inRange", args).unwrap_or_else(|| {
+                swc::Expr::Bin(swc::BinExpr {
                     span: DUMMY_SP,
-                    op: swc::BinaryOp::LtEq,
-                    left: Box::new(swc::Expr::Ident(ident(subject_var))),
-                    right: Box::new(translate_expr(to)),
-                })),
+                    op: swc::BinaryOp::LogicalAnd,
+                    left: Box::new(swc::Expr::Bin(swc::BinExpr {
+                        span: DUMMY_SP,
+                        op: swc::BinaryOp::GtEq,
+                        left: Box::new(subject()),
+                        right: Box::new(from),
+                    })),
+                    right: Box::new(swc::Expr::Bin(swc::BinExpr {
+                        span: DUMMY_SP,
+                        op: if *inclusive { swc::BinaryOp::LtEq } else { swc::BinaryOp::Lt },
+                        left: Box::new(subject()),
+                        right: Box::new(to),
+                    })),
+                })
             });
             (Some(cond), Vec::new())
         }
@@ -1246,25 +3080,142 @@ fn translate_pattern_to_condition(
 
 // ── Import translation ─────────────────────────────────────
 
-fn translate_import(imp: &Import) -> swc::ModuleDecl {
-    let src = Box::new(swc::Str {
-        span: DUMMY_SP,
-        value: imp.path.clone().into(),
-        raw: None,
-    });
+/// A module's import statements, with one per source module: later named
+/// and side-effect imports of a module are merged into its first, as are
+/// the imports of its referenced `@js` externs, and repeated specifiers
+/// are dropped, as are those of types with no runtime value (an import of
+/// types alone is dropped entirely).
+struct MergedImports {
+    /// Imports of externs from modules the source does not import, by path.
+    leading: Vec<swc::ModuleDecl>,
+    /// The statement each `import` item becomes, by item index; `None` for
+    /// one merged into an earlier import.
+    in_place: HashMap<usize, Option<swc::ModuleDecl>>,
+}
 
-    if let Some(ref alias) = imp.namespace {
-        // import * as alias from "path"
-        swc::ModuleDecl::Import(swc::ImportDecl {
-            span: DUMMY_SP,
-            specifiers: vec![swc::ImportSpecifier::Namespace(
-                swc::ImportStarAsSpecifier {
-                    span: DUMMY_SP,
-                    local: ident(alias),
-                },
-            )],
-            src,
-            type_only: false,
+/// Merge the static imports of `module` with those of `externs`, in the
+/// order given. A name bound to two different imports is an error.
+fn merge_imports(
+    cx: &TranslateCtx,
+    module: &Module,
+    externs: &[(&str, &JsExternInfo)],
+) -> Result<MergedImports, CodegenError> {
+    // Emitted path, source path, index of the import it is merged into,
+    // names
+    let mut groups: Vec<(String, &str, Option<usize>, Vec<ImportName>)> = Vec::new();
+    let mut in_place = HashMap::new();
+    // What each local name is bound to: the path and imported name (`*`
+    // for a namespace)
+    let mut bound: HashMap<&str, (&str, &str)> = HashMap::new();
+    // Whether `local` is newly bound, rather than by the same import again
+    fn bind<'a>(
+        bound: &mut HashMap<&'a str, (&'a str, &'a str)>,
+        local: &'a str,
+        path: &'a str,
+        imported: &'a str,
+        span: Span,
+    ) -> Result<bool, CodegenError> {
+        match bound.get(local) {
+            Some(&(p, i)) if (p, i) == (path, imported) => Ok(false),
+            Some(&(p, i)) => Err(CodegenError {
+                message: format!(
+                    "conflicting imports of `{local}`: `{i}` from \"{p}\" and `{imported}` from \"{path}\""
+                ),
+                span,
+            }),
+            None => {
+                bound.insert(local, (path, imported));
+                Ok(true)
+            }
+        }
+    }
+
+    for (index, item) in module.items.iter().enumerate() {
+        let Item::Import(imp @ Import { path: ImportPath::Static(path), .. }) = item else { continue };
+        if let Some(ns) = &imp.namespace {
+            let fresh = bind(&mut bound, ns, path, "*", imp.span)?;
+            in_place.insert(index, fresh.then(|| translate_import(cx, imp)));
+            continue;
+        }
+        let is_type = |n: &ImportName| cx.rewrites.type_imports.contains(&n.span);
+        if !imp.names.is_empty() && imp.names.iter().all(is_type) {
+            in_place.insert(index, None);
+            continue;
+        }
+        let mut names = Vec::new();
+        for n in imp.names.iter().filter(|n| !is_type(n)) {
+            if bind(&mut bound, n.alias.as_deref().unwrap_or(&n.name), path, &n.name, n.span)? {
+                names.push(n.clone());
+            }
+        }
+        let emitted = cx.import_path(path);
+        match groups.iter_mut().find(|(p, ..)| *p == emitted) {
+            Some((.., group)) => {
+                group.extend(names);
+                in_place.insert(index, None);
+            }
+            None => groups.push((emitted, path, Some(index), names)),
+        }
+    }
+    for &(ag_name, info) in externs {
+        let js_name = info.js_name.as_deref().unwrap_or(ag_name);
+        if !bind(&mut bound, ag_name, &info.module, js_name, info.span)? {
+            continue;
+        }
+        let name = ImportName {
+            name: js_name.to_string(),
+            alias: (js_name != ag_name).then(|| ag_name.to_string()),
+            span: info.span,
+        };
+        let emitted = cx.import_path(&info.module);
+        match groups.iter_mut().find(|(p, ..)| *p == emitted) {
+            Some((.., group)) => group.push(name),
+            None => groups.push((emitted, &info.module, None, vec![name])),
+        }
+    }
+
+    let mut leading = Vec::new();
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+    for (_, path, index, names) in groups {
+        let decl = translate_import(cx, &Import {
+            names,
+            path: ImportPath::Static(path.to_string()),
+            namespace: None,
+            span: Span::dummy(),
+        });
+        match index {
+            Some(index) => {
+                in_place.insert(index, Some(decl));
+            }
+            None => leading.push(decl),
+        }
+    }
+    Ok(MergedImports { leading, in_place })
+}
+
+fn translate_import(cx: &TranslateCtx, imp: &Import) -> swc::ModuleDecl {
+    let path = match &imp.path {
+        ImportPath::Static(path) => path,
+        ImportPath::Dynamic(_) => unreachable!("rejected in translate_module"),
+    };
+    let src = Box::new(swc::Str {
+        span: DUMMY_SP,
+        value: cx.import_path(path).into(),
+        raw: None,
+    });
+
+    if let Some(ref alias) = imp.namespace {
+        // import * as alias from "path"
+        swc::ModuleDecl::Import(swc::ImportDecl {
+            span: DUMMY_SP,
+            specifiers: vec![swc::ImportSpecifier::Namespace(
+                swc::ImportStarAsSpecifier {
+                    span: DUMMY_SP,
+                    local: ident(alias),
+                },
+            )],
+            src,
+            type_only: false,
             with: None,
             phase: Default::default(),
         })
@@ -1275,11 +3226,11 @@ fn translate_import(imp: &Import) -> swc::ModuleDecl {
             .map(|n| {
                 swc::ImportSpecifier::Named(swc::ImportNamedSpecifier {
                     span: DUMMY_SP,
-                    local: ident(&n.name),
+                    local: ident(n.alias.as_ref().unwrap_or(&n.name)),
                     imported: n
                         .alias
-                        .as_ref()
-                        .map(|a| swc::ModuleExportName::Ident(ident(a))),
+                        .is_some()
+                        .then(|| swc::ModuleExportName::Ident(ident(&n.name))),
                     is_type_only: false,
                 })
             })
@@ -1295,16 +3246,40 @@ fn translate_import(imp: &Import) -> swc::ModuleDecl {
     }
 }
 
+fn translate_export_alias(ea: &ExportAlias) -> swc::ModuleDecl {
+    // export { local as exported, ... }
+    let specifiers = ea
+        .names
+        .iter()
+        .map(|n| {
+            swc::ExportSpecifier::Named(swc::ExportNamedSpecifier {
+                span: DUMMY_SP,
+                orig: swc::ModuleExportName::Ident(ident(&n.local)),
+                exported: (n.exported != n.local)
+                    .then(|| swc::ModuleExportName::Ident(ident(&n.exported))),
+                is_type_only: false,
+            })
+        })
+        .collect();
+    swc::ModuleDecl::ExportNamed(swc::NamedExport {
+        span: DUMMY_SP,
+        specifiers,
+        src: None,
+        type_only: false,
+        with: None,
+    })
+}
+
 // ── Utility functions ──────────────────────────────────────
 
-fn block_to_expr(block: &Block) -> swc::Expr {
+fn block_to_expr(cx: &TranslateCtx, block: &Block) -> swc::Expr {
     if block.stmts.is_empty() {
         if let Some(ref tail) = block.tail_expr {
-            return translate_expr(tail);
+            return translate_expr(cx, tail);
         }
     }
     // Wrap in IIFE
-    let body = translate_block_with_implicit_return(block);
+    let body = cx.without_early_return(|| translate_block_with_implicit_return(cx, block));
     make_iife(body.stmts)
 }
 
@@ -1334,92 +3309,836 @@ fn make_iife(stmts: Vec<swc::Stmt>) -> swc::Expr {
     })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use ag_parser;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ag_parser;
+
+    fn compile(src: &str) -> String {
+        let parsed = ag_parser::parse(src);
+        assert!(
+            parsed.diagnostics.is_empty(),
+            "parse errors: {:?}",
+            parsed.diagnostics
+        );
+        codegen(&parsed.module)
+    }
+
+    #[test]
+    fn large_int_literal_keeps_its_digits() {
+        let js = compile("let a = 9007199254740993\nlet b = 42\nlet c = -9007199254740993\nlet d = - -9007199254740993");
+        assert!(js.contains("const a = 9007199254740993;"), "got: {}", js);
+        assert!(js.contains("const b = 42;"), "got: {}", js);
+        assert!(js.contains("const c = -9007199254740993;"), "got: {}", js);
+        assert!(js.contains("const d = 9007199254740993;"), "got: {}", js);
+    }
+
+    #[test]
+    fn nil_emits_null_by_default() {
+        let js = compile("fn f() -> int? { nil }");
+        assert!(js.contains("return null"), "got: {}", js);
+    }
+
+    #[test]
+    fn nil_to_undefined_option() {
+        let parsed = ag_parser::parse("let x: int? = nil\nfn f(y: int?) -> bool { y == nil }");
+        let mut translator = Translator::new();
+        translator.set_options(CodegenOptions { nil_to_undefined: true, ..Default::default() });
+        let js = translator.codegen(&parsed.module).unwrap();
+        assert!(js.contains("const x = undefined"), "got: {}", js);
+        assert!(js.contains("y === undefined"), "got: {}", js);
+        assert!(!js.contains("null"), "got: {}", js);
+    }
+
+    fn compile_optimized(src: &str) -> String {
+        let parsed = ag_parser::parse(src);
+        assert!(parsed.diagnostics.is_empty(), "parse errors: {:?}", parsed.diagnostics);
+        let mut translator = Translator::new();
+        translator.set_options(CodegenOptions { optimize_tail_calls: true, ..Default::default() });
+        translator.codegen(&parsed.module).unwrap()
+    }
+
+    fn compile_split(src: &str, split_output: bool) -> Vec<(String, String)> {
+        let options = ag_parser::ParseOptions { preserve_comments: true };
+        let parsed = ag_parser::parse_with_options(src, options);
+        assert!(parsed.diagnostics.is_empty(), "parse errors: {:?}", parsed.diagnostics);
+        let translator = Translator::builder()
+            .with_options(CodegenOptions { split_output, ..Default::default() })
+            .build();
+        translator.codegen_split(&parsed.module).unwrap()
+    }
+
+    #[test]
+    fn split_output_emits_a_file_per_pub_fn() {
+        let src = r#"import { readFile, writeFile } from "node:fs"
+import { join } from "node:path"
+const SEP = "/"
+fn clean(p: str) -> str { p.trim() }
+fn unused() -> int { 1 }
+/// Reads a file.
+pub fn read(p: str) -> any { readFile(clean(p)) }
+pub fn write(p: str, data: str) -> any { writeFile(join(clean(p), SEP), data) }"#;
+        let files = compile_split(src, true);
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["read.js", "write.js"]);
+
+        let read = &files[0].1;
+        assert!(read.contains(r#"import { readFile, writeFile } from "node:fs""#), "got: {}", read);
+        assert!(read.contains("function clean(p)"), "got: {}", read);
+        assert!(read.contains("/** Reads a file. */"), "got: {}", read);
+        assert!(read.contains("export function read(p)"), "got: {}", read);
+        for absent in ["node:path", "SEP", "unused", "function write"] {
+            assert!(!read.contains(absent), "{} in: {}", absent, read);
+        }
+
+        let write = &files[1].1;
+        assert!(write.contains(r#"import { join } from "node:path""#), "got: {}", write);
+        assert!(write.contains(r#"const SEP = "/""#), "got: {}", write);
+        assert!(write.contains("function clean(p)"), "got: {}", write);
+        assert!(write.contains("export function write(p, data)"), "got: {}", write);
+        assert!(!write.contains("function read"), "got: {}", write);
+    }
+
+    #[test]
+    fn split_output_imports_other_pub_fns() {
+        let src = "pub fn double(x: int) -> int { x * 2 }\npub fn quadruple(x: int) -> int { double(double(x)) }";
+        let files = compile_split(src, true);
+        assert_eq!(files.len(), 2);
+        let quadruple = &files[1].1;
+        assert!(quadruple.contains(r#"import { double } from "./double.js""#), "got: {}", quadruple);
+        assert!(!quadruple.contains("function double"), "got: {}", quadruple);
+        assert!(!files[0].1.contains("import"), "got: {}", files[0].1);
+
+        let parsed = ag_parser::parse(src);
+        let rewrite = ImportRewrite { extension: "mjs".to_string(), extensionless: false };
+        let translator = Translator::builder()
+            .with_options(CodegenOptions { split_output: true, import_rewrite: Some(rewrite), ..Default::default() })
+            .build();
+        let files = translator.codegen_split(&parsed.module).unwrap();
+        assert_eq!(files[1].0, "quadruple.mjs");
+        assert!(files[1].1.contains(r#"import { double } from "./double.mjs""#), "got: {}", files[1].1);
+    }
+
+    #[test]
+    fn split_output_keeps_modules_with_state_whole() {
+        let stateful = "mut count = 0\npub fn bump() { count = count + 1 }\npub fn get() -> int { count }";
+        let single = "fn helper() -> int { 1 }\npub fn only() -> int { helper() }";
+        let independent = "pub fn a() -> int { 1 }\npub fn b() -> int { 2 }";
+        for (src, split_output) in [(stateful, true), (single, true), (independent, false)] {
+            let files = compile_split(src, split_output);
+            assert_eq!(files.len(), 1, "{}", src);
+            assert_eq!(files[0].0, "index.js");
+            assert_eq!(files[0].1, compile(src));
+        }
+    }
+
+    const RUNTIME_SRC: &str = r#"enum Shape { Dot, Circle(r: num), Rect(w: num, h: num) }
+extern fn parse(s: str) -> any
+fn size(s: Shape) -> str {
+    match s {
+        Shape::Circle(r) => "circle",
+        Shape::Rect(w, h) => match w {
+            0..10 => "small",
+            10..=100 => "medium",
+            _ => "large",
+        },
+        _ => "dot",
+    }
+}
+fn load(s: str) -> any {
+    let v = parse(s)?
+    v
+}"#;
+
+    fn compile_with_runtime(src: &str, runtime_module: Option<&str>) -> String {
+        let parsed = ag_parser::parse(src);
+        assert!(parsed.diagnostics.is_empty(), "parse errors: {:?}", parsed.diagnostics);
+        let runtime_module = runtime_module.map(str::to_string);
+        Translator::builder()
+            .with_options(CodegenOptions { runtime_module, ..Default::default() })
+            .build()
+            .codegen(&parsed.module)
+            .unwrap()
+    }
+
+    #[test]
+    fn inline_lowerings_by_default() {
+        let js = compile_with_runtime(RUNTIME_SRC, None);
+        assert_eq!(js, r#"const Shape = {
+    Dot: {
+        tag: "Dot"
+    },
+    Circle: (r)=>({
+            tag: "Circle",
+            r
+        }),
+    Rect: (w, h)=>({
+            tag: "Rect",
+            w,
+            h
+        })
+};
+function size(s) {
+    {
+        const _match = s;
+        if (_match.tag === "Circle") {
+            const r = _match.r;
+            return "circle";
+        } else if (_match.tag === "Rect") {
+            const w = _match.w;
+            const h = _match.h;
+            {
+                const _match1 = w;
+                if (_match1 >= 0 && _match1 < 10) {
+                    return "small";
+                } else if (_match1 >= 10 && _match1 <= 100) {
+                    return "medium";
+                } else {
+                    return "large";
+                }
+            }
+        } else {
+            return "dot";
+        }
+    }
+}
+function load(s) {
+    const _tmp = parse(s);
+    if (_tmp instanceof Error) return _tmp;
+    const v = _tmp;
+    return v;
+}
+"#);
+        assert_eq!(js, compile(RUNTIME_SRC));
+    }
+
+    #[test]
+    fn runtime_helpers_replace_inline_lowerings() {
+        let js = compile_with_runtime(RUNTIME_SRC, Some("./runtime.js"));
+        assert_eq!(js, r#"import * as __ag from "./runtime.js";
+const Shape = {
+    Dot: {
+        tag: "Dot"
+    },
+    Circle: __ag.variant("Circle", [
+        "r"
+    ]),
+    Rect: __ag.variant("Rect", [
+        "w",
+        "h"
+    ])
+};
+function size(s) {
+    {
+        const _match = s;
+        if (__ag.matchTag(_match, "Circle")) {
+            const r = _match.r;
+            return "circle";
+        } else if (__ag.matchTag(_match, "Rect")) {
+            const w = _match.w;
+            const h = _match.h;
+            {
+                const _match1 = w;
+                if (__ag.inRange(_match1, 0, 10)) {
+                    return "small";
+                } else if (__ag.inRange(_match1, 10, 100, true)) {
+                    return "medium";
+                } else {
+                    return "large";
+                }
+            }
+        } else {
+            return "dot";
+        }
+    }
+}
+function load(s) {
+    const _tmp = parse(s);
+    if (__ag.isError(_tmp)) return _tmp;
+    const v = _tmp;
+    return v;
+}
+"#);
+        for helper in ["variant", "matchTag", "inRange", "isError"] {
+            assert!(RUNTIME_JS.contains(&format!("export function {helper}(")), "{}", helper);
+        }
+        // Only modules that use a helper import them
+        let js = compile_with_runtime("enum Color { Red, Green }\nfn f(x: int) -> int { x + 1 }", Some("./runtime.js"));
+        assert!(!js.contains("__ag"), "got: {}", js);
+    }
+
+    #[test]
+    fn user_binding_clashing_with_runtime_import_is_an_error() {
+        let options = || CodegenOptions { runtime_module: Some("./runtime.js".into()), ..Default::default() };
+        for src in [
+            "enum E { A(x: int) }\nlet __ag = E::A(1)",
+            "enum E { A(x: int) }\nfn f() { let __ag = 1\n E::A(__ag) }",
+            "import { log as __ag } from \"./log.js\"\nenum E { A(x: int) }\nlet a = E::A(1)",
+        ] {
+            let parsed = ag_parser::parse(src);
+            assert!(parsed.diagnostics.is_empty(), "parse errors: {:?}", parsed.diagnostics);
+            let err = Translator::builder().with_options(options()).build().codegen(&parsed.module).unwrap_err();
+            assert!(err.message.contains("`__ag` clashes with the runtime helpers import"), "{}", err.message);
+        }
+        // Without the runtime module, or when no helper is used, `__ag` is an ordinary name
+        let parsed = ag_parser::parse("enum E { A(x: int) }\nlet __ag = E::A(1)");
+        assert!(Translator::builder().build().codegen(&parsed.module).is_ok());
+        let parsed = ag_parser::parse("let __ag = 1");
+        assert!(Translator::builder().with_options(options()).build().codegen(&parsed.module).is_ok());
+    }
+
+    const TOP_LEVEL_AWAIT_SRC: &str = "extern fn fetch(url: str) -> Promise<any>
+fn show(x: any) {}
+fn main() { show(data) }
+let url = \"https://example.com\"
+show(url)
+let data = await fetch(url)
+show(data)
+let size = match data { nil => 0, _ => 1 }
+";
+
+    fn compile_with_top_level_await(src: &str, top_level_await: TopLevelAwait) -> String {
+        let parsed = ag_parser::parse(src);
+        assert!(parsed.diagnostics.is_empty(), "parse errors: {:?}", parsed.diagnostics);
+        Translator::builder()
+            .with_options(CodegenOptions { top_level_await, ..Default::default() })
+            .build()
+            .codegen(&parsed.module)
+            .unwrap()
+    }
+
+    #[test]
+    fn top_level_await_emitted_as_is() {
+        for mode in [TopLevelAwait::Allow, TopLevelAwait::Error] {
+            let js = compile_with_top_level_await(TOP_LEVEL_AWAIT_SRC, mode);
+            assert_eq!(js, r#"function show(x) {}
+function main() {
+    return show(data);
+}
+const url = "https://example.com";
+show(url);
+const data = await fetch(url);
+show(data);
+let size;
+{
+    const _match = data;
+    if (_match === null) {
+        size = 0;
+    } else {
+        size = 1;
+    }
+}"#, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn top_level_await_wrapped_in_main() {
+        let js = compile_with_top_level_await(TOP_LEVEL_AWAIT_SRC, TopLevelAwait::WrapInMain);
+        assert_eq!(js, r#"function show(x) {}
+function main() {
+    return show(data);
+}
+const url = "https://example.com";
+show(url);
+let data;
+let size;
+async function main1() {
+    data = await fetch(url);
+    show(data);
+    {
+        const _match = data;
+        if (_match === null) {
+            size = 0;
+        } else {
+            size = 1;
+        }
+    }
+}
+main1().catch((e)=>{
+    console.error(e);
+    process.exitCode = 1;
+});
+"#);
+        // A module that never awaits is left alone
+        let js = compile_with_top_level_await("let x = 1\nprint(x)", TopLevelAwait::WrapInMain);
+        assert_eq!(js, "const x = 1;\nprint(x);\n");
+    }
+
+    #[test]
+    fn tail_recursion_becomes_a_loop() {
+        let src = "fn sum(n: int, acc: int) -> int { if n == 0 { acc } else { sum(n - 1, acc + n) } }";
+        let js = compile_optimized(src);
+        assert!(js.contains("while(true){"), "got: {}", js);
+        assert!(js.contains("[n, acc] = [\n                n - 1,\n                acc + n\n            ];\n            continue;"), "got: {}", js);
+        assert!(js.contains("return acc;"), "got: {}", js);
+        assert!(!js.contains("sum(n - 1"), "got: {}", js);
+        // Off by default
+        assert!(compile(src).contains("sum(n - 1, acc + n)"));
+
+        let js = compile_optimized("fn count(n: int) { if n <= 0 { ret }\n ret count(n - 1) }");
+        assert!(js.contains("n = n - 1;\n        continue;"), "got: {}", js);
+
+        // `ret` in an `if` statement
+        let js = compile_optimized("fn down(n: int) -> int { if n > 0 { ret down(n - 1) }\n 0 }");
+        assert!(js.contains("if (n > 0) {\n            n = n - 1;\n            continue;\n        }"), "got: {}", js);
+    }
+
+    #[test]
+    fn non_tail_recursion_is_unchanged() {
+        for src in [
+            "fn fact(n: int) -> int { if n <= 1 { 1 } else { n * fact(n - 1) } }",
+            "fn f(n: int) -> int { for x in [1] { ret f(n - 1) }\n 0 }",
+            "fn f(n: int, fs: [() -> int]) -> [() -> int] { if n == 0 { fs } else { f(n - 1, [() => n]) } }",
+            "async fn f(n: int) -> int { if n == 0 { 0 } else { f(n - 1) } }",
+            // Reassigning `acc` would assign the local `const acc`
+            "fn f(n: int, acc: int) -> int { if n == 0 { acc } else { let acc = 5\n f(n - 1, acc) } }",
+        ] {
+            let js = compile_optimized(src);
+            assert!(!js.contains("while(true)"), "got: {}", js);
+        }
+    }
+
+    fn compile_with_comments(src: &str) -> String {
+        let options = ag_parser::ParseOptions { preserve_comments: true };
+        let parsed = ag_parser::parse_with_options(src, options);
+        assert!(parsed.diagnostics.is_empty(), "parse errors: {:?}", parsed.diagnostics);
+        codegen(&parsed.module)
+    }
+
+    #[test]
+    fn const_fn_compiles_to_plain_function() {
+        let js = compile("const fn kib(n: int) -> int = n * 1024\nconst LIMIT = kib(64)");
+        assert!(js.contains("function kib(n) {\n    return n * 1024;"), "got: {}", js);
+        assert!(js.contains("const LIMIT = kib(64);"), "got: {}", js);
+    }
+
+    #[test]
+    fn derive_eq_for_struct() {
+        let js = compile("@derive(Eq) struct Point { x: int, y: int }\n@derive(Eq) struct Path { points: [Point], label: str? }");
+        assert!(js.contains("function Point_eq(a, b) {\n    return a.x === b.x && a.y === b.y;"), "got: {}", js);
+        assert!(
+            js.contains("a.points.length === b.points.length && a.points.every((x0, i0)=>Point_eq(x0, b.points[i0])) && a.label === b.label"),
+            "got: {}",
+            js
+        );
+    }
+
+    #[test]
+    fn derive_debug_for_struct() {
+        let js = compile("@derive(Debug) struct Point { x: int, name: str, tags: [str] }");
+        assert!(js.contains("function Point_toString(self)"), "got: {}", js);
+        assert!(
+            js.contains(r#""Point { " + "x: " + self.x + ", name: " + JSON.stringify(self.name) + ", tags: " + "[" + self.tags.map((x0)=>JSON.stringify(x0)).join(", ") + "]" + " }""#),
+            "got: {}",
+            js
+        );
+    }
+
+    #[test]
+    fn derive_for_enum() {
+        let js = compile("@derive(Eq, Debug) enum Shape { Dot, Circle(r: num, tags: [str]?) }");
+        assert!(js.contains(r#"a.tag === b.tag && (a.tag !== "Circle" || a.r === b.r && (a.tags === b.tags || (a.tags != null && b.tags != null && a.tags.length === b.tags.length && a.tags.every((x0, i0)=>x0 === b.tags[i0]))))"#), "got: {}", js);
+        assert!(js.contains(r#"self.tag === "Circle" ? "Shape::Circle(" + "r: " + self.r + ", tags: " + (self.tags == null ? "nil" : "[" + self.tags.map((x0)=>JSON.stringify(x0)).join(", ") + "]") + ")" : "Shape::" + self.tag"#), "got: {}", js);
+    }
+
+    #[test]
+    fn derived_functions_of_pub_types_are_exported() {
+        let js = compile("@derive(Eq, Debug) pub struct Point { x: int }\n@derive(Eq) pub enum Dir { Up, Down }\n@derive(Eq) struct Local { x: int }");
+        assert!(js.contains("export function Point_eq(a, b)"), "got: {}", js);
+        assert!(js.contains("export function Point_toString(self)"), "got: {}", js);
+        assert!(js.contains("export function Dir_eq(a, b)"), "got: {}", js);
+        assert!(js.contains("\nfunction Local_eq(a, b)"), "got: {}", js);
+    }
+
+    #[test]
+    fn derived_methods_are_called_as_functions() {
+        let js = compile_checked(
+            "@derive(Eq, Debug) struct Point { x: int, y: int }\n@derive(Eq) enum Dir { Up, Down }\n\
+             fn f(a: Point, b: Point, d: Dir) -> str { if a.eq(b) && d.eq(Dir::Up) { a.toString() } else { \"\" } }",
+        );
+        assert!(js.contains("return Point_eq(a, b) && Dir_eq(d, Dir.Up) ? Point_toString(a) : \"\";"), "got: {}", js);
+    }
+
+    #[test]
+    fn doc_comment_on_pub_fn_emits_jsdoc() {
+        let js = compile_with_comments("/// Adds two numbers.\npub fn add(a: int, b: int) -> int { a + b }");
+        assert!(js.contains("/** Adds two numbers. */ export function add"), "got: {}", js);
+    }
+
+    #[test]
+    fn multi_line_doc_comment() {
+        let src = "fn helper() {}\n/// Greets someone.\n///\n/// Returns the greeting.\npub fn greet(name: str) -> str { \"hi\" }";
+        let js = compile_with_comments(src);
+        assert!(
+            js.contains("/**\n * Greets someone.\n *\n * Returns the greeting.\n */ export function greet"),
+            "got: {}",
+            js
+        );
+    }
+
+    #[test]
+    fn doc_comments_only_on_exported_fns() {
+        let src = "/// Private.\nfn a() {}\n// Not a doc comment.\npub fn b() {}\npub fn c() {\n    /// Inside a body.\n    let x = 1\n}";
+        let js = compile_with_comments(src);
+        assert!(!js.contains("/*"), "got: {}", js);
+        assert!(!js.contains("//"), "got: {}", js);
+    }
+
+    #[test]
+    fn doc_comments_on_exported_enums_and_struct_factories() {
+        let src = "/// Directions.\npub enum Dir { Up, Down }\n/// Settings.\n@derive(Eq) pub struct Config { retries: int = 3 }\n\
+                   /// No factory.\n@derive(Eq) pub struct Point { x: int }";
+        let js = compile_with_comments(src);
+        assert!(js.contains("/** Directions. */ export const Dir"), "got: {}", js);
+        assert!(js.contains("/** Settings. */ export function Config(fields)"), "got: {}", js);
+        assert_eq!(js.matches("Settings").count(), 1, "got: {}", js);
+        assert!(!js.contains("No factory"), "got: {}", js);
+    }
+
+    #[test]
+    fn doc_comments_dropped_unless_preserved() {
+        let js = compile("/// Adds.\npub fn add(a: int, b: int) -> int { a + b }");
+        assert!(!js.contains("Adds"), "got: {}", js);
+    }
+
+    #[test]
+    fn let_binding() {
+        let js = compile("let x = 42");
+        assert!(js.contains("const x = 42"));
+    }
+
+    #[test]
+    fn mut_binding() {
+        let js = compile("mut counter = 0");
+        assert!(js.contains("let counter = 0"));
+    }
+
+    #[test]
+    fn const_binding() {
+        let js = compile("const MAX = 100");
+        assert!(js.contains("const MAX = 100"));
+    }
+
+    #[test]
+    fn simple_function() {
+        let js = compile("fn add(a: int, b: int) -> int { a + b }");
+        assert!(js.contains("function add(a, b)"));
+        assert!(js.contains("return a + b"));
+    }
+
+    #[test]
+    fn pub_function() {
+        let js = compile("pub fn greet(name: str) -> str { name }");
+        assert!(js.contains("export function greet(name)"));
+    }
+
+    #[test]
+    fn default_params() {
+        let js = compile("fn greet(name: str, loud: bool = false) -> str { name }");
+        assert!(js.contains("loud = false"));
+    }
+
+    #[test]
+    fn arrow_function() {
+        let js = compile("let double = (x: int) => x * 2");
+        assert!(js.contains("const double = (x)=>x * 2"));
+    }
+
+    #[test]
+    fn struct_erased() {
+        let js = compile("struct User { name: str, age: int }");
+        assert!(js.trim().is_empty());
+        let js = compile_checked("struct User { name: str, age: int }\nlet u: User = { name: \"a\", age: 1 }");
+        assert!(js.contains("const u = {\n    name: \"a\","), "got: {}", js);
+    }
+
+    #[test]
+    fn struct_defaults_applied_by_factory() {
+        let js = compile_checked(
+            "pub struct Config { retries: int = 3, verbose: bool = false, name: str }\n\
+             fn make(c: Config) -> Config { c }\n\
+             let a: Config = { name: \"a\" }\n\
+             let b = make({ name: \"b\", retries: 5 })",
+        );
+        assert!(
+            js.contains("export function Config(fields) {\n    return {\n        retries: 3,\n        verbose: false,\n        ...fields\n    };\n}"),
+            "got: {}",
+            js
+        );
+        assert!(js.contains("const a = Config({\n    name: \"a\"\n});"), "got: {}", js);
+        // Explicit values come after the defaults, overriding them
+        assert!(js.contains("const b = make(Config({\n    name: \"b\",\n    retries: 5\n}));"), "got: {}", js);
+
+        // Array elements, branch results, returns and nested fields too
+        let js = compile_checked(
+            "struct Config { retries: int = 3, name: str }\nstruct Job { config: Config }\n\
+             let xs: [Config] = [{ name: \"a\" }, { name: \"b\", retries: 1 }]\n\
+             fn pick(flag: bool) -> Config { if flag { { name: \"c\" } } else { ret { name: \"d\" } } }\n\
+             let job: Job = { config: { name: \"e\" } }",
+        );
+        assert_eq!(js.matches("Config({").count(), 5, "got: {}", js);
+        assert!(js.contains("} else {\n        return Config({\n            name: \"d\"\n        });"), "got: {}", js);
+    }
+
+    #[test]
+    fn enum_runtime_constructors() {
+        let js = compile("enum Status { Pending, Active(since: str, by: str) }\npub enum Level { Low }");
+        assert!(js.contains("const Status = {\n    Pending: {\n        tag: \"Pending\"\n    },"), "got: {}", js);
+        assert!(js.contains("Active: (since, by)=>({\n            tag: \"Active\",\n            since,\n            by\n        })"), "got: {}", js);
+        assert!(js.contains("export const Level = {"), "got: {}", js);
+    }
+
+    #[test]
+    fn enum_construct_then_match() {
+        let js = compile(
+            "enum Res { Ok(value: int), Err(code: int, msg: str) }\n\
+             fn show(r: Res) -> str { match r { Res::Ok(v) => \"ok\", Res::Err(c, m) => m } }\n\
+             let a = show(Res::Err(404, \"missing\"))",
+        );
+        // Constructed as `{ tag: "Err", code, msg }`, the bindings read the
+        // fields they stand for
+        assert!(js.contains("Err: (code, msg)=>({"), "got: {}", js);
+        assert!(js.contains("_match.tag === \"Ok\""), "got: {}", js);
+        assert!(js.contains("const v = _match.value;"), "got: {}", js);
+        assert!(js.contains("const c = _match.code;"), "got: {}", js);
+        assert!(js.contains("const m = _match.msg;"), "got: {}", js);
+        assert!(js.contains("const a = show(Res.Err(404, \"missing\"));"), "got: {}", js);
+
+        // The checker supplies the fields of an imported enum
+        let parsed = ag_parser::parse(
+            "import { Res } from \"./res\"\nfn show(r: Res) -> str { match r { Res::Err(c, m) => m, _ => \"ok\" } }",
+        );
+        let mut translator = Translator::new();
+        let fields = vec!["code".to_string(), "msg".to_string()];
+        translator.set_variant_fields(HashMap::from([(("Res".to_string(), "Err".to_string()), fields)]));
+        let js = translator.codegen(&parsed.module).unwrap();
+        assert!(js.contains("const c = _match.code;"), "got: {}", js);
+        assert!(js.contains("const m = _match.msg;"), "got: {}", js);
+    }
+
+    #[test]
+    fn interface_erased() {
+        let js = compile("interface Shape { fn area(self) -> num }");
+        assert!(js.trim().is_empty());
+    }
+
+    #[test]
+    fn impl_methods_become_functions() {
+        let js = compile(
+            "struct Circle { r: num }\nimpl Shape for Circle { fn area(self) -> num { self.r * self.r } }",
+        );
+        assert!(js.contains("function Circle_area(self)"), "got: {}", js);
+        assert!(js.contains("self.r * self.r"), "got: {}", js);
+    }
+
+    #[test]
+    fn pub_use_exports_renamed_names() {
+        let js = compile("fn internalHelper() {}\nlet count = 1\npub use { internalHelper as helper, count }");
+        assert!(js.contains("export { internalHelper as helper, count }"), "got: {}", js);
+    }
+
+    #[test]
+    fn fn_expression_body_returns_expr() {
+        let js = compile("fn square(x: num) -> num = x * x");
+        assert!(js.contains("return x * x"), "got: {}", js);
+    }
+
+    #[test]
+    fn type_alias_erased() {
+        let js = compile("type ID = str");
+        assert!(js.trim().is_empty());
+    }
+
+    #[test]
+    fn for_of_loop() {
+        let js = compile("fn f(items: [int]) { for item in items { process(item) } }");
+        assert!(js.contains("for (const item of items)"));
+    }
+
+    #[test]
+    fn for_range_loop() {
+        let js = compile("fn f(n: int) { for let i = 0; i < n; i += 1 { log(i) } }");
+        assert!(js.contains("for(let i = 0; i < n; i += 1)"), "got: {}", js);
+    }
+
+    #[test]
+    fn while_loop() {
+        let js = compile("fn f() { while x > 0 { x = x - 1 } }");
+        assert!(js.contains("while"));
+    }
+
+    #[test]
+    fn while_let_lowers_to_loop_with_break() {
+        let js = compile("fn f() { while let Opt::Some(v) = next() { consume(v) } }");
+        assert!(js.contains("while(true)"), "got: {}", js);
+        assert!(js.contains("const _match = next();"), "got: {}", js);
+        assert!(js.contains("consume(v);"), "got: {}", js);
+        assert!(js.contains("break;"), "got: {}", js);
+        assert!(!js.contains("=>"), "arm bodies must not be wrapped in an IIFE: {}", js);
+    }
+
+    #[test]
+    fn match_statement_is_an_if_chain() {
+        let js = compile("fn f(x: int) {\n  match x {\n    1 => log(\"one\"),\n    _ => { log(\"other\") }\n  }\n  log(\"done\")\n}");
+        assert!(js.contains("const _match = x;"), "got: {}", js);
+        assert!(js.contains("if (_match === 1) {"), "got: {}", js);
+        assert!(js.contains("log(\"other\");"), "got: {}", js);
+        assert!(!js.contains("=>"), "statement match must not be an IIFE: {}", js);
+    }
+
+    #[test]
+    fn statement_and_return_positions_need_no_iife() {
+        let src = "fn kind(x: int) -> str {\n  match x {\n    1 => \"one\",\n    _ => { let s = \"many\"\n s }\n  }\n}\n\
+            fn sign(x: int) -> int {\n  if x > 0 { log(x)\n 1 } else if x < 0 { -1 } else { 0 }\n}\n\
+            fn pick(x: int) -> int { ret match x { 1 => 10, _ => 20 } }\n\
+            fn maybe(x: int) { if x > 0 { log(x) } }\n\
+            if flag { log(\"top\") }\n\
+            match 1 { 1 => log(\"a\"), _ => log(\"b\") }";
+        let js = compile(src);
+        assert_eq!(js.matches("=>").count(), 0, "got: {}", js);
+        assert!(js.contains("} else {\n            const s = \"many\";\n            return s;"), "got: {}", js);
+        assert!(js.contains("log(x);\n        return 1;\n    } else if (x < 0) {\n        return -1;"), "got: {}", js);
+        assert!(js.contains("if (_match1 === 1) {\n            return 10;"), "got: {}", js);
+        assert!(js.contains("if (flag) {\n    log(\"top\");\n}"), "got: {}", js);
+
+        // Simple branches stay a ternary; a match used as a value is an IIFE
+        let js = compile("fn f(x: int) -> int { if x > 0 { 1 } else { 2 } }\nfn g(x: int) -> int { h(match x { _ => 1 }) }");
+        assert!(js.contains("return x > 0 ? 1 : 2;"), "got: {}", js);
+        assert_eq!(js.matches("(()=>").count(), 1, "got: {}", js);
+    }
 
-    fn compile(src: &str) -> String {
-        let parsed = ag_parser::parse(src);
-        assert!(
-            parsed.diagnostics.is_empty(),
-            "parse errors: {:?}",
-            parsed.diagnostics
+    #[test]
+    fn match_initializer_assigns_from_arms() {
+        let js = compile(
+            "async fn f(n: int, g: any) -> str {\n  let x = match n {\n    1 => \"one\",\n    2 => await g(),\n    _ => { let s = \"many\"\n s }\n  }\n  x\n}",
         );
-        codegen(&parsed.module)
+        assert!(js.contains("let x;"), "got: {}", js);
+        assert!(js.contains("x = \"one\";"), "got: {}", js);
+        assert!(js.contains("x = await g();"), "got: {}", js);
+        assert!(js.contains("const s = \"many\";\n                x = s;"), "got: {}", js);
+        assert!(!js.contains("=>"), "match initializer must not be an IIFE: {}", js);
     }
 
     #[test]
-    fn let_binding() {
-        let js = compile("let x = 42");
-        assert!(js.contains("const x = 42"));
+    fn match_arm_blocks_in_a_value_match() {
+        let js = compile("fn f(x: int) -> int { h(match x { 0 => { let y = compute()\n y * 2 }, _ => 0 }) }");
+        assert_eq!(js, "function f(x) {\n    return h((()=>{\n        const _match = x;\n        if (_match === 0) {\n            const y = compute();\n            return y * 2;\n        } else {\n            return 0;\n        }\n    })());\n}\n");
     }
 
     #[test]
-    fn mut_binding() {
-        let js = compile("mut counter = 0");
-        assert!(js.contains("let counter = 0"));
+    fn range_patterns_exclusive_and_inclusive() {
+        let js = compile(r#"fn f(n: int) -> str { match n { 0..10 => "digit", 10..=99 => "two", _ => "big" } }"#);
+        assert!(js.contains("_match >= 0 && _match < 10"), "got: {}", js);
+        assert!(js.contains("_match >= 10 && _match <= 99"), "got: {}", js);
     }
 
     #[test]
-    fn const_binding() {
-        let js = compile("const MAX = 100");
-        assert!(js.contains("const MAX = 100"));
+    fn char_literals_are_code_points() {
+        let js = compile("fn f(c: int) -> bool { match c { 'a'..='z' => true, _ => c == '\\n' } }");
+        assert!(js.contains("_match >= 97 && _match <= 122"), "got: {}", js);
+        assert!(js.contains("c === 10"), "got: {}", js);
     }
 
     #[test]
-    fn simple_function() {
-        let js = compile("fn add(a: int, b: int) -> int { a + b }");
-        assert!(js.contains("function add(a, b)"));
-        assert!(js.contains("return a + b"));
+    fn literal_matches_lower_to_switch() {
+        let js = compile("fn f(s: str) {\n  match s {\n    \"a\" => log(1),\n    \"b\" => { log(2) }\n    \"a\" => log(3),\n    other => log(other)\n  }\n  log(0)\n}");
+        assert!(js.contains("switch(_match){"), "got: {}", js);
+        // Each case ends in a `break`; the repeated `"a"` is dropped
+        assert!(js.contains("case \"a\":\n                log(1);\n                break;\n            case \"b\":\n                log(2);\n                break;\n            default:"), "got: {}", js);
+        assert_eq!(js.matches("case \"a\"").count(), 1, "got: {}", js);
+        assert!(js.contains("const other = _match;\n                    log(other);"), "got: {}", js);
+
+        // Returning cases need no `break`; `'a'` is the same case as `97`
+        let js = compile("fn f(c: int) -> str { match c { 97 => \"a\", 'a' => \"dup\", 98 => \"b\", _ => \"?\" } }");
+        assert!(js.contains("switch(c){\n        case 97:\n            return \"a\";\n        case 98:\n            return \"b\";\n        default:\n            return \"?\";"), "got: {}", js);
     }
 
     #[test]
-    fn pub_function() {
-        let js = compile("pub fn greet(name: str) -> str { name }");
-        assert!(js.contains("export function greet(name)"));
+    fn matches_that_cannot_be_switches_stay_if_chains() {
+        for src in [
+            // `break` would leave the switch rather than the loop
+            "fn f(xs: [int]) { for x in xs { match x { 1 => { break }, 2 => log(2), _ => {} } } }",
+            "fn f(n: int) { match n { 1 => log(1), 2 if n > 0 => log(2), _ => {} } }",
+            "fn f(n: int) { match n { 1 => log(1), 2..5 => log(2), _ => {} } }",
+            "fn f(n: int) { match n { 1 => log(1), _ => {} } }",
+        ] {
+            let js = compile(src);
+            assert!(!js.contains("switch"), "{src}: {}", js);
+            assert!(js.contains("if (_match === 1)"), "{src}: {}", js);
+        }
     }
 
     #[test]
-    fn default_params() {
-        let js = compile("fn greet(name: str, loud: bool = false) -> str { name }");
-        assert!(js.contains("loud = false"));
+    fn negative_literal_patterns() {
+        let js = compile(r#"fn f(n: num) -> str { match n { -1 => "minus one", -3.14 => "minus pi", _ => "other" } }"#);
+        assert!(js.contains("case -1:"), "got: {}", js);
+        assert!(js.contains("case -3.14:"), "got: {}", js);
+        let js = compile("let a = 1 - -2");
+        assert!(js.contains("const a = 1 - -2;"), "got: {}", js);
     }
 
     #[test]
-    fn arrow_function() {
-        let js = compile("let double = (x: int) => x * 2");
-        assert!(js.contains("const double = (x)=>x * 2"));
+    fn method_calls_use_impl_functions() {
+        let js = compile_checked(
+            "struct Sq { side: num }\nimpl Sq {\n  fn unit() -> Sq { { side: 1.0 } }\n  fn area(self) -> num { self.side * self.side }\n}\nlet a = Sq::unit().area()",
+        );
+        assert!(js.contains("const a = Sq_area(Sq_unit());"), "got: {}", js);
     }
 
     #[test]
-    fn struct_erased() {
-        let js = compile("struct User { name: str, age: int }");
-        assert!(js.trim().is_empty());
+    fn loop_statement() {
+        let js = compile("fn f() { loop { if done() { break } }\n tick() }");
+        assert!(js.contains("while(true)"), "got: {}", js);
+        assert!(js.contains("break;"), "got: {}", js);
+        assert!(!js.contains("=>"), "statement loop must not be wrapped: {}", js);
     }
 
     #[test]
-    fn enum_erased() {
-        let js = compile("enum Status { Pending, Active(since: str) }");
-        assert!(js.trim().is_empty());
+    fn loop_value_hoisted_into_result_variable() {
+        let js = compile("fn f() -> int { let x = loop { if ready() { break 1 } }\n x }");
+        assert!(js.contains("let _loop_result;"), "got: {}", js);
+        assert!(js.contains("_loop_result = 1;"), "got: {}", js);
+        assert!(js.contains("const x = _loop_result;"), "got: {}", js);
+        assert!(!js.contains("=>"), "let-bound loop must not be wrapped: {}", js);
     }
 
     #[test]
-    fn type_alias_erased() {
-        let js = compile("type ID = str");
-        assert!(js.trim().is_empty());
+    fn nested_value_loops_use_distinct_results() {
+        let js = compile("fn f() { let a = loop { let b = loop { break 1 }\n break b } }");
+        assert!(js.contains("_loop_result1 = 1;"), "got: {}", js);
+        assert!(js.contains("const b = _loop_result1;"), "got: {}", js);
+        assert!(js.contains("_loop_result = b;"), "got: {}", js);
     }
 
     #[test]
-    fn for_of_loop() {
-        let js = compile("fn f(items: [int]) { for item in items { process(item) } }");
-        assert!(js.contains("for (const item of items)"));
+    fn loop_results_avoid_unread_declarations() {
+        let js = compile("fn f(n: int) -> int { let _loop_result = 0; let x = loop { break n }; x }");
+        assert!(js.contains("const _loop_result = 0;\n    let _loop_result1;"), "got: {}", js);
+        assert!(js.contains("const x = _loop_result1;"), "got: {}", js);
+        // Parameters and pattern bindings in nested functions count too
+        let js = compile("fn f(xs: [int]) { g((_loop_result: int) => 1)\n let a = loop { break 1 } }");
+        assert!(js.contains("let _loop_result1;"), "got: {}", js);
+        let js = compile("fn _loop_result() {}\nfn f(x: int) { let a = loop { break 1 } }");
+        assert!(js.contains("let _loop_result1;"), "got: {}", js);
     }
 
     #[test]
-    fn while_loop() {
-        let js = compile("fn f() { while x > 0 { x = x - 1 } }");
-        assert!(js.contains("while"));
+    fn loop_value_in_expression_position() {
+        let js = compile("fn f() { g(loop { break 2 }) }");
+        assert!(js.contains("_loop_result = 2;"), "got: {}", js);
+        assert!(js.contains("return _loop_result;"), "got: {}", js);
+    }
+
+    #[test]
+    fn raw_string_backslashes_escaped_in_js() {
+        let js = compile(r#"let p = r"C:\temp\new""#);
+        assert!(js.contains(r#""C:\\temp\\new""#), "got: {}", js);
+    }
+
+    #[test]
+    fn shebang_preserved() {
+        let js = compile("#!/usr/bin/env node\nlet x = 1");
+        assert!(js.starts_with("#!/usr/bin/env node\n"), "got: {}", js);
     }
 
     #[test]
@@ -1437,12 +4156,193 @@ mod tests {
         assert!(js.contains("write"));
     }
 
+    #[test]
+    fn aliased_imports_bind_the_alias() {
+        let js = compile(
+            r#"import { readFile as rf, write } from "./fs"
+fn load(p: str) -> any { rf(p) }"#,
+        );
+        assert!(js.contains(r#"import { readFile as rf, write } from "./fs""#), "got: {}", js);
+        assert!(js.contains("return rf(p)"), "got: {}", js);
+    }
+
+    #[test]
+    fn js_extern_imports_alias_the_js_name() {
+        let js = compile(
+            r#"@js("my-lib", name = "doWork")
+extern fn do_work(input: str) -> str
+@js("my-lib", name = "helper")
+extern fn helper() -> str
+fn main() { do_work(helper()) }"#,
+        );
+        assert!(js.contains(r#"import { doWork as do_work, helper } from "my-lib""#), "got: {}", js);
+        assert!(js.contains("do_work(helper())"), "got: {}", js);
+    }
+
+    #[test]
+    fn imports_of_a_module_are_merged() {
+        let js = compile(
+            r#"import { join } from "node:path"
+import * as fs from "node:fs"
+import "./polyfills"
+import { join, sep } from "node:path"
+import * as fs from "node:fs"
+@js("node:path")
+extern fn resolve(p: str) -> str
+@js("node:path", name = "basename")
+extern fn base(p: str) -> str
+@js("node:fs")
+extern fn readFileSync(p: str) -> str
+@js("node:os")
+extern fn tmpdir() -> str
+@js("node:os")
+extern fn homedir() -> str
+@js("./polyfills")
+extern fn shim() -> nil
+fn main() {
+    log(join(resolve(tmpdir()), base(homedir()), sep), fs, readFileSync("x"), shim())
+}"#,
+        );
+        let imports: Vec<&str> = js.lines().filter(|l| l.starts_with("import")).collect();
+        assert_eq!(
+            imports,
+            [
+                r#"import { readFileSync } from "node:fs";"#,
+                r#"import { homedir, tmpdir } from "node:os";"#,
+                r#"import { join, sep, basename as base, resolve } from "node:path";"#,
+                r#"import * as fs from "node:fs";"#,
+                r#"import { shim } from "./polyfills";"#,
+            ]
+        );
+        let imports = compile(r#"import { a } from "m"
+import "m"
+fn f() { a() }"#);
+        assert!(imports.starts_with("import { a } from \"m\";\nfunction f()"), "got: {}", imports);
+    }
+
+    #[test]
+    fn repeated_js_externs_share_one_specifier() {
+        let js = compile(
+            r#"@js("my-lib", name = "doWork")
+extern fn work(input: str) -> str
+@js("my-lib", name = "doWork")
+extern fn work(input: str) -> str
+@js("my-lib")
+extern fn doWork(input: str) -> str
+@js("my-lib", name = "doWork")
+extern fn run(input: str) -> str
+@js("my-lib", name = "cleanup")
+extern fn zap() -> nil
+fn main() { work(doWork(run("x")))
+ zap() }"#,
+        );
+        let imports: Vec<&str> = js.lines().filter(|l| l.starts_with("import")).collect();
+        assert_eq!(
+            imports,
+            [r#"import { cleanup as zap, doWork, doWork as run, doWork as work } from "my-lib";"#]
+        );
+    }
+
+    #[test]
+    fn conflicting_imports_are_an_error() {
+        let parsed = ag_parser::parse(
+            r#"import { read } from "./a"
+import { write as read } from "./b""#,
+        );
+        let err = Translator::new().codegen(&parsed.module).unwrap_err();
+        assert_eq!(
+            err.message,
+            r#"conflicting imports of `read`: `read` from "./a" and `write` from "./b""#
+        );
+    }
+
+    #[test]
+    fn type_only_import_specifiers_are_dropped() {
+        let parsed = ag_parser::parse(
+            r#"import { Id, load } from "./model"
+import { Named, Shape as S } from "./types"
+load(1)"#,
+        );
+        let mut rewrites = Rewrites::default();
+        for item in &parsed.module.items {
+            let Item::Import(imp) = item else { continue };
+            rewrites.type_imports.extend(imp.names.iter().filter(|n| n.name != "load").map(|n| n.span));
+        }
+        let js = Translator::builder().with_rewrites(rewrites).build().codegen(&parsed.module).unwrap();
+        let imports: Vec<&str> = js.lines().filter(|l| l.starts_with("import")).collect();
+        assert_eq!(imports, [r#"import { load } from "./model";"#]);
+    }
+
+    #[test]
+    fn import_paths_rewritten_to_emitted_files() {
+        let parsed = ag_parser::parse(
+            r#"import { a } from "./a.ag"
+import { b } from "../b"
+import * as fs from "node:fs"
+import { z } from "zod"
+import "./styles.css"
+@js("./native")
+extern fn native() -> str
+@js("./lib/util.ag", name = "helper")
+extern fn helper() -> str
+fn main() { a(b(fs, z, native(), helper())) }"#,
+        );
+        let import_paths = |import_rewrite| {
+            let translator = Translator::builder()
+                .with_options(CodegenOptions { import_rewrite, ..Default::default() })
+                .build();
+            let js = translator.codegen(&parsed.module).unwrap();
+            js.lines()
+                .filter_map(|l| l.split(" from ").nth(1).or(l.strip_prefix("import ")))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        let rewrite = |extensionless| ImportRewrite { extension: "mjs".to_string(), extensionless };
+        assert_eq!(
+            import_paths(Some(rewrite(true))),
+            [
+                r#""./lib/util.mjs";"#,
+                r#""./native.mjs";"#,
+                r#""./a.mjs";"#,
+                r#""../b.mjs";"#,
+                r#""node:fs";"#,
+                r#""zod";"#,
+                r#""./styles.css";"#,
+            ]
+        );
+        assert_eq!(
+            import_paths(Some(rewrite(false))),
+            [
+                r#""./lib/util.mjs";"#,
+                r#""./native";"#,
+                r#""./a.mjs";"#,
+                r#""../b";"#,
+                r#""node:fs";"#,
+                r#""zod";"#,
+                r#""./styles.css";"#,
+            ]
+        );
+        assert_eq!(import_paths(None)[2], r#""./a.ag";"#);
+    }
+
     #[test]
     fn namespace_import() {
         let js = compile(r#"import * as fs from "./fs""#);
         assert!(js.contains("* as fs"));
     }
 
+    #[test]
+    fn side_effect_import() {
+        let js = compile("import \"./polyfills\"");
+        assert_eq!(js.trim(), r#"import "./polyfills";"#);
+    }
+
+    #[test]
+    fn template_import_path_emits_string() {
+        let js = compile("import { read } from `./fs`");
+        assert!(js.contains(r#"import { read } from "./fs""#), "got: {}", js);
+    }
+
     #[test]
     fn pipe_simple() {
         let js = compile("let x = data |> parse");
@@ -1467,6 +4367,12 @@ mod tests {
         assert!(js.contains("`"));
     }
 
+    #[test]
+    fn tagged_template() {
+        let js = compile("let x = html`<div>${content}</div>`");
+        assert!(js.contains("html`<div>${content}</div>`"), "got: {}", js);
+    }
+
     // ── DSL codegen tests (prompt-dsl handler) ──
 
     #[test]
@@ -1508,6 +4414,270 @@ mod tests {
         assert!(err.message.contains("graphql"));
     }
 
+    #[test]
+    fn sourcemap_maps_statements_to_ag_lines() {
+        let src = "extern fn log(x: any)\n\nfn main() {\n    let x = 1\n    log(x)\n}\n";
+        let parsed = ag_parser::parse(src);
+        let translator = Translator::new();
+        let (js, map) = translator.codegen_with_sourcemap(&parsed.module, src, "main.ag").unwrap();
+        // The same code as without a source map
+        assert_eq!(js, translator.codegen(&parsed.module).unwrap());
+
+        let map = swc_sourcemap::SourceMap::from_slice(map.as_bytes()).unwrap();
+        assert_eq!(map.get_source(0).map(|s| &**s), Some("main.ag"));
+        let (line, text) = js.lines().enumerate().find(|(_, l)| l.contains("log(x)")).unwrap();
+        let col = text.find("log").unwrap();
+        let token = map.lookup_token(line as u32, col as u32).unwrap();
+        assert_eq!((token.get_src_line(), token.get_src_col()), (4, 4));
+        let (line, text) = js.lines().enumerate().find(|(_, l)| l.contains("const x")).unwrap();
+        let token = map.lookup_token(line as u32, text.find("const").unwrap() as u32).unwrap();
+        assert_eq!(token.get_src_line(), 3);
+
+        // Doc comments are attached once, although the function now has a position too
+        let src = "/// Adds.\npub fn add(a: int, b: int) -> int { a + b }";
+        let parsed = ag_parser::parse_with_options(src, ag_parser::ParseOptions { preserve_comments: true });
+        let (js, _) = translator.codegen_with_sourcemap(&parsed.module, src, "add.ag").unwrap();
+        assert_eq!(js.matches("Adds.").count(), 1, "got: {}", js);
+    }
+
+    #[test]
+    fn match_guards_read_pattern_bindings() {
+        let js = compile(
+            "enum Status { Ok, Error(code: int) }\nfn describe(s: Status) -> str {\n  match s {\n    Status::Error(code) if code > 400 => `server ${code}`,\n    _ => \"ok\",\n  }\n}",
+        );
+        assert!(js.contains("if (_match.tag === \"Error\" && _match.code > 400) {\n            const code = _match.code;"), "got: {}", js);
+        let js = compile("fn sign(x: int) -> str { match x { n if n > 0 => \"pos\", _ => \"non-pos\" } }");
+        assert!(js.contains("if (_match > 0) {\n            const n = _match;"), "got: {}", js);
+        // The arm body still uses the declared binding
+        let js = compile("fn f(x: int) -> int { match x { n if n > 0 => n * 2, _ => 0 } }");
+        assert!(js.contains("return n * 2;"), "got: {}", js);
+        // Names the guard binds anew are its own
+        let js = compile(
+            "fn f(x: int, xs: [int]) -> bool { match x { n if n > 0 && some(xs, (n: int) => n > 3) => true, _ => false } }",
+        );
+        assert!(js.contains("_match > 0 && some(xs, (n)=>n > 3)"), "got: {}", js);
+        let js = compile(
+            "fn f(x: int) -> bool { match x { n if { let n = 2; n > 1 } => true, _ => false } }",
+        );
+        assert!(js.contains("const n = 2;\n            return n > 1;"), "got: {}", js);
+        // A nested guard still reads the outer arm's bindings
+        let js = compile(
+            "fn f(x: int, y: int) -> bool { match x { n if match y { k if k > n => true, _ => false } => true, _ => false } }",
+        );
+        assert!(js.contains("_match1 > _match"), "got: {}", js);
+    }
+
+    #[test]
+    fn nested_match_subjects_are_distinct() {
+        let js = compile(
+            "fn classify(a: int, b: int) -> str {\n  match a {\n    0 => match b { 0 => \"both\", _ => `a only ${a}` },\n    _ => \"neither\",\n  }\n}",
+        );
+        assert!(js.contains("const _match = a;"), "got: {}", js);
+        assert!(js.contains("const _match1 = b;\n                if (_match1 === 0) {"), "got: {}", js);
+
+        // Numbering starts over for each module
+        let parsed = ag_parser::parse("fn f(a: int) -> int { match a { _ => 1 } }");
+        let translator = Translator::new();
+        let first = translator.codegen(&parsed.module).unwrap();
+        assert_eq!(first, translator.codegen(&parsed.module).unwrap());
+        assert!(first.contains("const _match = a;"), "got: {}", first);
+    }
+
+    #[test]
+    fn error_propagation_temporary_avoids_user_names() {
+        let js = compile("fn parse(n: int) -> int { n + 1 }\nfn f() -> int {\n  let _tmp = 5\n  let r = parse(_tmp)?\n  r\n}");
+        assert!(js.contains("const _tmp = 5;"), "got: {}", js);
+        assert!(js.contains("const _tmp1 = parse(_tmp);"), "got: {}", js);
+        assert!(js.contains("if (_tmp1 instanceof Error) return _tmp1;"), "got: {}", js);
+    }
+
+    #[test]
+    fn error_propagation_returns_from_the_function() {
+        let js = compile("fn f(s: str) -> any {\n  let x = parse(s)?\n  log(s, check(x)?)\n  x\n}");
+        assert!(
+            js.contains("const _tmp = parse(s);\n    if (_tmp instanceof Error) return _tmp;\n    const x = _tmp;"),
+            "got: {}",
+            js
+        );
+        // `s` is evaluated before the hoisted check, as it comes first
+        assert!(
+            js.contains("const _tmp1 = check(x);\n    if (_tmp1 instanceof Error) return _tmp1;\n    log(s, _tmp1);"),
+            "got: {}",
+            js
+        );
+        let js = compile("fn f(a: any, b: any) -> any { a.len + parse(b)? }");
+        assert!(js.contains("const _tmp = a.len;\n    const _tmp1 = parse(b);"), "got: {}", js);
+        assert!(!js.contains("=>"), "got: {}", js);
+    }
+
+    #[test]
+    fn error_propagation_in_conditions_and_arrows() {
+        let js = compile("fn f(xs: any) -> any {\n  let g = (x: any) => parse(x)?\n  while next(xs)? { }\n  if a { } else if parse(xs)? { }\n  0\n}");
+        assert!(js.contains("const g = (x)=>{\n        const _tmp = parse(x);\n        if (_tmp instanceof Error) return _tmp;\n        return _tmp;"), "got: {}", js);
+        assert!(js.contains("while(true){\n        const _tmp1 = next(xs);\n        if (_tmp1 instanceof Error) return _tmp1;\n        if (!(_tmp1)) break;"), "got: {}", js);
+        assert!(js.contains("} else {\n        const _tmp2 = parse(xs);"), "got: {}", js);
+    }
+
+    #[test]
+    fn error_propagation_keeps_operand_order() {
+        let js = compile("fn f(s: str) -> str { `${a()}:${parse(s)?}` }");
+        assert!(js.contains("const _tmp = a();\n    const _tmp1 = parse(s);"), "got: {}", js);
+        assert!(js.contains("return `${_tmp}:${_tmp1}`;"), "got: {}", js);
+        let js = compile("fn f(s: str) -> any { g()[parse(s)?] }");
+        assert!(js.contains("const _tmp = g();\n    const _tmp1 = parse(s);"), "got: {}", js);
+        assert!(js.contains("return _tmp[_tmp1];"), "got: {}", js);
+        let js = compile("fn f(s: str) { g().x = parse(s)? }");
+        assert!(js.contains("const _tmp = g();\n    const _tmp1 = parse(s);"), "got: {}", js);
+        assert!(js.contains("_tmp.x = _tmp1;"), "got: {}", js);
+    }
+
+    #[test]
+    fn error_propagation_in_value_branches() {
+        let js = compile("fn f(a: bool, s: str) -> any {\n  let x = if a { parse(s)? } else { 0 }\n  x\n}");
+        assert!(
+            js.contains("let _result;\n    if (a) {\n        const _tmp = parse(s);\n        if (_tmp instanceof Error) return _tmp;\n        _result = _tmp;\n    } else {\n        _result = 0;\n    }\n    const x = _result;"),
+            "got: {}",
+            js
+        );
+        let js = compile("fn f(n: int, s: str) { log(g(), match n { 0 => parse(s)?, _ => 1 }) }");
+        assert!(js.contains("const _tmp = g();\n    let _result;"), "got: {}", js);
+        assert!(js.contains("_result = _tmp1;"), "got: {}", js);
+        assert!(js.contains("log(_tmp, _result);"), "got: {}", js);
+    }
+
+    #[test]
+    fn error_propagation_without_early_return_is_an_error() {
+        for (src, message) in [
+            ("let x = parse(s)?", "`?` outside of a function has nothing to return from"),
+            ("fn f(a: bool) -> any { a && parse(s)? }", "`?` cannot return early from here"),
+            ("fn f(a: any) -> any { a ?? parse(s)? }", "`?` cannot return early from here"),
+        ] {
+            let parsed = ag_parser::parse(src);
+            let err = Translator::new().codegen(&parsed.module).unwrap_err();
+            assert!(err.message.starts_with(message), "{src}: {}", err.message);
+            assert_eq!(&src[err.span.start as usize..err.span.end as usize], "?", "{src}");
+        }
+    }
+
+    fn compile_ts_comments(src: &str) -> String {
+        let options = ag_parser::ParseOptions { preserve_comments: true };
+        let parsed = ag_parser::parse_with_options(src, options);
+        assert!(parsed.diagnostics.is_empty(), "parse errors: {:?}", parsed.diagnostics);
+        let translator = Translator::builder()
+            .with_options(CodegenOptions { ts_comments: true, ..Default::default() })
+            .build();
+        translator.codegen(&parsed.module).unwrap()
+    }
+
+    #[test]
+    fn ts_comments_on_typed_variables() {
+        let js = compile_ts_comments(
+            "let name: str = \"Alice\"\nlet n = 1\nfn body(xs: [int | str], seed: any) {\n  mut total: {str: num} = seed\n  let f: (int) -> bool? = (x: int) => x > 0\n  let m: str = match 1 { 1 => \"a\", _ => \"b\" }\n}",
+        );
+        assert!(js.contains("/** @type {string} */ const name = \"Alice\";"), "got: {}", js);
+        assert!(js.contains("\nconst n = 1;"), "got: {}", js);
+        assert!(js.contains("/** @type {Record<string, number>} */ let total = seed;"), "got: {}", js);
+        assert!(js.contains("/** @type {(arg0: number) => boolean | null} */ const f ="), "got: {}", js);
+        assert!(js.contains("/** @type {string} */ let m;"), "got: {}", js);
+        // Off by default
+        assert!(!compile("let name: str = \"Alice\"").contains("/*"));
+    }
+
+    #[test]
+    fn ts_comments_on_function_signatures() {
+        let js = compile_ts_comments(
+            "/// Doubles.\npub fn double(a: int, loud: bool = false) -> int { a * 2 }\nasync fn load(urls: [str | nil]) -> str? { nil }\nfn untyped(x) { x }",
+        );
+        assert!(
+            js.contains("/**\n * Doubles.\n * @param {number} a\n * @param {boolean} [loud]\n * @returns {number}\n */ export function double("),
+            "got: {}",
+            js
+        );
+        assert!(
+            js.contains("/**\n * @param {(string | null)[]} urls\n * @returns {Promise<string | null>}\n */ async function load("),
+            "got: {}",
+            js
+        );
+        assert!(js.contains("\nfunction untyped(x)"), "got: {}", js);
+    }
+
+    #[test]
+    fn ts_comments_on_methods() {
+        let js = compile_ts_comments(
+            "struct Circle { r: num }\nimpl Circle {\n  fn grow(mut self, by: num) -> Circle { { r: self.r + by } }\n  fn unit() -> Circle { { r: 1.0 } }\n}",
+        );
+        assert!(
+            js.contains("/**\n * @param {Circle} self\n * @param {number} by\n * @returns {Circle}\n */ function Circle_grow(self, by)"),
+            "got: {}",
+            js
+        );
+        assert!(js.contains("/** @returns {Circle} */ function Circle_unit()"), "got: {}", js);
+    }
+
+    #[test]
+    fn ts_comments_parenthesize_functions_in_unions() {
+        let js = compile_ts_comments("fn f(cb: ((int) -> int)?, g: ((str) -> str) | int) {}");
+        assert!(js.contains("@param {((arg0: number) => number) | null} cb"), "got: {}", js);
+        assert!(js.contains("@param {((arg0: string) => string) | number} g"), "got: {}", js);
+    }
+
+    #[test]
+    fn builder_registers_handlers_and_options() {
+        let parsed = ag_parser::parse("@prompt greeting <<EOF\n@role system\nHello\nEOF\nlet x: int? = nil");
+        let translator = TranslatorBuilder::new()
+            .with_handler("prompt", Box::new(ag_dsl_prompt::handler::PromptDslHandler))
+            .with_options(CodegenOptions { nil_to_undefined: true, ..Default::default() })
+            .build();
+        let js = translator.codegen(&parsed.module).unwrap();
+        assert!(js.contains("const greeting"), "got: {}", js);
+        assert!(js.contains("const x = undefined"), "got: {}", js);
+
+        // Only the handlers given are registered
+        let parsed = ag_parser::parse("@agent bot <<EOF\n@model \"gpt\"\nEOF\n");
+        let err = Translator::builder().build().codegen(&parsed.module).unwrap_err();
+        assert!(err.message.contains("no handler registered"), "got: {}", err.message);
+        let js = Translator::builder().with_builtin_handlers().build().codegen(&parsed.module);
+        assert!(js.is_ok());
+
+        let parsed = ag_parser::parse("@component Card <<EOF\nexport default function Card() { return <div/> }\nEOF\n");
+        let err = Translator::builder().with_builtin_handlers().build().codegen(&parsed.module).unwrap_err();
+        assert!(err.message.contains("components are not emitted yet"), "got: {}", err.message);
+    }
+
+    #[test]
+    fn member_and_index_assignment_targets() {
+        let js = compile(
+            "fn f(user: any, xs: [int], i: int, a: any, v: int) {\n  user.name = \"x\"\n  xs[i] += 1\n  a.b.c[0] = v\n}",
+        );
+        assert!(js.contains(r#"user.name = "x";"#), "got: {}", js);
+        assert!(js.contains("xs[i] += 1;"), "got: {}", js);
+        assert!(js.contains("a.b.c[0] = v;"), "got: {}", js);
+    }
+
+    #[test]
+    fn invalid_assignment_target_error() {
+        let src = "fn f() { 1 = 2 }";
+        let parsed = ag_parser::parse(src);
+        let err = Translator::new().codegen(&parsed.module).unwrap_err();
+        assert_eq!(err.message, "invalid assignment target");
+        assert_eq!(err.span.start, 9);
+    }
+
+    #[test]
+    fn async_arrow_awaiting_in_body() {
+        let js = compile("async fn run(g: any) {\n  let f = async (x: int) => await g(x)\n  let h = async (x: int) => { let y = await g(x)\n y }\n}");
+        assert!(js.contains("const f = async (x)=>await g(x);"), "got: {}", js);
+        assert!(js.contains("const h = async (x)=>{"), "got: {}", js);
+    }
+
+    #[test]
+    fn nested_arrow_without_await_stays_sync() {
+        let js = compile("async fn run(g: any) {\n  let f = async (x: int) => await g((y: int) => y + 1)\n  let k = (x: int) => async (y: int) => await g(y)\n}");
+        assert!(js.contains("async (x)=>await g((y)=>y + 1)"), "got: {}", js);
+        // Only the inner arrow is async
+        assert!(js.contains("const k = (x)=>async (y)=>await g(y);"), "got: {}", js);
+    }
+
     #[test]
     fn dsl_handler_uses_block_name() {
         let js = compile("@prompt my_prompt <<EOF\n@role system\nContent here\nEOF\n");
@@ -1547,6 +4717,128 @@ mod tests {
         codegen_with_tools(&parsed.module, checked.tool_registry)
     }
 
+    fn compile_checked(src: &str) -> String {
+        let parsed = ag_parser::parse(src);
+        assert!(
+            parsed.diagnostics.is_empty(),
+            "parse errors: {:?}",
+            parsed.diagnostics
+        );
+        let checked = ag_checker::check(&parsed.module);
+        assert!(checked.diagnostics.is_empty(), "check errors: {:?}", checked.diagnostics);
+        codegen_with_type_info(&parsed.module, checked.tool_registry, checked.rewrites, checked.variant_fields)
+    }
+
+    #[test]
+    fn builtin_members_use_js_names() {
+        let js = compile_checked(
+            r#"fn f(s: str, xs: [int]) -> bool { s.len > xs.len && s.trim().starts_with("a") }"#,
+        );
+        assert!(js.contains("s.length > xs.length"), "got: {}", js);
+        assert!(js.contains("s.trim().startsWith(\"a\")"), "got: {}", js);
+    }
+
+    #[test]
+    fn map_members_are_object_helpers() {
+        let js = compile_checked(
+            "fn f(m: {str: int}) -> int { mut total = 0; for k in m.keys() { total = total + m[k] }\n if m.has(\"a\") { m[\"a\"] = total }\n total }",
+        );
+        assert!(js.contains("for (const k of Object.keys(m))"), "got: {}", js);
+        assert!(js.contains("Object.hasOwn(m, \"a\")"), "got: {}", js);
+        assert!(js.contains("m[\"a\"] = total"), "got: {}", js);
+    }
+
+    #[test]
+    fn async_arrow() {
+        let js = compile("let fetchAll = async (urls) => await Promise.all(urls.map(fetch))");
+        assert!(js.contains("const fetchAll = async (urls)=>await Promise.all(urls.map(fetch))"), "got: {}", js);
+    }
+
+    #[test]
+    fn arrow_is_async_only_when_declared() {
+        let js = compile_checked(
+            "extern fn load(u: str) -> Promise<str>\nasync fn f() -> str { let g = async fn(u: str) { await load(u) }\n let h = (u: str) => u\n await g(h(\"a\")) }",
+        );
+        assert!(js.contains("const g = async (u)=>{"), "got: {}", js);
+        assert!(js.contains("const h = (u)=>u"), "got: {}", js);
+    }
+
+    #[test]
+    fn for_over_map_yields_keys() {
+        let js = compile_checked("fn f(m: {str: int}) -> int { mut t = 0; for k in m { t += m[k] }\n t }");
+        assert!(js.contains("for(const k in m)"), "got: {}", js);
+        let js = compile_checked("fn f(xs: [int]) { for x in xs { } }");
+        assert!(js.contains("for (const x of xs)"), "got: {}", js);
+    }
+
+    #[test]
+    fn index_and_field_assignment_in_fn_body() {
+        let js = compile("fn f(arr: [int], point: { x: num }) { arr[0] = 10; point.x = 5.0 }");
+        assert!(js.contains("arr[0] = 10"), "got: {}", js);
+        assert!(js.contains("point.x = 5"), "got: {}", js);
+    }
+
+    #[test]
+    fn member_assignment() {
+        let js = compile_checked("fn f() { mut cfg = { retries: 1 }; cfg.retries = 3; cfg.retries += 1 }");
+        assert!(js.contains("cfg.retries = 3"), "got: {}", js);
+        assert!(js.contains("cfg.retries += 1"), "got: {}", js);
+    }
+
+    #[test]
+    fn not_in_and_is_not() {
+        let js = compile_checked(
+            "fn f(key: str, config: {str: int}, value: int?, xs: [int]) { if key not in config { } if value is not nil { } if 1 in xs { } }",
+        );
+        assert!(js.contains("if (!Object.hasOwn(config, key))"), "got: {}", js);
+        assert!(js.contains("if (value !== null)"), "got: {}", js);
+        assert!(js.contains("if (xs.includes(1))"), "got: {}", js);
+    }
+
+    #[test]
+    fn in_on_untyped_value_uses_js_in() {
+        let js = compile("fn f(obj: any) -> bool { \"a\" in obj }");
+        assert!(js.contains("\"a\" in obj"), "got: {}", js);
+    }
+
+    #[test]
+    fn chained_comparison() {
+        let js = compile("fn f(x: int) -> bool { 1 < x < 10 }");
+        assert!(js.contains("1 < x && x < 10"), "got: {}", js);
+    }
+
+    #[test]
+    fn chained_comparison_evaluates_middle_once() {
+        let js = compile("fn f() -> bool { 0 <= next() < 10 }");
+        assert!(js.contains("let _cmp;\n    return 0 <= (_cmp = next()) && _cmp < 10;"), "got: {}", js);
+
+        // The temporary avoids the parameter `_cmp`, and `await` stays in the async fn
+        let js = compile("extern fn g() -> Promise<int>\nasync fn f(_cmp: int) -> bool { _cmp < await g() < 10 }");
+        assert!(js.contains("let _cmp1;\n    return _cmp < (_cmp1 = await g()) && _cmp1 < 10;"), "got: {}", js);
+
+        // Later middle operands are assigned where they are compared
+        let js = compile("extern fn g() -> int\nextern fn h() -> int\nlet ok = [1].map((x) => 0 < g() < h() < x)");
+        assert!(
+            js.contains("(x)=>{\n    let _cmp;\n    let _cmp1;\n    return 0 < (_cmp = g()) && _cmp < (_cmp1 = h()) && _cmp1 < x;\n}"),
+            "got: {}",
+            js
+        );
+        let js = compile("extern fn g() -> int\nlet ok = 0 < g() < 10");
+        assert!(js.contains("let _cmp;\nconst ok = 0 < (_cmp = g()) && _cmp < 10;"), "got: {}", js);
+    }
+
+    #[test]
+    fn modulo_assignment() {
+        let js = compile_checked("fn f() -> int { mut x = 10; x %= 3\n x }");
+        assert!(js.contains("x %= 3"), "got: {}", js);
+    }
+
+    #[test]
+    fn struct_field_named_like_builtin_not_renamed() {
+        let js = compile_checked("struct S { len: int }\nfn f(s: S) -> int { s.len }");
+        assert!(js.contains("s.len"), "got: {}", js);
+    }
+
     #[test]
     fn tool_fn_emits_schema() {
         let js = compile_with_tools(
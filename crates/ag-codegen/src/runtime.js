@@ -0,0 +1,28 @@
+// Helpers shared by modules compiled with the `runtime_module` codegen
+// option, which import them as `__ag` instead of inlining each lowering.
+
+/** A constructor for the enum variant `tag`, taking its fields in order. */
+export function variant(tag, fields) {
+  return (...values) => {
+    const value = { tag };
+    fields.forEach((field, i) => {
+      value[field] = values[i];
+    });
+    return value;
+  };
+}
+
+/** Whether `value` is the enum variant `tag`. */
+export function matchTag(value, tag) {
+  return value.tag === tag;
+}
+
+/** Whether `x` is in `from..to`, or `from..=to` when `inclusive`. */
+export function inRange(x, from, to, inclusive) {
+  return x >= from && (inclusive ? x <= to : x < to);
+}
+
+/** Whether `value` is an error, which `?` returns early with. */
+export function isError(value) {
+  return value instanceof Error;
+}
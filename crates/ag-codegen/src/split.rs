@@ -0,0 +1,174 @@
+//! Splitting a module into one module per `pub fn`, for
+//! [`Translator::codegen_split`](crate::Translator::codegen_split).
+//! Each keeps the private functions and constants its function uses, so
+//! that a bundler importing one function loads none of the others.
+
+use std::collections::{HashMap, HashSet};
+
+use ag_ast::*;
+
+use crate::{collect_idents_block, collect_idents_expr};
+
+/// The modules to emit for `module`, one per `pub fn`, named after it, or
+/// `None` if it has fewer than two or they are not independent. Files
+/// import each other as `./<name>.<extension>`.
+///
+/// A `pub fn` gets the private functions and constants it reaches, the
+/// imports it uses, and the module's types and externs; one that calls
+/// another `pub fn` imports it from that function's file. Statements,
+/// DSL blocks, re-exports and `let`/`mut` variables run or hold state
+/// once per module, and exported types and enums would be exported by
+/// every file, so a module with any of these is not split.
+pub(crate) fn split_module(module: &Module, extension: &str) -> Option<Vec<(String, Module)>> {
+    let pub_fns: Vec<(usize, &FnDecl)> = module
+        .items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| match item {
+            Item::FnDecl(f) if f.is_pub => Some((index, f)),
+            _ => None,
+        })
+        .collect();
+    if pub_fns.len() < 2 || !module.items.iter().all(is_splittable) {
+        return None;
+    }
+
+    // Top-level names a function body can refer to, with the items they
+    // pull in. Types are copied into every file, so only functions,
+    // constants and imports are tracked.
+    let mut declared: HashMap<&str, usize> = HashMap::new();
+    for (index, item) in module.items.iter().enumerate() {
+        match item {
+            Item::FnDecl(f) => {
+                declared.insert(&f.name, index);
+            }
+            Item::VarDecl(v) => {
+                declared.insert(&v.name, index);
+            }
+            Item::Import(imp) => {
+                for name in &imp.names {
+                    declared.insert(name.alias.as_deref().unwrap_or(&name.name), index);
+                }
+                if let Some(ns) = &imp.namespace {
+                    declared.insert(ns, index);
+                }
+            }
+            _ => {}
+        }
+    }
+    let types_refs: HashSet<String> = module
+        .items
+        .iter()
+        .filter(|item| is_shared(item))
+        .flat_map(item_references)
+        .collect();
+
+    let mut files = Vec::new();
+    for &(root, f) in &pub_fns {
+        let mut included: HashSet<usize> = HashSet::from([root]);
+        let mut imported: Vec<&str> = Vec::new();
+        let mut pending: Vec<String> = item_references(&module.items[root]);
+        pending.extend(types_refs.iter().cloned());
+        while let Some(name) = pending.pop() {
+            let Some(&index) = declared.get(name.as_str()) else { continue };
+            if let Item::FnDecl(other) = &module.items[index]
+                && other.is_pub
+                && index != root
+            {
+                if !imported.contains(&other.name.as_str()) {
+                    imported.push(&other.name);
+                }
+                continue;
+            }
+            if included.insert(index) {
+                pending.extend(item_references(&module.items[index]));
+            }
+        }
+
+        imported.sort();
+        let mut items: Vec<Item> = imported
+            .iter()
+            .map(|name| {
+                Item::Import(Import {
+                    names: vec![ImportName { name: name.to_string(), alias: None, span: Span::dummy() }],
+                    path: ImportPath::Static(format!("./{name}.{extension}")),
+                    namespace: None,
+                    span: Span::dummy(),
+                })
+            })
+            .collect();
+        let mut new_index = HashMap::new();
+        for (index, item) in module.items.iter().enumerate() {
+            let keep = included.contains(&index)
+                || is_shared(item)
+                || matches!(item, Item::Import(imp) if imp.names.is_empty() && imp.namespace.is_none());
+            if keep {
+                new_index.insert(index, items.len());
+                items.push(item.clone());
+            }
+        }
+        let comments = module
+            .comments
+            .iter()
+            .filter_map(|c| {
+                let item = match c.item {
+                    Some(index) => Some(*new_index.get(&index)?),
+                    None => None,
+                };
+                Some(Comment { item, ..c.clone() })
+            })
+            .collect();
+        files.push((f.name.clone(), Module { items, shebang: None, comments }));
+    }
+    Some(files)
+}
+
+/// Whether `item` can be copied into, or left out of, any of the files.
+fn is_splittable(item: &Item) -> bool {
+    match item {
+        Item::VarDecl(v) => v.kind == VarKind::Const,
+        Item::StructDecl(s) => !s.is_pub || s.fields.iter().all(|f| f.default.is_none()),
+        Item::EnumDecl(e) => !e.is_pub,
+        Item::ExprStmt(_) | Item::DslBlock(_) | Item::ExportAlias(_) => false,
+        _ => true,
+    }
+}
+
+/// Declarations copied into every file: types, impl blocks and externs,
+/// which emit nothing or functions a bundler can drop when unused.
+fn is_shared(item: &Item) -> bool {
+    matches!(
+        item,
+        Item::StructDecl(_)
+            | Item::EnumDecl(_)
+            | Item::InterfaceDecl(_)
+            | Item::ImplBlock(_)
+            | Item::TypeAlias(_)
+            | Item::ExternFnDecl(_)
+            | Item::ExternStructDecl(_)
+            | Item::ExternTypeDecl(_)
+    )
+}
+
+/// Identifiers used by the code `item` emits.
+fn item_references(item: &Item) -> Vec<String> {
+    let mut set = HashSet::new();
+    let fn_refs = |f: &FnDecl, set: &mut HashSet<String>| {
+        for default in f.params.iter().filter_map(|p| p.default.as_ref()) {
+            collect_idents_expr(default, set);
+        }
+        collect_idents_block(&f.body, set);
+    };
+    match item {
+        Item::FnDecl(f) => fn_refs(f, &mut set),
+        Item::ImplBlock(ib) => ib.methods.iter().for_each(|m| fn_refs(m, &mut set)),
+        Item::VarDecl(v) => collect_idents_expr(&v.init, &mut set),
+        Item::StructDecl(s) => {
+            for default in s.fields.iter().filter_map(|f| f.default.as_ref()) {
+                collect_idents_expr(default, &mut set);
+            }
+        }
+        _ => {}
+    }
+    set.into_iter().collect()
+}
@@ -0,0 +1,212 @@
+//! Self-recursive tail calls compiled to loops (`CodegenOptions::optimize_tail_calls`).
+//!
+//! ```text
+//! fn sum(n: int, acc: int) -> int { if n == 0 { acc } else { sum(n - 1, acc + n) } }
+//! ```
+//! becomes
+//! ```text
+//! function sum(n, acc) {
+//!     while(true){
+//!         if (n === 0) { return acc; } else { [n, acc] = [n - 1, acc + n]; continue; }
+//!     }
+//! }
+//! ```
+//!
+//! Tail positions are the body's tail expression, `ret` values, and the
+//! branches of `if`/`else` and blocks in those positions. `ret` inside loops
+//! and `match` arms are left as ordinary calls, and so is a function whose
+//! body rebinds a parameter, which the jump could not reassign.
+
+use std::collections::HashSet;
+
+use ag_ast::*;
+use ag_dsl_core::swc_helpers::{binding_ident, bool_lit, expr_or_spread};
+use swc_common::{SyntaxContext, DUMMY_SP};
+use swc_ecma_ast as swc;
+
+use crate::{
+    block_stmt, collect_declared_block, stmt_with_hoisted, translate_expr, translate_expr_stmt, translate_stmt_into,
+    walk, with_hoisted, TranslateCtx,
+};
+
+/// The body of `f` as a `while (true)` loop, if it has a self-recursive
+/// tail call that can be turned into a jump.
+pub(crate) fn loop_body(cx: &TranslateCtx, f: &FnDecl) -> Option<swc::BlockStmt> {
+    // Closures would observe the reassigned parameters, and an async call
+    // returns before the callee runs.
+    if f.is_async || walk::block_any(&f.body, &|e| matches!(e, Expr::Arrow(_))) {
+        return None;
+    }
+    // `let n = ...` in the body would be assigned as the parameter `n`
+    let mut declared = HashSet::new();
+    collect_declared_block(&f.body, &mut declared);
+    if f.params.iter().any(|p| declared.contains(&p.name)) {
+        return None;
+    }
+    let tc = TailCalls { f };
+    if !tc.block_has_tail_call(&f.body) {
+        return None;
+    }
+    let stmts = tc.block(cx, &f.body, true);
+    let body = swc::Stmt::While(swc::WhileStmt {
+        span: DUMMY_SP,
+        test: Box::new(bool_lit(true)),
+        body: Box::new(block_stmt(stmts)),
+    });
+    Some(swc::BlockStmt {
+        span: DUMMY_SP,
+        ctxt: SyntaxContext::empty(),
+        stmts: vec![body],
+    })
+}
+
+struct TailCalls<'a> {
+    f: &'a FnDecl,
+}
+
+impl TailCalls<'_> {
+    /// Whether `expr` calls the function itself with every parameter given.
+    fn is_self_call<'e>(&self, expr: &'e Expr) -> Option<&'e CallExpr> {
+        match expr {
+            Expr::Call(call)
+                if matches!(&*call.callee, Expr::Ident(id) if id.name == self.f.name)
+                    && call.args.len() == self.f.params.len() =>
+            {
+                Some(call)
+            }
+            _ => None,
+        }
+    }
+
+    fn block_has_tail_call(&self, block: &Block) -> bool {
+        block.stmts.iter().any(|stmt| self.stmt_has_tail_call(stmt))
+            || block.tail_expr.as_ref().is_some_and(|tail| self.expr_has_tail_call(tail))
+    }
+
+    fn stmt_has_tail_call(&self, stmt: &Stmt) -> bool {
+        match stmt {
+            Stmt::Return(r) => r.value.as_ref().is_some_and(|v| self.expr_has_tail_call(v)),
+            // The branches' values are discarded, but `ret`s in them are tail calls
+            Stmt::ExprStmt(ExprStmt { expr: Expr::If(i), .. }) => self.if_has_tail_call(i, &|block| {
+                block.stmts.iter().any(|stmt| self.stmt_has_tail_call(stmt))
+            }),
+            _ => false,
+        }
+    }
+
+    fn expr_has_tail_call(&self, expr: &Expr) -> bool {
+        match expr {
+            _ if self.is_self_call(expr).is_some() => true,
+            Expr::If(i) if i.else_branch.is_some() => {
+                self.if_has_tail_call(i, &|block| self.block_has_tail_call(block))
+            }
+            Expr::Block(b) => self.block_has_tail_call(b),
+            _ => false,
+        }
+    }
+
+    fn if_has_tail_call(&self, i: &IfExpr, in_block: &dyn Fn(&Block) -> bool) -> bool {
+        in_block(&i.then_block)
+            || match &i.else_branch {
+                Some(ElseBranch::Block(b)) => in_block(b),
+                Some(ElseBranch::If(elif)) => self.if_has_tail_call(elif, in_block),
+                None => false,
+            }
+    }
+
+    /// The statements of `block`; `returns` says whether its tail
+    /// expression is the function's result.
+    fn block(&self, cx: &TranslateCtx, block: &Block, returns: bool) -> Vec<swc::Stmt> {
+        let mut out = Vec::new();
+        for stmt in &block.stmts {
+            match stmt {
+                Stmt::Return(ReturnStmt { value: Some(value), .. }) => {
+                    with_hoisted(cx, &mut out, |out| self.tail(cx, value, out))
+                }
+                Stmt::ExprStmt(ExprStmt { expr: Expr::If(i), .. }) if self.stmt_has_tail_call(stmt) => {
+                    with_hoisted(cx, &mut out, |out| out.push(self.if_stmt(cx, i, false)))
+                }
+                _ => translate_stmt_into(cx, stmt, &mut out),
+            }
+        }
+        match &block.tail_expr {
+            Some(tail) if returns => with_hoisted(cx, &mut out, |out| self.tail(cx, tail, out)),
+            Some(tail) => with_hoisted(cx, &mut out, |out| out.push(translate_expr_stmt(cx, tail))),
+            // Falling off the end returns, rather than looping again
+            None if returns && !matches!(out.last(), Some(swc::Stmt::Return(_) | swc::Stmt::Continue(_))) => {
+                out.push(swc::Stmt::Return(swc::ReturnStmt { span: DUMMY_SP, arg: None }));
+            }
+            None => {}
+        }
+        out
+    }
+
+    fn if_stmt(&self, cx: &TranslateCtx, i: &IfExpr, returns: bool) -> swc::Stmt {
+        let alt = i.else_branch.as_ref().map(|eb| {
+            Box::new(match eb {
+                ElseBranch::Block(b) => block_stmt(self.block(cx, b, returns)),
+                ElseBranch::If(elif) => stmt_with_hoisted(cx, || self.if_stmt(cx, elif, returns)),
+            })
+        });
+        swc::Stmt::If(swc::IfStmt {
+            span: DUMMY_SP,
+            test: Box::new(translate_expr(cx, &i.condition)),
+            cons: Box::new(block_stmt(self.block(cx, &i.then_block, returns))),
+            alt,
+        })
+    }
+
+    /// Return the value of `expr`, jumping back to the top of the loop for
+    /// a self call.
+    fn tail(&self, cx: &TranslateCtx, expr: &Expr, out: &mut Vec<swc::Stmt>) {
+        if let Some(call) = self.is_self_call(expr) {
+            // Arguments are evaluated before any parameter is reassigned:
+            // `[n, acc] = [n - 1, acc + n]`
+            let params = &self.f.params;
+            let assign = match params.len() {
+                0 => None,
+                1 => Some((
+                    swc::AssignTarget::Simple(swc::SimpleAssignTarget::Ident(binding_ident(&params[0].name))),
+                    translate_expr(cx, &call.args[0]),
+                )),
+                _ => Some((
+                    swc::AssignTarget::Pat(swc::AssignTargetPat::Array(swc::ArrayPat {
+                        span: DUMMY_SP,
+                        elems: params.iter().map(|p| Some(swc::Pat::Ident(binding_ident(&p.name)))).collect(),
+                        optional: false,
+                        type_ann: None,
+                    })),
+                    swc::Expr::Array(swc::ArrayLit {
+                        span: DUMMY_SP,
+                        elems: call
+                            .args
+                            .iter()
+                            .map(|arg| Some(expr_or_spread(translate_expr(cx, arg))))
+                            .collect(),
+                    }),
+                )),
+            };
+            if let Some((left, right)) = assign {
+                out.push(swc::Stmt::Expr(swc::ExprStmt {
+                    span: DUMMY_SP,
+                    expr: Box::new(swc::Expr::Assign(swc::AssignExpr {
+                        span: DUMMY_SP,
+                        op: swc::AssignOp::Assign,
+                        left,
+                        right: Box::new(right),
+                    })),
+                }));
+            }
+            out.push(swc::Stmt::Continue(swc::ContinueStmt { span: DUMMY_SP, label: None }));
+            return;
+        }
+        match expr {
+            Expr::If(i) if i.else_branch.is_some() => out.push(self.if_stmt(cx, i, true)),
+            Expr::Block(b) => out.push(block_stmt(self.block(cx, b, true))),
+            _ => out.push(swc::Stmt::Return(swc::ReturnStmt {
+                span: DUMMY_SP,
+                arg: Some(Box::new(translate_expr(cx, expr))),
+            })),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! TypeScript types in JSDoc comments (`CodegenOptions::ts_comments`), so
+//! that the emitted JavaScript can be checked with `// @ts-check`:
+//!
+//! ```text
+//! /** @type {string} */ const name = "Alice";
+//! /**
+//!  * @param {number} a
+//!  * @returns {number}
+//!  */ function double(a) { ... }
+//! ```
+
+use ag_ast::*;
+
+use crate::CodegenOptions;
+
+/// The TypeScript spelling of `ty`.
+pub(crate) fn type_text(ty: &TypeExpr, options: &CodegenOptions) -> String {
+    match ty {
+        TypeExpr::Named(name, _) => match name.as_str() {
+            "str" => "string".to_string(),
+            "num" | "int" => "number".to_string(),
+            "bool" => "boolean".to_string(),
+            "nil" if options.nil_to_undefined => "undefined".to_string(),
+            "nil" => "null".to_string(),
+            _ => name.clone(),
+        },
+        TypeExpr::Array(inner, _) => format!("{}[]", operand_text(inner, options)),
+        TypeExpr::Map(k, v, _) => format!("Record<{}, {}>", type_text(k, options), type_text(v, options)),
+        TypeExpr::Nullable(inner, _) => {
+            let nil = if options.nil_to_undefined { "undefined" } else { "null" };
+            format!("{} | {nil}", member_text(inner, options))
+        }
+        TypeExpr::Union(a, b, _) => format!("{} | {}", member_text(a, options), member_text(b, options)),
+        TypeExpr::Function(ft) => {
+            let params: Vec<String> = ft
+                .params
+                .iter()
+                .enumerate()
+                .map(|(i, p)| format!("arg{i}: {}", type_text(p, options)))
+                .collect();
+            format!("({}) => {}", params.join(", "), type_text(&ft.ret, options))
+        }
+        TypeExpr::Object(ot) => {
+            let fields: Vec<String> =
+                ot.fields.iter().map(|f| format!("{}: {}", f.name, type_text(&f.ty, options))).collect();
+            format!("{{ {} }}", fields.join(", "))
+        }
+        TypeExpr::Promise(inner, _) => format!("Promise<{}>", type_text(inner, options)),
+        TypeExpr::Generic(name, args, _) => {
+            let args: Vec<String> = args.iter().map(|a| type_text(a, options)).collect();
+            format!("{name}<{}>", args.join(", "))
+        }
+    }
+}
+
+/// `ty` as the operand of a postfix `[]`, parenthesized where needed.
+fn operand_text(ty: &TypeExpr, options: &CodegenOptions) -> String {
+    match ty {
+        TypeExpr::Nullable(..) | TypeExpr::Union(..) | TypeExpr::Function(_) => {
+            format!("({})", type_text(ty, options))
+        }
+        _ => type_text(ty, options),
+    }
+}
+
+/// `ty` as a member of a union, with a function type parenthesized so
+/// that the union does not become its return type.
+fn member_text(ty: &TypeExpr, options: &CodegenOptions) -> String {
+    match ty {
+        TypeExpr::Function(_) => format!("({})", type_text(ty, options)),
+        _ => type_text(ty, options),
+    }
+}
+
+/// The body of the `/** @type {T} */` comment for a variable of type `ty`.
+pub(crate) fn var_comment(ty: &TypeExpr, options: &CodegenOptions) -> String {
+    format!("* @type {{{}}} ", type_text(ty, options))
+}
+
+/// `@param` and `@returns` lines for the annotated parameters and return
+/// type of `f`. For a method, `self_ty` is the type of its `self`.
+pub(crate) fn fn_lines(f: &FnDecl, self_ty: Option<&str>, options: &CodegenOptions) -> Vec<String> {
+    let mut lines = Vec::new();
+    for p in &f.params {
+        let ty = match (&p.ty, self_ty) {
+            (Some(ty), _) => type_text(ty, options),
+            (None, Some(self_ty)) if p.name == "self" => self_ty.to_string(),
+            (None, _) => continue,
+        };
+        let ty = if p.is_variadic { format!("...{ty}") } else { ty };
+        let name = if p.default.is_some() { format!("[{}]", p.name) } else { p.name.clone() };
+        lines.push(format!("@param {{{ty}}} {name}"));
+    }
+    if let Some(ret) = &f.return_type {
+        let ret = type_text(ret, options);
+        let ret = if f.is_async { format!("Promise<{ret}>") } else { ret };
+        lines.push(format!("@returns {{{ret}}}"));
+    }
+    lines
+}
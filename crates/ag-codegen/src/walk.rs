@@ -0,0 +1,131 @@
+//! Searching a function body for expressions, without descending into the
+//! bodies of nested functions.
+
+use ag_ast::*;
+
+/// Whether `pred` holds for some expression in `block`.
+pub(crate) fn block_any(block: &Block, pred: &dyn Fn(&Expr) -> bool) -> bool {
+    block.stmts.iter().any(|stmt| stmt_any(stmt, pred)) || block.tail_expr.as_deref().is_some_and(|e| expr_any(e, pred))
+}
+
+fn stmt_any(stmt: &Stmt, pred: &dyn Fn(&Expr) -> bool) -> bool {
+    match stmt {
+        Stmt::VarDecl(v) => expr_any(&v.init, pred),
+        Stmt::ExprStmt(e) => expr_any(&e.expr, pred),
+        Stmt::Return(r) => r.value.as_ref().is_some_and(|v| expr_any(v, pred)),
+        Stmt::If(i) => if_any(i, pred),
+        Stmt::For(f) => expr_any(&f.iter, pred) || block_any(&f.body, pred),
+        Stmt::ForRange(fr) => {
+            expr_any(&fr.init.init, pred)
+                || expr_any(&fr.condition, pred)
+                || expr_any(&fr.update, pred)
+                || block_any(&fr.body, pred)
+        }
+        Stmt::While(w) => expr_any(&w.condition, pred) || block_any(&w.body, pred),
+        Stmt::Loop(l) => block_any(&l.body, pred),
+        Stmt::Break(b) => b.value.as_ref().is_some_and(|v| expr_any(v, pred)),
+        Stmt::Match(m) => match_any(m, pred),
+        Stmt::TryCatch(tc) => block_any(&tc.try_block, pred) || block_any(&tc.catch_block, pred),
+    }
+}
+
+fn if_any(i: &IfExpr, pred: &dyn Fn(&Expr) -> bool) -> bool {
+    expr_any(&i.condition, pred)
+        || block_any(&i.then_block, pred)
+        || match &i.else_branch {
+            Some(ElseBranch::Block(b)) => block_any(b, pred),
+            Some(ElseBranch::If(elif)) => if_any(elif, pred),
+            None => false,
+        }
+}
+
+fn match_any(m: &MatchExpr, pred: &dyn Fn(&Expr) -> bool) -> bool {
+    expr_any(&m.subject, pred)
+        || m.arms.iter().any(|arm| {
+            let body = match &arm.body {
+                MatchArmBody::Expr(e) => expr_any(e, pred),
+                MatchArmBody::Block(b) => block_any(b, pred),
+            };
+            body || arm.guard.as_ref().is_some_and(|g| expr_any(g, pred))
+        })
+}
+
+/// Whether `expr` has a `break` that leaves a loop around it. Loops in
+/// `expr` own the `break`s in their bodies.
+pub(crate) fn expr_breaks(expr: &Expr) -> bool {
+    match expr {
+        Expr::Block(b) => block_breaks(b),
+        Expr::If(i) => if_breaks(i),
+        Expr::Match(m) => match_breaks(m),
+        _ => false,
+    }
+}
+
+/// Whether an arm of `m` has a `break` that leaves a loop around it.
+fn match_breaks(m: &MatchExpr) -> bool {
+    m.arms.iter().any(|arm| arm_breaks(&arm.body))
+}
+
+pub(crate) fn arm_breaks(body: &MatchArmBody) -> bool {
+    match body {
+        MatchArmBody::Expr(e) => expr_breaks(e),
+        MatchArmBody::Block(b) => block_breaks(b),
+    }
+}
+
+fn block_breaks(block: &Block) -> bool {
+    block.stmts.iter().any(|stmt| match stmt {
+        Stmt::Break(_) => true,
+        Stmt::For(_) | Stmt::ForRange(_) | Stmt::While(_) | Stmt::Loop(_) => false,
+        Stmt::VarDecl(v) => expr_breaks(&v.init),
+        Stmt::ExprStmt(e) => expr_breaks(&e.expr),
+        Stmt::Return(r) => r.value.as_ref().is_some_and(expr_breaks),
+        Stmt::If(i) => if_breaks(i),
+        Stmt::Match(m) => match_breaks(m),
+        Stmt::TryCatch(tc) => block_breaks(&tc.try_block) || block_breaks(&tc.catch_block),
+    }) || block.tail_expr.as_deref().is_some_and(expr_breaks)
+}
+
+fn if_breaks(i: &IfExpr) -> bool {
+    block_breaks(&i.then_block)
+        || match &i.else_branch {
+            Some(ElseBranch::Block(b)) => block_breaks(b),
+            Some(ElseBranch::If(elif)) => if_breaks(elif),
+            None => false,
+        }
+}
+
+/// Whether `pred` holds for `expr` or one of its subexpressions. An arrow
+/// is tested itself, but its body is not searched.
+pub(crate) fn expr_any(expr: &Expr, pred: &dyn Fn(&Expr) -> bool) -> bool {
+    if pred(expr) {
+        return true;
+    }
+    match expr {
+        Expr::Arrow(_) | Expr::Literal(_) | Expr::Ident(_) | Expr::Placeholder(_) => false,
+        Expr::Binary(b) => expr_any(&b.left, pred) || expr_any(&b.right, pred),
+        Expr::Comparison(c) => c.operands.iter().any(|operand| expr_any(operand, pred)),
+        Expr::Unary(u) => expr_any(&u.operand, pred),
+        Expr::Call(c) => expr_any(&c.callee, pred) || c.args.iter().any(|a| expr_any(a, pred)),
+        Expr::Member(m) => expr_any(&m.object, pred),
+        Expr::Index(i) => expr_any(&i.object, pred) || expr_any(&i.index, pred),
+        Expr::If(i) => if_any(i, pred),
+        Expr::Match(m) => match_any(m, pred),
+        Expr::Loop(l) => block_any(&l.body, pred),
+        Expr::Block(b) => block_any(b, pred),
+        Expr::Array(a) => a.elements.iter().any(|e| expr_any(e, pred)),
+        Expr::Object(o) => o.fields.iter().any(|f| expr_any(&f.value, pred)),
+        Expr::Pipe(p) => expr_any(&p.left, pred) || expr_any(&p.right, pred),
+        Expr::OptionalChain(oc) => expr_any(&oc.object, pred),
+        Expr::NullishCoalesce(nc) => expr_any(&nc.left, pred) || expr_any(&nc.right, pred),
+        Expr::Await(a) => expr_any(&a.expr, pred),
+        Expr::ErrorPropagate(ep) => expr_any(&ep.expr, pred),
+        Expr::Assign(a) => expr_any(&a.target, pred) || expr_any(&a.value, pred),
+        Expr::TemplateString(t) => template_any(t, pred),
+        Expr::TaggedTemplate(t) => expr_any(&t.tag, pred) || template_any(&t.template, pred),
+    }
+}
+
+fn template_any(t: &TemplateStringExpr, pred: &dyn Fn(&Expr) -> bool) -> bool {
+    t.parts.iter().any(|part| matches!(part, TemplatePart::Expr(e) if expr_any(e, pred)))
+}
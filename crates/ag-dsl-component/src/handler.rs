@@ -0,0 +1,81 @@
+use ag_dsl_core::{
+    CodegenContext, DslBlock, DslContent, DslDiagnostic, DslError, DslHandler, ModuleItem,
+    ValidateContext,
+};
+
+use crate::parse_component;
+use crate::validator::{self, Severity};
+
+/// Validates `@component` blocks; they are not compiled yet.
+pub struct ComponentDslHandler;
+
+impl DslHandler for ComponentDslHandler {
+    fn handle(
+        &self,
+        block: &DslBlock,
+        _ctx: &mut dyn CodegenContext,
+    ) -> Result<Vec<ModuleItem>, DslError> {
+        Err(DslError {
+            message: format!("cannot compile @component `{}`: components are not emitted yet", block.name),
+            span: Some(block.span),
+        })
+    }
+
+    fn validate(&self, block: &DslBlock, _ctx: &dyn ValidateContext) -> Vec<DslDiagnostic> {
+        let DslContent::Inline { parts } = &block.content else {
+            return Vec::new();
+        };
+        match parse_component(&block.name, parts) {
+            Ok(meta) => validator::validate(&meta)
+                .into_iter()
+                .map(|d| match d.severity {
+                    Severity::Error => DslDiagnostic::error(d.message, None),
+                    Severity::Warning => DslDiagnostic::warning(d.message, None),
+                })
+                .collect(),
+            Err(e) => vec![DslDiagnostic::error(e.message, e.span)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ag_dsl_core::{DslPart, DslSeverity, Span};
+
+    struct NoTypes;
+
+    impl ValidateContext for NoTypes {
+        fn is_type(&self, _name: &str) -> bool {
+            false
+        }
+    }
+
+    fn block(source: &str) -> DslBlock {
+        DslBlock {
+            kind: "component".to_string(),
+            name: "Card".to_string(),
+            content: DslContent::Inline {
+                parts: vec![DslPart::Text(source.to_string(), Span::new(20, 20 + source.len() as u32))],
+            },
+            span: Span::new(0, 40 + source.len() as u32),
+        }
+    }
+
+    #[test]
+    fn validate_reports_missing_export() {
+        let diags = ComponentDslHandler.validate(&block("function Card() { return <div/> }"), &NoTypes);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].severity, DslSeverity::Error);
+        assert!(diags[0].message.contains("no `export default` function"));
+    }
+
+    #[test]
+    fn validate_passes_on_validator_warnings() {
+        let diags = ComponentDslHandler.validate(&block("export default function Card() { return <div/> }"), &NoTypes);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].severity, DslSeverity::Warning);
+        assert_eq!(diags[0].message, "component has no props defined");
+        assert_eq!(diags[0].span, None);
+    }
+}
@@ -1,3 +1,4 @@
+pub mod handler;
 pub mod validator;
 
 use ag_dsl_core::{DslError, DslPart};
@@ -16,6 +17,7 @@ pub struct ComponentMeta {
     pub name: String,
     pub description: Option<String>,
     pub props: Vec<ComponentProp>,
+    pub slots: Vec<ComponentSlot>,
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +26,14 @@ pub struct ComponentProp {
     pub ty: String,
     pub description: Option<String>,
     pub has_default: bool,
+    /// `on*` prop typed as a function, e.g. `onClick`.
+    pub is_event_handler: bool,
+}
+
+/// A named slot, used in the component body as `children.<name>`.
+#[derive(Debug, Clone)]
+pub struct ComponentSlot {
+    pub name: String,
 }
 
 // ── Main entry point ─────────────────────────────────────
@@ -99,6 +109,7 @@ pub fn parse_component(name: &str, parts: &[DslPart]) -> Result<ComponentMeta, D
                         name: func_name,
                         description,
                         props,
+                        slots: extract_slots_from_function(&fn_expr.function),
                     });
                 }
             }
@@ -114,10 +125,18 @@ pub fn parse_component(name: &str, parts: &[DslPart]) -> Result<ComponentMeta, D
                                 prop.has_default = true;
                             }
                         }
+                        let mut slots = Vec::new();
+                        match &*arrow.body {
+                            swc::BlockStmtOrExpr::BlockStmt(block) => {
+                                collect_slots_stmts(&block.stmts, &mut slots)
+                            }
+                            swc::BlockStmtOrExpr::Expr(expr) => collect_slots_expr(expr, &mut slots),
+                        }
                         return Ok(ComponentMeta {
                             name: name.to_string(),
                             description,
                             props,
+                            slots,
                         });
                     }
                     swc::Expr::Fn(fn_expr) => {
@@ -138,6 +157,7 @@ pub fn parse_component(name: &str, parts: &[DslPart]) -> Result<ComponentMeta, D
                             name: func_name,
                             description,
                             props,
+                            slots: extract_slots_from_function(&fn_expr.function),
                         });
                     }
                     _ => {}
@@ -213,15 +233,14 @@ fn parse_param_line(line: &str) -> Option<ComponentProp> {
     // Remove the `@param` prefix.
     let rest = line.strip_prefix("@param")?.trim();
 
-    let (ty, after_type) = if rest.starts_with('{') {
+    let (raw_type, after_type) = if rest.starts_with('{') {
         // Extract `{type}`.
         let close = rest.find('}')?;
-        let raw_type = &rest[1..close];
-        (map_jsdoc_type(raw_type.trim()), rest[close + 1..].trim())
-
+        (rest[1..close].trim(), rest[close + 1..].trim())
     } else {
-        ("any".to_string(), rest)
+        ("*", rest)
     };
+    let ty = map_jsdoc_type(raw_type);
 
     // Next token is the param name.
     let (name, desc_part) = match after_type.split_once(char::is_whitespace) {
@@ -241,11 +260,14 @@ fn parse_param_line(line: &str) -> Option<ComponentProp> {
         if d.is_empty() { None } else { Some(d.to_string()) }
     };
 
+    let is_event_handler = name.starts_with("on") && is_function_type(raw_type);
+
     Some(ComponentProp {
         name,
         ty,
         description,
         has_default: false,
+        is_event_handler,
     })
 }
 
@@ -270,6 +292,49 @@ fn map_jsdoc_type(jsdoc_type: &str) -> String {
     }
 }
 
+/// Whether a JSDoc type denotes a function: `Function`, `function(...)`
+/// or an arrow type such as `(e) => void`.
+fn is_function_type(jsdoc_type: &str) -> bool {
+    matches!(jsdoc_type, "Function" | "function")
+        || jsdoc_type.starts_with("function(")
+        || jsdoc_type.contains("=>")
+}
+
+// ── ag-lang type declaration ─────────────────────────────
+
+/// Render the component's props as an ag-lang struct declaration, e.g.
+/// `struct ButtonProps { label: str, onClick: ((any) -> nil)? }`.
+///
+/// Props with a default and event handlers are optional, so their types
+/// are nullable; event handlers are typed as `(any) -> nil`. Function and
+/// union types are parenthesized so that the `?` applies to the whole type.
+pub fn meta_to_ag_type(meta: &ComponentMeta) -> String {
+    let fields: Vec<String> = meta
+        .props
+        .iter()
+        .map(|prop| {
+            let ty = if prop.is_event_handler {
+                "(any) -> nil"
+            } else {
+                prop.ty.as_str()
+            };
+            let optional = prop.has_default || prop.is_event_handler;
+            if !optional {
+                format!("{}: {}", prop.name, ty)
+            } else if ty.contains("->") || ty.contains('|') {
+                format!("{}: ({})?", prop.name, ty)
+            } else {
+                format!("{}: {}?", prop.name, ty)
+            }
+        })
+        .collect();
+    if fields.is_empty() {
+        format!("struct {}Props {{}}", meta.name)
+    } else {
+        format!("struct {}Props {{ {} }}", meta.name, fields.join(", "))
+    }
+}
+
 // ── Default extraction ───────────────────────────────────
 
 /// Walk function params looking for destructured props with default values.
@@ -305,6 +370,135 @@ fn extract_defaults_from_pat(pat: &swc::Pat, defaults: &mut Vec<String>) {
     }
 }
 
+// ── Slot extraction ──────────────────────────────────────
+
+/// Collect `children.<name>` accesses in a function body as named slots.
+fn extract_slots_from_function(func: &swc::Function) -> Vec<ComponentSlot> {
+    let mut slots = Vec::new();
+    if let Some(body) = &func.body {
+        collect_slots_stmts(&body.stmts, &mut slots);
+    }
+    slots
+}
+
+fn collect_slots_stmts(stmts: &[swc::Stmt], slots: &mut Vec<ComponentSlot>) {
+    for stmt in stmts {
+        collect_slots_stmt(stmt, slots);
+    }
+}
+
+fn collect_slots_stmt(stmt: &swc::Stmt, slots: &mut Vec<ComponentSlot>) {
+    match stmt {
+        swc::Stmt::Return(ret) => {
+            if let Some(arg) = &ret.arg {
+                collect_slots_expr(arg, slots);
+            }
+        }
+        swc::Stmt::Expr(e) => collect_slots_expr(&e.expr, slots),
+        swc::Stmt::Decl(swc::Decl::Var(var)) => {
+            for decl in &var.decls {
+                if let Some(init) = &decl.init {
+                    collect_slots_expr(init, slots);
+                }
+            }
+        }
+        swc::Stmt::If(if_stmt) => {
+            collect_slots_expr(&if_stmt.test, slots);
+            collect_slots_stmt(&if_stmt.cons, slots);
+            if let Some(alt) = &if_stmt.alt {
+                collect_slots_stmt(alt, slots);
+            }
+        }
+        swc::Stmt::Block(block) => collect_slots_stmts(&block.stmts, slots),
+        _ => {}
+    }
+}
+
+fn collect_slots_expr(expr: &swc::Expr, slots: &mut Vec<ComponentSlot>) {
+    match expr {
+        swc::Expr::Member(member) => {
+            if let (swc::Expr::Ident(obj), swc::MemberProp::Ident(prop)) =
+                (&*member.obj, &member.prop)
+                && obj.sym == *"children"
+            {
+                let name = prop.sym.to_string();
+                if !slots.iter().any(|s| s.name == name) {
+                    slots.push(ComponentSlot { name });
+                }
+                return;
+            }
+            collect_slots_expr(&member.obj, slots);
+        }
+        swc::Expr::Paren(p) => collect_slots_expr(&p.expr, slots),
+        swc::Expr::Cond(c) => {
+            collect_slots_expr(&c.test, slots);
+            collect_slots_expr(&c.cons, slots);
+            collect_slots_expr(&c.alt, slots);
+        }
+        swc::Expr::Bin(b) => {
+            collect_slots_expr(&b.left, slots);
+            collect_slots_expr(&b.right, slots);
+        }
+        swc::Expr::Call(call) => {
+            if let swc::Callee::Expr(callee) = &call.callee {
+                collect_slots_expr(callee, slots);
+            }
+            for arg in &call.args {
+                collect_slots_expr(&arg.expr, slots);
+            }
+        }
+        swc::Expr::Arrow(arrow) => match &*arrow.body {
+            swc::BlockStmtOrExpr::BlockStmt(block) => collect_slots_stmts(&block.stmts, slots),
+            swc::BlockStmtOrExpr::Expr(e) => collect_slots_expr(e, slots),
+        },
+        swc::Expr::Array(arr) => {
+            for elem in arr.elems.iter().flatten() {
+                collect_slots_expr(&elem.expr, slots);
+            }
+        }
+        swc::Expr::JSXElement(el) => collect_slots_jsx_element(el, slots),
+        swc::Expr::JSXFragment(frag) => collect_slots_jsx_children(&frag.children, slots),
+        _ => {}
+    }
+}
+
+fn collect_slots_jsx_element(el: &swc::JSXElement, slots: &mut Vec<ComponentSlot>) {
+    for attr in &el.opening.attrs {
+        match attr {
+            swc::JSXAttrOrSpread::JSXAttr(attr) => match &attr.value {
+                Some(swc::JSXAttrValue::JSXExprContainer(container)) => {
+                    if let swc::JSXExpr::Expr(e) = &container.expr {
+                        collect_slots_expr(e, slots);
+                    }
+                }
+                Some(swc::JSXAttrValue::JSXElement(inner)) => {
+                    collect_slots_jsx_element(inner, slots)
+                }
+                _ => {}
+            },
+            swc::JSXAttrOrSpread::SpreadElement(spread) => collect_slots_expr(&spread.expr, slots),
+        }
+    }
+    collect_slots_jsx_children(&el.children, slots);
+}
+
+fn collect_slots_jsx_children(children: &[swc::JSXElementChild], slots: &mut Vec<ComponentSlot>) {
+    for child in children {
+        match child {
+            swc::JSXElementChild::JSXExprContainer(container) => {
+                if let swc::JSXExpr::Expr(e) = &container.expr {
+                    collect_slots_expr(e, slots);
+                }
+            }
+            swc::JSXElementChild::JSXElement(el) => collect_slots_jsx_element(el, slots),
+            swc::JSXElementChild::JSXFragment(frag) => {
+                collect_slots_jsx_children(&frag.children, slots)
+            }
+            _ => {}
+        }
+    }
+}
+
 // ── Tests ────────────────────────────────────────────────
 
 #[cfg(test)]
@@ -460,6 +654,87 @@ function Foo() {
         assert_eq!(map_jsdoc_type("SomeCustomType"), "any");
     }
 
+    #[test]
+    fn event_handler_props() {
+        let source = r#"
+/**
+ * @param {Function} onClick - click handler
+ * @param {(value: string) => void} onChange - change handler
+ * @param {string} onLabel - label text
+ * @param {Function} render - render prop
+ */
+export default function Button({ onClick, onChange, onLabel, render }) {
+  return <button onClick={onClick}>{onLabel}</button>
+}
+"#;
+        let parts = vec![text(source)];
+        let meta = parse_component("Button", &parts).expect("should parse");
+        assert_eq!(meta.props.len(), 4);
+        assert!(meta.props[0].is_event_handler);
+        assert!(meta.props[1].is_event_handler);
+        assert!(!meta.props[2].is_event_handler);
+        assert!(!meta.props[3].is_event_handler);
+    }
+
+    #[test]
+    fn component_named_slots() {
+        let source = r#"
+export default function Layout({ children }) {
+  return <div>{children.header}<main>{children.body}</main>{children.footer}{children.header}</div>
+}
+"#;
+        let parts = vec![text(source)];
+        let meta = parse_component("Layout", &parts).expect("should parse");
+        let slots: Vec<&str> = meta.slots.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(slots, vec!["header", "body", "footer"]);
+    }
+
+    #[test]
+    fn component_without_slots() {
+        let source = "export default ({ children }) => <div>{children}</div>";
+        let meta = parse_component("Box", &[text(source)]).expect("should parse");
+        assert!(meta.slots.is_empty());
+    }
+
+    #[test]
+    fn props_struct_declaration() {
+        let source = r#"
+/**
+ * @param {string} label - button text
+ * @param {Function} onClick - click handler
+ */
+export default function Button({ label, onClick }) {
+  return <button onClick={onClick}>{label}</button>
+}
+"#;
+        let meta = parse_component("Button", &[text(source)]).expect("should parse");
+        let decl = meta_to_ag_type(&meta);
+        assert_eq!(decl, "struct ButtonProps { label: str, onClick: ((any) -> nil)? }");
+
+        let parsed = ag_parser::parse(&decl);
+        assert!(parsed.diagnostics.is_empty(), "{:?}", parsed.diagnostics);
+        let [ag_ast::Item::StructDecl(s)] = &parsed.module.items[..] else { panic!("expected a struct") };
+        assert_eq!(s.name, "ButtonProps");
+        // The handler itself is optional, not its result
+        assert!(matches!(&s.fields[1].ty, ag_ast::TypeExpr::Nullable(inner, _) if matches!(**inner, ag_ast::TypeExpr::Function(_))));
+    }
+
+    #[test]
+    fn props_struct_marks_defaults_optional() {
+        let source = r#"
+/**
+ * @param {number[]} items
+ * @param {boolean} open
+ */
+export default ({ items, open = false }) => <ul/>
+"#;
+        let meta = parse_component("List", &[text(source)]).expect("should parse");
+        assert_eq!(meta_to_ag_type(&meta), "struct ListProps { items: [num], open: bool? }");
+
+        let empty = parse_component("Foo", &[text("export default () => <div/>")]).unwrap();
+        assert_eq!(meta_to_ag_type(&empty), "struct FooProps {}");
+    }
+
     #[test]
     fn multi_text_parts() {
         let parts = vec![
@@ -75,6 +75,7 @@ mod tests {
             name: "TestComponent".to_string(),
             description: None,
             props,
+            slots: Vec::new(),
         }
     }
 
@@ -84,6 +85,7 @@ mod tests {
             ty: ty.to_string(),
             description: None,
             has_default: false,
+            is_event_handler: false,
         }
     }
 
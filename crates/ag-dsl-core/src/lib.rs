@@ -58,6 +58,114 @@ impl std::fmt::Debug for DslPart {
     }
 }
 
+// ── Directives ────────────────────────────────────────────
+
+/// The rest of the line after `@name` (such as `@model gpt-4o`), trimmed,
+/// for the first such line in `block`'s inline text. Like the DSL lexers,
+/// a directive must start a line or a text part.
+pub fn extract_directive(block: &DslBlock, name: &str) -> Option<String> {
+    let DslContent::Inline { parts } = &block.content else {
+        return None;
+    };
+    directives(parts, name).into_iter().next().map(|(value, _)| value)
+}
+
+/// Every `@name` directive line in `parts`, as its trimmed value and the
+/// span of the line (without its newline).
+pub fn directives(parts: &[DslPart], name: &str) -> Vec<(String, Span)> {
+    let mut found = Vec::new();
+    for part in parts {
+        let DslPart::Text(text, span) = part else { continue };
+        let mut offset = span.start;
+        for line in text.split_inclusive('\n') {
+            if let Some(value) = directive_value(line, name) {
+                let len = line.trim_end_matches(['\n', '\r']).len() as u32;
+                found.push((value, Span::new(offset, offset + len)));
+            }
+            offset += line.len() as u32;
+        }
+    }
+    found
+}
+
+/// `parts` without the `@name` directive lines. Text parts are split
+/// around the lines removed, so each piece keeps the span of its text;
+/// pieces left empty are dropped.
+pub fn remove_directive(parts: Vec<DslPart>, name: &str) -> Vec<DslPart> {
+    let mut kept = Vec::new();
+    for part in parts {
+        let DslPart::Text(text, span) = part else {
+            kept.push(part);
+            continue;
+        };
+        let mut run = String::new();
+        let mut run_start = span.start;
+        let mut offset = span.start;
+        for line in text.split_inclusive('\n') {
+            if directive_value(line, name).is_some() {
+                if !run.is_empty() {
+                    kept.push(DslPart::Text(std::mem::take(&mut run), Span::new(run_start, offset)));
+                }
+                run_start = offset + line.len() as u32;
+            } else {
+                run.push_str(line);
+            }
+            offset += line.len() as u32;
+        }
+        if !run.is_empty() {
+            kept.push(DslPart::Text(run, Span::new(run_start, offset)));
+        }
+    }
+    kept
+}
+
+/// The trimmed value of `line` if it is the directive `@name`.
+fn directive_value(line: &str, name: &str) -> Option<String> {
+    let rest = line.strip_prefix('@')?.strip_prefix(name)?;
+    // `@model` is not `@models`
+    if rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some(rest.trim().to_string())
+}
+
+// ── DslDiagnostic ─────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DslSeverity {
+    Error,
+    Warning,
+}
+
+/// A problem a handler finds while validating a block.
+#[derive(Debug, Clone)]
+pub struct DslDiagnostic {
+    pub message: String,
+    pub severity: DslSeverity,
+    /// Where in the source, from the block's part spans; `None` for the
+    /// whole block.
+    pub span: Option<Span>,
+}
+
+impl DslDiagnostic {
+    pub fn error(message: impl Into<String>, span: Option<Span>) -> Self {
+        Self { message: message.into(), severity: DslSeverity::Error, span }
+    }
+
+    pub fn warning(message: impl Into<String>, span: Option<Span>) -> Self {
+        Self { message: message.into(), severity: DslSeverity::Warning, span }
+    }
+}
+
+// ── ValidateContext trait ─────────────────────────────────
+
+/// What the checker knows about the module around a block being validated.
+pub trait ValidateContext {
+    /// Whether `name` is a type: a primitive, `[T]` of one, or a type
+    /// declared in the module.
+    fn is_type(&self, name: &str) -> bool;
+}
+
 // ── CodegenContext trait ──────────────────────────────────
 
 pub trait CodegenContext {
@@ -67,10 +175,99 @@ pub trait CodegenContext {
 
 // ── DslHandler trait ─────────────────────────────────────
 
+/// The items a handler emits, for handlers built on another version of
+/// `swc_ecma_ast`.
+pub use swc_ecma_ast::ModuleItem;
+
 pub trait DslHandler {
     fn handle(
         &self,
         block: &DslBlock,
         ctx: &mut dyn CodegenContext,
     ) -> Result<Vec<swc_ecma_ast::ModuleItem>, DslError>;
+
+    /// Report problems with `block` while checking, before any codegen, so
+    /// they surface with the checker's diagnostics. Captures are checked
+    /// by the checker itself.
+    fn validate(&self, _block: &DslBlock, _ctx: &dyn ValidateContext) -> Vec<DslDiagnostic> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> DslPart {
+        DslPart::Text(s.to_string(), Span::dummy())
+    }
+
+    fn inline(parts: Vec<DslPart>) -> DslBlock {
+        DslBlock {
+            kind: "prompt".to_string(),
+            name: "p".to_string(),
+            content: DslContent::Inline { parts },
+            span: Span::dummy(),
+        }
+    }
+
+    fn texts(parts: &[DslPart]) -> Vec<&str> {
+        parts
+            .iter()
+            .map(|part| match part {
+                DslPart::Text(s, _) => s.as_str(),
+                DslPart::Capture(..) => "<capture>",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn extract_directive_value() {
+        let block = inline(vec![
+            text("@role system\nYou are helpful. @model inline\n"),
+            DslPart::Capture(Box::new(()), Span::dummy()),
+            text("\n@models many\n@model  gpt-4o \n@model second\n"),
+        ]);
+        assert_eq!(extract_directive(&block, "role").as_deref(), Some("system"));
+        assert_eq!(extract_directive(&block, "model").as_deref(), Some("gpt-4o"));
+        assert_eq!(extract_directive(&block, "temperature"), None);
+        assert_eq!(extract_directive(&inline(vec![text("@stream")]), "stream").as_deref(), Some(""));
+
+        let file = DslBlock {
+            content: DslContent::FileRef { path: "p.md".to_string(), span: Span::dummy() },
+            ..inline(Vec::new())
+        };
+        assert_eq!(extract_directive(&file, "model"), None);
+    }
+
+    #[test]
+    fn directive_spans() {
+        let parts = vec![
+            DslPart::Text("Hi\n@role sytem\r\nBody\n".to_string(), Span::new(10, 34)),
+            DslPart::Capture(Box::new(()), Span::new(34, 38)),
+            DslPart::Text("\n@roles all\n@role user".to_string(), Span::new(38, 60)),
+        ];
+        assert_eq!(
+            directives(&parts, "role"),
+            [("sytem".to_string(), Span::new(13, 24)), ("user".to_string(), Span::new(50, 60))]
+        );
+    }
+
+    #[test]
+    fn remove_directive_lines() {
+        let parts = vec![
+            text("@model gpt-4o\n@role system\nHello "),
+            DslPart::Capture(Box::new(()), Span::dummy()),
+            text("\n@model other\n"),
+            text("@model last"),
+        ];
+        let parts = remove_directive(parts, "model");
+        assert_eq!(texts(&parts), ["@role system\nHello ", "<capture>", "\n"]);
+
+        // The text after a removed line keeps its own span
+        let parts = vec![DslPart::Text("Hi\n@tools x\n@role user\n".to_string(), Span::new(10, 33))];
+        let parts = remove_directive(parts, "tools");
+        assert_eq!(texts(&parts), ["Hi\n", "@role user\n"]);
+        assert_eq!(directives(&parts, "role"), [("user".to_string(), Span::new(22, 32))]);
+    }
 }
@@ -1,9 +1,13 @@
-use ag_dsl_core::{CodegenContext, DslBlock, DslContent, DslError, DslHandler, DslPart};
+use ag_dsl_core::{
+    directives, CodegenContext, DslBlock, DslContent, DslDiagnostic, DslError, DslHandler, DslPart,
+    ValidateContext,
+};
 use swc_ecma_ast as swc;
 
+use crate::ast::{OutputKind, RoleName};
 use crate::codegen;
 use crate::lexer;
-use crate::parser;
+use crate::parser::{self, Diagnostic, Severity};
 use crate::validator;
 
 pub struct PromptDslHandler;
@@ -51,6 +55,45 @@ impl DslHandler for PromptDslHandler {
             }
         }
     }
+
+    fn validate(&self, block: &DslBlock, ctx: &dyn ValidateContext) -> Vec<DslDiagnostic> {
+        let DslContent::Inline { parts } = &block.content else {
+            return Vec::new();
+        };
+        let tokens = lexer::lex(parts);
+        let template = match parser::parse(&block.name, &tokens) {
+            Ok(template) => template,
+            Err(errors) => return errors.into_iter().map(block_diagnostic).collect(),
+        };
+        let mut diags: Vec<DslDiagnostic> = validator::validate(&template).into_iter().map(block_diagnostic).collect();
+        if let Some(OutputKind::Inline(fields)) = template.output.as_ref().map(|o| &o.kind) {
+            let span = directives(parts, "output").first().map(|(_, span)| *span);
+            for field in fields.iter().filter(|f| !ctx.is_type(&f.ty)) {
+                diags.push(DslDiagnostic::error(
+                    format!("@output field `{}` has unknown type `{}`", field.name, field.ty),
+                    span,
+                ));
+            }
+        }
+        // Other roles are passed through to the model API, which rejects them
+        for (role, span) in directives(parts, "role") {
+            if matches!(RoleName::from_str(&role), RoleName::Custom(_)) {
+                diags.push(DslDiagnostic::warning(
+                    format!("unknown role `{role}`, expected `system`, `user` or `assistant`"),
+                    Some(span),
+                ));
+            }
+        }
+        diags
+    }
+}
+
+/// A parser or validator diagnostic, which applies to the whole block.
+fn block_diagnostic(d: Diagnostic) -> DslDiagnostic {
+    match d.severity {
+        Severity::Error => DslDiagnostic::error(d.message, None),
+        Severity::Warning => DslDiagnostic::warning(d.message, None),
+    }
 }
 
 #[cfg(test)]
@@ -164,4 +207,62 @@ mod tests {
         let err = result.unwrap_err();
         assert!(err.message.contains("empty prompt"));
     }
+
+    struct NoTypes;
+
+    impl ValidateContext for NoTypes {
+        fn is_type(&self, _name: &str) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn validate_reports_unknown_roles() {
+        let text = "@role system\nBe brief.\n@role sytem\nHi\n";
+        let block = DslBlock {
+            kind: "prompt".to_string(),
+            name: "p".to_string(),
+            content: DslContent::Inline {
+                parts: vec![DslPart::Text(text.to_string(), Span::new(100, 100 + text.len() as u32))],
+            },
+            span: Span::new(80, 130),
+        };
+        let diags = PromptDslHandler.validate(&block, &NoTypes);
+        assert_eq!(diags.len(), 1, "got: {diags:?}");
+        assert_eq!(diags[0].message, "unknown role `sytem`, expected `system`, `user` or `assistant`");
+        assert_eq!(diags[0].span, Some(Span::new(123, 134)));
+
+        let empty = DslBlock {
+            content: DslContent::Inline { parts: vec![DslPart::Text(String::new(), Span::dummy())] },
+            ..block
+        };
+        let diags = PromptDslHandler.validate(&empty, &NoTypes);
+        assert!(diags[0].message.contains("empty prompt"), "got: {diags:?}");
+        assert_eq!(diags[0].span, None);
+    }
+
+    #[test]
+    fn validate_checks_output_types() {
+        struct Types;
+
+        impl ValidateContext for Types {
+            fn is_type(&self, name: &str) -> bool {
+                matches!(name, "str" | "[Source]")
+            }
+        }
+
+        let text = "@role system\nAnswer.\n@output {\n  answer: str\n  refs: [Source]\n  score: Score\n}\n";
+        let block = DslBlock {
+            kind: "prompt".to_string(),
+            name: "p".to_string(),
+            content: DslContent::Inline {
+                parts: vec![DslPart::Text(text.to_string(), Span::new(0, text.len() as u32))],
+            },
+            span: Span::new(0, text.len() as u32),
+        };
+        let diags = PromptDslHandler.validate(&block, &Types);
+        assert_eq!(diags.len(), 1, "got: {diags:?}");
+        assert_eq!(diags[0].message, "@output field `score` has unknown type `Score`");
+        assert_eq!(diags[0].span, Some(Span::new(21, 30)));
+    }
 }
@@ -1,4 +1,4 @@
-use ag_ast::Span;
+use ag_ast::{Diagnostic, Span};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TokenKind {
@@ -13,6 +13,8 @@ pub enum TokenKind {
     In,
     Of,
     While,
+    Loop,
+    Break,
     Match,
     Ret,
     Yield,
@@ -25,6 +27,7 @@ pub enum TokenKind {
     Type,
     Struct,
     Enum,
+    Interface,
     Impl,
     Pub,
     SelfKw,
@@ -44,6 +47,8 @@ pub enum TokenKind {
     IntLiteral(String),
     FloatLiteral(String),
     StringLiteral(String),
+    /// `'a'`: exactly one character, after escapes.
+    CharLiteral(char),
 
     // Template strings
     TemplateNoSub(String),
@@ -76,11 +81,13 @@ pub enum TokenKind {
     MinusEq,
     StarEq,
     SlashEq,
+    PercentEq,
     FatArrow,
     ThinArrow,
     ColonColon,
     At,
     DotDot,
+    DotDotEq,
     DotDotDot,
 
     // Punctuation
@@ -96,6 +103,9 @@ pub enum TokenKind {
     Dot,
     Question,
 
+    /// Interpreter line at the very start of a file, without the `#!`.
+    Shebang(String),
+
     // Comments
     LineComment(String),
     BlockComment(String),
@@ -129,6 +139,9 @@ pub struct Lexer<'a> {
     dsl_capture_depth: u32,
     dsl_block_start_pos: usize,
     dsl_heredoc_label: Option<String>,
+    /// Problems that do not stop a token from being produced, such as an
+    /// invalid escape in a string literal.
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl<'a> Lexer<'a> {
@@ -142,14 +155,19 @@ impl<'a> Lexer<'a> {
             dsl_capture_depth: 0,
             dsl_block_start_pos: 0,
             dsl_heredoc_label: None,
+            diagnostics: Vec::new(),
         }
     }
 
     pub fn tokenize(source: &str) -> Vec<Token> {
-        let mut lexer = Lexer::new(source);
+        Lexer::new(source).tokens()
+    }
+
+    /// All remaining tokens, through `Eof`.
+    pub fn tokens(&mut self) -> Vec<Token> {
         let mut tokens = Vec::new();
         loop {
-            let tok = lexer.next_token();
+            let tok = self.next_token();
             let is_eof = tok.kind == TokenKind::Eof;
             tokens.push(tok);
             if is_eof {
@@ -167,6 +185,18 @@ impl<'a> Lexer<'a> {
         self.bytes.get(self.pos + offset).copied()
     }
 
+    /// Consume a `#!...` line through its newline.
+    fn lex_shebang(&mut self) -> Token {
+        let line_end = self.source.find('\n').unwrap_or(self.source.len());
+        let text = &self.source[..line_end];
+        self.pos = (line_end + 1).min(self.source.len());
+        Token {
+            kind: TokenKind::Shebang(text[2..].to_string()),
+            span: Span::new(0, line_end as u32),
+            text: text.to_string(),
+        }
+    }
+
     fn advance(&mut self) -> Option<u8> {
         let ch = self.bytes.get(self.pos).copied();
         if ch.is_some() {
@@ -360,6 +390,11 @@ impl<'a> Lexer<'a> {
     }
 
     pub fn next_token(&mut self) -> Token {
+        // `#!` is only a shebang as the first bytes of the file
+        if self.pos == 0 && self.source.starts_with("#!") {
+            return self.lex_shebang();
+        }
+
         // DSL raw mode: scan raw text
         if self.dsl_raw_mode {
             return self.lex_dsl_raw();
@@ -412,7 +447,7 @@ impl<'a> Lexer<'a> {
             b'a'..=b'z' | b'A'..=b'Z' | b'_' => self.lex_ident_or_keyword(start),
             b'0'..=b'9' => self.lex_number(start),
             b'"' => self.lex_string(start, b'"'),
-            b'\'' => self.lex_string(start, b'\''),
+            b'\'' => self.lex_char(start),
             b'`' => self.lex_template_start(start),
             b'/' => self.lex_slash(start),
             _ => self.lex_punct_or_operator(start),
@@ -428,6 +463,10 @@ impl<'a> Lexer<'a> {
             }
         }
         let text = &self.source[start..self.pos];
+        // `r"..."` / `r'...'`: raw string literal
+        if text == "r" && matches!(self.peek(), Some(b'"' | b'\'')) {
+            return self.lex_raw_string(start);
+        }
         let kind = match text {
             "fn" => TokenKind::Fn,
             "let" => TokenKind::Let,
@@ -439,6 +478,8 @@ impl<'a> Lexer<'a> {
             "in" => TokenKind::In,
             "of" => TokenKind::Of,
             "while" => TokenKind::While,
+            "loop" => TokenKind::Loop,
+            "break" => TokenKind::Break,
             "match" => TokenKind::Match,
             "ret" => TokenKind::Ret,
             "yield" => TokenKind::Yield,
@@ -451,6 +492,7 @@ impl<'a> Lexer<'a> {
             "type" => TokenKind::Type,
             "struct" => TokenKind::Struct,
             "enum" => TokenKind::Enum,
+            "interface" => TokenKind::Interface,
             "impl" => TokenKind::Impl,
             "pub" => TokenKind::Pub,
             "self" => TokenKind::SelfKw,
@@ -545,8 +587,10 @@ impl<'a> Lexer<'a> {
                     };
                 }
                 Some(b'\\') => {
+                    let escape_start = self.pos;
                     self.pos += 1;
                     match self.advance() {
+                        Some(b'u') => value.push(self.lex_unicode_escape(escape_start)),
                         Some(b'n') => value.push('\n'),
                         Some(b't') => value.push('\t'),
                         Some(b'r') => value.push('\r'),
@@ -554,6 +598,9 @@ impl<'a> Lexer<'a> {
                         Some(b'\\') => value.push('\\'),
                         Some(b'\'') => value.push('\''),
                         Some(b'"') => value.push('"'),
+                        // Line continuation: drop the backslash and newline
+                        Some(b'\n') => {}
+                        Some(b'\r') if self.peek() == Some(b'\n') => self.pos += 1,
                         Some(ch) => {
                             value.push('\\');
                             value.push(ch as char);
@@ -578,6 +625,94 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// `'a'`, which is quoted like a string but holds a single character.
+    /// Single quotes used to delimit strings as well, so `'hello'` is
+    /// reported with its double-quoted spelling.
+    fn lex_char(&mut self, start: usize) -> Token {
+        let mut token = self.lex_string(start, b'\'');
+        if let TokenKind::StringLiteral(value) = &token.kind {
+            let mut chars = value.chars();
+            token.kind = match (chars.next(), chars.next()) {
+                (Some(ch), None) => TokenKind::CharLiteral(ch),
+                _ => TokenKind::Error(format!(
+                    "character literal must hold exactly one character; single quotes no longer delimit strings, write {value:?}"
+                )),
+            };
+        }
+        token
+    }
+
+    /// Decode `\u{1F600}` or `\u00E9` after the `\u`. An invalid code point
+    /// is reported and decodes to U+FFFD.
+    fn lex_unicode_escape(&mut self, escape_start: usize) -> char {
+        let braced = self.peek() == Some(b'{');
+        let digits_start = self.pos + braced as usize;
+        let mut digits_end = digits_start;
+        while self.bytes.get(digits_end).is_some_and(u8::is_ascii_hexdigit) && (braced || digits_end < digits_start + 4)
+        {
+            digits_end += 1;
+        }
+        let digits = &self.source[digits_start..digits_end];
+        let well_formed = if braced {
+            !digits.is_empty() && self.peek_at(digits_end - self.pos) == Some(b'}')
+        } else {
+            digits.len() == 4
+        };
+        if !well_formed {
+            self.pos = digits_end;
+            let msg = if braced {
+                "invalid Unicode escape: expected hex digits and `}` after `\\u{`"
+            } else {
+                "invalid Unicode escape: expected 4 hex digits after `\\u`"
+            };
+            self.diagnostics.push(Diagnostic::error(msg, Span::new(escape_start as u32, self.pos as u32)));
+            return char::REPLACEMENT_CHARACTER;
+        }
+        self.pos = digits_end + braced as usize;
+        match u32::from_str_radix(digits, 16).ok().and_then(char::from_u32) {
+            Some(ch) => ch,
+            None => {
+                let escape = &self.source[escape_start..self.pos];
+                self.diagnostics.push(Diagnostic::warning(
+                    format!("`{escape}` is not a valid Unicode code point"),
+                    Span::new(escape_start as u32, self.pos as u32),
+                ));
+                char::REPLACEMENT_CHARACTER
+            }
+        }
+    }
+
+    /// Lex a raw string whose opening quote is at `self.pos`: backslashes
+    /// are kept as written and the literal ends at the next matching quote.
+    fn lex_raw_string(&mut self, start: usize) -> Token {
+        let quote = self.bytes[self.pos];
+        self.pos += 1; // consume opening quote
+        let content_start = self.pos;
+        loop {
+            match self.peek() {
+                None | Some(b'\n') => {
+                    let text = self.source[start..self.pos].to_string();
+                    return Token {
+                        kind: TokenKind::Error("unterminated raw string literal".to_string()),
+                        span: Span::new(start as u32, self.pos as u32),
+                        text,
+                    };
+                }
+                Some(ch) if ch == quote => {
+                    let value = self.source[content_start..self.pos].to_string();
+                    self.pos += 1; // consume closing quote
+                    let text = self.source[start..self.pos].to_string();
+                    return Token {
+                        kind: TokenKind::StringLiteral(value),
+                        span: Span::new(start as u32, self.pos as u32),
+                        text,
+                    };
+                }
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
     fn lex_template_start(&mut self, start: usize) -> Token {
         self.pos += 1; // consume opening backtick
         let mut value = String::new();
@@ -853,6 +988,13 @@ impl<'a> Lexer<'a> {
                             span: Span::new(start as u32, self.pos as u32),
                             text: "...".to_string(),
                         }
+                    } else if self.peek() == Some(b'=') {
+                        self.pos += 1;
+                        Token {
+                            kind: TokenKind::DotDotEq,
+                            span: Span::new(start as u32, self.pos as u32),
+                            text: "..=".to_string(),
+                        }
                     } else {
                         Token {
                             kind: TokenKind::DotDot,
@@ -958,11 +1100,22 @@ impl<'a> Lexer<'a> {
                     }
                 }
             }
-            b'%' => Token {
-                kind: TokenKind::Percent,
-                span: Span::new(start as u32, self.pos as u32),
-                text: "%".to_string(),
-            },
+            b'%' => {
+                if self.peek() == Some(b'=') {
+                    self.pos += 1;
+                    Token {
+                        kind: TokenKind::PercentEq,
+                        span: Span::new(start as u32, self.pos as u32),
+                        text: "%=".to_string(),
+                    }
+                } else {
+                    Token {
+                        kind: TokenKind::Percent,
+                        span: Span::new(start as u32, self.pos as u32),
+                        text: "%".to_string(),
+                    }
+                }
+            }
             b'=' => {
                 if self.peek() == Some(b'=') {
                     self.pos += 1;
@@ -1151,6 +1304,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn char_literals() {
+        assert_eq!(kinds("'A'"), vec![TokenKind::CharLiteral('A')]);
+        assert_eq!(kinds(r"'\n'"), vec![TokenKind::CharLiteral('\n')]);
+        assert_eq!(kinds(r"'\''"), vec![TokenKind::CharLiteral('\'')]);
+        assert!(matches!(&kinds("'ab'")[0], TokenKind::Error(msg) if msg.contains("exactly one character")));
+        assert_eq!(
+            kinds("'hello'"),
+            vec![TokenKind::Error(
+                "character literal must hold exactly one character; single quotes no longer delimit strings, write \"hello\""
+                    .into()
+            )]
+        );
+        assert!(matches!(kinds("''")[0], TokenKind::Error(_)));
+        // Raw strings may still be single-quoted
+        assert_eq!(kinds("r'ab'"), vec![TokenKind::StringLiteral("ab".into())]);
+    }
+
     #[test]
     fn escape_sequences() {
         assert_eq!(
@@ -1159,6 +1330,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn unicode_escapes() {
+        assert_eq!(
+            kinds(r#""\u{1F600} caf\u00e9""#),
+            vec![TokenKind::StringLiteral("\u{1F600} caf\u{e9}".into())]
+        );
+
+        let mut lexer = Lexer::new(r#""a\uD800b""#);
+        let tokens = lexer.tokens();
+        assert_eq!(tokens[0].kind, TokenKind::StringLiteral("a\u{FFFD}b".into()));
+        assert_eq!(lexer.diagnostics.len(), 1);
+        assert_eq!(lexer.diagnostics[0].message, r"`\uD800` is not a valid Unicode code point");
+        assert!(!lexer.diagnostics[0].is_error());
+        assert_eq!(lexer.diagnostics[0].span, Span::new(2, 8));
+
+        let mut lexer = Lexer::new(r#""\u{zz}""#);
+        let tokens = lexer.tokens();
+        assert_eq!(tokens[0].kind, TokenKind::StringLiteral("\u{FFFD}zz}".into()));
+        assert!(lexer.diagnostics[0].is_error());
+    }
+
     #[test]
     fn unterminated_string() {
         let tokens = kinds(r#""hello"#);
@@ -1257,6 +1449,74 @@ mod tests {
         assert!(doc.is_some());
     }
 
+    #[test]
+    fn string_line_continuation() {
+        assert_eq!(
+            kinds("\"hello \\\nworld\""),
+            vec![TokenKind::StringLiteral("hello world".into())]
+        );
+        assert_eq!(
+            kinds("\"a\\\r\nb\""),
+            vec![TokenKind::StringLiteral("ab".into())]
+        );
+        // Raw strings cannot span lines
+        assert!(matches!(kinds("r\"a\\\nb\"")[0], TokenKind::Error(_)));
+    }
+
+    #[test]
+    fn raw_string_keeps_backslashes() {
+        assert_eq!(
+            kinds(r#"r"line1\nline2""#),
+            vec![TokenKind::StringLiteral(r"line1\nline2".into())]
+        );
+        assert_eq!(
+            kinds(r#"r'C:\dir\'"#),
+            vec![TokenKind::StringLiteral(r"C:\dir\".into())]
+        );
+    }
+
+    #[test]
+    fn raw_string_quotes_inside() {
+        assert_eq!(
+            kinds(r#"r'say "hi"'"#),
+            vec![TokenKind::StringLiteral(r#"say "hi""#.into())]
+        );
+    }
+
+    #[test]
+    fn raw_string_vs_regular_string() {
+        assert_eq!(kinds(r#"r"a\nb""#), kinds(r#""a\\nb""#));
+        assert_ne!(kinds(r#"r"a\nb""#), kinds(r#""a\nb""#));
+        // `r` alone is still an identifier
+        assert_eq!(
+            kinds("r + 1"),
+            vec![
+                TokenKind::Ident("r".into()),
+                TokenKind::Plus,
+                TokenKind::IntLiteral("1".into()),
+            ]
+        );
+        assert!(matches!(kinds(r#"r"open"#)[0], TokenKind::Error(_)));
+    }
+
+    #[test]
+    fn shebang_line() {
+        assert_eq!(
+            kinds("#!/usr/bin/env ag\nlet x"),
+            vec![
+                TokenKind::Shebang("/usr/bin/env ag".into()),
+                TokenKind::Let,
+                TokenKind::Ident("x".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn shebang_only_at_start() {
+        let tokens = Lexer::tokenize("x\n#!/usr/bin/env ag");
+        assert!(!tokens.iter().any(|t| matches!(t.kind, TokenKind::Shebang(_))));
+    }
+
     #[test]
     fn block_comment() {
         let tokens = Lexer::tokenize("x /* block */ y");
@@ -1289,19 +1549,35 @@ mod tests {
     #[test]
     fn assignment_ops() {
         assert_eq!(
-            kinds("+= -= *= /="),
+            kinds("+= -= *= /= %="),
             vec![
                 TokenKind::PlusEq,
                 TokenKind::MinusEq,
                 TokenKind::StarEq,
                 TokenKind::SlashEq,
+                TokenKind::PercentEq,
+            ]
+        );
+    }
+
+    #[test]
+    fn modulo_assign() {
+        assert_eq!(
+            kinds("x %= 3"),
+            vec![
+                TokenKind::Ident("x".into()),
+                TokenKind::PercentEq,
+                TokenKind::IntLiteral("3".into()),
             ]
         );
     }
 
     #[test]
     fn range_and_spread() {
-        assert_eq!(kinds(".. ..."), vec![TokenKind::DotDot, TokenKind::DotDotDot]);
+        assert_eq!(
+            kinds(".. ..= ..."),
+            vec![TokenKind::DotDot, TokenKind::DotDotEq, TokenKind::DotDotDot]
+        );
     }
 
     #[test]
@@ -1437,6 +1713,39 @@ mod tests {
         assert_eq!(lexer.next_token().kind, TokenKind::DslBlockEnd);
     }
 
+    #[test]
+    fn loop_keywords() {
+        assert_eq!(
+            kinds("while let"),
+            vec![TokenKind::While, TokenKind::Let]
+        );
+        assert_eq!(kinds("break"), vec![TokenKind::Break]);
+        assert_eq!(kinds("loop"), vec![TokenKind::Loop]);
+        assert_eq!(kinds("breaker"), vec![TokenKind::Ident("breaker".into())]);
+    }
+
+    #[test]
+    fn interface_keyword() {
+        assert_eq!(
+            kinds("interface impl self"),
+            vec![TokenKind::Interface, TokenKind::Impl, TokenKind::SelfKw]
+        );
+    }
+
+    #[test]
+    fn contextual_keywords_are_identifiers() {
+        assert_eq!(
+            kinds("not in is not where"),
+            vec![
+                TokenKind::Ident("not".into()),
+                TokenKind::In,
+                TokenKind::Ident("is".into()),
+                TokenKind::Ident("not".into()),
+                TokenKind::Ident("where".into()),
+            ]
+        );
+    }
+
     // ── Extern keyword tests ──
 
     #[test]
@@ -6,6 +6,9 @@ pub struct Parser<'a> {
     pos: usize,
     diagnostics: Vec<Diagnostic>,
     source: &'a str,
+    /// For each parsed top-level item: the end of the token before it and
+    /// the start of its first token. Comments in between precede the item.
+    item_gaps: Vec<(u32, u32)>,
 }
 
 pub struct ParseResult {
@@ -13,18 +16,58 @@ pub struct ParseResult {
     pub diagnostics: Vec<Diagnostic>,
 }
 
+impl ParseResult {
+    /// Whether any diagnostic is an error; the lexer also reports warnings.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.is_error())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Keep comments in `Module::comments` instead of discarding them.
+    pub preserve_comments: bool,
+}
+
 pub fn parse(source: &str) -> ParseResult {
-    let tokens: Vec<Token> = Lexer::tokenize(source)
+    parse_with_options(source, ParseOptions::default())
+}
+
+pub fn parse_with_options(source: &str, options: ParseOptions) -> ParseResult {
+    let mut shebang = None;
+    let mut comments = Vec::new();
+    let mut lexer = Lexer::new(source);
+    let tokens: Vec<Token> = lexer
+        .tokens()
         .into_iter()
         .filter(|t| {
-            !matches!(
-                t.kind,
-                TokenKind::LineComment(_) | TokenKind::BlockComment(_) | TokenKind::DocComment(_)
-            )
+            let kind = match &t.kind {
+                TokenKind::Shebang(line) => {
+                    shebang = Some(line.clone());
+                    return false;
+                }
+                TokenKind::LineComment(_) => CommentKind::Line,
+                TokenKind::BlockComment(_) => CommentKind::Block,
+                TokenKind::DocComment(_) => CommentKind::Doc,
+                _ => return true,
+            };
+            if options.preserve_comments {
+                comments.push(Comment { kind, text: t.text.clone(), span: t.span, item: None });
+            }
+            false
         })
         .collect();
     let mut parser = Parser::new(tokens, source);
-    let module = parser.parse_module();
+    parser.diagnostics = lexer.diagnostics;
+    let mut module = parser.parse_module();
+    module.shebang = shebang;
+    for comment in &mut comments {
+        comment.item = parser
+            .item_gaps
+            .iter()
+            .position(|&(prev_end, start)| prev_end <= comment.span.start && comment.span.end <= start);
+    }
+    module.comments = comments;
     ParseResult {
         module,
         diagnostics: parser.diagnostics,
@@ -38,6 +81,7 @@ impl<'a> Parser<'a> {
             pos: 0,
             diagnostics: Vec::new(),
             source,
+            item_gaps: Vec::new(),
         }
     }
 
@@ -71,10 +115,10 @@ impl<'a> Parser<'a> {
             Some(self.advance().clone())
         } else {
             let span = self.peek_token().span;
-            self.diagnostics.push(Diagnostic {
-                message: format!("expected {:?}, found {:?}", expected, self.peek()),
+            self.diagnostics.push(Diagnostic::error(
+                format!("expected {:?}, found {:?}", expected, self.peek()),
                 span,
-            });
+            ));
             None
         }
     }
@@ -89,24 +133,44 @@ impl<'a> Parser<'a> {
             }
         } else {
             let span = self.peek_token().span;
-            self.diagnostics.push(Diagnostic {
-                message: format!("expected identifier, found {:?}", self.peek()),
+            self.diagnostics.push(Diagnostic::error(
+                format!("expected identifier, found {:?}", self.peek()),
                 span,
-            });
+            ));
             None
         }
     }
 
+    /// Whether the current token is the identifier `word`, for contextual
+    /// keywords such as `where`, `is` and `not`.
+    fn at_word(&self, word: &str) -> bool {
+        matches!(self.peek(), TokenKind::Ident(w) if w == word)
+    }
+
+    /// The kind of the token after the current one.
+    fn peek_next(&self) -> &TokenKind {
+        self.tokens
+            .get(self.pos + 1)
+            .map(|t| &t.kind)
+            .unwrap_or(&TokenKind::Eof)
+    }
+
     fn current_span(&self) -> Span {
         self.peek_token().span
     }
 
+    /// Whether the current token starts right where the previous one ended.
+    fn follows_without_space(&self) -> bool {
+        self.pos > 0
+            && self
+                .tokens
+                .get(self.pos)
+                .is_some_and(|t| t.span.start == self.tokens[self.pos - 1].span.end)
+    }
+
     fn error(&mut self, msg: impl Into<String>) {
         let span = self.current_span();
-        self.diagnostics.push(Diagnostic {
-            message: msg.into(),
-            span,
-        });
+        self.diagnostics.push(Diagnostic::error(msg, span));
     }
 
     fn synchronize(&mut self) {
@@ -124,11 +188,15 @@ impl<'a> Parser<'a> {
                 | TokenKind::Const
                 | TokenKind::Struct
                 | TokenKind::Enum
+                | TokenKind::Interface
+                | TokenKind::Impl
                 | TokenKind::Type
                 | TokenKind::Import
                 | TokenKind::Pub
                 | TokenKind::For
                 | TokenKind::While
+                | TokenKind::Loop
+                | TokenKind::Break
                 | TokenKind::Try
                 | TokenKind::If
                 | TokenKind::Match
@@ -147,17 +215,37 @@ impl<'a> Parser<'a> {
     fn parse_module(&mut self) -> Module {
         let mut items = Vec::new();
         while !matches!(self.peek(), TokenKind::Eof) {
+            let start = self.pos;
+            let prev_end = if start > 0 { self.tokens[start - 1].span.end } else { 0 };
+            let first = self.current_span().start;
             match self.parse_item() {
-                Some(item) => items.push(item),
-                None => self.synchronize(),
+                Some(item) => {
+                    items.push(item);
+                    self.item_gaps.push((prev_end, first));
+                }
+                None => {
+                    self.synchronize();
+                    // Synchronization stops at `}`, which no item can
+                    // start with; skip it so parsing makes progress.
+                    if self.pos == start {
+                        self.advance();
+                    }
+                }
             }
         }
-        Module { items }
+        Module {
+            items,
+            shebang: None,
+            comments: Vec::new(),
+        }
     }
 
     fn parse_item(&mut self) -> Option<Item> {
         match self.peek() {
             TokenKind::Import => self.parse_import().map(Item::Import),
+            TokenKind::Const if matches!(self.peek_next(), TokenKind::Fn | TokenKind::Async) => {
+                self.parse_fn_decl(false).map(Item::FnDecl)
+            }
             TokenKind::Let | TokenKind::Mut | TokenKind::Const => {
                 self.parse_var_decl().map(Item::VarDecl)
             }
@@ -165,9 +253,13 @@ impl<'a> Parser<'a> {
             TokenKind::Pub => {
                 self.advance(); // consume 'pub'
                 match self.peek() {
-                    TokenKind::Fn | TokenKind::Async => self.parse_fn_decl(true).map(Item::FnDecl),
+                    TokenKind::Fn | TokenKind::Async | TokenKind::Const => self.parse_fn_decl(true).map(Item::FnDecl),
+                    TokenKind::Use => self.parse_export_alias().map(Item::ExportAlias),
+                    TokenKind::Struct | TokenKind::Enum | TokenKind::Interface | TokenKind::Type => {
+                        self.parse_pub_type_decl()
+                    }
                     TokenKind::At => {
-                        // Check for `pub @tool fn`
+                        // Check for `pub @tool fn` and `pub @derive(...) struct`
                         if self.pos + 1 < self.tokens.len() {
                             if let TokenKind::Ident(ref name) = self.tokens[self.pos + 1].kind {
                                 if name == "tool" {
@@ -178,24 +270,32 @@ impl<'a> Parser<'a> {
                                     }
                                     return self.parse_fn_decl_with_tool(true, Some(annotation)).map(Item::FnDecl);
                                 }
+                                if name == "derive" {
+                                    return self.parse_derive_annotated_item(true);
+                                }
                             }
                         }
-                        self.error("expected `fn` after `pub`");
+                        self.error("expected `fn`, `struct`, `enum`, `interface`, `type` or `use` after `pub`");
                         None
                     }
                     _ => {
-                        self.error("expected `fn` after `pub`");
+                        self.error("expected `fn`, `struct`, `enum`, `interface`, `type` or `use` after `pub`");
                         None
                     }
                 }
             }
             TokenKind::Struct => self.parse_struct_decl().map(Item::StructDecl),
             TokenKind::Enum => self.parse_enum_decl().map(Item::EnumDecl),
+            TokenKind::Interface => self.parse_interface_decl().map(Item::InterfaceDecl),
+            TokenKind::Impl => self.parse_impl_block().map(Item::ImplBlock),
             TokenKind::Type => self.parse_type_alias().map(Item::TypeAlias),
             TokenKind::Extern => self.parse_extern_item(None),
             TokenKind::At => {
                 // Check if this is @js or @tool annotation (followed by ident)
                 if self.pos + 1 < self.tokens.len() {
+                    if matches!(self.tokens[self.pos + 1].kind, TokenKind::Extern) {
+                        return self.parse_extern_annotation(None);
+                    }
                     if let TokenKind::Ident(ref name) = self.tokens[self.pos + 1].kind {
                         if name == "js" {
                             return self.parse_js_annotated_extern();
@@ -203,16 +303,24 @@ impl<'a> Parser<'a> {
                         if name == "tool" {
                             return self.parse_tool_annotated_fn();
                         }
+                        if name == "derive" {
+                            return self.parse_derive_annotated_item(false);
+                        }
                     }
                 }
                 self.parse_dsl_block().map(Item::DslBlock)
             }
             // Control flow statements at top level — wrap as ExprStmt containing block-level constructs
-            TokenKind::For | TokenKind::While | TokenKind::Try | TokenKind::Ret => {
+            TokenKind::For
+            | TokenKind::While
+            | TokenKind::Loop
+            | TokenKind::Try
+            | TokenKind::Ret => {
                 let span = self.current_span();
                 let stmt = match self.peek() {
-                    TokenKind::For => self.parse_for().map(Stmt::For)?,
-                    TokenKind::While => self.parse_while().map(Stmt::While)?,
+                    TokenKind::For => self.parse_for()?,
+                    TokenKind::While => self.parse_while()?,
+                    TokenKind::Loop => self.parse_loop().map(Stmt::Loop)?,
                     TokenKind::Try => self.parse_try_catch().map(Stmt::TryCatch)?,
                     TokenKind::Ret => {
                         let r = self.parse_ret()?;
@@ -244,19 +352,46 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// A `struct`, `enum`, `interface` or `type` declaration after `pub`.
+    fn parse_pub_type_decl(&mut self) -> Option<Item> {
+        match self.peek() {
+            TokenKind::Struct => self.parse_struct_decl().map(|s| Item::StructDecl(StructDecl { is_pub: true, ..s })),
+            TokenKind::Enum => self.parse_enum_decl().map(|e| Item::EnumDecl(EnumDecl { is_pub: true, ..e })),
+            TokenKind::Interface => {
+                self.parse_interface_decl().map(|i| Item::InterfaceDecl(InterfaceDecl { is_pub: true, ..i }))
+            }
+            _ => self.parse_type_alias().map(|t| Item::TypeAlias(TypeAlias { is_pub: true, ..t })),
+        }
+    }
+
     // ── Import ─────────────────────────────────────────────
 
     fn parse_import(&mut self) -> Option<Import> {
         let start = self.current_span();
         self.advance(); // consume 'import'
 
+        // Side-effect import: import "path"
+        if matches!(
+            self.peek(),
+            TokenKind::StringLiteral(_) | TokenKind::TemplateNoSub(_) | TokenKind::TemplateHead(_)
+        ) {
+            let path = self.parse_import_path()?;
+            let end = self.current_span();
+            return Some(Import {
+                names: Vec::new(),
+                path,
+                namespace: None,
+                span: Span::new(start.start, end.end),
+            });
+        }
+
         // Check for namespace import: import * as name from "path"
         if matches!(self.peek(), TokenKind::Star) {
             self.advance(); // consume '*'
             self.expect(&TokenKind::As)?;
             let alias = self.expect_ident()?;
             self.expect(&TokenKind::From)?;
-            let path = self.parse_string_literal()?;
+            let path = self.parse_import_path()?;
             let end = self.current_span();
             return Some(Import {
                 names: Vec::new(),
@@ -289,7 +424,7 @@ impl<'a> Parser<'a> {
         }
         self.expect(&TokenKind::RBrace)?;
         self.expect(&TokenKind::From)?;
-        let path = self.parse_string_literal()?;
+        let path = self.parse_import_path()?;
         let end = self.current_span();
         Some(Import {
             names,
@@ -299,6 +434,69 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parse the specifier after `from`: a string, or a template string
+    /// (which must not interpolate).
+    fn parse_import_path(&mut self) -> Option<ImportPath> {
+        if let TokenKind::TemplateHead(head) = self.peek() {
+            let head = head.clone();
+            let start = self.current_span();
+            self.advance();
+            let Expr::TemplateString(template) = self.parse_template_string(head, start)? else {
+                return None;
+            };
+            self.diagnostics.push(Diagnostic::error(
+                "dynamic import paths are not supported in static imports",
+                template.span,
+            ));
+            return Some(ImportPath::Dynamic(template));
+        }
+        if let TokenKind::TemplateNoSub(path) = self.peek() {
+            let path = path.clone();
+            self.advance();
+            return Some(ImportPath::Static(path));
+        }
+        self.parse_string_literal().map(ImportPath::Static)
+    }
+
+    // ── Export alias ───────────────────────────────────────
+
+    /// Parse `use { a as b, c }` after `pub` has been consumed.
+    fn parse_export_alias(&mut self) -> Option<ExportAlias> {
+        let start = self.current_span();
+        self.advance(); // consume 'use'
+        self.expect(&TokenKind::LBrace)?;
+        let mut names = Vec::new();
+        while !matches!(self.peek(), TokenKind::RBrace | TokenKind::Eof) {
+            let name_span = self.current_span();
+            let local = self.expect_ident()?;
+            let exported = if matches!(self.peek(), TokenKind::As) {
+                self.advance();
+                self.expect_ident()?
+            } else {
+                local.clone()
+            };
+            names.push(ExportName {
+                local,
+                exported,
+                span: name_span,
+            });
+            if matches!(self.peek(), TokenKind::Comma) {
+                self.advance();
+            }
+        }
+        let end = self.current_span();
+        self.expect(&TokenKind::RBrace)?;
+        if matches!(self.peek(), TokenKind::Semi) {
+            self.advance();
+        }
+        Some(ExportAlias {
+            names,
+            span: Span::new(start.start, end.end),
+        })
+    }
+
+    /// A quoted string literal; callers that also take a template string,
+    /// such as import paths, handle it themselves.
     fn parse_string_literal(&mut self) -> Option<String> {
         if let TokenKind::StringLiteral(_) = self.peek() {
             let tok = self.advance().clone();
@@ -357,7 +555,17 @@ impl<'a> Parser<'a> {
     fn parse_fn_decl_with_tool(&mut self, is_pub: bool, tool_annotation: Option<ToolAnnotation>) -> Option<FnDecl> {
         let start = self.current_span();
 
+        let is_const = if matches!(self.peek(), TokenKind::Const) {
+            self.advance();
+            true
+        } else {
+            false
+        };
         let is_async = if matches!(self.peek(), TokenKind::Async) {
+            if is_const {
+                self.error("a `const fn` cannot be async");
+                return None;
+            }
             self.advance();
             true
         } else {
@@ -366,6 +574,7 @@ impl<'a> Parser<'a> {
 
         self.expect(&TokenKind::Fn)?;
         let name = self.expect_ident()?;
+        let type_params = self.parse_type_params()?;
 
         self.expect(&TokenKind::LParen)?;
         let params = self.parse_params()?;
@@ -377,27 +586,96 @@ impl<'a> Parser<'a> {
         } else {
             None
         };
+        let where_clause = self.parse_where_clause()?;
 
-        let body = self.parse_block()?;
+        let body = if matches!(self.peek(), TokenKind::Eq) {
+            self.parse_expr_body()?
+        } else {
+            self.parse_block()?
+        };
         let end = body.span;
 
         Some(FnDecl {
             name,
+            type_params,
             params,
             return_type,
+            where_clause,
             body,
             is_pub,
             is_async,
+            is_const,
             tool_annotation,
             span: Span::new(start.start, end.end),
         })
     }
 
+    /// Parse `where T: A, U: B + C` into one constraint per bound; nothing
+    /// if there is no `where`. `where` is contextual, but a function's body
+    /// must follow its signature, so there it can only start a clause.
+    fn parse_where_clause(&mut self) -> Option<Vec<TypeConstraint>> {
+        let mut constraints = Vec::new();
+        if !self.at_word("where") {
+            return Some(constraints);
+        }
+        self.advance();
+        loop {
+            let start = self.current_span();
+            let type_param = self.expect_ident()?;
+            self.expect(&TokenKind::Colon)?;
+            loop {
+                let bound = self.parse_type()?;
+                let end = self.tokens[self.pos - 1].span;
+                constraints.push(TypeConstraint {
+                    type_param: type_param.clone(),
+                    bound,
+                    span: Span::new(start.start, end.end),
+                });
+                if !matches!(self.peek(), TokenKind::Plus) {
+                    break;
+                }
+                self.advance();
+            }
+            if !matches!(self.peek(), TokenKind::Comma) {
+                break;
+            }
+            self.advance();
+        }
+        Some(constraints)
+    }
+
+    /// Parse an expression body `= expr` as a block whose tail is `expr`.
+    fn parse_expr_body(&mut self) -> Option<Block> {
+        let start = self.current_span();
+        self.advance(); // consume '='
+        let expr = self.parse_expr(0)?;
+        let end = self.tokens[self.pos - 1].span;
+        if matches!(self.peek(), TokenKind::Semi) {
+            self.advance();
+        }
+        Some(Block {
+            stmts: Vec::new(),
+            tail_expr: Some(Box::new(expr)),
+            span: Span::new(start.start, end.end),
+        })
+    }
+
     fn parse_params(&mut self) -> Option<Vec<Param>> {
         let mut params = Vec::new();
         while !matches!(self.peek(), TokenKind::RParen | TokenKind::Eof) {
             let start = self.current_span();
-            let name = self.expect_ident()?;
+            let is_mut = params.is_empty()
+                && matches!(self.peek(), TokenKind::Mut)
+                && matches!(self.tokens.get(self.pos + 1).map(|t| &t.kind), Some(TokenKind::SelfKw));
+            if is_mut {
+                self.advance();
+            }
+            let name = if params.is_empty() && matches!(self.peek(), TokenKind::SelfKw) {
+                self.advance();
+                "self".to_string()
+            } else {
+                self.expect_ident()?
+            };
 
             let ty = if matches!(self.peek(), TokenKind::Colon) {
                 self.advance();
@@ -419,6 +697,7 @@ impl<'a> Parser<'a> {
                 ty,
                 default,
                 is_variadic: false,
+                is_mut,
                 span: Span::new(start.start, end.end),
             });
 
@@ -476,6 +755,8 @@ impl<'a> Parser<'a> {
         Some(StructDecl {
             name,
             fields,
+            derives: Vec::new(),
+            is_pub: false,
             span: Span::new(start.start, end.end),
         })
     }
@@ -530,22 +811,119 @@ impl<'a> Parser<'a> {
         Some(EnumDecl {
             name,
             variants,
+            derives: Vec::new(),
+            is_pub: false,
+            span: Span::new(start.start, end.end),
+        })
+    }
+
+    // ── Interfaces and impl blocks ─────────────────────────
+
+    fn parse_interface_decl(&mut self) -> Option<InterfaceDecl> {
+        let start = self.current_span();
+        self.advance(); // consume 'interface'
+        let name = self.expect_ident()?;
+        let type_params = self.parse_type_params()?;
+
+        // `extends` is contextual, not a keyword
+        let mut extends = Vec::new();
+        if matches!(self.peek(), TokenKind::Ident(kw) if kw == "extends") {
+            self.advance();
+            extends.push(self.expect_ident()?);
+            while matches!(self.peek(), TokenKind::Comma) {
+                self.advance();
+                extends.push(self.expect_ident()?);
+            }
+        }
+
+        self.expect(&TokenKind::LBrace)?;
+        let mut methods = Vec::new();
+        while !matches!(self.peek(), TokenKind::RBrace | TokenKind::Eof) {
+            if !matches!(self.peek(), TokenKind::Fn) {
+                self.error("expected method signature in interface");
+                return None;
+            }
+            methods.push(self.parse_method_signature("interface")?);
+            if matches!(self.peek(), TokenKind::Comma | TokenKind::Semi) {
+                self.advance();
+            }
+        }
+        self.expect(&TokenKind::RBrace)?;
+        let end = self.current_span();
+        Some(InterfaceDecl {
+            name,
+            type_params,
+            methods,
+            extends,
+            is_pub: false,
             span: Span::new(start.start, end.end),
         })
     }
 
-    // ── Type alias ─────────────────────────────────────────
+    /// Parse `<T, U>` after an interface or function name; nothing if there
+    /// is no `<`.
+    fn parse_type_params(&mut self) -> Option<Vec<TypeParam>> {
+        let mut type_params = Vec::new();
+        if matches!(self.peek(), TokenKind::Lt) {
+            self.advance();
+            while !matches!(self.peek(), TokenKind::Gt | TokenKind::Eof) {
+                let span = self.current_span();
+                type_params.push(TypeParam {
+                    name: self.expect_ident()?,
+                    span,
+                });
+                if matches!(self.peek(), TokenKind::Comma) {
+                    self.advance();
+                }
+            }
+            self.expect(&TokenKind::Gt)?;
+        }
+        Some(type_params)
+    }
+
+    fn parse_impl_block(&mut self) -> Option<ImplBlock> {
+        let start = self.current_span();
+        self.advance(); // consume 'impl'
+        let first = self.expect_ident()?;
+        let (interface, target) = if matches!(self.peek(), TokenKind::For) {
+            self.advance();
+            (Some(first), self.expect_ident()?)
+        } else {
+            (None, first)
+        };
+
+        self.expect(&TokenKind::LBrace)?;
+        let mut methods = Vec::new();
+        while !matches!(self.peek(), TokenKind::RBrace | TokenKind::Eof) {
+            if !matches!(self.peek(), TokenKind::Fn | TokenKind::Async) {
+                self.error("expected `fn` in impl block");
+                return None;
+            }
+            methods.push(self.parse_fn_decl(false)?);
+        }
+        self.expect(&TokenKind::RBrace)?;
+        let end = self.current_span();
+        Some(ImplBlock {
+            interface,
+            target,
+            methods,
+            span: Span::new(start.start, end.end),
+        })
+    }
 
     fn parse_type_alias(&mut self) -> Option<TypeAlias> {
         let start = self.current_span();
         self.advance(); // consume 'type'
         let name = self.expect_ident()?;
+        let type_params = self.parse_type_params()?;
         self.expect(&TokenKind::Eq)?;
         let ty = self.parse_type()?;
         let end = self.current_span();
         Some(TypeAlias {
             name,
+            type_params,
             ty,
+            is_pub: false,
             span: Span::new(start.start, end.end),
         })
     }
@@ -705,10 +1083,8 @@ impl<'a> Parser<'a> {
                             let mut sub_parser = Parser::new(capture_tokens, self.source);
                             let (stmts, tail_expr) = sub_parser.parse_block_body();
                             if stmts.is_empty() && tail_expr.is_none() {
-                                self.diagnostics.push(Diagnostic {
-                                    message: "empty capture".into(),
-                                    span: cap_start_span,
-                                });
+                                self.diagnostics
+                                    .push(Diagnostic::error("empty capture", cap_start_span));
                             } else if stmts.is_empty() {
                                 // Single expression — use directly (backward compatible)
                                 if let Some(expr) = tail_expr {
@@ -738,10 +1114,7 @@ impl<'a> Parser<'a> {
                                 byte_offset as u32 + tok.span.start,
                                 byte_offset as u32 + tok.span.end,
                             );
-                            self.diagnostics.push(Diagnostic {
-                                message: msg.clone(),
-                                span,
-                            });
+                            self.diagnostics.push(Diagnostic::error(msg.clone(), span));
                             dsl_pos += 1;
                             break;
                         }
@@ -778,6 +1151,11 @@ impl<'a> Parser<'a> {
 
     fn parse_js_annotated_extern(&mut self) -> Option<Item> {
         let annotation = self.parse_js_annotation()?;
+        if matches!(self.peek(), TokenKind::At)
+            && matches!(self.tokens.get(self.pos + 1).map(|t| &t.kind), Some(TokenKind::Extern))
+        {
+            return self.parse_extern_annotation(Some(annotation));
+        }
         if !matches!(self.peek(), TokenKind::Extern) {
             self.error("@js annotation can only be applied to extern declarations");
             return None;
@@ -785,6 +1163,13 @@ impl<'a> Parser<'a> {
         self.parse_extern_item(Some(annotation))
     }
 
+    /// `@extern fn ...`: the annotation form of the `extern` keyword, which
+    /// produces the same declaration.
+    fn parse_extern_annotation(&mut self, js_annotation: Option<JsAnnotation>) -> Option<Item> {
+        self.advance(); // consume '@'
+        self.parse_extern_item(js_annotation)
+    }
+
     fn parse_js_annotation(&mut self) -> Option<JsAnnotation> {
         let start = self.current_span();
         self.advance(); // consume '@'
@@ -840,6 +1225,48 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// `@derive(Eq, Debug)` followed by a struct or enum declaration, which
+    /// may be `pub` either before or after the annotation.
+    fn parse_derive_annotated_item(&mut self, is_pub: bool) -> Option<Item> {
+        self.advance(); // consume '@'
+        self.advance(); // consume 'derive'
+        self.expect(&TokenKind::LParen)?;
+        let mut derives = Vec::new();
+        while !matches!(self.peek(), TokenKind::RParen | TokenKind::Eof) {
+            let span = self.current_span();
+            let name = self.expect_ident()?;
+            derives.push(Ident { name, span });
+            if matches!(self.peek(), TokenKind::Comma) {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        self.expect(&TokenKind::RParen)?;
+        let is_pub = is_pub || matches!(self.peek(), TokenKind::Pub);
+        if matches!(self.peek(), TokenKind::Pub) {
+            self.advance();
+        }
+        match self.peek() {
+            TokenKind::Struct => {
+                let mut s = self.parse_struct_decl()?;
+                s.derives = derives;
+                s.is_pub = is_pub;
+                Some(Item::StructDecl(s))
+            }
+            TokenKind::Enum => {
+                let mut e = self.parse_enum_decl()?;
+                e.derives = derives;
+                e.is_pub = is_pub;
+                Some(Item::EnumDecl(e))
+            }
+            _ => {
+                self.error("@derive can only be applied to struct or enum declarations");
+                None
+            }
+        }
+    }
+
     fn parse_tool_annotated_fn(&mut self) -> Option<Item> {
         let annotation = self.parse_tool_annotation()?;
         let is_pub = if matches!(self.peek(), TokenKind::Pub) {
@@ -933,6 +1360,7 @@ impl<'a> Parser<'a> {
                 ty,
                 default,
                 is_variadic,
+                is_mut: false,
                 span: Span::new(start.start, end.end),
             });
 
@@ -955,6 +1383,35 @@ impl<'a> Parser<'a> {
         Some((params, variadic))
     }
 
+    /// Parse a bodiless `fn name(params) -> ret`; `owner` names the
+    /// enclosing declaration kind in the error for a stray body.
+    fn parse_method_signature(&mut self, owner: &str) -> Option<MethodSignature> {
+        let start = self.current_span();
+        self.advance(); // consume 'fn'
+        let name = self.expect_ident()?;
+        self.expect(&TokenKind::LParen)?;
+        let params = self.parse_params()?;
+        self.expect(&TokenKind::RParen)?;
+        let return_type = if matches!(self.peek(), TokenKind::ThinArrow) {
+            self.advance();
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        // Reject method body
+        if matches!(self.peek(), TokenKind::LBrace) {
+            self.error(format!("{owner} methods must not have a body"));
+            return None;
+        }
+        let end = self.current_span();
+        Some(MethodSignature {
+            name,
+            params,
+            return_type,
+            span: Span::new(start.start, end.end),
+        })
+    }
+
     fn parse_extern_struct_decl(&mut self, start: Span, js_annotation: Option<JsAnnotation>) -> Option<ExternStructDecl> {
         self.advance(); // consume 'struct'
         let name = self.expect_ident()?;
@@ -965,31 +1422,7 @@ impl<'a> Parser<'a> {
 
         while !matches!(self.peek(), TokenKind::RBrace | TokenKind::Eof) {
             if matches!(self.peek(), TokenKind::Fn) {
-                // Method signature
-                let mstart = self.current_span();
-                self.advance(); // consume 'fn'
-                let mname = self.expect_ident()?;
-                self.expect(&TokenKind::LParen)?;
-                let mparams = self.parse_params()?;
-                self.expect(&TokenKind::RParen)?;
-                let mret = if matches!(self.peek(), TokenKind::ThinArrow) {
-                    self.advance();
-                    Some(self.parse_type()?)
-                } else {
-                    None
-                };
-                // Reject method body
-                if matches!(self.peek(), TokenKind::LBrace) {
-                    self.error("extern struct methods must not have a body");
-                    return None;
-                }
-                let mend = self.current_span();
-                methods.push(MethodSignature {
-                    name: mname,
-                    params: mparams,
-                    return_type: mret,
-                    span: Span::new(mstart.start, mend.end),
-                });
+                methods.push(self.parse_method_signature("extern struct")?);
             } else {
                 // Field
                 let fstart = self.current_span();
@@ -1164,6 +1597,10 @@ impl<'a> Parser<'a> {
                     }
                 }
                 self.expect(&TokenKind::RParen)?;
+                // Grouping: `((int) -> nil)?` is a nullable function
+                if params.len() == 1 && !matches!(self.peek(), TokenKind::ThinArrow) {
+                    return params.pop();
+                }
                 self.expect(&TokenKind::ThinArrow)?;
                 let ret = self.parse_type()?;
                 let end = self.current_span();
@@ -1186,6 +1623,16 @@ impl<'a> Parser<'a> {
                             Box::new(inner),
                             Span::new(tok.span.start, end.end),
                         ))
+                    } else if matches!(self.peek(), TokenKind::Lt) {
+                        self.advance(); // consume '<'
+                        let mut args = vec![self.parse_type()?];
+                        while matches!(self.peek(), TokenKind::Comma) {
+                            self.advance();
+                            args.push(self.parse_type()?);
+                        }
+                        self.expect(&TokenKind::Gt)?;
+                        let end = self.current_span();
+                        Some(TypeExpr::Generic(name, args, Span::new(tok.span.start, end.end)))
                     } else {
                         Some(TypeExpr::Named(name, tok.span))
                     }
@@ -1233,12 +1680,33 @@ impl<'a> Parser<'a> {
                 }
                 TokenKind::For => {
                     if let Some(f) = self.parse_for() {
-                        stmts.push(Stmt::For(f));
+                        stmts.push(f);
                     }
                 }
                 TokenKind::While => {
                     if let Some(w) = self.parse_while() {
-                        stmts.push(Stmt::While(w));
+                        stmts.push(w);
+                    }
+                }
+                TokenKind::Loop => {
+                    if let Some(l) = self.parse_loop() {
+                        if matches!(self.peek(), TokenKind::RBrace) {
+                            // A trailing loop is the block's value
+                            tail_expr = Some(Box::new(Expr::Loop(Box::new(l))));
+                        } else {
+                            stmts.push(Stmt::Loop(l));
+                            if matches!(self.peek(), TokenKind::Semi) {
+                                self.advance();
+                            }
+                        }
+                    }
+                }
+                TokenKind::Break => {
+                    if let Some(b) = self.parse_break() {
+                        stmts.push(Stmt::Break(b));
+                    }
+                    if matches!(self.peek(), TokenKind::Semi) {
+                        self.advance();
                     }
                 }
                 TokenKind::Try => {
@@ -1249,13 +1717,16 @@ impl<'a> Parser<'a> {
                 _ => {
                     // Parse expression — could be tail or statement
                     if let Some(expr) = self.parse_expr(0) {
+                        let is_tail = matches!(self.peek(), TokenKind::RBrace | TokenKind::Eof);
                         if matches!(self.peek(), TokenKind::Semi) {
                             self.advance();
-                            let span = self.current_span();
-                            stmts.push(Stmt::ExprStmt(ExprStmt { expr, span }));
-                        } else if matches!(self.peek(), TokenKind::RBrace | TokenKind::Eof) {
+                        }
+                        if is_tail {
                             // This is the tail expression (implicit return)
                             tail_expr = Some(Box::new(expr));
+                        } else if let Expr::Match(m) = expr {
+                            // A `match` whose value is discarded
+                            stmts.push(Stmt::Match(*m));
                         } else {
                             let span = self.current_span();
                             stmts.push(Stmt::ExprStmt(ExprStmt { expr, span }));
@@ -1308,35 +1779,137 @@ impl<'a> Parser<'a> {
         })
     }
 
-    fn parse_for(&mut self) -> Option<ForStmt> {
+    fn parse_break(&mut self) -> Option<BreakStmt> {
+        let start = self.current_span();
+        self.advance(); // consume 'break'
+
+        let value = if matches!(
+            self.peek(),
+            TokenKind::Semi | TokenKind::RBrace | TokenKind::Eof
+        ) {
+            None
+        } else {
+            Some(self.parse_expr(0)?)
+        };
+
+        let end = self.current_span();
+        Some(BreakStmt {
+            value,
+            span: Span::new(start.start, end.end),
+        })
+    }
+
+    fn parse_for(&mut self) -> Option<Stmt> {
         let start = self.current_span();
         self.advance(); // consume 'for'
+        if matches!(self.peek(), TokenKind::Let | TokenKind::Mut) {
+            return self.parse_for_range(start);
+        }
         let binding = self.expect_ident()?;
         self.expect(&TokenKind::In)?;
         let iter = self.parse_expr(0)?;
         let body = self.parse_block()?;
         let end = body.span;
-        Some(ForStmt {
+        Some(Stmt::For(ForStmt {
             binding,
             iter,
             body,
             span: Span::new(start.start, end.end),
-        })
+        }))
     }
 
-    fn parse_while(&mut self) -> Option<WhileStmt> {
+    /// `for let i = 0; i < n; i += 1 { ... }`, after the `for`.
+    fn parse_for_range(&mut self, start: Span) -> Option<Stmt> {
+        let init = self.parse_var_decl()?;
+        // The declaration consumes its `;`, which is optional elsewhere
+        if !matches!(self.tokens[self.pos - 1].kind, TokenKind::Semi) {
+            self.expect(&TokenKind::Semi)?;
+        }
+        let condition = self.parse_expr(0)?;
+        self.expect(&TokenKind::Semi)?;
+        let update = self.parse_expr(0)?;
+        let body = self.parse_block()?;
+        let end = body.span;
+        Some(Stmt::ForRange(ForRangeStmt {
+            init,
+            condition,
+            update,
+            body,
+            span: Span::new(start.start, end.end),
+        }))
+    }
+
+    fn parse_while(&mut self) -> Option<Stmt> {
         let start = self.current_span();
         self.advance(); // consume 'while'
+        if matches!(self.peek(), TokenKind::Let) {
+            return self.parse_while_let(start);
+        }
         let condition = self.parse_expr(0)?;
         let body = self.parse_block()?;
         let end = body.span;
-        Some(WhileStmt {
+        Some(Stmt::While(WhileStmt {
             condition,
             body,
             span: Span::new(start.start, end.end),
+        }))
+    }
+
+    fn parse_loop(&mut self) -> Option<LoopExpr> {
+        let start = self.current_span();
+        self.advance(); // consume 'loop'
+        let body = self.parse_block()?;
+        let end = body.span;
+        Some(LoopExpr {
+            body,
+            span: Span::new(start.start, end.end),
         })
     }
 
+    /// `while let <pattern> = <expr> { body }`, desugared to
+    /// `loop { match <expr> { <pattern> => { body }, _ => { break } } }`.
+    fn parse_while_let(&mut self, start: Span) -> Option<Stmt> {
+        self.advance(); // consume 'let'
+        let pattern = self.parse_pattern()?;
+        self.expect(&TokenKind::Eq)?;
+        let subject = self.parse_expr(0)?;
+        let body = self.parse_block()?;
+        let span = Span::new(start.start, body.span.end);
+
+        let body_span = body.span;
+        let break_arm = MatchArm {
+            pattern: Pattern::Wildcard(span),
+            guard: None,
+            body: MatchArmBody::Block(Block {
+                stmts: vec![Stmt::Break(BreakStmt { value: None, span })],
+                tail_expr: None,
+                span,
+            }),
+            span,
+        };
+        let match_expr = MatchExpr {
+            subject,
+            arms: vec![
+                MatchArm {
+                    pattern,
+                    guard: None,
+                    body: MatchArmBody::Block(body),
+                    span: body_span,
+                },
+                break_arm,
+            ],
+            span,
+        };
+        Some(Stmt::Loop(LoopExpr {
+            body: Block {
+                stmts: vec![Stmt::Match(match_expr)],
+                tail_expr: None,
+                span,
+            },
+            span,
+        }))
+    }
+
     fn parse_try_catch(&mut self) -> Option<TryCatchStmt> {
         let start = self.current_span();
         self.advance(); // consume 'try'
@@ -1427,6 +2000,22 @@ impl<'a> Parser<'a> {
                     });
                     continue;
                 }
+                TokenKind::TemplateNoSub(_) | TokenKind::TemplateHead(_)
+                    if matches!(lhs, Expr::Ident(_) | Expr::Member(_))
+                        && self.follows_without_space() =>
+                {
+                    // Tagged template: tag`...`
+                    let span = self.current_span();
+                    let Some(Expr::TemplateString(template)) = self.parse_prefix() else {
+                        return None;
+                    };
+                    lhs = Expr::TaggedTemplate(TaggedTemplateExpr {
+                        tag: Box::new(lhs),
+                        span: Span::new(span.start, template.span.end),
+                        template,
+                    });
+                    continue;
+                }
                 TokenKind::Question => {
                     // Error propagation postfix
                     // But only if not followed by something that makes it a ternary (which AG doesn't have)
@@ -1448,17 +2037,33 @@ impl<'a> Parser<'a> {
             // Infix operators with binding power
             let (op_bp, assoc) = match self.peek() {
                 TokenKind::Eq => (2, Assoc::Right),
-                TokenKind::PlusEq | TokenKind::MinusEq | TokenKind::StarEq | TokenKind::SlashEq => {
-                    (2, Assoc::Right)
-                }
+                TokenKind::PlusEq | TokenKind::MinusEq | TokenKind::StarEq | TokenKind::SlashEq
+                | TokenKind::PercentEq => (2, Assoc::Right),
                 TokenKind::PipeGt => (4, Assoc::Left),
                 TokenKind::QuestionQuestion => (6, Assoc::Left),
                 TokenKind::PipePipe => (8, Assoc::Left),
                 TokenKind::AmpAmp => (10, Assoc::Left),
                 TokenKind::EqEq | TokenKind::BangEq => (12, Assoc::Left),
+                // `is not` and `not in` are contextual: `is` and `not` are
+                // identifiers elsewhere
+                TokenKind::Ident(w) if w == "is" && matches!(self.peek_next(), TokenKind::Ident(n) if n == "not") => {
+                    (12, Assoc::Left)
+                }
+                // `x is y`; `is(...)` and `is[...]` on the next line are
+                // left to start a statement
+                TokenKind::Ident(w)
+                    if w == "is"
+                        && starts_operand(self.peek_next())
+                        && !matches!(self.peek_next(), TokenKind::LParen | TokenKind::LBracket | TokenKind::Minus) =>
+                {
+                    self.error("expected `not` after `is`; use `==` to compare for equality");
+                    return None;
+                }
                 TokenKind::Lt | TokenKind::Gt | TokenKind::LtEq | TokenKind::GtEq => {
                     (14, Assoc::Left)
                 }
+                TokenKind::In => (14, Assoc::Left),
+                TokenKind::Ident(w) if w == "not" && matches!(self.peek_next(), TokenKind::In) => (14, Assoc::Left),
                 TokenKind::Plus | TokenKind::Minus => (16, Assoc::Left),
                 TokenKind::Star | TokenKind::Slash | TokenKind::Percent => (18, Assoc::Left),
                 TokenKind::StarStar => (20, Assoc::Right),
@@ -1529,6 +2134,44 @@ impl<'a> Parser<'a> {
                     }));
                     continue;
                 }
+                TokenKind::PercentEq => {
+                    let rhs = self.parse_expr(next_bp)?;
+                    lhs = Expr::Assign(Box::new(AssignExpr {
+                        target: lhs,
+                        value: rhs,
+                        op: AssignOp::ModAssign,
+                        span: op_span,
+                    }));
+                    continue;
+                }
+                // `x not in y` → `!(x in y)`
+                TokenKind::Ident(w) if w == "not" => {
+                    self.advance(); // consume 'in'
+                    let rhs = self.parse_expr(next_bp)?;
+                    lhs = Expr::Unary(UnaryExpr {
+                        op: UnaryOp::Not,
+                        operand: Box::new(Expr::Binary(BinaryExpr {
+                            op: BinaryOp::In,
+                            left: Box::new(lhs),
+                            right: Box::new(rhs),
+                            span: op_span,
+                        })),
+                        span: op_span,
+                    });
+                    continue;
+                }
+                // `x is not y` → `x != y`
+                TokenKind::Ident(w) if w == "is" => {
+                    self.advance(); // consume 'not'
+                    let rhs = self.parse_expr(next_bp)?;
+                    lhs = Expr::Binary(BinaryExpr {
+                        op: BinaryOp::Ne,
+                        left: Box::new(lhs),
+                        right: Box::new(rhs),
+                        span: op_span,
+                    });
+                    continue;
+                }
                 _ => {}
             }
 
@@ -1569,9 +2212,15 @@ impl<'a> Parser<'a> {
                 TokenKind::GtEq => BinaryOp::Ge,
                 TokenKind::AmpAmp => BinaryOp::And,
                 TokenKind::PipePipe => BinaryOp::Or,
+                TokenKind::In => BinaryOp::In,
                 _ => unreachable!(),
             };
 
+            if relational_op(&op_tok.kind).is_some() && relational_op(self.peek()).is_some() {
+                lhs = self.parse_comparison_chain(lhs, op, rhs, op_span, next_bp)?;
+                continue;
+            }
+
             lhs = Expr::Binary(BinaryExpr {
                 op,
                 left: Box::new(lhs),
@@ -1583,9 +2232,32 @@ impl<'a> Parser<'a> {
         Some(lhs)
     }
 
+    /// Continue `a < b` into a chain such as `a < b <= c`, which means
+    /// `a < b && b <= c`; codegen evaluates each operand once.
+    fn parse_comparison_chain(
+        &mut self,
+        first: Expr,
+        op: BinaryOp,
+        second: Expr,
+        span: Span,
+        bp: u8,
+    ) -> Option<Expr> {
+        let mut operands = vec![first, second];
+        let mut ops = vec![(op, span)];
+        while let Some(op) = relational_op(self.peek()) {
+            ops.push((op, self.current_span()));
+            self.advance();
+            operands.push(self.parse_expr(bp)?);
+        }
+
+        Some(Expr::Comparison(Box::new(ComparisonChain { operands, ops, span })))
+    }
+
     fn parse_prefix(&mut self) -> Option<Expr> {
         match self.peek() {
-            TokenKind::Bang => {
+            // `not x` is a word alias for `!x`; a `not` that no operand
+            // follows is an identifier
+            _ if matches!(self.peek(), TokenKind::Bang) || (self.at_word("not") && starts_operand(self.peek_next())) => {
                 let span = self.current_span();
                 self.advance();
                 let operand = self.parse_expr(22)?; // Unary bp
@@ -1599,6 +2271,17 @@ impl<'a> Parser<'a> {
                 let span = self.current_span();
                 self.advance();
                 let operand = self.parse_expr(22)?;
+                // `-42` is a literal, so it can be folded and matched on
+                if let Expr::Literal(lit) = operand {
+                    return match lit.negate(span) {
+                        Ok(lit) => Some(Expr::Literal(lit)),
+                        Err(lit) => Some(Expr::Unary(UnaryExpr {
+                            op: UnaryOp::Neg,
+                            operand: Box::new(Expr::Literal(lit)),
+                            span,
+                        })),
+                    };
+                }
                 Some(Expr::Unary(UnaryExpr {
                     op: UnaryOp::Neg,
                     operand: Box::new(operand),
@@ -1615,13 +2298,23 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// The integer literal `text` at the current token, reporting values
+    /// beyond `i64`.
+    fn int_literal(&mut self, text: String, span: Span) -> Literal {
+        let val = text.parse().unwrap_or_else(|_| {
+            self.error(format!("integer literal `{text}` is too large"));
+            i64::MAX
+        });
+        Literal::Int(val, text, span)
+    }
+
     fn parse_primary(&mut self) -> Option<Expr> {
         let start = self.current_span();
         match self.peek().clone() {
             TokenKind::IntLiteral(s) => {
+                let lit = self.int_literal(s, start);
                 self.advance();
-                let val: i64 = s.parse().unwrap_or(0);
-                Some(Expr::Literal(Literal::Int(val, start)))
+                Some(Expr::Literal(lit))
             }
             TokenKind::FloatLiteral(s) => {
                 self.advance();
@@ -1633,6 +2326,10 @@ impl<'a> Parser<'a> {
                 self.advance();
                 Some(Expr::Literal(Literal::String(s, start)))
             }
+            TokenKind::CharLiteral(c) => {
+                self.advance();
+                Some(Expr::Literal(Literal::Char(c, start)))
+            }
             TokenKind::True => {
                 self.advance();
                 Some(Expr::Literal(Literal::Bool(true, start)))
@@ -1649,6 +2346,13 @@ impl<'a> Parser<'a> {
                 self.advance();
                 Some(Expr::Placeholder(start))
             }
+            TokenKind::SelfKw => {
+                self.advance();
+                Some(Expr::Ident(Ident {
+                    name: "self".to_string(),
+                    span: start,
+                }))
+            }
             TokenKind::Ident(_) => {
                 let tok = self.advance().clone();
                 if let TokenKind::Ident(name) = tok.kind {
@@ -1671,7 +2375,7 @@ impl<'a> Parser<'a> {
                     self.advance(); // consume ')'
                     if matches!(self.peek(), TokenKind::FatArrow) {
                         self.advance(); // consume '=>'
-                        return self.parse_arrow_body(Vec::new(), start);
+                        return self.parse_arrow_body(Vec::new(), false, start);
                     }
                     // Not an arrow — backtrack (rare case of empty parens as expr)
                     self.pos = saved;
@@ -1685,7 +2389,7 @@ impl<'a> Parser<'a> {
                 if let Some(params) = self.try_parse_arrow_params() {
                     if matches!(self.peek(), TokenKind::FatArrow) {
                         self.advance(); // consume '=>'
-                        return self.parse_arrow_body(params, start);
+                        return self.parse_arrow_body(params, false, start);
                     }
                 }
                 // Backtrack — it's a grouped expression
@@ -1768,6 +2472,9 @@ impl<'a> Parser<'a> {
             }
             TokenKind::If => self.parse_if_expr(),
             TokenKind::Match => self.parse_match_expr(),
+            TokenKind::Loop => self
+                .parse_loop()
+                .map(|l| Expr::Loop(Box::new(l))),
             TokenKind::TemplateNoSub(s) => {
                 let s = s.clone();
                 self.advance();
@@ -1781,6 +2488,15 @@ impl<'a> Parser<'a> {
                 self.advance();
                 self.parse_template_string(s, start)
             }
+            TokenKind::Async if matches!(self.peek_next(), TokenKind::LParen) => {
+                // async (params) => body
+                self.advance(); // consume 'async'
+                self.advance(); // consume '('
+                let params = self.parse_params()?;
+                self.expect(&TokenKind::RParen)?;
+                self.expect(&TokenKind::FatArrow)?;
+                self.parse_arrow_body(params, true, start)
+            }
             TokenKind::Fn | TokenKind::Async => {
                 // fn(params) -> RetType { body } as expression (parsed as ArrowExpr)
                 let is_async = matches!(self.peek(), TokenKind::Async);
@@ -1851,6 +2567,7 @@ impl<'a> Parser<'a> {
                 ty,
                 default,
                 is_variadic: false,
+                is_mut: false,
                 span: Span::new(start.start, end.end),
             });
             if matches!(self.peek(), TokenKind::Comma) {
@@ -1865,7 +2582,7 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_arrow_body(&mut self, params: Vec<Param>, start: Span) -> Option<Expr> {
+    fn parse_arrow_body(&mut self, params: Vec<Param>, is_async: bool, start: Span) -> Option<Expr> {
         let body = if matches!(self.peek(), TokenKind::LBrace) {
             ArrowBody::Block(self.parse_block()?)
         } else {
@@ -1875,7 +2592,7 @@ impl<'a> Parser<'a> {
         Some(Expr::Arrow(Box::new(ArrowExpr {
             params,
             body,
-            is_async: false,
+            is_async,
             span: Span::new(start.start, end.end),
         })))
     }
@@ -1925,7 +2642,12 @@ impl<'a> Parser<'a> {
                 None
             };
             self.expect(&TokenKind::FatArrow)?;
-            let body = self.parse_expr(0)?;
+            // `{` may also start an object literal, so blocks are told
+            // apart by parsing an expression
+            let body = match self.parse_expr(0)? {
+                Expr::Block(b) => MatchArmBody::Block(*b),
+                other => MatchArmBody::Expr(other),
+            };
             let arm_end = self.current_span();
             arms.push(MatchArm {
                 pattern,
@@ -1946,25 +2668,35 @@ impl<'a> Parser<'a> {
         })))
     }
 
+    /// The rest of `lo..hi` or `lo..=hi`, at the `..`/`..=`.
+    fn parse_range_pattern(&mut self, lo: Expr, start: Span) -> Option<Pattern> {
+        let inclusive = matches!(self.peek(), TokenKind::DotDotEq);
+        self.advance();
+        let hi = self.parse_expr(0)?;
+        let end = self.current_span();
+        Some(Pattern::Range(Box::new(lo), Box::new(hi), inclusive, Span::new(start.start, end.end)))
+    }
+
     fn parse_pattern(&mut self) -> Option<Pattern> {
         let start = self.current_span();
         match self.peek().clone() {
+            // Negative number: `-1`, `-3.14`, `-10..0`
+            TokenKind::Minus if matches!(self.peek_next(), TokenKind::IntLiteral(_) | TokenKind::FloatLiteral(_)) => {
+                self.advance();
+                let Some(Expr::Literal(lit)) = self.parse_primary() else { return None };
+                let lit = lit.negate(start).ok()?;
+                if matches!(lit, Literal::Int(..)) && matches!(self.peek(), TokenKind::DotDot | TokenKind::DotDotEq) {
+                    return self.parse_range_pattern(Expr::Literal(lit), start);
+                }
+                Some(Pattern::Literal(lit))
+            }
             TokenKind::IntLiteral(s) => {
+                let lit = self.int_literal(s, start);
                 self.advance();
-                let val: i64 = s.parse().unwrap_or(0);
-                let mut pat = Pattern::Literal(Literal::Int(val, start));
-                // Check for range pattern
-                if matches!(self.peek(), TokenKind::DotDot) {
-                    self.advance();
-                    let end_expr = self.parse_expr(0)?;
-                    let end_span = self.current_span();
-                    pat = Pattern::Range(
-                        Box::new(Expr::Literal(Literal::Int(val, start))),
-                        Box::new(end_expr),
-                        Span::new(start.start, end_span.end),
-                    );
+                if matches!(self.peek(), TokenKind::DotDot | TokenKind::DotDotEq) {
+                    return self.parse_range_pattern(Expr::Literal(lit), start);
                 }
-                Some(pat)
+                Some(Pattern::Literal(lit))
             }
             TokenKind::FloatLiteral(s) => {
                 self.advance();
@@ -1976,6 +2708,15 @@ impl<'a> Parser<'a> {
                 self.advance();
                 Some(Pattern::Literal(Literal::String(s, start)))
             }
+            // `'a'` or `'a'..='z'`
+            TokenKind::CharLiteral(c) => {
+                self.advance();
+                let lit = Literal::Char(c, start);
+                if matches!(self.peek(), TokenKind::DotDot | TokenKind::DotDotEq) {
+                    return self.parse_range_pattern(Expr::Literal(lit), start);
+                }
+                Some(Pattern::Literal(lit))
+            }
             TokenKind::True => {
                 self.advance();
                 Some(Pattern::Literal(Literal::Bool(true, start)))
@@ -2040,19 +2781,8 @@ impl<'a> Parser<'a> {
                         span: Span::new(start.start, end.end),
                     }))
                 } else {
-                    // Check for range pattern
-                    if matches!(self.peek(), TokenKind::DotDot) {
-                        self.advance();
-                        let end_expr = self.parse_expr(0)?;
-                        let end_span = self.current_span();
-                        Some(Pattern::Range(
-                            Box::new(Expr::Ident(Ident {
-                                name,
-                                span: start,
-                            })),
-                            Box::new(end_expr),
-                            Span::new(start.start, end_span.end),
-                        ))
+                    if matches!(self.peek(), TokenKind::DotDot | TokenKind::DotDotEq) {
+                        self.parse_range_pattern(Expr::Ident(Ident { name, span: start }), start)
                     } else {
                         Some(Pattern::Ident(name, start))
                     }
@@ -2112,6 +2842,43 @@ enum Assoc {
     Right,
 }
 
+/// The comparison operator `kind` denotes, if it is one that chains.
+fn relational_op(kind: &TokenKind) -> Option<BinaryOp> {
+    match kind {
+        TokenKind::Lt => Some(BinaryOp::Lt),
+        TokenKind::Gt => Some(BinaryOp::Gt),
+        TokenKind::LtEq => Some(BinaryOp::Le),
+        TokenKind::GtEq => Some(BinaryOp::Ge),
+        _ => None,
+    }
+}
+
+/// Whether a token of `kind` can start an operand, which tells the
+/// operators `not x` and `x is y` from the identifiers `not` and `is`.
+fn starts_operand(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Ident(_)
+            | TokenKind::IntLiteral(_)
+            | TokenKind::FloatLiteral(_)
+            | TokenKind::StringLiteral(_)
+            | TokenKind::CharLiteral(_)
+            | TokenKind::TemplateNoSub(_)
+            | TokenKind::TemplateHead(_)
+            | TokenKind::True
+            | TokenKind::False
+            | TokenKind::Nil
+            | TokenKind::SelfKw
+            | TokenKind::LParen
+            | TokenKind::LBracket
+            | TokenKind::Bang
+            | TokenKind::Minus
+            | TokenKind::Await
+            | TokenKind::If
+            | TokenKind::Match
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2147,6 +2914,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn int_literal_out_of_range() {
+        let result = parse("let big = 99999999999999999999");
+        assert_eq!(result.diagnostics.len(), 1, "got: {:?}", result.diagnostics);
+        assert_eq!(result.diagnostics[0].message, "integer literal `99999999999999999999` is too large");
+        assert_eq!(result.diagnostics[0].span, Span::new(10, 30));
+
+        let m = parse_ok("let max = 9223372036854775807");
+        let Item::VarDecl(v) = &m.items[0] else { panic!("expected var decl") };
+        assert!(matches!(&v.init, Expr::Literal(Literal::Int(i64::MAX, text, _)) if text == "9223372036854775807"));
+    }
+
     #[test]
     fn const_decl() {
         let m = parse_ok("const MAX = 100");
@@ -2165,6 +2944,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn const_function() {
+        let m = parse_ok("const fn add(a: int, b: int) -> int { a + b }\npub const fn one() -> int = 1\nconst ONE = one()");
+        let Item::FnDecl(f) = &m.items[0] else { panic!("expected fn, got {:?}", m.items[0]) };
+        assert!(f.is_const && !f.is_pub);
+        let Item::FnDecl(f) = &m.items[1] else { panic!("expected fn, got {:?}", m.items[1]) };
+        assert!(f.is_const && f.is_pub);
+        assert!(matches!(&m.items[2], Item::VarDecl(v) if v.kind == VarKind::Const));
+
+        let result = parse("const async fn f() {}");
+        assert_eq!(result.diagnostics[0].message, "a `const fn` cannot be async");
+    }
+
     #[test]
     fn pub_async_function() {
         let m = parse_ok("pub async fn fetch(url: str) -> str { url }");
@@ -2190,6 +2982,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn async_arrow_function() {
+        let m = parse_ok("let fetchAll = async (urls) => await Promise.all(urls.map(fetch))\nlet f = async () => { 1 }\nlet g = (x) => x");
+        let arrows: Vec<&ArrowExpr> = m
+            .var_decls()
+            .map(|v| match &v.init {
+                Expr::Arrow(a) => a.as_ref(),
+                other => panic!("expected arrow, got {:?}", other),
+            })
+            .collect();
+        assert!(arrows[0].is_async);
+        assert_eq!(arrows[0].params.len(), 1);
+        assert!(matches!(arrows[0].body, ArrowBody::Expr(Expr::Await(_))));
+        assert!(arrows[1].is_async);
+        assert!(matches!(arrows[1].body, ArrowBody::Block(_)));
+        assert!(!arrows[2].is_async);
+    }
+
     #[test]
     fn struct_decl() {
         let m = parse_ok("struct User { name: str, age: int }");
@@ -2199,6 +3009,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn derive_annotation() {
+        let m = parse_ok("@derive(Eq, Debug)\nstruct Point { x: int, y: int }\n@derive(Eq) enum Dir { Up, Down }");
+        let Item::StructDecl(s) = &m.items[0] else { panic!("expected struct") };
+        let names: Vec<&str> = s.derives.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["Eq", "Debug"]);
+        assert_eq!(s.derives[1].span, Span::new(12, 17));
+        let Item::EnumDecl(e) = &m.items[1] else { panic!("expected enum") };
+        assert_eq!(e.derives[0].name, "Eq");
+        let Item::StructDecl(s) = &parse_ok("struct P { x: int }").items[0] else { panic!("expected struct") };
+        assert!(s.derives.is_empty());
+
+        let result = parse("@derive(Eq) fn f() {}");
+        assert!(result.diagnostics[0].message.contains("@derive can only be applied to struct or enum declarations"));
+    }
+
+    #[test]
+    fn pub_type_declarations() {
+        let m = parse_ok("pub struct P { x: int }\npub enum E { A }\npub interface I { fn f(self) }\npub type T = int\n@derive(Eq) pub struct Q { x: int }\npub @derive(Eq) enum F { B }\nstruct R { x: int }");
+        let visibility: Vec<bool> = m
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::StructDecl(s) => s.is_pub,
+                Item::EnumDecl(e) => e.is_pub,
+                Item::InterfaceDecl(i) => i.is_pub,
+                Item::TypeAlias(t) => t.is_pub,
+                other => panic!("unexpected item {other:?}"),
+            })
+            .collect();
+        assert_eq!(visibility, [true, true, true, true, true, true, false]);
+
+        let result = parse("pub let x = 1");
+        assert_eq!(result.diagnostics[0].message, "expected `fn`, `struct`, `enum`, `interface`, `type` or `use` after `pub`");
+    }
+
     #[test]
     fn enum_decl() {
         let m = parse_ok("enum Status { Pending, Active(since: str), Error(code: int, msg: str) }");
@@ -2211,12 +3057,162 @@ mod tests {
         }
     }
 
+    #[test]
+    fn interface_decl() {
+        let m = parse_ok(
+            "interface Shape<T> extends Named, Sized {\n  fn area(self) -> num\n  fn scale(self, by: num)\n}",
+        );
+        let Item::InterfaceDecl(i) = &m.items[0] else { panic!("expected interface") };
+        assert_eq!(i.name, "Shape");
+        assert_eq!(i.type_params.len(), 1);
+        assert_eq!(i.extends, vec!["Named", "Sized"]);
+        assert_eq!(i.methods.len(), 2);
+        assert_eq!(i.methods[0].params[0].name, "self");
+        assert_eq!(i.methods[1].params.len(), 2);
+    }
+
+    #[test]
+    fn fn_where_clause() {
+        let m = parse_ok("fn process<T, U>(x: T, y: U) -> T where T: Serializable, U: Named + Sized { x }");
+        let Item::FnDecl(f) = &m.items[0] else { panic!("expected fn") };
+        let names: Vec<&str> = f.type_params.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["T", "U"]);
+        let constraints: Vec<(&str, &str)> = f
+            .where_clause
+            .iter()
+            .map(|c| match &c.bound {
+                TypeExpr::Named(bound, _) => (c.type_param.as_str(), bound.as_str()),
+                other => panic!("unexpected bound {other:?}"),
+            })
+            .collect();
+        assert_eq!(constraints, [("T", "Serializable"), ("U", "Named"), ("U", "Sized")]);
+        assert!(f.body.tail_expr.is_some());
+
+        let m = parse_ok("fn id<T>(x: T) -> T where T: Eq = x");
+        let Item::FnDecl(f) = &m.items[0] else { panic!("expected fn") };
+        assert_eq!(f.where_clause.len(), 1);
+
+        let m = parse_ok("fn plain(x: int) -> int { x }");
+        let Item::FnDecl(f) = &m.items[0] else { panic!("expected fn") };
+        assert!(f.type_params.is_empty() && f.where_clause.is_empty());
+
+        // `where` is contextual: a name elsewhere in the same declaration
+        let m = parse_ok("fn where<T>(where: T) -> T where T: Eq { where }");
+        let Item::FnDecl(f) = &m.items[0] else { panic!("expected fn") };
+        assert_eq!((f.name.as_str(), f.params[0].name.as_str()), ("where", "where"));
+        assert_eq!(f.where_clause.len(), 1);
+        assert!(matches!(&f.body.tail_expr, Some(e) if matches!(e.as_ref(), Expr::Ident(id) if id.name == "where")));
+
+        let result = parse("fn id<T>(x: T) -> T where T Eq { x }");
+        assert!(result.diagnostics.iter().any(|d| d.message.contains("expected Colon")), "got: {:?}", result.diagnostics);
+    }
+
+    #[test]
+    fn interface_method_body_rejected() {
+        let result = parse("interface Shape { fn area(self) -> num { 1 } }");
+        assert!(result
+            .diagnostics
+            .iter()
+            .any(|d| d.message.contains("interface methods must not have a body")));
+    }
+
+    #[test]
+    fn impl_blocks() {
+        let m = parse_ok(
+            "impl Shape for Circle { fn area(self) -> num { self.r } }\nimpl Circle { fn new(r: num) -> Circle { { r: r } } }",
+        );
+        let Item::ImplBlock(a) = &m.items[0] else { panic!("expected impl") };
+        assert_eq!(a.interface.as_deref(), Some("Shape"));
+        assert_eq!(a.target, "Circle");
+        assert_eq!(a.methods[0].name, "area");
+        let Item::ImplBlock(b) = &m.items[1] else { panic!("expected impl") };
+        assert!(b.interface.is_none());
+        assert_eq!(b.methods[0].params[0].name, "r");
+        assert!(!a.methods[0].params[0].is_mut);
+
+        let m = parse_ok("impl Circle { fn grow(mut self, by: num) { self.r += by } }");
+        let Item::ImplBlock(c) = &m.items[0] else { panic!("expected impl") };
+        assert!(c.methods[0].params[0].is_mut);
+        assert_eq!(c.methods[0].params[0].name, "self");
+    }
+
+    #[test]
+    fn pub_use_export_alias() {
+        let m = parse_ok("fn internalHelper() {}\npub use { internalHelper as helper, other }");
+        let Item::ExportAlias(ea) = &m.items[1] else { panic!("expected export alias") };
+        assert_eq!(ea.names[0].local, "internalHelper");
+        assert_eq!(ea.names[0].exported, "helper");
+        assert_eq!(ea.names[1].local, "other");
+        assert_eq!(ea.names[1].exported, "other");
+    }
+
+    #[test]
+    fn module_item_iterators() {
+        let m = parse_ok(
+            "fn a() {}\nstruct S { x: int }\nlet v = 1\nfn b() {}\nextern fn now() -> num\nfn c() {}",
+        );
+        let names: Vec<&str> = m.fn_decls().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert_eq!(m.struct_decls().next().map(|s| s.name.as_str()), Some("S"));
+        assert_eq!(m.var_decls().count(), 1);
+        assert_eq!(m.extern_fn_decls().count(), 1);
+        assert_eq!(m.enum_decls().count(), 0);
+    }
+
+    #[test]
+    fn fn_expression_body() {
+        let m = parse_ok("fn add(a: int, b: int) -> int = a + b\nfn square(x: num) = x * x\nfn braced(a: int, b: int) -> int { a + b }");
+        let fns: Vec<&FnDecl> = m.fn_decls().collect();
+        assert_eq!(fns.len(), 3);
+        let (short, braced) = (fns[0], fns[2]);
+        assert_eq!(short.params.len(), braced.params.len());
+        assert!(short.return_type.is_some());
+        assert!(short.body.stmts.is_empty());
+        assert!(matches!(
+            short.body.tail_expr.as_deref(),
+            Some(Expr::Binary(b)) if b.op == BinaryOp::Add
+        ));
+        assert_eq!(
+            format!("{:?}", short.body.tail_expr).replace(char::is_numeric, ""),
+            format!("{:?}", braced.body.tail_expr).replace(char::is_numeric, "")
+        );
+        assert!(fns[1].return_type.is_none());
+    }
+
     #[test]
     fn type_alias() {
         let m = parse_ok("type ID = str");
         assert!(matches!(m.items[0], Item::TypeAlias(_)));
     }
 
+    #[test]
+    fn generic_type_alias() {
+        let m = parse_ok("type Result<T, E> = T | E\nlet r: Result<str, Map<str, [int]>> = \"ok\"");
+        let Item::TypeAlias(t) = &m.items[0] else { panic!("expected type alias") };
+        let params: Vec<&str> = t.type_params.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(params, ["T", "E"]);
+        let Item::VarDecl(v) = &m.items[1] else { panic!("expected let") };
+        let Some(TypeExpr::Generic(name, args, _)) = &v.ty else { panic!("expected generic type") };
+        assert_eq!(name, "Result");
+        assert!(matches!(&args[..], [TypeExpr::Named(..), TypeExpr::Generic(map, inner, _)] if map == "Map" && inner.len() == 2));
+    }
+
+    #[test]
+    fn parenthesized_types() {
+        let m = parse_ok("type F = ((any) -> nil)?\ntype G = (any) -> nil?\ntype H = (int)");
+        let tys: Vec<&TypeExpr> = m
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::TypeAlias(t) => &t.ty,
+                _ => panic!("expected type alias"),
+            })
+            .collect();
+        assert!(matches!(tys[0], TypeExpr::Nullable(inner, _) if matches!(**inner, TypeExpr::Function(_))));
+        assert!(matches!(tys[1], TypeExpr::Function(f) if matches!(*f.ret, TypeExpr::Nullable(..))));
+        assert!(matches!(tys[2], TypeExpr::Named(name, _) if name == "int"));
+    }
+
     #[test]
     fn union_type_alias() {
         let m = parse_ok("type Result = str | Error");
@@ -2258,12 +3254,67 @@ mod tests {
         assert!(result.diagnostics.is_empty());
     }
 
+    #[test]
+    fn for_range_loop() {
+        let m = parse_ok("fn f(n: int) { for let i = 0; i < n; i += 1 { log(i) } }");
+        let Item::FnDecl(f) = &m.items[0] else { panic!("expected fn") };
+        let Stmt::ForRange(fr) = &f.body.stmts[0] else { panic!("expected for range") };
+        assert_eq!(fr.init.name, "i");
+        assert!(matches!(fr.condition, Expr::Binary(BinaryExpr { op: BinaryOp::Lt, .. })));
+        assert!(matches!(fr.update, Expr::Assign(_)));
+        assert_eq!(fr.body.stmts.len() + fr.body.tail_expr.iter().len(), 1);
+
+        let result = parse("fn f() { for let i = 0 i < 3; i += 1 { } }");
+        assert!(!result.diagnostics.is_empty());
+    }
+
     #[test]
     fn while_loop() {
         let result = parse("fn f() { while x > 0 { x = x - 1 } }");
         assert!(result.diagnostics.is_empty());
     }
 
+    #[test]
+    fn while_let_desugars_to_loop_match() {
+        let m = parse_ok("fn f() { while let Option::Some(val) = next() { consume(val) } }");
+        let Item::FnDecl(f) = &m.items[0] else { panic!("expected fn") };
+        let Stmt::Loop(l) = &f.body.stmts[0] else { panic!("expected loop") };
+        let Stmt::Match(mat) = &l.body.stmts[0] else { panic!("expected match") };
+        assert!(matches!(mat.subject, Expr::Call(_)));
+        assert_eq!(mat.arms.len(), 2);
+        assert!(matches!(&mat.arms[0].pattern, Pattern::Enum(p) if p.bindings == ["val"]));
+        assert!(matches!(mat.arms[1].pattern, Pattern::Wildcard(_)));
+        let MatchArmBody::Block(b) = &mat.arms[1].body else { panic!("expected block") };
+        assert!(matches!(b.stmts[..], [Stmt::Break(_)]));
+    }
+
+    #[test]
+    fn loop_statement_and_expression() {
+        let m = parse_ok("fn f() { loop { break }\n let x = loop { if done() { break } }\n loop { } }");
+        let Item::FnDecl(f) = &m.items[0] else { panic!("expected fn") };
+        assert!(matches!(f.body.stmts[0], Stmt::Loop(_)));
+        let Stmt::VarDecl(v) = &f.body.stmts[1] else { panic!("expected let") };
+        assert!(matches!(v.init, Expr::Loop(_)));
+        assert!(matches!(f.body.tail_expr.as_deref(), Some(Expr::Loop(_))));
+    }
+
+    #[test]
+    fn break_in_while() {
+        let m = parse_ok("fn f() { while true { break } }");
+        let Item::FnDecl(f) = &m.items[0] else { panic!("expected fn") };
+        let Stmt::While(w) = &f.body.stmts[0] else { panic!("expected while") };
+        assert!(matches!(w.body.stmts[..], [Stmt::Break(BreakStmt { value: None, .. })]));
+    }
+
+    #[test]
+    fn break_with_value() {
+        let m = parse_ok("let x = loop { break 1 + 2 }");
+        let Item::VarDecl(v) = &m.items[0] else { panic!("expected let") };
+        let Expr::Loop(l) = &v.init else { panic!("expected loop") };
+        let [Stmt::Break(b)] = &l.body.stmts[..] else { panic!("expected break") };
+        assert!(matches!(b.value, Some(Expr::Binary(_))));
+    }
+
     #[test]
     fn match_with_guard() {
         let m = parse_ok(r#"let x = match n { 0 => "zero", n if n > 100 => "big", _ => "other" }"#);
@@ -2275,6 +3326,112 @@ mod tests {
         }
     }
 
+    #[test]
+    fn match_arm_block_bodies() {
+        let m = parse_ok("let x = match n { 0 => { let y = compute()\n y * 2 }, 1 => { a: 1 }, _ => 0 }");
+        let Item::VarDecl(v) = &m.items[0] else { panic!("expected let") };
+        let Expr::Match(m) = &v.init else { panic!("expected match") };
+        let MatchArmBody::Block(b) = &m.arms[0].body else { panic!("expected block") };
+        assert!(matches!(b.stmts[..], [Stmt::VarDecl(_)]));
+        assert!(matches!(b.tail_expr.as_deref(), Some(Expr::Binary(_))));
+        // An object literal is still an expression
+        assert!(matches!(&m.arms[1].body, MatchArmBody::Expr(Expr::Object(_))));
+        assert!(matches!(&m.arms[2].body, MatchArmBody::Expr(Expr::Literal(_))));
+    }
+
+    #[test]
+    fn range_patterns() {
+        let m = parse_ok(r#"let x = match n { 0..10 => "digit", 10..=99 => "two", LO..HI => "mid", _ => "big" }"#);
+        let Item::VarDecl(v) = &m.items[0] else { panic!("expected let") };
+        let Expr::Match(m) = &v.init else { panic!("expected match") };
+        let inclusive: Vec<bool> = m
+            .arms
+            .iter()
+            .filter_map(|arm| match arm.pattern {
+                Pattern::Range(_, _, inclusive, _) => Some(inclusive),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(inclusive, [false, true, false]);
+    }
+
+    #[test]
+    fn char_range_patterns() {
+        let m = parse_ok("let x = match c { 'a'..='z' => 1, '_' => 2, _ => 3 }");
+        let Item::VarDecl(v) = &m.items[0] else { panic!("expected let") };
+        let Expr::Match(m) = &v.init else { panic!("expected match") };
+        assert!(matches!(
+            &m.arms[0].pattern,
+            Pattern::Range(lo, hi, true, _)
+                if matches!(**lo, Expr::Literal(Literal::Char('a', _))) && matches!(**hi, Expr::Literal(Literal::Char('z', _)))
+        ));
+        assert!(matches!(&m.arms[1].pattern, Pattern::Literal(Literal::Char('_', _))));
+    }
+
+    #[test]
+    fn match_in_statement_position() {
+        let m = parse_ok("fn f(x: int) -> int {\n  match x { 1 => log(), _ => {} }\n  match x { _ => 2 }\n}");
+        let Item::FnDecl(f) = &m.items[0] else { panic!("expected fn") };
+        assert!(matches!(&f.body.stmts[..], [Stmt::Match(_)]));
+        // The last one is still the block's value
+        assert!(matches!(f.body.tail_expr.as_deref(), Some(Expr::Match(_))));
+    }
+
+    #[test]
+    fn negative_number_literals() {
+        let m = parse_ok("let a = -42\nlet b = -x\nlet c = -1.5.toFixed()");
+        let inits: Vec<&Expr> = m
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::VarDecl(v) => &v.init,
+                _ => panic!("expected let"),
+            })
+            .collect();
+        assert!(matches!(inits[0], Expr::Literal(Literal::Int(-42, text, _)) if text == "-42"));
+        assert!(matches!(inits[1], Expr::Unary(_)));
+        assert!(matches!(inits[2], Expr::Unary(_)), "postfix binds tighter than `-`");
+
+        let m = parse_ok(r#"let x = match n { -1 => "a", -3.14 => "b", -10..=-2 => "c", _ => "d" }"#);
+        let Item::VarDecl(v) = &m.items[0] else { panic!("expected let") };
+        let Expr::Match(m) = &v.init else { panic!("expected match") };
+        assert!(matches!(&m.arms[0].pattern, Pattern::Literal(Literal::Int(-1, _, _))));
+        assert!(matches!(&m.arms[1].pattern, Pattern::Literal(Literal::Float(f, _)) if f.to_string() == "-3.14"));
+        let Pattern::Range(lo, hi, true, _) = &m.arms[2].pattern else { panic!("expected range") };
+        assert!(matches!(lo.as_ref(), Expr::Literal(Literal::Int(-10, _, _))));
+        assert!(matches!(hi.as_ref(), Expr::Literal(Literal::Int(-2, _, _))));
+    }
+
+    #[test]
+    fn shebang_recorded_on_module() {
+        let m = parse_ok("#!/usr/bin/env node\nlet x = 1");
+        assert_eq!(m.shebang.as_deref(), Some("/usr/bin/env node"));
+        assert_eq!(m.items.len(), 1);
+        assert!(parse_ok("let x = 1").shebang.is_none());
+    }
+
+    #[test]
+    fn comments_preserved_and_attached() {
+        let src = "/// Doc for a.\nfn a() {\n    // inside a\n}\n/* block */\n/// Doc for b.\npub fn b() {}\n// trailing";
+        assert!(parse_ok(src).comments.is_empty());
+
+        let result = parse_with_options(src, ParseOptions { preserve_comments: true });
+        assert!(result.diagnostics.is_empty());
+        let comments: Vec<_> = result.module.comments.iter().map(|c| (c.kind, c.text.as_str(), c.item)).collect();
+        assert_eq!(
+            comments,
+            vec![
+                (CommentKind::Doc, "/// Doc for a.", Some(0)),
+                (CommentKind::Line, "// inside a", None),
+                (CommentKind::Block, "/* block */", Some(1)),
+                (CommentKind::Doc, "/// Doc for b.", Some(1)),
+                (CommentKind::Line, "// trailing", None),
+            ]
+        );
+        let docs: Vec<_> = result.module.doc_comments(1).map(|c| c.text.as_str()).collect();
+        assert_eq!(docs, ["/// Doc for b."]);
+    }
+
     #[test]
     fn try_catch() {
         let result = parse("fn f() { try { parse(input) } catch e { log(e) } }");
@@ -2290,6 +3447,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn template_import_path() {
+        let m = parse_ok("import { x } from `./config`");
+        let Item::Import(i) = &m.items[0] else { panic!("expected import") };
+        assert_eq!(i.path.as_static(), Some("./config"));
+
+        let result = parse("import { x } from `./config/${env}`");
+        assert_eq!(result.diagnostics.len(), 1, "got: {:?}", result.diagnostics);
+        assert_eq!(
+            result.diagnostics[0].message,
+            "dynamic import paths are not supported in static imports"
+        );
+        let Item::Import(i) = &result.module.items[0] else { panic!("expected import") };
+        assert!(matches!(i.path, ImportPath::Dynamic(_)));
+
+        // Only import paths take templates; other string positions do not
+        let result = parse("@js(`node:fs`)\nextern fn readFile(path: str) -> str");
+        assert!(
+            result.diagnostics.iter().any(|d| d.message == "expected string literal"),
+            "got: {:?}",
+            result.diagnostics
+        );
+    }
+
     #[test]
     fn namespace_import() {
         let m = parse_ok(r#"import * as fs from "./fs""#);
@@ -2298,6 +3479,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn side_effect_import() {
+        let m = parse_ok("import \"./setup\"\nimport `./polyfills`");
+        let Item::Import(i) = &m.items[0] else { panic!("expected import") };
+        assert!(i.names.is_empty());
+        assert!(i.namespace.is_none());
+        assert_eq!(i.path.as_static(), Some("./setup"));
+        assert!(matches!(&m.items[1], Item::Import(i) if i.path.as_static() == Some("./polyfills")));
+    }
+
     #[test]
     fn implicit_return() {
         let m = parse_ok("fn foo() -> int { let x = 1; x + 1 }");
@@ -2362,6 +3553,110 @@ fn foo() -> int { 42 }"#,
         }
     }
 
+    #[test]
+    fn tagged_template_parsing() {
+        let m = parse_ok("let x = html`<div>${content}</div>`\nlet y = sql.query`select 1`");
+        let Item::VarDecl(v) = &m.items[0] else { panic!("expected var decl") };
+        let Expr::TaggedTemplate(tt) = &v.init else { panic!("expected tagged template") };
+        assert!(matches!(tt.tag.as_ref(), Expr::Ident(id) if id.name == "html"));
+        assert_eq!(tt.template.parts.len(), 3);
+        let Item::VarDecl(v) = &m.items[1] else { panic!("expected var decl") };
+        let Expr::TaggedTemplate(tt) = &v.init else { panic!("expected tagged template") };
+        assert!(matches!(tt.tag.as_ref(), Expr::Member(_)));
+    }
+
+    #[test]
+    fn template_after_space_is_not_tagged() {
+        let m = parse_ok("html\n`<div></div>`");
+        assert_eq!(m.items.len(), 2);
+        assert!(matches!(&m.items[1], Item::ExprStmt(e) if matches!(e.expr, Expr::TemplateString(_))));
+    }
+
+    #[test]
+    fn not_in_desugars_to_negated_in() {
+        let m = parse_ok("let a = key not in config");
+        let Item::VarDecl(v) = &m.items[0] else { panic!("expected var decl") };
+        let Expr::Unary(u) = &v.init else { panic!("expected unary, got {:?}", v.init) };
+        assert_eq!(u.op, UnaryOp::Not);
+        assert!(matches!(u.operand.as_ref(), Expr::Binary(b) if b.op == BinaryOp::In));
+    }
+
+    #[test]
+    fn is_not_desugars_to_ne() {
+        let m = parse_ok("let a = value is not nil");
+        let Item::VarDecl(v) = &m.items[0] else { panic!("expected var decl") };
+        let Expr::Binary(b) = &v.init else { panic!("expected binary, got {:?}", v.init) };
+        assert_eq!(b.op, BinaryOp::Ne);
+        assert!(matches!(b.right.as_ref(), Expr::Literal(Literal::Nil(_))));
+    }
+
+    #[test]
+    fn not_is_prefix_negation() {
+        let m = parse_ok("let a = not done && x in xs");
+        let Item::VarDecl(v) = &m.items[0] else { panic!("expected var decl") };
+        let Expr::Binary(b) = &v.init else { panic!("expected binary") };
+        assert_eq!(b.op, BinaryOp::And);
+        assert!(matches!(b.left.as_ref(), Expr::Unary(u) if u.op == UnaryOp::Not));
+        assert!(matches!(b.right.as_ref(), Expr::Binary(r) if r.op == BinaryOp::In));
+    }
+
+    #[test]
+    fn chained_comparison() {
+        let m = parse_ok("let a = 1 < f(x) <= 10 > y");
+        let Item::VarDecl(v) = &m.items[0] else { panic!("expected var decl") };
+        let Expr::Comparison(chain) = &v.init else { panic!("expected comparison chain, got {:?}", v.init) };
+        let ops: Vec<BinaryOp> = chain.ops.iter().map(|(op, _)| *op).collect();
+        assert_eq!(ops, [BinaryOp::Lt, BinaryOp::Le, BinaryOp::Gt]);
+        assert_eq!(chain.operands.len(), 4);
+        assert!(matches!(&chain.operands[1], Expr::Call(_)));
+        assert!(matches!(&chain.operands[3], Expr::Ident(id) if id.name == "y"));
+        assert_eq!(chain.span, chain.ops[0].1);
+    }
+
+    #[test]
+    fn parenthesized_comparison_does_not_chain() {
+        let m = parse_ok("let a = (1 < x) == y");
+        let Item::VarDecl(v) = &m.items[0] else { panic!("expected var decl") };
+        let Expr::Binary(b) = &v.init else { panic!("expected binary, got {:?}", v.init) };
+        assert_eq!(b.op, BinaryOp::Eq);
+        let Expr::Binary(l) = b.left.as_ref() else { panic!("expected comparison, got {:?}", b.left) };
+        assert_eq!(l.op, BinaryOp::Lt);
+        assert!(matches!(l.left.as_ref(), Expr::Literal(Literal::Int(1, _, _))));
+        assert!(matches!(l.right.as_ref(), Expr::Ident(id) if id.name == "x"));
+        assert!(matches!(b.right.as_ref(), Expr::Ident(id) if id.name == "y"));
+    }
+
+    #[test]
+    fn bare_is_is_an_error() {
+        let result = parse("let a = x is y");
+        assert!(result.diagnostics.iter().any(|d| d.message.contains("expected `not` after `is`")));
+    }
+
+    #[test]
+    fn contextual_keywords_as_names() {
+        let m = parse_ok(
+            "q.where(1)\nexpect(x).not.toBe(1)\nlet o = { is: 1, not: 2, where: 3 }\n\
+             fn f(where: int, is: bool) -> int where T: Eq { if is { where } else { 0 } }\nlet not = 1\nlet b = not",
+        );
+        let Item::ExprStmt(s) = &m.items[0] else { panic!("expected expression statement") };
+        let Expr::Call(c) = &s.expr else { panic!("expected call, got {:?}", s.expr) };
+        assert!(matches!(c.callee.as_ref(), Expr::Member(mem) if mem.field == "where"));
+        let Item::ExprStmt(s) = &m.items[1] else { panic!("expected expression statement") };
+        let Expr::Call(c) = &s.expr else { panic!("expected call, got {:?}", s.expr) };
+        let Expr::Member(to_be) = c.callee.as_ref() else { panic!("expected member") };
+        assert!(matches!(to_be.object.as_ref(), Expr::Member(mem) if mem.field == "not"));
+        let Item::VarDecl(v) = &m.items[2] else { panic!("expected var decl") };
+        let Expr::Object(o) = &v.init else { panic!("expected object, got {:?}", v.init) };
+        let keys: Vec<&str> = o.fields.iter().map(|f| f.key.as_str()).collect();
+        assert_eq!(keys, ["is", "not", "where"]);
+        let Item::FnDecl(f) = &m.items[3] else { panic!("expected fn") };
+        let names: Vec<&str> = f.params.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["where", "is"]);
+        assert_eq!(f.where_clause.len(), 1);
+        let Item::VarDecl(v) = &m.items[5] else { panic!("expected var decl") };
+        assert!(matches!(&v.init, Expr::Ident(id) if id.name == "not"));
+    }
+
     // ── DSL block tests ──
 
     #[test]
@@ -2523,6 +3818,28 @@ fn foo() -> int { 42 }"#,
         }
     }
 
+    #[test]
+    fn extern_annotation_matches_keyword() {
+        // The leading space keeps the two sources' spans aligned
+        for (annotated, keyword) in [
+            ("@extern fn log(msg: str)", " extern fn log(msg: str)"),
+            ("@extern struct Req { url: str }", " extern struct Req { url: str }"),
+            ("@extern type Headers", " extern type Headers"),
+        ] {
+            let a = parse_ok(annotated);
+            let k = parse_ok(keyword);
+            assert_eq!(format!("{:?}", a.items), format!("{:?}", k.items), "{annotated}");
+        }
+
+        let m = parse_ok("@js(\"node:fs\")\n@extern fn readFile(path: str) -> Promise<str>");
+        if let Item::ExternFnDecl(ef) = &m.items[0] {
+            assert_eq!(ef.name, "readFile");
+            assert_eq!(ef.js_annotation.as_ref().unwrap().module, Some("node:fs".to_string()));
+        } else {
+            panic!("expected ExternFnDecl");
+        }
+    }
+
     #[test]
     fn js_annotation_module_only() {
         let m = parse_ok("@js(\"node:fs\")\nextern fn readFile(path: str) -> Promise<str>");